@@ -18,9 +18,9 @@ pub struct Tokenizer<'a> {
     raw_source: &'a str,
 }
 
-const SYMBOL_ENDING: [char; 29] = [
+const SYMBOL_ENDING: [char; 32] = [
     ' ', '(', ')', '{', '}', '#', ';', '\n', '\t', '\r', '+', '-', '/', '\\', '%', '*', '^', '!',
-    '&', '|', '<', '>', '=', ',', '[', ']', '$', '`', '"',
+    '&', '|', '<', '>', '=', ',', '[', ']', '$', '@', '`', '"', '?', '~',
 ];
 impl<'a> Tokenizer<'a> {
     /// Returns a new tokenizer from an R program.
@@ -69,7 +69,7 @@ impl<'a> Tokenizer<'a> {
     /// println!("{tokens:?}");
     /// ```
     ///
-    pub fn tokenize(&mut self) -> Vec<CommentedToken> {
+    pub fn tokenize(&mut self) -> Vec<CommentedToken<'_>> {
         let mut tokens = vec![];
         self.next();
         while self.it < self.raw_source.len() {
@@ -123,7 +123,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '*' => {
-                    let next_char = self.lookahead().expect("Script does not end on '*'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         // That's undocumented, but it actually works...
                         '*' => {
@@ -151,7 +151,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '<' => {
-                    let next_char = self.lookahead().expect("Script does not end on '<'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         '-' => {
                             self.push_token(LAssign, &mut tokens);
@@ -171,7 +171,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '>' => {
-                    let next_char = self.lookahead().expect("Script does not end on '>'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         '=' => {
                             self.push_token(GreaterEqual, &mut tokens);
@@ -184,7 +184,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '|' => {
-                    let next_char = self.lookahead().expect("Script does not end on '|'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         '|' => {
                             self.push_token(Or, &mut tokens);
@@ -199,7 +199,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '&' => {
-                    let next_char = self.lookahead().expect("Script does not end on '&'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         '&' => {
                             self.push_token(And, &mut tokens);
@@ -210,7 +210,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '=' => {
-                    let next_char = self.lookahead().expect("Script does not end on '='");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         '=' => {
                             self.push_token(Equal, &mut tokens);
@@ -225,7 +225,7 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '-' => {
-                    let next_char = self.lookahead().expect("Script does not end on '-'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         '>' => {
                             self.push_token(RAssign, &mut tokens);
@@ -236,17 +236,18 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 '!' => {
-                    self.next();
-                    match self.current_char {
+                    let next_char = self.lookahead().unwrap_or_default();
+                    match next_char {
                         '=' => {
                             self.push_token(NotEqual, &mut tokens);
                             self.next();
                         }
                         _ => self.push_token(UnaryNot, &mut tokens),
                     }
+                    self.next();
                 }
                 '.' => {
-                    let next_char = self.lookahead().expect("Script does not end on '.'");
+                    let next_char = self.lookahead().unwrap_or_default();
                     match next_char {
                         'a'..='z' | 'A'..='Z' => {
                             self.identifier(&mut tokens);
@@ -267,9 +268,9 @@ impl<'a> Tokenizer<'a> {
                     self.identifier(&mut tokens);
                 }
                 '%' => {
-                    let next_char = self.lookahead().expect("Script does not end on '%'");
+                    let next_char = self.lookahead();
                     match next_char {
-                        '%' => {
+                        Some('%') => {
                             self.push_token(Modulo, &mut tokens);
                             self.next();
                             self.next();
@@ -277,15 +278,22 @@ impl<'a> Tokenizer<'a> {
                         _ => {
                             let custom_binary_start = self.it;
                             self.next();
-                            while self.current_char != '%' {
+                            while self.it < self.raw_source.len()
+                                && self.current_char != '%'
+                                && self.current_char != '\n'
+                            {
                                 self.next();
                             }
-                            let custom_binary_end = self.it;
-                            self.push_token(
-                                Special(&self.raw_source[custom_binary_start..=custom_binary_end]),
+                            // An unterminated operator ends at the end of the line
+                            // and parsing fails on it
+                            if self.it < self.raw_source.len() && self.current_char == '%' {
+                                self.next();
+                            }
+                            self.push_token_at(
+                                Special(&self.raw_source[custom_binary_start..self.it]),
+                                custom_binary_start,
                                 &mut tokens,
                             );
-                            self.next()
                         }
                     }
                 }
@@ -311,31 +319,36 @@ impl<'a> Tokenizer<'a> {
                     self.next();
                 }
                 ':' => {
+                    let start = self.offset;
                     self.next();
                     let next = self.lookahead();
 
                     match (self.current_char, next) {
                         // :::
                         (':', Some(':')) => {
-                            self.push_token(NsGetInt, &mut tokens);
+                            self.push_token_at(NsGetInt, start, &mut tokens);
                             self.next();
                             self.next();
                         }
                         // ::
                         (':', _) => {
-                            self.push_token(NsGet, &mut tokens);
+                            self.push_token_at(NsGet, start, &mut tokens);
                             self.next()
                         }
                         // :=
                         ('=', _) => {
-                            self.push_token(ColonAssign, &mut tokens);
+                            self.push_token_at(ColonAssign, start, &mut tokens);
                             self.next()
                         }
                         // :
-                        _ => self.push_token(Colon, &mut tokens),
+                        _ => self.push_token_at(Colon, start, &mut tokens),
                     }
                 }
-                _ => unreachable!(),
+                // Other letters, e.g. `é`, and characters R does not allow
+                // in the symbols are kept verbatim
+                _ => {
+                    self.identifier_or_reserved(&mut tokens);
+                }
             }
         }
         tokens.push(CommentedToken::new(EOF, self.offset));
@@ -347,13 +360,24 @@ impl<'a> Tokenizer<'a> {
         tokens.push(CommentedToken::new(token, self.offset));
     }
 
+    /// Pushes a token spanning multiple characters, which are already consumed,
+    /// so the current offset is past the token.
+    fn push_token_at(
+        &mut self,
+        token: Token<'a>,
+        offset: usize,
+        tokens: &mut Vec<CommentedToken<'a>>,
+    ) {
+        tokens.push(CommentedToken::new(token, offset));
+    }
+
     fn string_literal(&mut self, tokens: &mut Vec<CommentedToken<'a>>) {
         let delimiter = self.current_char;
         let start_offset = self.offset;
         let start_it = self.it;
         let mut in_escape = false;
         self.next();
-        while self.current_char != delimiter || in_escape {
+        while self.it < self.raw_source.len() && (self.current_char != delimiter || in_escape) {
             if in_escape {
                 in_escape = !in_escape;
             } else if self.current_char == '\\' {
@@ -361,8 +385,10 @@ impl<'a> Tokenizer<'a> {
             }
             self.next()
         }
+        // An unterminated string runs to the end of the input
+        let end = (self.it + 1).min(self.raw_source.len());
         tokens.push(CommentedToken::new(
-            Literal(&self.raw_source[start_it..=self.it]),
+            Literal(&self.raw_source[start_it..end]),
             start_offset,
         ));
     }
@@ -449,51 +475,79 @@ impl<'a> Tokenizer<'a> {
                 }
             }
         }
-        self.push_token(Literal(&self.raw_source[start_it..self.it]), tokens);
+        self.push_token_at(
+            Literal(&self.raw_source[start_it..self.it]),
+            start_it,
+            tokens,
+        );
     }
 
     fn identifier(&mut self, tokens: &mut Vec<CommentedToken<'a>>) {
         let start_it = self.it;
         let mut in_backticks = false;
-        while self.it < self.raw_source.len() && in_backticks
-            || self.current_char.is_alphabetic()
-            || self.current_char.is_ascii_digit()
-            || self.current_char == '.'
-            || self.current_char == '_'
-            || self.current_char == '`'
+        while self.it < self.raw_source.len()
+            && (in_backticks
+                || self.current_char.is_alphabetic()
+                || self.current_char.is_ascii_digit()
+                || self.current_char == '.'
+                || self.current_char == '_'
+                || self.current_char == '`')
         {
             if self.current_char == '`' {
                 in_backticks = !in_backticks;
             }
             self.next();
         }
-        match &self.raw_source[start_it..self.it] {
-            "TRUE" | "T" => self.push_token(Literal("TRUE"), tokens),
-            "FALSE" | "F" => self.push_token(Literal("FALSE"), tokens),
-            _ => self.push_token(Symbol(&self.raw_source[start_it..self.it]), tokens),
-        }
+        let token = match &self.raw_source[start_it..self.it] {
+            "TRUE" | "T" => Literal("TRUE"),
+            "FALSE" | "F" => Literal("FALSE"),
+            _ => Symbol(&self.raw_source[start_it..self.it]),
+        };
+        self.push_token_at(token, start_it, tokens);
     }
 
     fn identifier_or_reserved(&mut self, tokens: &mut Vec<CommentedToken<'a>>) {
         let start_it = self.it;
         while self.it < self.raw_source.len() && !SYMBOL_ENDING.contains(&self.current_char) {
+            // `::` and `:::` stay in the symbol, e.g. `dplyr::filter`, but `:` ends it
+            if self.current_char == ':' {
+                if self.lookahead() != Some(':') {
+                    break;
+                }
+                while self.it < self.raw_source.len() && self.current_char == ':' {
+                    self.next();
+                }
+                // The name after `::` may be quoted, e.g. base::`names<-`
+                if matches!(self.current_char, '`' | '"' | '\'') && self.it < self.raw_source.len()
+                {
+                    let quote = self.current_char;
+                    self.next();
+                    while self.it < self.raw_source.len() && self.current_char != quote {
+                        self.next();
+                    }
+                    self.next();
+                    break;
+                }
+                continue;
+            }
             self.next();
         }
 
-        match &self.raw_source[start_it..self.it] {
-            "continue" => self.push_token(Continue, tokens),
-            "break" => self.push_token(Break, tokens),
-            "for" => self.push_token(For, tokens),
-            "if" => self.push_token(If, tokens),
-            "else" => self.push_token(Else, tokens),
-            "in" => self.push_token(In, tokens),
-            "while" => self.push_token(While, tokens),
-            "repeat" => self.push_token(Repeat, tokens),
-            "function" => self.push_token(Function, tokens),
-            "TRUE" | "T" => self.push_token(Literal("TRUE"), tokens),
-            "FALSE" | "F" => self.push_token(Literal("FALSE"), tokens),
-            _ => self.push_token(Symbol(&self.raw_source[start_it..self.it]), tokens),
-        }
+        let token = match &self.raw_source[start_it..self.it] {
+            "continue" => Continue,
+            "break" => Break,
+            "for" => For,
+            "if" => If,
+            "else" => Else,
+            "in" => In,
+            "while" => While,
+            "repeat" => Repeat,
+            "function" => Function,
+            "TRUE" | "T" => Literal("TRUE"),
+            "FALSE" | "F" => Literal("FALSE"),
+            _ => Symbol(&self.raw_source[start_it..self.it]),
+        };
+        self.push_token_at(token, start_it, tokens);
     }
 
     fn comment(&mut self, tokens: &mut Vec<CommentedToken<'a>>) {
@@ -508,9 +562,21 @@ impl<'a> Tokenizer<'a> {
                 offset: _,
                 leading_comments: _,
                 inline_comment: _,
-            }) => self.push_token(Comment(&self.raw_source[start_it..self.it]), tokens),
-            Some(_) => self.push_token(InlineComment(&self.raw_source[start_it..self.it]), tokens),
-            None => self.push_token(Comment(&self.raw_source[start_it..self.it]), tokens),
+            }) => self.push_token_at(
+                Comment(&self.raw_source[start_it..self.it]),
+                start_it,
+                tokens,
+            ),
+            Some(_) => self.push_token_at(
+                InlineComment(&self.raw_source[start_it..self.it]),
+                start_it,
+                tokens,
+            ),
+            None => self.push_token_at(
+                Comment(&self.raw_source[start_it..self.it]),
+                start_it,
+                tokens,
+            ),
         }
     }
 
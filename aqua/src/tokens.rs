@@ -4,12 +4,16 @@
 /// and the column offset. Additionally, it stores the comments that are associated
 /// with the token.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommentedToken<'a> {
     /// The actual token stored in this struct.
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub token: Token<'a>,
     /// The column offset of the start of this token.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub offset: usize,
     /// Preceding comments.
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub leading_comments: Option<Vec<&'a str>>,
     /// Trailing inline comment.
     pub inline_comment: Option<&'a str>,
@@ -65,6 +69,7 @@ impl std::fmt::Display for CommentedToken<'_> {
 /// This represents all the different token types encountered
 /// in an R program.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token<'a> {
     Symbol(&'a str),
     Literal(&'a str),
@@ -1,4 +1,7 @@
-use tergo_tokenizer::{tokenizer::Tokenizer, tokens::Token};
+use tergo_tokenizer::{
+    tokenizer::Tokenizer,
+    tokens::{CommentedToken, Token},
+};
 
 fn log_init() {
     match simple_logger::init_with_env() {
@@ -351,3 +354,231 @@ fn escape_in_strings() {
         assert!(!res.is_empty())
     }
 }
+
+#[test]
+fn offsets_point_at_token_starts() {
+    log_init();
+
+    let example = "abc <- 123 # comment\nif (TRUE) 'a'";
+    let mut tokenizer = Tokenizer::new(example);
+    let offsets: Vec<(Token, usize)> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| (token.token, token.offset))
+        .collect();
+    assert_eq!(
+        offsets,
+        vec![
+            (Token::Symbol("abc"), 0),
+            (Token::LAssign, 4),
+            (Token::Literal("123"), 7),
+            (Token::InlineComment("# comment"), 11),
+            (Token::Newline, 20),
+            (Token::If, 21),
+            (Token::LParen, 24),
+            (Token::Literal("TRUE"), 25),
+            (Token::RParen, 29),
+            (Token::Literal("'a'"), 31),
+            (Token::EOF, 34),
+        ]
+    );
+}
+
+#[test]
+fn help_operator_ends_symbols() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("package?stats");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("package"),
+            Token::Help,
+            Token::Symbol("stats"),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn slot_ends_symbols() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("object@slot");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("object"),
+            Token::Slot,
+            Token::Symbol("slot"),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn colon_ends_symbols_but_namespace_access_does_not() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("x:y\ndplyr::filter");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("x"),
+            Token::Colon,
+            Token::Symbol("y"),
+            Token::Newline,
+            Token::Symbol("dplyr::filter"),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn namespace_access_of_a_quoted_name_is_one_symbol() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("base::`names<-`(x)\nbase::\"c\"");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("base::`names<-`"),
+            Token::LParen,
+            Token::Symbol("x"),
+            Token::RParen,
+            Token::Newline,
+            Token::Symbol("base::\"c\""),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn backtick_symbol_at_the_end_of_the_input() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("x:`a b`");
+    let tokens: Vec<CommentedToken> = tokenizer.tokenize();
+    assert_eq!(
+        tokens,
+        vec![
+            CommentedToken::new(Token::Symbol("x"), 0),
+            CommentedToken::new(Token::Colon, 1),
+            CommentedToken::new(Token::Symbol("`a b`"), 2),
+            CommentedToken::new(Token::EOF, 7)
+        ]
+    );
+}
+
+#[test]
+fn unterminated_custom_operator_ends_at_the_end_of_the_line() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("x %in\ny %");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("x"),
+            Token::Special("%in"),
+            Token::Newline,
+            Token::Symbol("y"),
+            Token::Special("%"),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn non_ascii_letters_start_symbols() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("größe <- 1");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("größe"),
+            Token::LAssign,
+            Token::Literal("1"),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn tilde_ends_symbols() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("y~x");
+    let tokens: Vec<Token> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Symbol("y"),
+            Token::Tilde,
+            Token::Symbol("x"),
+            Token::EOF
+        ]
+    );
+}
+
+#[test]
+fn offsets_of_the_operators_point_at_their_starts() {
+    log_init();
+
+    let mut tokenizer = Tokenizer::new("!a != b %in% c := d : e");
+    let offsets: Vec<(Token, usize)> = tokenizer
+        .tokenize()
+        .into_iter()
+        .map(|token| (token.token, token.offset))
+        .collect();
+    assert_eq!(
+        offsets,
+        vec![
+            (Token::UnaryNot, 0),
+            (Token::Symbol("a"), 1),
+            (Token::NotEqual, 3),
+            (Token::Symbol("b"), 6),
+            (Token::Special("%in%"), 8),
+            (Token::Symbol("c"), 13),
+            (Token::ColonAssign, 15),
+            (Token::Symbol("d"), 18),
+            (Token::Colon, 20),
+            (Token::Symbol("e"), 22),
+            (Token::EOF, 23),
+        ]
+    );
+}
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tergo_lib::fuzzing::Program;
+
+fuzz_target!(|program: Program| tergo_lib::fuzzing::fuzz_round_trip(&program));
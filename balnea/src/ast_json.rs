@@ -0,0 +1,343 @@
+//! Formatting of the parsed code serialized to JSON.
+//!
+//! The JSON is the `serde` serialization of the top-level statements
+//! of the code, see [parser::ast]. Each token is an object with the `token`
+//! key, e.g. `{"token": {"Symbol": "x"}}`, and an expression is an object
+//! with a single key naming its kind, e.g. `{"Symbol": <token>}`
+//! or `{"Bop": [<operator token>, <left hand side>, <right hand side>]}`.
+
+use std::collections::HashMap;
+
+use parser::{
+    Input,
+    ast::{
+        Arg, Args, Delimiter, ElseIfConditional, Expression, ForLoop, FunctionCall,
+        FunctionDefinition, IfConditional, IfExpression, Lambda, RepeatExpression,
+        SubsetExpression, TermExpr, TrailingElse, WhileExpression,
+    },
+    parse, pre_parse,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tokenizer::{Tokenizer, tokens::CommentedToken};
+
+use crate::{Config, format_expression, format_strings};
+
+/// Parses the code and serializes its top-level statements to JSON,
+/// which [format_ast_json] accepts.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::parse_to_ast_json;
+///
+/// let json = parse_to_ast_json("x <- 1").unwrap();
+/// assert!(json.starts_with(r#"[{"MultiBop":[{"Symbol":{"token":{"Symbol":"x"}"#));
+/// ```
+pub fn parse_to_ast_json(input: &str) -> Result<String, String> {
+    let mut tokenizer = Tokenizer::new(input);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let statements = parse(parser::Input(&tokens))?;
+    serde_json::to_string(&statements).map_err(|e| e.to_string())
+}
+
+/// Formats the code given as the JSON produced by [parse_to_ast_json].
+///
+/// Programs generating R code can build the JSON directly and get
+/// formatted code without writing the source code first. The `offset`,
+/// `leading_comments` and `inline_comment` keys of the tokens can be left out.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::format_ast_json;
+///
+/// let json = r#"[{"Bop": [
+///     {"token": "LAssign"},
+///     {"Symbol": {"token": {"Symbol": "x"}}},
+///     {"Literal": {"token": {"Literal": "1"}}}
+/// ]}]"#;
+/// assert_eq!(format_ast_json(json, None).unwrap(), "x <- 1\n");
+/// ```
+pub fn format_ast_json(json: &str, config: Option<&Config>) -> Result<String, String> {
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let mut tokens = HashMap::new();
+    collect_tokens(&value, &mut tokens)?;
+    let reader = AstReader { tokens: &tokens };
+    let statements = value
+        .as_array()
+        .ok_or("Expected an array of the top-level statements")?
+        .iter()
+        .map(|statement| reader.expression(statement))
+        .collect::<Result<Vec<_>, _>>()?;
+    let top_node = Expression::Term(Box::new(TermExpr::new(None, statements, None)));
+    Ok(format_strings(
+        format_expression(top_node, config).0,
+        config,
+    ))
+}
+
+/// The tokens of the JSON, by the address of their JSON object.
+type Tokens<'a> = HashMap<*const Value, CommentedToken<'a>>;
+
+/// Deserializes all the tokens in the JSON.
+///
+/// The expressions borrow their tokens, so the tokens must be kept
+/// in place before the expressions are built.
+fn collect_tokens<'a>(value: &'a Value, tokens: &mut Tokens<'a>) -> Result<(), String> {
+    match value {
+        Value::Object(object) if object.contains_key("token") => {
+            let token = CommentedToken::deserialize(value)
+                .map_err(|e| format!("Invalid token {value}: {e}"))?;
+            tokens.insert(value, token);
+        }
+        Value::Object(object) => {
+            for value in object.values() {
+                collect_tokens(value, tokens)?;
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_tokens(value, tokens)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Returns the kind and the content of an expression,
+/// e.g. `("Symbol", <token>)` for `{"Symbol": <token>}`.
+fn variant(value: &Value) -> Result<(&str, &Value), String> {
+    match value.as_object() {
+        Some(object) if object.len() == 1 => {
+            let (kind, content) = object.iter().next().unwrap();
+            Ok((kind, content))
+        }
+        _ => Err(format!(
+            "Expected an object with a single key, found {value}"
+        )),
+    }
+}
+
+fn field<'v>(value: &'v Value, name: &str) -> Result<&'v Value, String> {
+    value
+        .get(name)
+        .ok_or_else(|| format!("Missing the {name:?} key in {value}"))
+}
+
+fn elements<const N: usize>(value: &Value) -> Result<&[Value; N], String> {
+    value
+        .as_array()
+        .and_then(|elements| elements.as_slice().try_into().ok())
+        .ok_or_else(|| format!("Expected an array of {N} elements, found {value}"))
+}
+
+/// Builds the expressions from the JSON, borrowing the collected tokens.
+struct AstReader<'a> {
+    tokens: &'a Tokens<'a>,
+}
+
+impl<'a> AstReader<'a> {
+    fn token(&self, value: &Value) -> Result<&'a CommentedToken<'a>, String> {
+        self.tokens
+            .get(&(value as *const Value))
+            .ok_or_else(|| format!("Expected a token, found {value}"))
+    }
+
+    fn optional_token(
+        &self,
+        value: Option<&Value>,
+    ) -> Result<Option<&'a CommentedToken<'a>>, String> {
+        match value {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => self.token(value).map(Some),
+        }
+    }
+
+    fn boxed(&self, value: &Value) -> Result<Box<Expression<'a>>, String> {
+        self.expression(value).map(Box::new)
+    }
+
+    fn optional_expression(&self, value: &Value) -> Result<Option<Expression<'a>>, String> {
+        match value {
+            Value::Null => Ok(None),
+            value => self.expression(value).map(Some),
+        }
+    }
+
+    fn expression(&self, value: &Value) -> Result<Expression<'a>, String> {
+        let (kind, content) = variant(value)?;
+        let expression = match kind {
+            "Symbol" => Expression::Symbol(self.token(content)?),
+            "Literal" => Expression::Literal(self.token(content)?),
+            "Comment" => Expression::Comment(self.token(content)?),
+            "Newline" => Expression::Newline(self.token(content)?),
+            "EOF" => Expression::EOF(self.token(content)?),
+            "Break" => Expression::Break(self.token(content)?),
+            "Continue" => Expression::Continue(self.token(content)?),
+            // The formatter prints nothing for the whitespace.
+            "Whitespace" => Expression::Whitespace(Input(&[])),
+            "Term" => Expression::Term(Box::new(TermExpr::new(
+                self.optional_token(content.get("pre_delimiters"))?,
+                field(content, "term")?
+                    .as_array()
+                    .ok_or_else(|| format!("Expected an array of expressions in {content}"))?
+                    .iter()
+                    .map(|expression| self.expression(expression))
+                    .collect::<Result<_, _>>()?,
+                self.optional_token(content.get("post_delimiters"))?,
+            ))),
+            "Unary" => {
+                let [operator, operand] = elements(content)?;
+                Expression::Unary(self.token(operator)?, self.boxed(operand)?)
+            }
+            "Bop" => {
+                let [operator, lhs, rhs] = elements(content)?;
+                Expression::Bop(self.token(operator)?, self.boxed(lhs)?, self.boxed(rhs)?)
+            }
+            "MultiBop" => {
+                let [lhs, others] = elements(content)?;
+                let others = others
+                    .as_array()
+                    .ok_or_else(|| format!("Expected an array of operations, found {others}"))?
+                    .iter()
+                    .map(|other| {
+                        let [operator, rhs] = elements(other)?;
+                        Ok((self.token(operator)?, self.boxed(rhs)?))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Expression::MultiBop(self.boxed(lhs)?, others)
+            }
+            "Formula" => {
+                let [tilde, term] = elements(content)?;
+                Expression::Formula(self.token(tilde)?, self.boxed(term)?)
+            }
+            "FunctionDef" => Expression::FunctionDef(FunctionDefinition::new(
+                self.token(field(content, "keyword")?)?,
+                self.args(field(content, "arguments")?)?,
+                self.boxed(field(content, "body")?)?,
+            )),
+            "LambdaFunction" => Expression::LambdaFunction(Lambda {
+                keyword: self.token(field(content, "keyword")?)?,
+                args: self.args(field(content, "args")?)?,
+                body: self.boxed(field(content, "body")?)?,
+            }),
+            "IfExpression" => Expression::IfExpression(IfExpression {
+                if_conditional: self.if_conditional(field(content, "if_conditional")?)?,
+                else_ifs: match content.get("else_ifs") {
+                    None | Some(Value::Null) => vec![],
+                    Some(else_ifs) => else_ifs
+                        .as_array()
+                        .ok_or_else(|| format!("Expected an array, found {else_ifs}"))?
+                        .iter()
+                        .map(|else_if| {
+                            Ok(ElseIfConditional {
+                                else_keyword: self.token(field(else_if, "else_keyword")?)?,
+                                if_conditional: self
+                                    .if_conditional(field(else_if, "if_conditional")?)?,
+                            })
+                        })
+                        .collect::<Result<_, String>>()?,
+                },
+                trailing_else: match content.get("trailing_else") {
+                    None | Some(Value::Null) => None,
+                    Some(trailing_else) => Some(TrailingElse {
+                        else_keyword: self.token(field(trailing_else, "else_keyword")?)?,
+                        body: self.boxed(field(trailing_else, "body")?)?,
+                    }),
+                },
+            }),
+            "WhileExpression" => Expression::WhileExpression(WhileExpression {
+                while_keyword: self.token(field(content, "while_keyword")?)?,
+                condition: self.boxed(field(content, "condition")?)?,
+                body: self.boxed(field(content, "body")?)?,
+            }),
+            "RepeatExpression" => Expression::RepeatExpression(RepeatExpression {
+                repeat_keyword: self.token(field(content, "repeat_keyword")?)?,
+                body: self.boxed(field(content, "body")?)?,
+            }),
+            "FunctionCall" => Expression::FunctionCall(FunctionCall {
+                function_ref: self.boxed(field(content, "function_ref")?)?,
+                args: self.args(field(content, "args")?)?,
+            }),
+            "SubsetExpression" => Expression::SubsetExpression(SubsetExpression {
+                object_ref: self.boxed(field(content, "object_ref")?)?,
+                args: self.args(field(content, "args")?)?,
+            }),
+            "ForLoopExpression" => Expression::ForLoopExpression(ForLoop {
+                keyword: self.token(field(content, "keyword")?)?,
+                left_delim: self.delimiter(field(content, "left_delim")?)?,
+                identifier: self.boxed(field(content, "identifier")?)?,
+                in_keyword: self.token(field(content, "in_keyword")?)?,
+                collection: self.boxed(field(content, "collection")?)?,
+                right_delim: self.delimiter(field(content, "right_delim")?)?,
+                body: self.boxed(field(content, "body")?)?,
+            }),
+            kind => return Err(format!("Unknown kind of expression: {kind}")),
+        };
+        Ok(expression)
+    }
+
+    fn if_conditional(&self, value: &Value) -> Result<IfConditional<'a>, String> {
+        Ok(IfConditional {
+            keyword: self.token(field(value, "keyword")?)?,
+            left_delimiter: self.token(field(value, "left_delimiter")?)?,
+            condition: self.boxed(field(value, "condition")?)?,
+            right_delimiter: self.token(field(value, "right_delimiter")?)?,
+            body: self.boxed(field(value, "body")?)?,
+        })
+    }
+
+    fn args(&self, value: &Value) -> Result<Args<'a>, String> {
+        let args = field(value, "args")?;
+        Ok(Args::new(
+            self.delimiter(field(value, "left_delimeter")?)?,
+            args.as_array()
+                .ok_or_else(|| format!("Expected an array of arguments, found {args}"))?
+                .iter()
+                .map(|arg| self.arg(arg))
+                .collect::<Result<_, _>>()?,
+            self.delimiter(field(value, "right_delimeter")?)?,
+        ))
+    }
+
+    fn arg(&self, value: &Value) -> Result<Arg<'a>, String> {
+        match variant(value)? {
+            ("Proper", content) => {
+                let [argument, comma] = elements(content)?;
+                Ok(Arg::Proper(
+                    self.optional_expression(argument)?,
+                    self.optional_expression(comma)?,
+                ))
+            }
+            ("EmptyEqual", content) => {
+                let [name, equal, comma] = elements(content)?;
+                Ok(Arg::EmptyEqual(
+                    self.expression(name)?,
+                    self.token(equal)?,
+                    self.optional_expression(comma)?,
+                ))
+            }
+            (kind, _) => Err(format!("Unknown kind of argument: {kind}")),
+        }
+    }
+
+    fn delimiter(&self, value: &Value) -> Result<Delimiter<'a>, String> {
+        match variant(value)? {
+            ("Paren", token) => Ok(Delimiter::Paren(self.token(token)?)),
+            ("SingleBracket", token) => Ok(Delimiter::SingleBracket(self.token(token)?)),
+            ("DoubleBracket", tokens) => {
+                let [first, second] = elements(tokens)?;
+                Ok(Delimiter::DoubleBracket((
+                    self.token(first)?,
+                    self.token(second)?,
+                )))
+            }
+            (kind, _) => Err(format!("Unknown kind of delimiter: {kind}")),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Checking that formatting kept every token of the code.
+
+use std::collections::BTreeMap;
+
+use formatter::comments::{is_prose_comment, section_marker};
+use tokenizer::{Token, Tokenizer};
+
+/// Counts the tokens of the code, except for the line breaks, the semicolons
+/// and the commas. The formatter may add or remove the first two and the
+/// trailing commas of the calls, so the commas are compared by the check
+/// of the meaning of the formatted code instead. The comments are counted
+/// by their text, because the formatter may move a comment to its own line,
+/// and the section comments, e.g. `# Setup ----`, without their marker,
+/// because the formatter may pad it.
+///
+/// The roxygen and the prose comments are joined without their whitespace
+/// and markers, because formatting the examples of the roxygen comments
+/// changes the whitespace and the lines of the code, and the prose comments
+/// may be split into more lines.
+fn count_tokens(code: &str) -> (BTreeMap<String, usize>, String) {
+    let mut tokenizer = Tokenizer::new(code);
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut joined = String::new();
+    let mut join =
+        |text: &str| joined.extend(text.chars().filter(|character| !character.is_whitespace()));
+    for token in tokenizer.tokenize() {
+        let key = match token.token {
+            Token::Newline | Token::Semicolon | Token::Comma | Token::EOF => continue,
+            Token::Comment(text) | Token::InlineComment(text) if text.starts_with("#'") => {
+                join(&text[2..]);
+                continue;
+            }
+            Token::Comment(text) | Token::InlineComment(text) if is_prose_comment(text) => {
+                join(text.trim_start_matches('#'));
+                continue;
+            }
+            Token::Comment(text) | Token::InlineComment(text) => match section_marker(text) {
+                Some(marker) => text.trim_end().trim_end_matches(marker).to_string(),
+                None => text.trim_end().to_string(),
+            },
+            token => format!("{token:?}"),
+        };
+        *counts.entry(key).or_default() += 1;
+    }
+    (counts, joined)
+}
+
+/// Fails if formatting dropped or duplicated any token of the code,
+/// e.g. a comment or a string.
+///
+/// Unlike the check of the meaning of the formatted code, which ignores
+/// the comments, this compares the tokens themselves, so it also finds
+/// lost comments. The line breaks, the semicolons and the commas are not compared.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::check_tokens_kept;
+///
+/// assert!(check_tokens_kept("a<-1 # one", "a <- 1 # one\n").is_ok());
+/// assert!(check_tokens_kept("a<-1 # one", "a <- 1\n").is_err());
+/// ```
+pub fn check_tokens_kept(original: &str, formatted: &str) -> Result<(), String> {
+    let (before, comments_before) = count_tokens(original);
+    let (after, comments_after) = count_tokens(formatted);
+    let changed = before
+        .iter()
+        .find(|(token, count)| after.get(*token) != Some(count))
+        .map(|(token, count)| (token, *count))
+        .or_else(|| {
+            after
+                .iter()
+                .find(|(token, _)| !before.contains_key(*token))
+                .map(|(token, _)| (token, 0))
+        });
+    let message = match changed {
+        Some((token, count)) => format!(
+            "The code has {count} of {token}, but the formatted code has {}",
+            after.get(token).copied().unwrap_or_default()
+        ),
+        None if comments_before != comments_after => {
+            "Formatting changed the text of the comments".to_string()
+        }
+        None => return Ok(()),
+    };
+    Err(format!(
+        "{message}. This is a bug in tergo; please report it with the code \
+         at https://github.com/kpagacz/tergo/issues"
+    ))
+}
@@ -0,0 +1,124 @@
+//! Measuring the performance of formatting, e.g. to report a slow file.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    time::Duration,
+};
+
+use formatter::{format_code_timed, timing::Stopwatch};
+use parser::{
+    ast::{Expression, TermExpr},
+    parse, pre_parse,
+};
+use tokenizer::Tokenizer;
+
+use crate::{Config, check_limits};
+
+thread_local! {
+    /// The number of the allocations of this thread made with [CountingAllocator].
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The global allocator counting the allocations of each thread,
+/// so [format_timings] can report them. Without it, the allocations
+/// are not counted.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::bench::CountingAllocator;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator;
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingAllocator;
+
+// SAFETY: the allocations are made by the system allocator, which upholds
+// the contract. Counting them does not allocate.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        // SAFETY: the caller upholds the contract of `alloc`
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: the caller upholds the contract of `dealloc`
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        // SAFETY: the caller upholds the contract of `realloc`
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// The time spent in the stages of formatting the code
+/// and the allocations made.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FormatTimings {
+    /// Tokenizing and parsing the code.
+    pub parsing: Duration,
+    /// Rewriting the parsed code and building the documents of its layout.
+    pub doc_construction: Duration,
+    /// Fitting the documents in the line length and printing them.
+    pub layout: Duration,
+    /// The number of the allocations, or `None` if [CountingAllocator]
+    /// is not the global allocator.
+    pub allocations: Option<u64>,
+}
+
+impl std::fmt::Display for FormatTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{:.1} ms parsing, {:.1} ms doc construction, {:.1} ms layout",
+            millis(self.parsing),
+            millis(self.doc_construction),
+            millis(self.layout)
+        )?;
+        match self.allocations {
+            Some(allocations) => write!(f, ", {allocations} allocations"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats the code and measures the time spent in each stage.
+///
+/// Unlike [crate::tergo_format], this does not check the meaning of the formatted
+/// code nor format the roxygen examples, so only the formatter itself
+/// is measured.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, bench::format_timings};
+///
+/// let timings = format_timings("f <- function(x) {x+1}", &Config::default()).unwrap();
+/// println!("{timings}");
+/// ```
+pub fn format_timings(source: &str, config: &Config) -> Result<FormatTimings, String> {
+    check_limits(source, config)?;
+    let allocations_before = ALLOCATIONS.with(Cell::get);
+    let started = Stopwatch::start();
+    let mut tokenizer = Tokenizer::new(source);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let cst = parse(parser::Input(&tokens))?;
+    let parsing = started.elapsed();
+    let top_node = Expression::Term(Box::new(TermExpr::new(None, cst, None)));
+    let (_, layout_timings) = format_code_timed(top_node, config);
+    let allocations = ALLOCATIONS.with(Cell::get) - allocations_before;
+    Ok(FormatTimings {
+        parsing,
+        doc_construction: layout_timings.doc_construction,
+        layout: layout_timings.layout,
+        // Parsing always allocates, so no allocations means they are not counted
+        allocations: (allocations > 0).then_some(allocations),
+    })
+}
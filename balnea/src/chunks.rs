@@ -0,0 +1,100 @@
+//! Formatting of R code embedded in other documents.
+
+use crate::{Config, tergo_format};
+
+/// A line of a document, split into its content and its line terminator.
+pub(crate) struct Line<'a> {
+    pub(crate) content: &'a str,
+    pub(crate) terminator: &'a str,
+}
+
+impl<'a> Line<'a> {
+    fn new(line: &'a str) -> Self {
+        let content = line.trim_end_matches(['\n', '\r']);
+        Self {
+            content,
+            terminator: &line[content.len()..],
+        }
+    }
+}
+
+/// Splits a document into lines, keeping the line terminators,
+/// so the document can be put back together byte for byte.
+pub(crate) fn lines(input: &str) -> Vec<Line<'_>> {
+    input.split_inclusive('\n').map(Line::new).collect()
+}
+
+/// Appends a line to the output unchanged.
+pub(crate) fn push_line(output: &mut String, line: &Line<'_>) {
+    output.push_str(line.content);
+    output.push_str(line.terminator);
+}
+
+/// Returns the number of the blank lines at the start and at the end
+/// of a chunk. Formatting removes them, but they separate the code
+/// from the surrounding text, so they are kept as they are.
+pub(crate) fn count_blank_ends(
+    lines: &[Line<'_>],
+    is_blank: impl Fn(&Line<'_>) -> bool,
+) -> (usize, usize) {
+    let leading = lines.iter().take_while(|line| is_blank(line)).count();
+    let trailing = lines[leading..]
+        .iter()
+        .rev()
+        .take_while(|line| is_blank(line))
+        .count();
+    (leading, trailing)
+}
+
+/// Formats the R code in a chunk.
+///
+/// `prefix` is the text preceding the code on every line of the chunk,
+/// e.g. the indentation of a chunk nested in a list or the `> ` markers
+/// of a chunk in a block quote. It is removed before formatting and
+/// added back afterwards. The line length is shortened by the width
+/// of the prefix, so the formatted lines still fit.
+///
+/// Returns `None` if the chunk should be left as it is, i.e. if it is empty
+/// or some of its lines do not start with the prefix.
+pub(crate) fn format_chunk(
+    body: &[Line<'_>],
+    prefix: &str,
+    config: &Config,
+) -> Option<Result<String, String>> {
+    if body.iter().all(|line| line.content.trim().is_empty()) {
+        return None;
+    }
+    let mut code = String::new();
+    for line in body {
+        match line.content.strip_prefix(prefix) {
+            Some(stripped) => code.push_str(stripped),
+            None if line.content.trim().is_empty() => {}
+            None if prefix.trim_end() == line.content => {}
+            None => return None,
+        }
+        code.push('\n');
+    }
+    let mut chunk_config = config.clone();
+    chunk_config.line_length.0 -= prefix.chars().count() as i32;
+    let formatted = match tergo_format(&code, Some(&chunk_config)) {
+        Ok(formatted) => formatted,
+        Err(e) => return Some(Err(e)),
+    };
+    let terminator = body.first().map_or("\n", |line| line.terminator);
+    let terminator = if terminator.is_empty() {
+        "\n"
+    } else {
+        terminator
+    };
+    let mut chunk = String::new();
+    for line in formatted.lines() {
+        if line.is_empty() {
+            chunk.push_str(prefix.trim_end());
+        } else {
+            chunk.push_str(prefix);
+            chunk.push_str(line);
+        }
+        chunk.push_str(terminator);
+    }
+    Some(Ok(chunk))
+}
@@ -0,0 +1,35 @@
+//! Comparing the syntax trees of the native parser and tree-sitter-r.
+
+use parser::{parse_with_token_ranges, parse_with_tree_sitter, pre_parse};
+use tokenizer::Tokenizer;
+
+/// Parses the code with the native parser and with tree-sitter-r and
+/// compares the syntax trees.
+///
+/// Returns an error with the line of the first top-level expression the
+/// parsers read differently, or the error of either parser.
+pub fn cross_check_parsers(code: &str) -> Result<(), String> {
+    let mut tokenizer = Tokenizer::new(code);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let native = parse_with_token_ranges(parser::Input(&tokens))
+        .map_err(|err| format!("The native parser could not parse the code: {err}"))?;
+    let tree_sitter = parse_with_tree_sitter(code, parser::Input(&tokens))?;
+    let difference = native
+        .iter()
+        .zip(&tree_sitter)
+        .position(|((native, _), tree_sitter)| native != tree_sitter);
+    let difference = match difference {
+        Some(difference) => difference,
+        None if native.len() == tree_sitter.len() => return Ok(()),
+        None => native.len().min(tree_sitter.len()),
+    };
+    let offset = native
+        .get(difference)
+        .and_then(|(_, range)| tokens.get(range.start))
+        .map_or(code.len(), |token| token.offset);
+    Err(format!(
+        "The native parser and tree-sitter-r read the code at line {} differently",
+        code[..offset].matches('\n').count() + 1
+    ))
+}
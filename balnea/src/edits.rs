@@ -0,0 +1,68 @@
+//! Edits turning the original code into the formatted one.
+
+use std::ops::Range;
+
+use similar::{DiffOp, TextDiff};
+
+use crate::{Config, tergo_format};
+
+/// A replacement of a part of the original code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// The byte offsets of the replaced part of the original code.
+    /// The edits always replace whole lines, so the range starts at
+    /// the beginning of a line.
+    pub range: Range<usize>,
+    /// The text replacing the range.
+    pub new_text: String,
+}
+
+/// Returns the edits turning `original` into `formatted`.
+///
+/// The edits replace only the changed lines, do not overlap and are sorted
+/// by their position, so they refer to the offsets in `original` and can be
+/// applied from the last to the first.
+pub fn diff_edits(original: &str, formatted: &str) -> Vec<Edit> {
+    let diff = TextDiff::from_lines(original, formatted);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    let mut line_offsets = Vec::with_capacity(old_lines.len() + 1);
+    let mut offset = 0;
+    line_offsets.push(offset);
+    for line in old_lines {
+        offset += line.len();
+        line_offsets.push(offset);
+    }
+    diff.ops()
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+        .map(|op| {
+            let old_range = op.old_range();
+            Edit {
+                range: line_offsets[old_range.start]..line_offsets[old_range.end],
+                new_text: new_lines[op.new_range()].concat(),
+            }
+        })
+        .collect()
+}
+
+/// Formats the code and returns the edits turning the code
+/// into the formatted code, see [diff_edits].
+///
+/// Applying a few small edits instead of replacing the whole code lets
+/// the editors keep the cursor position and the undo history.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::format_edits;
+///
+/// let source = "a <- 1\nb<-2\nc <- 3\n";
+/// let edits = format_edits(source, None).unwrap();
+/// assert_eq!(edits.len(), 1);
+/// assert_eq!(&source[edits[0].range.clone()], "b<-2\n");
+/// assert_eq!(edits[0].new_text, "b <- 2\n");
+/// ```
+pub fn format_edits(source: &str, config: Option<&Config>) -> Result<Vec<Edit>, String> {
+    Ok(diff_edits(source, &tergo_format(source, config)?))
+}
@@ -0,0 +1,361 @@
+//! The entry points of the fuzz targets in `fuzz/`.
+//!
+//! Only compiled with `--cfg fuzzing`, which `cargo fuzz` sets.
+//! Every function panics when it finds a bug, so the fuzzer reports the input.
+
+use std::fmt::Write;
+
+use arbitrary::Arbitrary;
+use tokenizer::Tokenizer;
+
+use crate::{Config, check_idempotent, check_tokens_kept, find_syntax_error, tergo_format};
+
+/// The deepest nesting of the generated expressions. Deeper expressions
+/// are replaced by a symbol, so the generated code stays readable.
+const MAX_DEPTH: usize = 12;
+
+/// Tokenizes arbitrary text. The tokenizer must not panic on any input.
+pub fn fuzz_tokenizer(data: &[u8]) {
+    if let Ok(input) = std::str::from_utf8(data) {
+        Tokenizer::new(input).tokenize();
+    }
+}
+
+/// Formats arbitrary text. Formatting must not panic, must keep every token
+/// and formatting the formatted code must not change it.
+///
+/// The text is often not valid R, so failing to format it is not a bug.
+pub fn fuzz_format(data: &[u8]) {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let config = Config::default();
+    let Ok(formatted) = tergo_format(input, Some(&config)) else {
+        return;
+    };
+    if let Err(error) = check_tokens_kept(input, &formatted)
+        .and_then(|_| check_idempotent(&formatted, Some(&config)).map(|_| ()))
+    {
+        panic!("{error}\nThe code:\n{input}");
+    }
+}
+
+/// Prints the generated program, parses it, formats it and checks that
+/// the formatted code means the same and that formatting it again
+/// does not change it.
+pub fn fuzz_round_trip(program: &Program) {
+    let code = program.to_string();
+    if let Some(error) = find_syntax_error(&code) {
+        panic!("The generated code does not parse at {error}:\n{code}");
+    }
+    let config = Config::builder()
+        .indent(i32::from(program.indent % 8 + 1))
+        .line_length(i32::from(program.line_length) + 10)
+        .build();
+    if let Err(error) = check_idempotent(&code, Some(&config)) {
+        panic!("{error}\nThe code:\n{code}");
+    }
+}
+
+/// A generated R script, together with the options to format it with.
+#[derive(Debug, Arbitrary)]
+pub struct Program {
+    indent: u8,
+    line_length: u8,
+    statements: Vec<Statement>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Statement {
+    Expression(Expr, Option<Comment>),
+    Assignment(Name, Expr, Option<Comment>),
+    Comment(Comment),
+    BlankLine,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Expr {
+    Symbol(Name),
+    Number(u16),
+    String(Name),
+    Constant(Constant),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Parenthesized(Box<Expr>),
+    Dollar(Box<Expr>, Name),
+    Call(Name, Vec<Argument>),
+    Subset(Box<Expr>, Vec<Expr>),
+    Function {
+        lambda: bool,
+        parameters: Vec<(Name, Option<Expr>)>,
+        body: Vec<Statement>,
+    },
+    If(Box<Expr>, Vec<Statement>, Option<Vec<Statement>>),
+    For(Name, Box<Expr>, Vec<Statement>),
+    While(Box<Expr>, Vec<Statement>),
+    Repeat(Vec<Statement>),
+}
+
+#[derive(Debug, Arbitrary)]
+struct Argument {
+    name: Option<Name>,
+    value: Expr,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Name(u8);
+
+#[derive(Debug, Arbitrary)]
+struct Comment(Name);
+
+#[derive(Debug, Arbitrary)]
+enum Constant {
+    True,
+    False,
+    Null,
+    Na,
+    Inf,
+}
+
+#[derive(Debug, Arbitrary)]
+enum UnaryOp {
+    Minus,
+    Plus,
+    Not,
+    Tilde,
+}
+
+#[derive(Debug, Arbitrary)]
+enum BinaryOp {
+    Plus,
+    Minus,
+    Times,
+    Divide,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    GreaterEqual,
+    And,
+    VectorizedOr,
+    Range,
+    In,
+    Pipe,
+    Tilde,
+}
+
+const NAMES: [&str; 8] = [
+    "x",
+    "y",
+    "df",
+    "value",
+    ".data",
+    "a_rather_long_variable_name",
+    "another_quite_long_name_for_a_variable",
+    "`non syntactic`",
+];
+
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(NAMES[usize::from(self.0) % NAMES.len()])
+    }
+}
+
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut code = String::new();
+        print_statements(&self.statements, 0, &mut code)?;
+        f.write_str(&code)
+    }
+}
+
+fn print_statements(statements: &[Statement], depth: usize, code: &mut String) -> std::fmt::Result {
+    for statement in statements {
+        match statement {
+            Statement::Expression(expr, comment) => {
+                print_expr(expr, depth, code)?;
+                print_inline_comment(comment, code)?;
+            }
+            Statement::Assignment(name, expr, comment) => {
+                write!(code, "{name} <- ")?;
+                print_expr(expr, depth, code)?;
+                print_inline_comment(comment, code)?;
+            }
+            Statement::Comment(Comment(text)) => write!(code, "# {text}")?,
+            Statement::BlankLine => {}
+        }
+        code.push('\n');
+    }
+    Ok(())
+}
+
+fn print_inline_comment(comment: &Option<Comment>, code: &mut String) -> std::fmt::Result {
+    match comment {
+        Some(Comment(text)) => write!(code, " # {text}"),
+        None => Ok(()),
+    }
+}
+
+fn print_block(statements: &[Statement], depth: usize, code: &mut String) -> std::fmt::Result {
+    code.push_str("{\n");
+    print_statements(statements, depth + 1, code)?;
+    code.push('}');
+    Ok(())
+}
+
+/// Prints the expression, wrapping it in parentheses unless it is atomic,
+/// so the generated code keeps the structure of the generated tree.
+fn print_operand(expr: &Expr, depth: usize, code: &mut String) -> std::fmt::Result {
+    let atomic = depth >= MAX_DEPTH
+        || matches!(
+            expr,
+            Expr::Symbol(_)
+                | Expr::Number(_)
+                | Expr::String(_)
+                | Expr::Constant(_)
+                | Expr::Parenthesized(_)
+                | Expr::Call(..)
+                | Expr::Subset(..)
+                | Expr::Dollar(..)
+        );
+    if atomic {
+        print_expr(expr, depth, code)
+    } else {
+        code.push('(');
+        print_expr(expr, depth, code)?;
+        code.push(')');
+        Ok(())
+    }
+}
+
+fn print_expr(expr: &Expr, depth: usize, code: &mut String) -> std::fmt::Result {
+    if depth >= MAX_DEPTH {
+        code.push('x');
+        return Ok(());
+    }
+    let depth = depth + 1;
+    match expr {
+        Expr::Symbol(name) => write!(code, "{name}"),
+        Expr::Number(number) => write!(code, "{number}"),
+        Expr::String(name) => write!(code, "\"{}\"", name.to_string().replace('`', "")),
+        Expr::Constant(constant) => code.write_str(match constant {
+            Constant::True => "TRUE",
+            Constant::False => "FALSE",
+            Constant::Null => "NULL",
+            Constant::Na => "NA",
+            Constant::Inf => "Inf",
+        }),
+        Expr::Unary(op, operand) => {
+            code.push_str(match op {
+                UnaryOp::Minus => "-",
+                UnaryOp::Plus => "+",
+                UnaryOp::Not => "!",
+                UnaryOp::Tilde => "~",
+            });
+            print_operand(operand, depth, code)
+        }
+        Expr::Binary(op, left, right) => {
+            print_operand(left, depth, code)?;
+            code.push_str(match op {
+                BinaryOp::Plus => " + ",
+                BinaryOp::Minus => " - ",
+                BinaryOp::Times => " * ",
+                BinaryOp::Divide => " / ",
+                BinaryOp::Power => "^",
+                BinaryOp::Equal => " == ",
+                BinaryOp::NotEqual => " != ",
+                BinaryOp::Less => " < ",
+                BinaryOp::GreaterEqual => " >= ",
+                BinaryOp::And => " && ",
+                BinaryOp::VectorizedOr => " | ",
+                BinaryOp::Range => ":",
+                BinaryOp::In => " %in% ",
+                BinaryOp::Pipe => " %>% ",
+                BinaryOp::Tilde => " ~ ",
+            });
+            print_operand(right, depth, code)
+        }
+        Expr::Parenthesized(inner) => {
+            code.push('(');
+            print_expr(inner, depth, code)?;
+            code.push(')');
+            Ok(())
+        }
+        Expr::Dollar(object, name) => {
+            print_operand(object, depth, code)?;
+            write!(code, "${name}")
+        }
+        Expr::Call(function, arguments) => {
+            write!(code, "{function}(")?;
+            for (index, argument) in arguments.iter().enumerate() {
+                if index > 0 {
+                    code.push_str(", ");
+                }
+                if let Some(name) = &argument.name {
+                    write!(code, "{name} = ")?;
+                }
+                print_expr(&argument.value, depth, code)?;
+            }
+            code.push(')');
+            Ok(())
+        }
+        Expr::Subset(object, indices) => {
+            print_operand(object, depth, code)?;
+            code.push('[');
+            for (index, expr) in indices.iter().enumerate() {
+                if index > 0 {
+                    code.push_str(", ");
+                }
+                print_expr(expr, depth, code)?;
+            }
+            code.push(']');
+            Ok(())
+        }
+        Expr::Function {
+            lambda,
+            parameters,
+            body,
+        } => {
+            code.push_str(if *lambda { "\\(" } else { "function(" });
+            for (index, (name, default)) in parameters.iter().enumerate() {
+                if index > 0 {
+                    code.push_str(", ");
+                }
+                write!(code, "{name}")?;
+                if let Some(default) = default {
+                    code.push_str(" = ");
+                    print_expr(default, depth, code)?;
+                }
+            }
+            code.push_str(") ");
+            print_block(body, depth, code)
+        }
+        Expr::If(condition, then, otherwise) => {
+            code.push_str("if (");
+            print_expr(condition, depth, code)?;
+            code.push_str(") ");
+            print_block(then, depth, code)?;
+            if let Some(otherwise) = otherwise {
+                code.push_str(" else ");
+                print_block(otherwise, depth, code)?;
+            }
+            Ok(())
+        }
+        Expr::For(variable, sequence, body) => {
+            write!(code, "for ({variable} in ")?;
+            print_expr(sequence, depth, code)?;
+            code.push_str(") ");
+            print_block(body, depth, code)
+        }
+        Expr::While(condition, body) => {
+            code.push_str("while (");
+            print_expr(condition, depth, code)?;
+            code.push_str(") ");
+            print_block(body, depth, code)
+        }
+        Expr::Repeat(body) => {
+            code.push_str("repeat ");
+            print_block(body, depth, code)
+        }
+    }
+}
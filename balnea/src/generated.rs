@@ -0,0 +1,34 @@
+//! Detecting the files generated by a tool.
+
+use crate::Config;
+
+/// The number of the first lines of a file searched for the markers.
+const MARKER_LINES: usize = 5;
+
+/// Whether the code was generated by a tool, e.g. by roxygen2 or Rcpp:
+/// a comment in its first lines contains one of the `generated_file_markers`
+/// of the configuration.
+///
+/// The tool overwrites the generated files, so formatting them is pointless.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, is_generated_file};
+///
+/// let code = "# Generated by roxygen2: do not edit by hand\n\nexport(f)\n";
+/// assert!(is_generated_file(code, &Config::default()));
+/// assert!(!is_generated_file("f <- function(x) x\n", &Config::default()));
+/// ```
+pub fn is_generated_file(code: &str, config: &Config) -> bool {
+    code.lines()
+        .take(MARKER_LINES)
+        .filter_map(|line| line.trim_start().strip_prefix('#'))
+        .any(|comment| {
+            config
+                .generated_file_markers
+                .0
+                .iter()
+                .any(|marker| !marker.is_empty() && comment.contains(marker.as_str()))
+        })
+}
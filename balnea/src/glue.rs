@@ -0,0 +1,146 @@
+//! The code in the interpolations of the `glue()` strings.
+
+use log::trace;
+use tokenizer::{Token, Tokenizer, tokens::CommentedToken};
+
+use crate::{Config, tergo_format};
+
+/// The names of the function whose string arguments are `glue` templates.
+const GLUE_FUNCTIONS: [&str; 2] = ["glue", "glue::glue"];
+
+/// Formats the code in the `{...}` interpolations of the strings passed
+/// to `glue()`, e.g. `glue("{x+1}")` becomes `glue("{x + 1}")`.
+///
+/// Only the unnamed string arguments are templates. The calls with
+/// custom delimiters, the strings with escapes and the interpolations
+/// which do not parse or would span more lines are left untouched.
+pub(crate) fn format_glue_strings(code: &str, config: &Config) -> String {
+    let mut tokenizer = Tokenizer::new(code);
+    let tokens = tokenizer.tokenize();
+    let tokens: Vec<&CommentedToken> = tokens
+        .iter()
+        .filter(|token| {
+            !matches!(
+                token.token,
+                Token::Newline | Token::Comment(_) | Token::InlineComment(_)
+            )
+        })
+        .collect();
+    let mut edits = vec![];
+    for (index, pair) in tokens.windows(2).enumerate() {
+        let is_glue_call = matches!(pair[0].token, Token::Symbol(name) if GLUE_FUNCTIONS.contains(&name))
+            && matches!(pair[1].token, Token::LParen);
+        if is_glue_call {
+            edits.extend(template_edits(&tokens[index + 1..], config));
+        }
+    }
+    let mut formatted = code.to_string();
+    // Replacing the last strings first keeps the offsets of the others valid
+    for (offset, length, template) in edits.into_iter().rev() {
+        formatted.replace_range(offset..offset + length, &template);
+    }
+    formatted
+}
+
+/// Returns the offsets, the lengths and the formatted text of the templates
+/// in the arguments of the call, which start with `(`.
+fn template_edits(tokens: &[&CommentedToken], config: &Config) -> Vec<(usize, usize, String)> {
+    let mut edits = vec![];
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.token {
+            Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBrace | Token::RBracket => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            Token::Symbol(".open" | ".close") if depth == 1 => return vec![],
+            Token::Literal(text) if depth == 1 => {
+                let is_unnamed = matches!(tokens[index - 1].token, Token::LParen | Token::Comma)
+                    && tokens
+                        .get(index + 1)
+                        .is_some_and(|next| matches!(next.token, Token::Comma | Token::RParen));
+                if let Some(template) = is_unnamed.then(|| format_template(text, config)).flatten()
+                {
+                    edits.push((token.offset, text.len(), template));
+                }
+            }
+            _ => {}
+        }
+    }
+    edits
+}
+
+/// Formats the interpolations of the string literal, quotes included.
+/// Returns `None` if the string is not a template or does not change.
+fn format_template(text: &str, config: &Config) -> Option<String> {
+    let quote = text
+        .chars()
+        .next()
+        .filter(|quote| matches!(quote, '"' | '\''))?;
+    let content = text.get(1..text.len() - 1)?;
+    if content.contains('\\') {
+        return None;
+    }
+    let mut template = String::with_capacity(text.len());
+    template.push(quote);
+    let mut rest = content;
+    while let Some(start) = rest.find('{') {
+        template.push_str(&rest[..start]);
+        rest = &rest[start..];
+        // `{{` is a literal brace
+        if rest.starts_with("{{") {
+            template.push_str("{{");
+            rest = &rest[2..];
+            continue;
+        }
+        let end = closing_brace(rest)?;
+        let expression = &rest[1..end];
+        template.push('{');
+        template.push_str(
+            &format_interpolation(expression, quote, config).unwrap_or(expression.to_string()),
+        );
+        template.push('}');
+        rest = &rest[end + 1..];
+    }
+    template.push_str(rest);
+    template.push(quote);
+    (template != text).then_some(template)
+}
+
+/// Returns the index of the `}` closing the `{` at the start of the text.
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, character) in text.char_indices() {
+        match (quote, character) {
+            (Some(open), _) if character == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(character),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Formats the code of an interpolation, if it fits in one line
+/// and can be put in the string quoted with `quote`.
+fn format_interpolation(expression: &str, quote: char, config: &Config) -> Option<String> {
+    if expression.trim().is_empty() || expression.contains(['\n', '#']) {
+        return None;
+    }
+    let formatted = tergo_format(expression, Some(config))
+        .inspect_err(|e| trace!("Leaving the interpolation unformatted: {e}"))
+        .ok()?;
+    let formatted = formatted.trim_end_matches('\n');
+    (!formatted.contains(['\n', '\\', quote])).then(|| formatted.to_string())
+}
@@ -0,0 +1,104 @@
+//! Formatting of the code edited since it was last formatted.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::{
+    Config, check_limits,
+    chunks::{lines, push_line},
+    range::statement_lines,
+    tergo_format,
+};
+
+/// Formats R scripts like [tergo_format], but lays out again only
+/// the top-level statements which changed since the last call,
+/// e.g. the function edited between two saves of a file.
+/// The statements are separated by the blank lines.
+///
+/// The formatted statements are kept by the hash of their code and of
+/// the configuration. The statements not in the last code formatted
+/// are forgotten, so one formatter should be kept for each document.
+///
+/// With `align_assignments`, `blank_lines_between_functions` or plugins,
+/// the layout of a statement depends on the statements around it,
+/// so the whole code is formatted every time.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, IncrementalFormatter};
+///
+/// let mut formatter = IncrementalFormatter::new();
+/// let config = Config::default();
+/// assert_eq!(formatter.format("a<-1\n\nb<-2\n", &config).unwrap(), "a <- 1\n\nb <- 2\n");
+/// // Only `b <- 3` is laid out
+/// assert_eq!(formatter.format("a<-1\n\nb<-3\n", &config).unwrap(), "a <- 1\n\nb <- 3\n");
+/// ```
+#[derive(Debug, Default)]
+pub struct IncrementalFormatter {
+    /// The formatted statements, by the hashes of the configuration and of their code.
+    statements: HashMap<(u64, u64), String>,
+}
+
+impl IncrementalFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Formats the code, reusing the statements formatted by the last call.
+    pub fn format(&mut self, input: &str, config: &Config) -> Result<String, String> {
+        let is_independent = !config.align_assignments.0
+            && config.blank_lines_between_functions.0 == 0
+            && config.plugins.0.is_empty();
+        if !is_independent {
+            return tergo_format(input, Some(config));
+        }
+        check_limits(input, config)?;
+        let statements = statement_lines(input)?;
+        let lines = lines(input);
+        // Each segment is a run of the statements not separated by blank lines
+        // and the blank lines after it. The statements in a run are formatted
+        // together, because e.g. the assignments aligned by hand stay aligned.
+        let mut starts = vec![0];
+        for pair in statements.windows(2) {
+            if pair[0].end < pair[1].start {
+                starts.push(pair[1].start);
+            }
+        }
+        starts.push(lines.len());
+        let config_hash = hash(&serde_json::to_string(config).unwrap_or_default());
+        let mut statements = HashMap::with_capacity(starts.len());
+        let mut output = String::with_capacity(input.len());
+        for (index, segment) in starts.windows(2).enumerate() {
+            let segment = &lines[segment[0]..segment[1]];
+            let mut code = String::new();
+            segment.iter().for_each(|line| push_line(&mut code, line));
+            let key = (config_hash, hash(&code));
+            let formatted = match self.statements.remove(&key) {
+                Some(formatted) => formatted,
+                None => tergo_format(&code, Some(config))?,
+            };
+            output.push_str(&formatted);
+            // Formatting keeps a single blank line between the statements
+            let is_last = index + 2 == starts.len();
+            let is_followed_by_blank = segment.len() > 1
+                && segment
+                    .last()
+                    .is_some_and(|line| line.content.trim().is_empty());
+            if !is_last && is_followed_by_blank && !formatted.trim().is_empty() {
+                output.push('\n');
+            }
+            statements.insert(key, formatted);
+        }
+        self.statements = statements;
+        Ok(output)
+    }
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
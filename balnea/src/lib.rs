@@ -1,13 +1,113 @@
+mod ast_json;
+mod audit;
+pub mod bench;
+mod chunks;
+#[cfg(feature = "tree-sitter")]
+mod cross_check;
+mod edits;
+#[cfg(fuzzing)]
+pub mod fuzzing;
+mod generated;
+mod glue;
+mod incremental;
+mod limits;
+mod long_lines;
+mod notebook;
+// Reads the files with threads, which WebAssembly in the browser does not have
+#[cfg(not(target_arch = "wasm32"))]
+mod package;
+mod partial;
+mod range;
+mod report;
+mod rmarkdown;
+mod roxygen;
+mod spin;
+mod sweave;
+mod syntax;
+mod verify;
+
+use std::time::Duration;
+
+pub use ast_json::{format_ast_json, parse_to_ast_json};
+pub use audit::check_tokens_kept;
+#[cfg(feature = "tree-sitter")]
+pub use cross_check::cross_check_parsers;
+pub use edits::{Edit, diff_edits, format_edits};
 pub use formatter::config::Config;
+pub use formatter::config::ConfigBuilder;
 pub use formatter::config::FunctionLineBreaks;
-use formatter::format_code;
+pub use formatter::config::NormalizeAssignment;
+pub use formatter::config::TrailingComma;
+pub use formatter::plugin::FormattingPlugin;
+use formatter::{LayoutReport, format_code_with_report, rewrite::RewriteCounts, timing::Stopwatch};
+pub use generated::is_generated_file;
+pub use incremental::IncrementalFormatter;
+pub use limits::check_limits;
 use log::trace;
+pub use long_lines::{LongLine, find_long_lines};
+pub use notebook::tergo_format_notebook;
+#[cfg(not(target_arch = "wasm32"))]
+pub use package::{FileResult, format_package};
+/// The parsed R code, rewritten by [FormattingPlugin]s.
+pub use parser::ast;
 use parser::{
     ast::{Expression, TermExpr},
     parse, pre_parse,
 };
+pub use partial::tergo_format_partial;
+pub use range::tergo_format_range;
+pub use report::{FormatReport, tergo_format_with_report};
+pub use rmarkdown::{tergo_format_quarto, tergo_format_rmarkdown};
+pub use spin::{is_spin_script, tergo_format_spin};
+pub use sweave::tergo_format_sweave;
+pub use syntax::{SyntaxError, find_syntax_error};
 use tokenizer::Tokenizer;
 
+/// An error of formatting the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The code is not valid R code.
+    Syntax(SyntaxError),
+    /// The code could not be formatted for another reason.
+    Other(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(error) => error.fmt(f),
+            Error::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Formats the R code with the configuration.
+///
+/// This is the stable entry point for the Rust users of `tergo`.
+/// Unlike [tergo_format], it reports where the code fails to parse.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, format_str};
+///
+/// let config = Config::builder().indent(4).build();
+/// let formatted = format_str("f <- function(){1}", &config).unwrap();
+/// assert_eq!(formatted, "f <- function() {\n    1\n}\n");
+///
+/// let error = format_str("f <- function(", &config).unwrap_err();
+/// println!("{error}");
+/// ```
+pub fn format_str(input: &str, config: &Config) -> Result<String, Error> {
+    tergo_format(input, Some(config)).map_err(|message| match find_syntax_error(input) {
+        Some(error) => Error::Syntax(error),
+        None => Error::Other(message),
+    })
+}
+
 /// Format the input code with the given configuration.
 ///
 /// # Arguments
@@ -33,18 +133,165 @@ use tokenizer::Tokenizer;
 /// let formatted = tergo_format(input, Some(&config)).unwrap();
 /// ```
 pub fn tergo_format(input: &str, config: Option<&Config>) -> Result<String, String> {
+    tergo_format_timed(input, config).map(|(formatted, _)| formatted)
+}
+
+/// Formats the code like [tergo_format], then formats the result again
+/// and fails if the second pass changes anything.
+///
+/// Formatting the formatted code must leave it as it is, so this helper
+/// lets tests check that the formatter is stable on their code.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::check_idempotent;
+///
+/// let formatted = check_idempotent("f <- function(x) { # comment\n x }", None).unwrap();
+/// assert_eq!(formatted, "f <- function(x) { # comment\n  x\n}\n");
+/// ```
+pub fn check_idempotent(input: &str, config: Option<&Config>) -> Result<String, String> {
+    let formatted = tergo_format(input, config)?;
+    let formatted_again = tergo_format(&formatted, config)?;
+    match first_different_line(&formatted, &formatted_again) {
+        None => Ok(formatted),
+        Some(line) => Err(format!(
+            "Formatting the formatted code changes line {line}. This is a bug in tergo; \
+             please report it with the code at https://github.com/kpagacz/tergo/issues"
+        )),
+    }
+}
+
+/// Returns the first line, counted from 1, which differs between the texts.
+pub fn first_different_line(text: &str, other: &str) -> Option<usize> {
+    if text == other {
+        return None;
+    }
+    let mut lines = text.lines().zip(other.lines());
+    let different = lines.position(|(line, other_line)| line != other_line);
+    Some(different.unwrap_or_else(|| text.lines().count().min(other.lines().count())) + 1)
+}
+
+/// The time spent in the stages of formatting the code.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    /// Tokenizing and parsing the code.
+    pub parsing: Duration,
+    /// Laying out the parsed code.
+    pub layout: Duration,
+}
+
+/// Formats the code like [tergo_format] and measures the time
+/// spent in parsing and in laying out the code.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_timed;
+///
+/// let (formatted, timings) = tergo_format_timed("a<-1", None).unwrap();
+/// assert_eq!(formatted, "a <- 1\n");
+/// println!("Parsed in {:?}", timings.parsing);
+/// ```
+pub fn tergo_format_timed(
+    input: &str,
+    config: Option<&Config>,
+) -> Result<(String, Timings), String> {
     let default_config = Config::default();
     let config = config.unwrap_or(&default_config);
+    format_in_stages(input, config).map(|formatted| (formatted.code, formatted.timings))
+}
+
+/// The formatted code with what formatting it took.
+struct Formatted {
+    code: String,
+    timings: Timings,
+    layout: LayoutReport,
+    rewrites: RewriteCounts,
+}
+
+/// Formats the code and measures the stages of formatting it,
+/// see [tergo_format_timed] and [tergo_format_with_report].
+fn format_in_stages(input: &str, config: &Config) -> Result<Formatted, String> {
     trace!("Formatting with config: {config}");
+    check_limits(input, config)?;
+    let started = Stopwatch::start();
     let mut tokenizer = Tokenizer::new(input);
     trace!("Tokenizer created");
     let mut commented_tokens = tokenizer.tokenize();
+    let rewritten_texts = formatter::rewrite::rewritten_token_texts(&commented_tokens, config);
+    let mut rewrites =
+        formatter::rewrite::rewrite_tokens(&mut commented_tokens, &rewritten_texts, config);
     trace!("Tokens with comments: {commented_tokens:?}",);
     let tokens_without_comments = pre_parse(&mut commented_tokens);
     let tokens_without_comments = parser::Input(&tokens_without_comments);
     trace!("Tokens without comments: {}", &tokens_without_comments);
-    let cst = parse(tokens_without_comments)?;
-    let top_node = Expression::Term(Box::new(TermExpr::new(None, cst, None)));
+    let cst = parse_code(input, tokens_without_comments)?;
+    let parsing = started.elapsed();
+    let parsed = Stopwatch::start();
+    let mut top_node = Expression::Term(Box::new(TermExpr::new(None, cst, None)));
+    // The rewritten code is the one the formatted code must mean
+    for (rule, changes) in formatter::rewrite::rewrite(&mut top_node, config) {
+        *rewrites.entry(rule).or_default() += changes;
+    }
+    let original = (config.verify.0 && config.plugins.0.is_empty()).then(|| match &top_node {
+        Expression::Term(term) => verify::syntax_tree(&term.term),
+        _ => unreachable!("The top node is a term"),
+    });
     trace!("CST: {:?}", top_node);
-    Ok(format_code(top_node, config))
+    let (formatted, layout) = format_expression(top_node, config);
+    let timings = Timings {
+        parsing,
+        layout: parsed.elapsed(),
+    };
+    if let Some(original) = original {
+        verify::verify(&original, &formatted)?;
+    }
+    Ok(Formatted {
+        // The strings change, so they are formatted after checking the meaning
+        code: format_strings(formatted, config),
+        timings,
+        layout,
+        rewrites,
+    })
+}
+
+/// Parses the tokens of the code. With the `tree-sitter` feature, the code
+/// the native parser cannot parse is parsed with tree-sitter-r.
+#[cfg(feature = "tree-sitter")]
+pub(crate) fn parse_code<'a>(
+    code: &str,
+    tokens: parser::Input<'a, 'a>,
+) -> Result<Vec<Expression<'a>>, String> {
+    parse(tokens.clone())
+        .or_else(|err| parser::parse_with_tree_sitter(code, tokens).map_err(|_| err))
+}
+
+/// Parses the tokens of the code.
+#[cfg(not(feature = "tree-sitter"))]
+pub(crate) fn parse_code<'a>(
+    _code: &str,
+    tokens: parser::Input<'a, 'a>,
+) -> Result<Vec<Expression<'a>>, String> {
+    parse(tokens)
+}
+
+/// Lays out the parsed code and formats the roxygen examples in it.
+fn format_expression(top_node: Expression, config: &Config) -> (String, LayoutReport) {
+    let (formatted, layout) = format_code_with_report(top_node, config);
+    if config.format_roxygen_examples.0 {
+        (roxygen::format_examples(&formatted, config), layout)
+    } else {
+        (formatted, layout)
+    }
+}
+
+/// Formats the code in the strings of the formatted code, which changes
+/// the syntax tree, unlike the rest of formatting.
+fn format_strings(formatted: String, config: &Config) -> String {
+    if config.format_glue_strings.0 {
+        glue::format_glue_strings(&formatted, config)
+    } else {
+        formatted
+    }
 }
@@ -0,0 +1,49 @@
+//! The limits of the size of the code formatted.
+
+use tokenizer::{Token, Tokenizer};
+
+use crate::Config;
+
+/// Fails if the code is larger than the `max_file_size` of the configuration
+/// or nested deeper than its `max_expression_depth`.
+///
+/// Formatting such code would take too long or exhaust the stack,
+/// so it is skipped instead. The limits of 0 are not checked.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, check_limits};
+///
+/// let config = Config::builder().max_expression_depth(2).build();
+/// assert!(check_limits("f(g(x))", &config).is_ok());
+/// assert!(check_limits("f(g(h(x)))", &config).is_err());
+/// ```
+pub fn check_limits(code: &str, config: &Config) -> Result<(), String> {
+    let max_file_size = usize::try_from(config.max_file_size.0).unwrap_or_default();
+    if max_file_size > 0 && code.len() > max_file_size {
+        return Err(format!(
+            "The code is {} bytes long, more than the max_file_size of {max_file_size}",
+            code.len()
+        ));
+    }
+    let max_depth = usize::try_from(config.max_expression_depth.0).unwrap_or_default();
+    if max_depth == 0 {
+        return Ok(());
+    }
+    let mut tokenizer = Tokenizer::new(code);
+    let mut depth: usize = 0;
+    for token in tokenizer.tokenize() {
+        match token.token {
+            Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBrace | Token::RBracket => depth = depth.saturating_sub(1),
+            _ => continue,
+        }
+        if depth > max_depth {
+            return Err(format!(
+                "The code is nested deeper than the max_expression_depth of {max_depth}"
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Finding the lines the formatter could not fit in the line length.
+
+use tokenizer::{Token, Tokenizer};
+
+use crate::Config;
+
+/// The longest part of a token shown in [LongLine::token].
+const MAX_TOKEN_LENGTH: usize = 40;
+
+/// A line of the formatted code longer than the line length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LongLine {
+    /// The line, counted from 1.
+    pub line: usize,
+    /// The length of the line in characters.
+    pub length: usize,
+    /// The token which cannot be split and does not fit in the line,
+    /// e.g. a long string or comment, shortened to 40 characters.
+    /// `None` if no single token makes the line too long.
+    pub token: Option<String>,
+}
+
+impl std::fmt::Display for LongLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The line is {} characters long", self.length)?;
+        match &self.token {
+            Some(token) => write!(f, " because of the unbreakable `{token}`"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the lines of the formatted code which are still longer
+/// than the line length of the configuration.
+///
+/// The formatter breaks the lines only between the tokens, so the lines
+/// with a long string, symbol or comment may not fit. These lines are
+/// a limitation of the formatter, not code left unformatted.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, find_long_lines};
+///
+/// let config = Config::builder().line_length(20).build();
+/// let long_lines = find_long_lines("x <- \"a string too long to fit\"\n", &config);
+/// assert_eq!(long_lines[0].line, 1);
+/// assert_eq!(long_lines[0].token.as_deref(), Some("\"a string too long to fit\""));
+/// ```
+pub fn find_long_lines(formatted: &str, config: &Config) -> Vec<LongLine> {
+    let line_length = usize::try_from(config.line_length.0).unwrap_or_default();
+    let mut tokenizer = Tokenizer::new(formatted);
+    let tokens = tokenizer.tokenize();
+    // The byte ranges of the tokens, without the whitespace after them
+    let spans: Vec<(usize, usize)> = tokens
+        .windows(2)
+        .filter(|pair| !matches!(pair[0].token, Token::Newline | Token::EOF))
+        .map(|pair| {
+            let text = formatted[pair[0].offset..pair[1].offset].trim_end();
+            (pair[0].offset, pair[0].offset + text.len())
+        })
+        .collect();
+
+    let mut long_lines = vec![];
+    let mut start = 0;
+    for (index, line) in formatted.split_inclusive('\n').enumerate() {
+        let end = start + line.trim_end_matches(['\n', '\r']).len();
+        let length = formatted[start..end].chars().count();
+        if length > line_length {
+            let longest = spans
+                .iter()
+                .map(|&(from, to)| (from.max(start), to.min(end)))
+                .filter(|(from, to)| from < to)
+                .max_by_key(|&(from, to)| formatted[from..to].chars().count());
+            // The token is to blame if the rest of the line fits
+            let token = longest
+                .map(|(from, to)| &formatted[from..to])
+                .filter(|token| length - token.chars().count() <= line_length)
+                .map(shorten);
+            long_lines.push(LongLine {
+                line: index + 1,
+                length,
+                token,
+            });
+        }
+        start += line.len();
+    }
+    long_lines
+}
+
+fn shorten(token: &str) -> String {
+    if token.chars().count() <= MAX_TOKEN_LENGTH {
+        return token.to_string();
+    }
+    let mut shortened: String = token.chars().take(MAX_TOKEN_LENGTH - 1).collect();
+    shortened.push('…');
+    shortened
+}
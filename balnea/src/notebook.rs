@@ -0,0 +1,135 @@
+//! Jupyter notebooks (`.ipynb`).
+
+use serde::Serialize;
+use serde_json::{Value, ser::PrettyFormatter};
+
+use crate::{Config, tergo_format};
+
+/// Returns true if the notebook runs an R kernel.
+fn is_r_notebook(notebook: &Value) -> bool {
+    let metadata = &notebook["metadata"];
+    [
+        &metadata["kernelspec"]["language"],
+        &metadata["language_info"]["name"],
+    ]
+    .iter()
+    .any(|language| {
+        language
+            .as_str()
+            .is_some_and(|language| language.eq_ignore_ascii_case("r"))
+    })
+}
+
+/// Returns the source of a cell, which is either a string
+/// or an array of lines.
+fn cell_source(source: &Value) -> Option<String> {
+    match source {
+        Value::String(source) => Some(source.clone()),
+        Value::Array(lines) => lines
+            .iter()
+            .map(|line| line.as_str())
+            .collect::<Option<String>>(),
+        _ => None,
+    }
+}
+
+/// Formats the source of a cell. Jupyter does not end the cells
+/// with a new line, so the formatted code does not end with one either
+/// unless the original code did.
+fn format_cell(source: &str, config: &Config) -> Result<String, String> {
+    let formatted = tergo_format(source, Some(config))?;
+    if source.ends_with('\n') {
+        Ok(formatted)
+    } else {
+        Ok(formatted.trim_end_matches('\n').to_string())
+    }
+}
+
+/// Returns the indentation of the notebook's JSON, so the formatted
+/// notebook does not differ from the original in whitespace.
+/// Jupyter indents with one space.
+fn json_indentation(input: &str) -> String {
+    input
+        .lines()
+        .nth(1)
+        .map(|line| line[..line.len() - line.trim_start().len()].to_string())
+        .filter(|indentation| !indentation.is_empty())
+        .unwrap_or_else(|| " ".to_string())
+}
+
+/// Formats the code cells of a Jupyter notebook running an R kernel.
+///
+/// The Markdown cells, the outputs, the cell ids and the metadata
+/// are left untouched. The notebooks running other kernels are returned
+/// as they are. If no cell changes, the notebook is returned byte for byte.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_notebook;
+///
+/// let input = r#"{
+///  "cells": [{"cell_type": "code", "id": "a1", "metadata": {}, "outputs": [], "source": ["a<-1"]}],
+///  "metadata": {"kernelspec": {"language": "R", "name": "ir"}},
+///  "nbformat": 4,
+///  "nbformat_minor": 5
+/// }"#;
+/// let formatted = tergo_format_notebook(input, None).unwrap();
+/// assert!(formatted.contains(r#""a <- 1""#));
+/// ```
+pub fn tergo_format_notebook(input: &str, config: Option<&Config>) -> Result<String, String> {
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let mut notebook: Value =
+        serde_json::from_str(input).map_err(|e| format!("Invalid notebook: {e}"))?;
+    if !is_r_notebook(&notebook) {
+        return Ok(input.to_string());
+    }
+    let Some(cells) = notebook["cells"].as_array_mut() else {
+        return Err("Invalid notebook: missing cells".to_string());
+    };
+    let mut changed = false;
+    for (index, cell) in cells.iter_mut().enumerate() {
+        if cell["cell_type"] != "code" {
+            continue;
+        }
+        let Some(source) = cell_source(&cell["source"]) else {
+            return Err(format!("Invalid notebook: the source of cell {index}"));
+        };
+        if source.trim().is_empty() {
+            continue;
+        }
+        let formatted = format_cell(&source, config)
+            .map_err(|e| format!("Failed to format the cell {index}: {e}"))?;
+        if formatted == source {
+            continue;
+        }
+        changed = true;
+        cell["source"] = match cell["source"] {
+            Value::String(_) => Value::String(formatted),
+            _ => Value::Array(
+                formatted
+                    .split_inclusive('\n')
+                    .map(|line| Value::String(line.to_string()))
+                    .collect(),
+            ),
+        };
+    }
+    if !changed {
+        return Ok(input.to_string());
+    }
+    let indentation = json_indentation(input);
+    let mut output = Vec::with_capacity(input.len());
+    let mut serializer = serde_json::Serializer::with_formatter(
+        &mut output,
+        PrettyFormatter::with_indent(indentation.as_bytes()),
+    );
+    notebook
+        .serialize(&mut serializer)
+        .map_err(|e| format!("Failed to write the notebook: {e}"))?;
+    let mut output = String::from_utf8(output).map_err(|e| e.to_string())?;
+    if input.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
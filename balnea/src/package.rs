@@ -0,0 +1,160 @@
+//! Formatting the files of an R package.
+
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind, resume_unwind},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    Config, is_spin_script, tergo_format, tergo_format_notebook, tergo_format_quarto,
+    tergo_format_rmarkdown, tergo_format_spin, tergo_format_sweave,
+};
+
+/// The directories of an R package with code, relative to its root.
+const PACKAGE_DIRECTORIES: [&str; 4] = ["R", "tests", "vignettes", "inst"];
+
+/// The result of formatting a file of a package, see [format_package].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileResult {
+    /// The path of the file, relative to the root of the package.
+    pub path: PathBuf,
+    /// The formatted code of the file, or why it could not be read
+    /// or formatted.
+    pub formatted: Result<String, String>,
+    /// Whether the formatted code differs from the code of the file.
+    pub changed: bool,
+}
+
+/// Formats the files of the R package in `root`: the R scripts in `R/`
+/// and `tests/`, and the R scripts, R Markdown, Quarto, Sweave
+/// and notebook files in `vignettes/` and `inst/`.
+///
+/// Only the scripts in `R/` have the roxygen comments of the package,
+/// so the roxygen examples are formatted only there, if enabled.
+/// The files in the `exclusion_list`, relative to `root`, are skipped.
+/// The files are formatted in parallel and are not changed; the results
+/// are sorted by their paths. A file the formatter panics on has an error.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::path::Path;
+/// use tergo_lib::{Config, format_package};
+///
+/// for file in format_package(Path::new("mypackage"), &Config::default()) {
+///     if let (Ok(formatted), true) = (&file.formatted, file.changed) {
+///         std::fs::write(Path::new("mypackage").join(&file.path), formatted).unwrap();
+///     }
+/// }
+/// ```
+pub fn format_package(root: &Path, config: &Config) -> Vec<FileResult> {
+    let mut files = vec![];
+    for directory in PACKAGE_DIRECTORIES {
+        collect_files(root, Path::new(directory), directory != "R", &mut files);
+    }
+    files.retain(|file| !is_excluded(file, config));
+    files.sort();
+
+    let mut without_examples = config.clone();
+    without_examples.format_roxygen_examples.0 = false;
+    let next = AtomicUsize::new(0);
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut results: Vec<FileResult> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(files.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    while let Some(path) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let config = if path.starts_with("R") {
+                            config
+                        } else {
+                            &without_examples
+                        };
+                        let result =
+                            catch_unwind(AssertUnwindSafe(|| format_file(root, path, config)));
+                        results.push(result.unwrap_or_else(|panic| {
+                            let message = panic
+                                .downcast_ref::<&str>()
+                                .copied()
+                                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                                .unwrap_or("unknown error");
+                            FileResult {
+                                path: path.clone(),
+                                formatted: Err(format!("The formatter failed: {message}")),
+                                changed: false,
+                            }
+                        }));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|panic| resume_unwind(panic)))
+            .collect()
+    });
+    results.sort_by(|result, other| result.path.cmp(&other.path));
+    results
+}
+
+/// Adds the files with code in `directory`, relative to `root`, and in its
+/// subdirectories to `files`. Only the R scripts are added, unless
+/// `documents` is set.
+fn collect_files(root: &Path, directory: &Path, documents: bool, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(root.join(directory)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = directory.join(entry.file_name());
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => collect_files(root, &path, documents, files),
+            Ok(file_type) if file_type.is_file() => {
+                let extension = path.extension().and_then(|extension| extension.to_str());
+                let is_code = match extension {
+                    Some("R" | "r") => true,
+                    Some("Rmd" | "rmd" | "qmd" | "Rnw" | "rnw" | "ipynb") => documents,
+                    _ => false,
+                };
+                if is_code {
+                    files.push(path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_excluded(file: &Path, config: &Config) -> bool {
+    config.exclusion_list.0.iter().any(|excluded| {
+        let excluded = excluded.strip_prefix("./").unwrap_or(excluded);
+        file.starts_with(excluded)
+    })
+}
+
+fn format_file(root: &Path, path: &Path, config: &Config) -> FileResult {
+    let content = std::fs::read_to_string(root.join(path)).map_err(|error| error.to_string());
+    let formatted = content
+        .as_deref()
+        .map_err(Clone::clone)
+        .and_then(
+            |content| match path.extension().and_then(|extension| extension.to_str()) {
+                Some("Rmd" | "rmd") => tergo_format_rmarkdown(content, Some(config)),
+                Some("qmd") => tergo_format_quarto(content, Some(config)),
+                Some("Rnw" | "rnw") => tergo_format_sweave(content, Some(config)),
+                Some("ipynb") => tergo_format_notebook(content, Some(config)),
+                _ if is_spin_script(content) => tergo_format_spin(content, Some(config)),
+                _ => tergo_format(content, Some(config)),
+            },
+        );
+    let changed = match (&content, &formatted) {
+        (Ok(content), Ok(formatted)) => content != formatted,
+        _ => false,
+    };
+    FileResult {
+        path: path.to_path_buf(),
+        formatted,
+        changed,
+    }
+}
@@ -0,0 +1,113 @@
+//! Formatting of the valid parts of code with syntax errors.
+
+use std::ops::Range;
+
+use parser::{parse_failure, pre_parse};
+use tokenizer::Tokenizer;
+
+use crate::{
+    Config,
+    chunks::{Line, count_blank_ends, format_chunk, lines, push_line},
+    tergo_format,
+};
+
+/// Formats the valid top-level statements of the code and keeps
+/// the statements with syntax errors as they are.
+///
+/// Returns the code and the ranges of the lines, counted from 0,
+/// which were kept because they could not be parsed. Without syntax
+/// errors, the code is formatted like with [tergo_format].
+///
+/// After a syntax error, formatting resumes at the first line starting
+/// at the first column which does not close a bracket, so the code
+/// in a broken function body is kept with its indentation.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_partial;
+///
+/// let input = "a<-1\nb <- )\nc<-3\n";
+/// let (formatted, skipped) = tergo_format_partial(input, None).unwrap();
+/// assert_eq!(formatted, "a <- 1\nb <- )\nc <- 3\n");
+/// assert_eq!(skipped, [1..2]);
+/// ```
+pub fn tergo_format_partial(
+    input: &str,
+    config: Option<&Config>,
+) -> Result<(String, Vec<Range<usize>>), String> {
+    if failed_lines(input).is_none() {
+        return tergo_format(input, config).map(|formatted| (formatted, vec![]));
+    }
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let lines = lines(input);
+    let mut output = String::with_capacity(input.len());
+    let mut skipped: Vec<Range<usize>> = vec![];
+    let mut start = 0;
+    while start < lines.len() {
+        let mut code = String::new();
+        lines[start..]
+            .iter()
+            .for_each(|line| push_line(&mut code, line));
+        let Some((statement, error)) = failed_lines(&code) else {
+            push_formatted(&mut output, &lines[start..], config)?;
+            break;
+        };
+        let (statement, error) = (start + statement, start + error);
+        push_formatted(&mut output, &lines[start..statement], config)?;
+        let end = (error + 1..lines.len())
+            .find(|&index| starts_statement(&lines[index]))
+            .unwrap_or(lines.len());
+        lines[statement..end]
+            .iter()
+            .for_each(|line| push_line(&mut output, line));
+        let (_, trailing_blank) = count_blank_ends(&lines[statement..end], is_blank);
+        let broken = statement..end - trailing_blank;
+        match skipped.last_mut() {
+            Some(last) if last.end >= broken.start => last.end = broken.end,
+            _ => skipped.push(broken),
+        }
+        start = end;
+    }
+    Ok((output, skipped))
+}
+
+/// Returns the lines, counted from 0, of the start of the statement
+/// which does not parse and of the syntax error in it.
+fn failed_lines(code: &str) -> Option<(usize, usize)> {
+    let mut tokenizer = Tokenizer::new(code);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let failure = parse_failure(parser::Input(&tokens))?;
+    let line_of = |offset: usize| code[..offset.min(code.len())].matches('\n').count();
+    let statement = line_of(failure.statement_offset);
+    Some((statement, line_of(failure.offset).max(statement)))
+}
+
+/// Formats the lines, which parse, keeping the blank lines around them.
+fn push_formatted(output: &mut String, lines: &[Line<'_>], config: &Config) -> Result<(), String> {
+    let (leading, trailing) = count_blank_ends(lines, is_blank);
+    lines[..leading]
+        .iter()
+        .for_each(|line| push_line(output, line));
+    if let Some(chunk) = format_chunk(&lines[leading..lines.len() - trailing], "", config) {
+        output.push_str(&chunk?);
+    }
+    lines[lines.len() - trailing..]
+        .iter()
+        .for_each(|line| push_line(output, line));
+    Ok(())
+}
+
+fn is_blank(line: &Line<'_>) -> bool {
+    line.content.trim().is_empty()
+}
+
+/// Whether the line can start a new top-level statement.
+fn starts_statement(line: &Line<'_>) -> bool {
+    !line.content.starts_with(char::is_whitespace)
+        && !line.content.is_empty()
+        && !line.content.starts_with(['}', ')', ']'])
+        && !line.content.starts_with("else")
+}
@@ -0,0 +1,111 @@
+//! Formatting of a part of a file.
+
+use std::ops::Range;
+
+use parser::{ast::Expression, parse_with_token_ranges, pre_parse};
+use tokenizer::{Token, Tokenizer, tokens::CommentedToken};
+
+use crate::{
+    Config,
+    chunks::{lines, push_line},
+    tergo_format,
+};
+
+/// Returns the number of the line breaks inside the token,
+/// e.g. in a multi-line string.
+fn line_breaks(token: &CommentedToken<'_>) -> usize {
+    match token.token {
+        Token::Symbol(text) | Token::Literal(text) | Token::Special(text) => {
+            text.matches('\n').count()
+        }
+        _ => 0,
+    }
+}
+
+/// Returns the lines (counted from 0) spanned by each of the top-level
+/// statements of the code, including the comments directly above them.
+pub(crate) fn statement_lines(input: &str) -> Result<Vec<Range<usize>>, String> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(input.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+    let source_lines: Vec<&str> = input.lines().collect();
+
+    let mut tokenizer = Tokenizer::new(input);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let expressions = parse_with_token_ranges(parser::Input(&tokens))?;
+
+    let mut statements = vec![];
+    for (expression, token_range) in expressions {
+        if matches!(expression, Expression::Whitespace(_) | Expression::EOF(_)) {
+            continue;
+        }
+        let mut code_tokens = tokens[token_range]
+            .iter()
+            .filter(|token| !matches!(token.token, Token::Newline | Token::Semicolon));
+        let Some(first) = code_tokens.next() else {
+            continue;
+        };
+        let last = code_tokens.next_back().unwrap_or(first);
+        let mut start = line_of(first.offset);
+        while start > 0 && source_lines[start - 1].trim_start().starts_with('#') {
+            start -= 1;
+        }
+        let end = line_of(last.offset) + line_breaks(last) + 1;
+        statements.push(start..end);
+    }
+    Ok(statements)
+}
+
+/// Formats the top-level statements overlapping the given lines, counted
+/// from 0, and leaves the rest of the code untouched.
+///
+/// The range is extended to whole statements, e.g. formatting any line
+/// of a function definition formats the whole definition.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_range;
+///
+/// let input = "a<-1\nb<-2\nc<-3\n";
+/// let formatted = tergo_format_range(input, 1..2, None).unwrap();
+/// assert_eq!(formatted, "a<-1\nb <- 2\nc<-3\n");
+/// ```
+pub fn tergo_format_range(
+    input: &str,
+    lines_to_format: Range<usize>,
+    config: Option<&Config>,
+) -> Result<String, String> {
+    let statements = statement_lines(input)?;
+    let mut overlapping = statements.iter().filter(|statement| {
+        statement.start < lines_to_format.end.max(lines_to_format.start + 1)
+            && lines_to_format.start < statement.end
+    });
+    let Some(first) = overlapping.next() else {
+        return Ok(input.to_string());
+    };
+    let last = overlapping.next_back().unwrap_or(first);
+    let region = first.start..last.end;
+
+    let lines = lines(input);
+    let mut code = String::new();
+    lines[region.clone()]
+        .iter()
+        .for_each(|line| push_line(&mut code, line));
+    let mut formatted = tergo_format(&code, config)?;
+    if !code.ends_with('\n') {
+        formatted.truncate(formatted.trim_end_matches('\n').len());
+    }
+
+    let mut output = String::with_capacity(input.len());
+    lines[..region.start]
+        .iter()
+        .for_each(|line| push_line(&mut output, line));
+    output.push_str(&formatted);
+    lines[region.end..]
+        .iter()
+        .for_each(|line| push_line(&mut output, line));
+    Ok(output)
+}
@@ -0,0 +1,70 @@
+//! The statistics of formatting the code.
+
+use similar::{DiffOp, TextDiff};
+
+use crate::{Config, LongLine, find_long_lines, format_in_stages};
+
+/// What formatting the code changed, see [tergo_format_with_report].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormatReport {
+    /// The number of the lines of the code removed or replaced
+    /// by formatting.
+    pub changed_lines: usize,
+    /// The number of the groups of the layout, e.g. of the calls
+    /// and the operations, which may break into more lines.
+    pub groups: usize,
+    /// The number of the groups broken into more lines, including
+    /// the groups enclosing them and the groups which always break,
+    /// e.g. the blocks.
+    pub broken_groups: usize,
+    /// The number of the changes made by each rewriting rule, by the name
+    /// of the option enabling it, e.g. `normalize_assignment`. The rules
+    /// which changed nothing are left out.
+    pub rewrites: std::collections::BTreeMap<&'static str, usize>,
+    /// The lines of the formatted code still longer than the line length.
+    pub long_lines: Vec<LongLine>,
+}
+
+/// Formats the code like [crate::tergo_format] and reports what formatting
+/// changed, e.g. to track how much of the code of many projects already
+/// follows the style.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::{Config, tergo_format_with_report};
+///
+/// let config = Config::builder().line_length(20).build();
+/// let (formatted, report) =
+///     tergo_format_with_report("a <- 1\nb<-c(first, second, third)\n", Some(&config)).unwrap();
+/// assert_eq!(formatted, "a <- 1\nb <- c(\n  first,\n  second,\n  third\n)\n");
+/// assert_eq!(report.changed_lines, 1);
+/// assert!(report.broken_groups > 0);
+/// assert!(report.long_lines.is_empty());
+/// ```
+pub fn tergo_format_with_report(
+    input: &str,
+    config: Option<&Config>,
+) -> Result<(String, FormatReport), String> {
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let formatted = format_in_stages(input, config)?;
+    let report = FormatReport {
+        changed_lines: changed_lines(input, &formatted.code),
+        groups: formatted.layout.groups,
+        broken_groups: formatted.layout.broken_groups,
+        rewrites: formatted.rewrites,
+        long_lines: find_long_lines(&formatted.code, config),
+    };
+    Ok((formatted.code, report))
+}
+
+/// The number of the lines of `original` removed or replaced in `formatted`.
+fn changed_lines(original: &str, formatted: &str) -> usize {
+    TextDiff::from_lines(original, formatted)
+        .ops()
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+        .map(|op| op.old_range().len())
+        .sum()
+}
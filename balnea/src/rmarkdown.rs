@@ -0,0 +1,142 @@
+//! R Markdown and Quarto documents.
+
+use log::trace;
+
+use crate::{
+    Config,
+    chunks::{Line, format_chunk, lines, push_line},
+};
+
+/// The opening fence of a code chunk.
+struct Fence<'a> {
+    /// The text before the backticks, e.g. indentation or `> `.
+    prefix: &'a str,
+    backticks: usize,
+    /// The engine of the chunk, e.g. `r` or `python`.
+    engine: &'a str,
+}
+
+/// Parses the opening fence of an executable code chunk,
+/// e.g. ```` ```{r setup, echo=FALSE} ````.
+///
+/// Plain Markdown code blocks, e.g. ```` ```r ````, are not executed
+/// and are not chunks.
+fn parse_opening_fence(line: &str) -> Option<Fence<'_>> {
+    let start = line.find("```")?;
+    let prefix = &line[..start];
+    if !prefix.chars().all(|c| c == ' ' || c == '\t' || c == '>') {
+        return None;
+    }
+    let rest = &line[start..];
+    let backticks = rest.chars().take_while(|&c| c == '`').count();
+    let header = rest[backticks..].trim();
+    let header = header.strip_prefix('{')?.strip_suffix('}')?;
+    let engine_length = header.find([' ', ',']).unwrap_or(header.len());
+    Some(Fence {
+        prefix,
+        backticks,
+        engine: &header[..engine_length],
+    })
+}
+
+fn is_closing_fence(line: &str, fence: &Fence<'_>) -> bool {
+    let Some(rest) = line.strip_prefix(fence.prefix.trim_end()) else {
+        return false;
+    };
+    let rest = rest.trim();
+    rest.len() >= fence.backticks && rest.chars().all(|c| c == '`')
+}
+
+fn is_r_engine(engine: &str) -> bool {
+    engine.eq_ignore_ascii_case("r")
+}
+
+fn is_chunk_option(line: &Line<'_>, prefix: &str) -> bool {
+    line.content
+        .strip_prefix(prefix)
+        .is_some_and(|line| line.trim_start().starts_with("#|"))
+}
+
+/// Formats the R chunks of an R Markdown document.
+///
+/// Only the code inside the chunks with the `r` engine changes.
+/// The Markdown text, the chunk headers, the `#|` chunk options
+/// and the chunks in other languages are left untouched.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_rmarkdown;
+///
+/// let input = "# Title\n\n```{r}\na<-1\n```\n";
+/// let formatted = tergo_format_rmarkdown(input, None).unwrap();
+/// assert_eq!(formatted, "# Title\n\n```{r}\na <- 1\n```\n");
+/// ```
+pub fn tergo_format_rmarkdown(input: &str, config: Option<&Config>) -> Result<String, String> {
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let lines = lines(input);
+    let mut output = String::with_capacity(input.len());
+    let mut index = 0;
+    while index < lines.len() {
+        let line = &lines[index];
+        push_line(&mut output, line);
+        index += 1;
+        let Some(fence) = parse_opening_fence(line.content) else {
+            continue;
+        };
+        let Some(length) = lines[index..]
+            .iter()
+            .position(|line| is_closing_fence(line.content, &fence))
+        else {
+            // An unclosed chunk runs until the end of the document
+            continue;
+        };
+        let body = &lines[index..index + length];
+        let chunk_line = index;
+        index += length;
+        if !is_r_engine(fence.engine) {
+            trace!("Skipping a {} chunk at line {chunk_line}", fence.engine);
+            body.iter().for_each(|line| push_line(&mut output, line));
+            continue;
+        }
+        // The chunk options must stay on the top of the chunk exactly as they are
+        let options = body
+            .iter()
+            .take_while(|line| is_chunk_option(line, fence.prefix))
+            .count();
+        body[..options]
+            .iter()
+            .for_each(|line| push_line(&mut output, line));
+        let body = &body[options..];
+        match format_chunk(body, fence.prefix, config) {
+            Some(Ok(formatted)) => output.push_str(&formatted),
+            Some(Err(e)) => {
+                return Err(format!(
+                    "Failed to format the chunk starting at line {chunk_line}: {e}"
+                ));
+            }
+            None => body.iter().for_each(|line| push_line(&mut output, line)),
+        }
+    }
+    Ok(output)
+}
+
+/// Formats the R chunks of a Quarto document.
+///
+/// Quarto documents follow the same rules as R Markdown documents,
+/// see [crate::tergo_format_rmarkdown]. The chunks in other languages,
+/// e.g. `{python}` or `{bash}`, are left untouched.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_quarto;
+///
+/// let input = "```{r}\n#| label: setup\na<-1\n```\n";
+/// let formatted = tergo_format_quarto(input, None).unwrap();
+/// assert_eq!(formatted, "```{r}\n#| label: setup\na <- 1\n```\n");
+/// ```
+pub fn tergo_format_quarto(input: &str, config: Option<&Config>) -> Result<String, String> {
+    tergo_format_rmarkdown(input, config)
+}
@@ -0,0 +1,128 @@
+//! The code examples in roxygen documentation.
+
+use log::trace;
+
+use crate::{
+    Config,
+    chunks::{Line, count_blank_ends, format_chunk, lines, push_line},
+};
+
+/// Returns the text of a roxygen comment line without the `#'` marker.
+fn roxygen_text(line: &str) -> Option<&str> {
+    line.trim_start().strip_prefix("#'")
+}
+
+/// Returns the prefix of the lines of an `@examples` or `@examplesIf` section,
+/// if the line opens one.
+fn examples_prefix(line: &str) -> Option<String> {
+    let text = roxygen_text(line)?.trim();
+    let tag = text.split_whitespace().next()?;
+    match tag {
+        // The code in the same line as `@examples` is unusual and left as it is
+        "@examples" if text == tag => {}
+        "@examplesIf" => {}
+        _ => return None,
+    }
+    let indentation = &line[..line.len() - line.trim_start().len()];
+    Some(format!("{indentation}#' "))
+}
+
+/// Whether the roxygen block ending before the `rest` of the lines documents
+/// a function, e.g. `add <- function(x, y)`, and not e.g. a data set
+/// documented before `"mydata"`.
+fn documents_function(rest: &[Line<'_>]) -> bool {
+    let mut code = rest
+        .iter()
+        .map(|line| line.content.trim())
+        .filter(|line| !line.is_empty());
+    let Some(value) = code.next().and_then(assigned_value) else {
+        return false;
+    };
+    // The value may be on the line after the assignment
+    let value = if value.is_empty() {
+        code.next().unwrap_or_default()
+    } else {
+        value
+    };
+    value.starts_with("function") || value.starts_with("\\(")
+}
+
+/// Returns the code after the assignment operator of the line
+/// assigning to a name, e.g. `function(x)` of `f <- function(x)`.
+fn assigned_value(line: &str) -> Option<&str> {
+    let name_length = match line.chars().next()? {
+        quote @ ('`' | '"' | '\'') => line[1..].find(quote)? + 2,
+        _ => line
+            .find(|c: char| !(c.is_alphanumeric() || c == '.' || c == '_'))
+            .unwrap_or(line.len()),
+    };
+    if name_length == 0 {
+        return None;
+    }
+    let operator = line[name_length..].trim_start();
+    let value = operator.strip_prefix("<-").or_else(|| {
+        operator
+            .strip_prefix('=')
+            .filter(|value| !value.starts_with('='))
+    })?;
+    Some(value.trim_start())
+}
+
+fn is_blank(line: &Line<'_>) -> bool {
+    roxygen_text(line.content).is_some_and(|text| text.trim().is_empty())
+}
+
+/// Formats the code in the `@examples` and `@examplesIf` sections
+/// of the roxygen blocks of functions. The rest of the documentation
+/// is left untouched.
+///
+/// The examples which are not valid R code, e.g. because they contain
+/// Rd macros such as `\dontrun{}`, are left untouched, too.
+pub(crate) fn format_examples(code: &str, config: &Config) -> String {
+    let lines = lines(code);
+    let mut output = String::with_capacity(code.len());
+    let mut index = 0;
+    while index < lines.len() {
+        let line = &lines[index];
+        push_line(&mut output, line);
+        index += 1;
+        let Some(prefix) = examples_prefix(line.content) else {
+            continue;
+        };
+        // The section ends with the next tag or the end of the roxygen block
+        let length = lines[index..]
+            .iter()
+            .position(|line| {
+                roxygen_text(line.content).is_none_or(|text| text.trim_start().starts_with('@'))
+            })
+            .unwrap_or(lines.len() - index);
+        let section = &lines[index..index + length];
+        index += length;
+        let block_length = lines[index..]
+            .iter()
+            .position(|line| roxygen_text(line.content).is_none())
+            .unwrap_or(lines.len() - index);
+        if !documents_function(&lines[index + block_length..]) {
+            section.iter().for_each(|line| push_line(&mut output, line));
+            continue;
+        }
+        // The blank lines around the examples are not a part of the code
+        let (leading, trailing) = count_blank_ends(section, is_blank);
+        let body = &section[leading..section.len() - trailing];
+        section[..leading]
+            .iter()
+            .for_each(|line| push_line(&mut output, line));
+        match format_chunk(body, &prefix, config) {
+            Some(Ok(formatted)) => output.push_str(&formatted),
+            Some(Err(e)) => {
+                trace!("Leaving the examples unformatted: {e}");
+                body.iter().for_each(|line| push_line(&mut output, line));
+            }
+            None => body.iter().for_each(|line| push_line(&mut output, line)),
+        }
+        section[section.len() - trailing..]
+            .iter()
+            .for_each(|line| push_line(&mut output, line));
+    }
+    output
+}
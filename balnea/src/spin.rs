@@ -0,0 +1,91 @@
+//! R scripts written for `knitr::spin()`.
+
+use crate::{
+    Config,
+    chunks::{Line, count_blank_ends, format_chunk, lines, push_line},
+};
+
+/// Returns true for the lines of Markdown text, e.g. `#' # Title`.
+fn is_prose(line: &str) -> bool {
+    line.starts_with("#'")
+}
+
+/// Returns true for the chunk headers, e.g. `#+ setup, echo=FALSE`
+/// or `# ---- label ----`.
+fn is_chunk_header(line: &str) -> bool {
+    line.starts_with("#+") || line.starts_with("# ----")
+}
+
+/// Returns true for the lines `knitr` evaluates inline, e.g. `{{ x }}`.
+fn is_inline_code(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("{{") && line.ends_with("}}")
+}
+
+fn is_document_line(line: &Line<'_>) -> bool {
+    is_prose(line.content) || is_chunk_header(line.content) || is_inline_code(line.content)
+}
+
+/// Returns true if the script looks like it is meant for `knitr::spin()`,
+/// i.e. it has chunk headers or starts with a YAML header in `#'` lines.
+///
+/// The `#'` lines alone are not enough, because roxygen comments
+/// use them, too.
+pub fn is_spin_script(input: &str) -> bool {
+    input.trim_start().starts_with("#' ---") || input.lines().any(|line| line.starts_with("#+"))
+}
+
+/// Formats the code of a script written for `knitr::spin()`.
+///
+/// The code between the Markdown text (`#'` lines), the chunk headers
+/// (`#+` lines) and the inline code (`{{ }}` lines) is formatted separately.
+/// The text, the headers and the inline code are left untouched.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_spin;
+///
+/// let input = "#' # Title\n#+ setup\na<-1\n";
+/// let formatted = tergo_format_spin(input, None).unwrap();
+/// assert_eq!(formatted, "#' # Title\n#+ setup\na <- 1\n");
+/// ```
+pub fn tergo_format_spin(input: &str, config: Option<&Config>) -> Result<String, String> {
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let lines = lines(input);
+    let mut output = String::with_capacity(input.len());
+    let mut index = 0;
+    while index < lines.len() {
+        if is_document_line(&lines[index]) {
+            push_line(&mut output, &lines[index]);
+            index += 1;
+            continue;
+        }
+        let length = lines[index..]
+            .iter()
+            .position(is_document_line)
+            .unwrap_or(lines.len() - index);
+        let code = &lines[index..index + length];
+        let code_line = index + 1;
+        index += length;
+        let (leading, trailing) = count_blank_ends(code, |line| line.content.trim().is_empty());
+        let body = &code[leading..code.len() - trailing];
+        code[..leading]
+            .iter()
+            .for_each(|line| push_line(&mut output, line));
+        match format_chunk(body, "", config) {
+            Some(Ok(formatted)) => output.push_str(&formatted),
+            Some(Err(e)) => {
+                return Err(format!(
+                    "Failed to format the code starting at line {code_line}: {e}"
+                ));
+            }
+            None => body.iter().for_each(|line| push_line(&mut output, line)),
+        }
+        code[code.len() - trailing..]
+            .iter()
+            .for_each(|line| push_line(&mut output, line));
+    }
+    Ok(output)
+}
@@ -0,0 +1,80 @@
+//! Sweave (`.Rnw`) documents.
+
+use log::trace;
+
+use crate::{
+    Config,
+    chunks::{format_chunk, lines, push_line},
+};
+
+/// Returns true for the header of a code chunk, e.g. `<<setup, echo=FALSE>>=`.
+fn is_chunk_header(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("<<") && line.ends_with(">>=")
+}
+
+/// Returns true for the line ending a code chunk, i.e. a line starting with `@`.
+fn is_chunk_end(line: &str) -> bool {
+    line.starts_with('@')
+}
+
+/// Returns true for a reference to another chunk, e.g. `<<setup>>`,
+/// which knitr replaces with the code of that chunk.
+fn is_chunk_reference(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("<<") && line.ends_with(">>")
+}
+
+/// Formats the code chunks of a Sweave document.
+///
+/// A code chunk starts with a `<<...>>=` line and ends with a line starting
+/// with `@` or with the header of the next chunk. The LaTeX text,
+/// the inline `\Sexpr{}` code and the chunks reusing other chunks
+/// via `<<label>>` references are left untouched.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::tergo_format_sweave;
+///
+/// let input = "\\section{Intro}\n<<setup>>=\na<-1\n@\n";
+/// let formatted = tergo_format_sweave(input, None).unwrap();
+/// assert_eq!(formatted, "\\section{Intro}\n<<setup>>=\na <- 1\n@\n");
+/// ```
+pub fn tergo_format_sweave(input: &str, config: Option<&Config>) -> Result<String, String> {
+    let default_config = Config::default();
+    let config = config.unwrap_or(&default_config);
+    let lines = lines(input);
+    let mut output = String::with_capacity(input.len());
+    let mut index = 0;
+    while index < lines.len() {
+        let line = &lines[index];
+        push_line(&mut output, line);
+        index += 1;
+        if !is_chunk_header(line.content) {
+            continue;
+        }
+        let length = lines[index..]
+            .iter()
+            .position(|line| is_chunk_end(line.content) || is_chunk_header(line.content))
+            .unwrap_or(lines.len() - index);
+        let body = &lines[index..index + length];
+        let chunk_line = index;
+        index += length;
+        if body.iter().any(|line| is_chunk_reference(line.content)) {
+            trace!("Skipping the chunk at line {chunk_line} reusing other chunks");
+            body.iter().for_each(|line| push_line(&mut output, line));
+            continue;
+        }
+        match format_chunk(body, "", config) {
+            Some(Ok(formatted)) => output.push_str(&formatted),
+            Some(Err(e)) => {
+                return Err(format!(
+                    "Failed to format the chunk starting at line {chunk_line}: {e}"
+                ));
+            }
+            None => body.iter().for_each(|line| push_line(&mut output, line)),
+        }
+    }
+    Ok(output)
+}
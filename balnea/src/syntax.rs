@@ -0,0 +1,192 @@
+//! Locating the syntax errors in R code.
+
+use parser::{parse_failure, pre_parse};
+use tokenizer::{Token, Tokenizer};
+
+/// The tokens which start an expression. When the parser expects
+/// a symbol, it expects any of them, so they are reported together.
+const EXPRESSION_STARTS: [&str; 17] = [
+    "a symbol",
+    "a literal",
+    "`(`",
+    "`{`",
+    "`if`",
+    "`for`",
+    "`while`",
+    "`repeat`",
+    "`function`",
+    "`\\`",
+    "`break`",
+    "`continue`",
+    "`!`",
+    "`-`",
+    "`+`",
+    "`~`",
+    "`?`",
+];
+
+/// The code that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// The line of the error, counted from 1.
+    pub line: usize,
+    /// The column of the error in characters, counted from 1.
+    pub column: usize,
+    /// The text of the token at which parsing fails,
+    /// or `None` if the code ends too early.
+    pub token: Option<String>,
+    /// What the parser expected instead of the token, e.g. "`)`" or "an expression".
+    pub expected: Vec<String>,
+    /// The line of the error, followed by a caret under the token.
+    pub snippet: String,
+}
+
+impl SyntaxError {
+    /// Describes the error in one line, without the snippet.
+    pub fn message(&self) -> String {
+        let mut message = format!(
+            "Could not parse the code at line {}, column {}: unexpected {}",
+            self.line,
+            self.column,
+            match &self.token {
+                Some(token) if token == "\n" => "line break".to_string(),
+                Some(token) => format!("`{token}`"),
+                None => "end of the code".to_string(),
+            }
+        );
+        if let Some((last, rest)) = self.expected.split_last() {
+            message.push_str(", expected ");
+            if !rest.is_empty() {
+                message.push_str(&rest.join(", "));
+                message.push_str(" or ");
+            }
+            message.push_str(last);
+        }
+        message
+    }
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n{}", self.message(), self.snippet)
+    }
+}
+
+/// Returns where and why parsing the code fails,
+/// or `None` if the code parses.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_lib::find_syntax_error;
+///
+/// let error = find_syntax_error("a <- 1\nb <- )\n").unwrap();
+/// assert_eq!(error.line, 2);
+/// assert_eq!(error.token.as_deref(), Some(")"));
+/// assert_eq!(error.expected, ["an expression"]);
+/// ```
+pub fn find_syntax_error(input: &str) -> Option<SyntaxError> {
+    let mut tokenizer = Tokenizer::new(input);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let failure = parse_failure(parser::Input(&tokens))?;
+    let follows_operand = tokens
+        .iter()
+        .rev()
+        .find(|token| token.offset < failure.offset && token.token != Token::Newline)
+        .is_some_and(|token| {
+            matches!(
+                token.token,
+                Token::Symbol(_)
+                    | Token::Literal(_)
+                    | Token::RParen
+                    | Token::RBracket
+                    | Token::RBrace
+            )
+        });
+    // The code ending too early is reported right after its last token,
+    // not on the blank lines after it
+    let end = input.trim_end().len();
+    let offset = failure.offset.min(end);
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    Some(SyntaxError {
+        line,
+        column,
+        token: (offset < end).then(|| token_at(&input[offset..])).flatten(),
+        expected: summarize(&failure.expected, follows_operand),
+        snippet: snippet(input, line_start, offset, line),
+    })
+}
+
+/// Returns the text of the first token of the code, if there is one.
+fn token_at(code: &str) -> Option<String> {
+    let mut tokenizer = Tokenizer::new(code);
+    let tokens = tokenizer.tokenize();
+    match tokens.as_slice() {
+        [first, ..] if first.token == Token::EOF => None,
+        [_, second, ..] => Some(
+            code[..second.offset]
+                .trim_end_matches([' ', '\t'])
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Replaces the tokens starting an expression with "an expression"
+/// and leaves out the line breaks, which are allowed almost everywhere.
+///
+/// After an operand, the parser also tries to continue it with a call,
+/// a subset or an implicit expression. These are left out when something
+/// else is expected, e.g. the `)` closing the arguments.
+fn summarize(expected: &[&str], follows_operand: bool) -> Vec<String> {
+    let expects_expression = expected.contains(&"a symbol");
+    let continues_operand = |description: &str| {
+        follows_operand && (EXPRESSION_STARTS.contains(&description) || description == "`[`")
+    };
+    let expected: Vec<&str> = if expected
+        .iter()
+        .any(|description| *description != "a line break" && !continues_operand(description))
+    {
+        expected
+            .iter()
+            .copied()
+            .filter(|description| !continues_operand(description))
+            .collect()
+    } else {
+        expected.to_vec()
+    };
+    let mut summary: Vec<String> = vec![];
+    for description in expected {
+        let description = if expects_expression && EXPRESSION_STARTS.contains(&description) {
+            "an expression"
+        } else {
+            description
+        };
+        if description != "a line break" && !summary.iter().any(|added| added == description) {
+            summary.push(description.to_string());
+        }
+    }
+    summary
+}
+
+/// Renders the line of the error with a caret under the error.
+fn snippet(input: &str, line_start: usize, offset: usize, line: usize) -> String {
+    let line_end = input[line_start..]
+        .find('\n')
+        .map_or(input.len(), |index| line_start + index);
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs stay in the caret line, so the caret lines up with the token
+    let indent: String = input[line_start..offset]
+        .chars()
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!(
+        "{gutter} |\n{number} | {}\n{gutter} | {indent}^",
+        input[line_start..line_end].trim_end_matches('\r')
+    )
+}
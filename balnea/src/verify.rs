@@ -0,0 +1,97 @@
+//! Checking that formatting did not change the meaning of the code.
+
+use parser::{ast::Expression, pre_parse};
+use serde_json::{Value, json};
+use tokenizer::Tokenizer;
+
+/// The kinds of expressions which do not change the meaning of the code.
+const TRIVIA: [&str; 4] = ["Newline", "Whitespace", "EOF", "Comment"];
+
+/// Returns the syntax tree of the statements without the whitespace,
+/// the comments, the positions of the tokens and the trailing commas
+/// of the calls.
+pub(crate) fn syntax_tree(statements: &[Expression]) -> Value {
+    serde_json::to_value(statements)
+        .map(strip_trivia)
+        .map(|mut tree| {
+            strip_trailing_commas(&mut tree);
+            tree
+        })
+        .unwrap_or(Value::Null)
+}
+
+fn strip_trivia(value: Value) -> Value {
+    match value {
+        Value::Object(mut object) if object.contains_key("token") => {
+            object.remove("token").unwrap_or(Value::Null)
+        }
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (key, strip_trivia(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .filter(|value| !is_trivia(value))
+                .map(strip_trivia)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Removes the comma after the last argument of the calls, e.g. `f(a,)`,
+/// and the empty argument after it, which the formatter may remove.
+fn strip_trailing_commas(value: &mut Value) {
+    let empty_arg = json!({"Proper": [null, null]});
+    if let Some(args) = value
+        .pointer_mut("/FunctionCall/args/args")
+        .and_then(Value::as_array_mut)
+    {
+        let len = args.len();
+        if len > 1
+            && args[len - 1] == empty_arg
+            && args[len - 2]
+                .pointer("/Proper/0")
+                .is_some_and(|arg| !arg.is_null())
+        {
+            args.pop();
+            if let Some(comma) = args[len - 2].pointer_mut("/Proper/1") {
+                *comma = Value::Null;
+            }
+        }
+    }
+    match value {
+        Value::Object(object) => object.values_mut().for_each(strip_trailing_commas),
+        Value::Array(values) => values.iter_mut().for_each(strip_trailing_commas),
+        _ => {}
+    }
+}
+
+fn is_trivia(value: &Value) -> bool {
+    value.as_object().is_some_and(|object| {
+        object.len() == 1 && TRIVIA.iter().any(|kind| object.contains_key(*kind))
+    })
+}
+
+/// Parses the formatted code and compares it to the syntax tree
+/// of the original code.
+pub(crate) fn verify(original: &Value, formatted: &str) -> Result<(), String> {
+    let mut tokenizer = Tokenizer::new(formatted);
+    let mut commented_tokens = tokenizer.tokenize();
+    let tokens = pre_parse(&mut commented_tokens);
+    let same = crate::parse_code(formatted, parser::Input(&tokens))
+        .is_ok_and(|statements| syntax_tree(&statements) == *original);
+    if same {
+        Ok(())
+    } else {
+        Err(
+            "The formatted code does not mean the same as the original code, so it was not \
+             formatted. This is a bug in tergo; please report it with the code \
+             at https://github.com/kpagacz/tergo/issues"
+                .to_string(),
+        )
+    }
+}
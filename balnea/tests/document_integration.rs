@@ -0,0 +1,189 @@
+use tergo_lib::{
+    Config, Edit, diff_edits, is_spin_script, tergo_format_notebook, tergo_format_quarto,
+    tergo_format_range, tergo_format_rmarkdown, tergo_format_spin, tergo_format_sweave,
+};
+
+fn log_init() {
+    let res = simple_logger::init_with_env();
+    match res {
+        Ok(_) => {}
+        Err(err) => println!("Failed to initialize logger {:?}", err),
+    }
+}
+
+macro_rules! rmarkdown_test {
+    ($name:ident, $file_name:literal) => {
+        #[test]
+        fn $name() {
+            log_init();
+            let input = include_str!(concat!("test_cases/", $file_name, ".Rmd"));
+            let expected = include_str!(concat!("test_cases/", $file_name, ".expected"));
+            assert_eq!(
+                tergo_format_rmarkdown(input, Some(&Config::default())).unwrap(),
+                expected
+            );
+        }
+    };
+}
+
+macro_rules! quarto_test {
+    ($name:ident, $file_name:literal) => {
+        #[test]
+        fn $name() {
+            log_init();
+            let input = include_str!(concat!("test_cases/", $file_name, ".qmd"));
+            let expected = include_str!(concat!("test_cases/", $file_name, ".expected"));
+            assert_eq!(
+                tergo_format_quarto(input, Some(&Config::default())).unwrap(),
+                expected
+            );
+        }
+    };
+}
+
+macro_rules! sweave_test {
+    ($name:ident, $file_name:literal) => {
+        #[test]
+        fn $name() {
+            log_init();
+            let input = include_str!(concat!("test_cases/", $file_name, ".Rnw"));
+            let expected = include_str!(concat!("test_cases/", $file_name, ".expected"));
+            assert_eq!(
+                tergo_format_sweave(input, Some(&Config::default())).unwrap(),
+                expected
+            );
+        }
+    };
+}
+
+macro_rules! spin_test {
+    ($name:ident, $file_name:literal) => {
+        #[test]
+        fn $name() {
+            log_init();
+            let input = include_str!(concat!("test_cases/", $file_name, ".R"));
+            let expected = include_str!(concat!("test_cases/", $file_name, ".expected"));
+            assert!(is_spin_script(input));
+            assert_eq!(
+                tergo_format_spin(input, Some(&Config::default())).unwrap(),
+                expected
+            );
+        }
+    };
+}
+
+macro_rules! notebook_test {
+    ($name:ident, $file_name:literal) => {
+        #[test]
+        fn $name() {
+            log_init();
+            let input = include_str!(concat!("test_cases/", $file_name, ".ipynb"));
+            let expected = include_str!(concat!("test_cases/", $file_name, ".expected"));
+            assert_eq!(
+                tergo_format_notebook(input, Some(&Config::default())).unwrap(),
+                expected
+            );
+        }
+    };
+}
+
+rmarkdown_test!(rmarkdown_chunks, "rmarkdown_001");
+quarto_test!(quarto_chunks, "quarto_001");
+sweave_test!(sweave_chunks, "sweave_001");
+spin_test!(spin_script, "spin_001");
+notebook_test!(notebook_cells, "notebook_001");
+
+#[test]
+fn rmarkdown_reports_the_line_of_an_invalid_chunk() {
+    log_init();
+    let input = "text\n\n```{r}\na <- (\n```\n";
+    let error = tergo_format_rmarkdown(input, None).unwrap_err();
+    assert!(error.contains("line 3"), "{error}");
+}
+
+#[test]
+fn rmarkdown_keeps_crlf_line_endings() {
+    log_init();
+    let input = "text\r\n```{r}\r\na<-1\r\n```\r\n";
+    assert_eq!(
+        tergo_format_rmarkdown(input, None).unwrap(),
+        "text\r\n```{r}\r\na <- 1\r\n```\r\n"
+    );
+}
+
+#[test]
+fn roxygen_comments_are_not_spin_scripts() {
+    let input = "#' Add\n#' @export\nadd <- function(x, y) x + y\n";
+    assert!(!is_spin_script(input));
+}
+
+#[test]
+fn notebooks_with_other_kernels_are_untouched() {
+    let input = r#"{"cells": [{"cell_type": "code", "source": "x=1"}],
+        "metadata": {"kernelspec": {"language": "python"}}}"#;
+    assert_eq!(tergo_format_notebook(input, None).unwrap(), input);
+}
+
+#[test]
+fn range_formatting_extends_to_whole_statements() {
+    log_init();
+    let input = "a<-1\n# Leading comment\nf<-function(x){\nx+1\n}\nb<-2\n";
+    assert_eq!(
+        tergo_format_range(input, 3..4, None).unwrap(),
+        "a<-1\n# Leading comment\nf <- function(x) {\n  x + 1\n}\nb<-2\n"
+    );
+}
+
+#[test]
+fn range_formatting_single_token_statements() {
+    log_init();
+    let input = "a<-1\nx\nb<-2";
+    assert_eq!(
+        tergo_format_range(input, 2..3, None).unwrap(),
+        "a<-1\nx\nb <- 2"
+    );
+    assert_eq!(tergo_format_range(input, 1..2, None).unwrap(), input);
+}
+
+#[test]
+fn diff_edits_replace_only_changed_lines() {
+    let original = "a<-1\nb <- 2\nc<-3";
+    let formatted = "a <- 1\nb <- 2\nc <- 3\n";
+    let edits = diff_edits(original, formatted);
+    assert_eq!(
+        edits,
+        vec![
+            Edit {
+                range: 0..5,
+                new_text: "a <- 1\n".to_string()
+            },
+            Edit {
+                range: 12..16,
+                new_text: "c <- 3\n".to_string()
+            },
+        ]
+    );
+    let mut applied = original.to_string();
+    for edit in edits.iter().rev() {
+        applied.replace_range(edit.range.clone(), &edit.new_text);
+    }
+    assert_eq!(applied, formatted);
+}
+
+#[test]
+fn range_formatting_multi_character_tokens_at_line_ends() {
+    log_init();
+    let input = "a<-22\nb<-T\nc<-'multi\nline'\nd<-1\n";
+    assert_eq!(
+        tergo_format_range(input, 0..1, None).unwrap(),
+        "a <- 22\nb<-T\nc<-'multi\nline'\nd<-1\n"
+    );
+    assert_eq!(
+        tergo_format_range(input, 1..2, None).unwrap(),
+        "a<-22\nb <- TRUE\nc<-'multi\nline'\nd<-1\n"
+    );
+    assert_eq!(
+        tergo_format_range(input, 2..3, None).unwrap(),
+        "a<-22\nb<-T\nc <- 'multi\nline'\nd<-1\n"
+    );
+}
@@ -1,6 +1,14 @@
 #![allow(clippy::field_reassign_with_default)]
-use formatter::config::{AllowNlAfterAssignment, EmbracingOpNoNl, Indent, LineLength};
-use tergo_lib::{Config, tergo_format};
+use formatter::config::{
+    AllowNlAfterAssignment, EmbracingOpNoNl, FormatRoxygenExamples, Indent, LineLength,
+};
+use tergo_lib::{
+    Config, Error, FormattingPlugin, IncrementalFormatter, NormalizeAssignment, TrailingComma,
+    ast::Expression, bench, check_idempotent, check_limits, check_tokens_kept, find_long_lines,
+    find_syntax_error, format_ast_json, format_package, format_str, parse_to_ast_json,
+    tergo_format, tergo_format_partial, tergo_format_with_report,
+};
+use tokenizer::Token;
 
 fn log_init() {
     let res = simple_logger::init_with_env();
@@ -298,6 +306,283 @@ comparison_test!(simple_function_call2, "100");
 comparison_test!(modulus_operator, "101");
 comparison_test!(string_escape, "102");
 comparison_test!(switch_case_statement, "103");
+comparison_test!(comments_ending_the_line, "104", Config::default());
+comparison_test!(lambdas_and_colons, "105", Config::default());
+comparison_test!(comments_after_semicolons, "106", Config::default());
+comparison_test!(
+    access_chains,
+    "107",
+    Config::builder().line_length(30).build()
+);
+comparison_test!(
+    nested_calls_break_outermost_first,
+    "108",
+    Config::builder().line_length(30).build()
+);
+comparison_test!(
+    aligned_named_arguments,
+    "109",
+    Config::builder()
+        .line_length(40)
+        .align_named_arguments(true)
+        .build()
+);
+comparison_test!(
+    aligned_assignments,
+    "110",
+    Config::builder().align_assignments(true).build()
+);
+comparison_test!(assignments_aligned_by_hand, "111", Config::default());
+comparison_test!(
+    data_table_subsetting,
+    "112",
+    Config::builder()
+        .line_length(60)
+        .data_table_dialect(true)
+        .build()
+);
+comparison_test!(
+    embracing_operator,
+    "113",
+    Config::builder().line_length(30).build()
+);
+comparison_test!(
+    closure_argument_functions,
+    "114",
+    Config::builder()
+        .last_braced_argument_heuristic(false)
+        .closure_argument_functions(["test_that", "reactive", "my_wrapper"])
+        .build()
+);
+comparison_test!(
+    multiline_strings,
+    "115",
+    Config::builder().line_length(40).build()
+);
+comparison_test!(
+    blank_lines,
+    "116",
+    Config::builder()
+        .max_blank_lines_in_blocks(2)
+        .blank_lines_between_functions(1)
+        .build()
+);
+comparison_test!(
+    function_defaults,
+    "117",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    keyword_spacing,
+    "118",
+    Config::builder()
+        .space_after_keyword(false)
+        .space_after_function(true)
+        .build()
+);
+comparison_test!(
+    trailing_comma,
+    "119",
+    Config::builder().line_length(40).build()
+);
+comparison_test!(
+    trailing_comma_never,
+    "120",
+    Config::builder()
+        .line_length(40)
+        .trailing_comma(TrailingComma::Never)
+        .build()
+);
+comparison_test!(
+    right_assign_pipelines,
+    "121",
+    Config::builder().line_length(50).build()
+);
+comparison_test!(
+    long_formulae,
+    "122",
+    Config::builder().line_length(50).build()
+);
+comparison_test!(
+    collapsed_blocks,
+    "123",
+    Config::builder()
+        .line_length(60)
+        .collapse_if_blocks(true)
+        .collapse_loop_blocks(true)
+        .collapse_function_blocks(true)
+        .build()
+);
+comparison_test!(
+    section_comments,
+    "124",
+    Config::builder()
+        .line_length(40)
+        .pad_section_comments(true)
+        .build()
+);
+comparison_test!(
+    wrapped_comments,
+    "125",
+    Config::builder()
+        .line_length(40)
+        .wrap_comments(true)
+        .build()
+);
+comparison_test!(commented_out_code, "126", Config::default());
+comparison_test!(
+    equal_assignment,
+    "127",
+    Config::builder()
+        .normalize_assignment(NormalizeAssignment::Equal)
+        .build()
+);
+comparison_test!(
+    condition_handlers,
+    "128",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    expanded_calls,
+    "129",
+    Config::builder()
+        .expanded_call_functions([
+            "fluidPage",
+            "sidebarLayout",
+            "sidebarPanel",
+            "mainPanel",
+            "fluidRow",
+        ])
+        .build()
+);
+comparison_test!(
+    function_applying_calls,
+    "130",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    parenthesized_logical_operators,
+    "131",
+    Config::builder()
+        .parenthesize_mixed_logical_operators(true)
+        .build()
+);
+comparison_test!(empty_blocks_and_calls, "132", Config::default());
+comparison_test!(
+    expanded_empty_blocks,
+    "133",
+    Config::builder().expand_empty_blocks(true).build()
+);
+comparison_test!(comments_around_else, "134", Config::default());
+comparison_test!(
+    prologue_comments,
+    "135",
+    Config::builder()
+        .line_length(30)
+        .wrap_comments(true)
+        .build()
+);
+comparison_test!(
+    glue_strings,
+    "136",
+    Config::builder().format_glue_strings(true).build()
+);
+comparison_test!(
+    continuation_indent,
+    "137",
+    Config::builder()
+        .line_length(60)
+        .continuation_indent(4)
+        .build()
+);
+comparison_test!(
+    hanging_binary_operations,
+    "138",
+    Config::builder()
+        .line_length(50)
+        .hanging_binary_operations(true)
+        .build()
+);
+comparison_test!(
+    compound_expressions,
+    "139",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    collapsed_compound_expressions,
+    "140",
+    Config::builder()
+        .line_length(60)
+        .collapse_if_blocks(true)
+        .collapse_loop_blocks(true)
+        .build()
+);
+comparison_test!(
+    for_loop_sequence_call,
+    "141",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    break_after_assignment,
+    "142",
+    Config::builder()
+        .line_length(60)
+        .break_after_assignment(true)
+        .build()
+);
+comparison_test!(
+    replacement_functions,
+    "143",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    replacement_function_calls,
+    "144",
+    Config::builder().line_length(60).build()
+);
+comparison_test!(
+    mixed_assignment_operators,
+    "145",
+    Config::builder().line_length(40).build()
+);
+comparison_test!(
+    line_length_tolerance,
+    "146",
+    Config::builder()
+        .line_length(78)
+        .line_length_tolerance(5)
+        .build()
+);
+comparison_test!(
+    comment_line_length,
+    "147",
+    Config::builder()
+        .line_length(60)
+        .comment_line_length(80)
+        .wrap_comments(true)
+        .pad_section_comments(true)
+        .build()
+);
+comparison_test!(
+    redundant_backticks,
+    "148",
+    Config::builder().remove_redundant_backticks(true).build()
+);
+comparison_test!(
+    normalized_numbers,
+    "149",
+    Config::builder().normalize_numbers(true).build()
+);
+comparison_test!(
+    layout_directives,
+    "150",
+    Config::builder().line_length(40).build()
+);
+comparison_test!(
+    roxygen_examples,
+    "roxygen_001",
+    Config::builder().format_roxygen_examples(true).build()
+);
 
 // Tidyverse styleguide examples
 comparison_test!(tidyverse_commas, "tidyverse_style_guide_001");
@@ -446,3 +731,429 @@ comparison_test!(
 );
 comparison_test!(rle_tmc, "real_life_004", Config::default());
 comparison_test!(rle_somehow_exceeds_120, "real_life_005", Config::default());
+
+#[test]
+fn roxygen_examples_untouched_if_disabled() {
+    log_init();
+    let mut config = Config::default();
+    config.format_roxygen_examples = FormatRoxygenExamples(false);
+    let input = "#' @examples\n#' add(1,2)\nadd <- function(x, y) x + y\n";
+    assert_eq!(tergo_format(input, Some(&config)).unwrap(), input);
+}
+
+#[test]
+fn parentheses_are_never_added_nor_removed_by_default() {
+    log_init();
+    let input = "a || b && c\n((x))\ny <- (a + b) * c\nz <- -(1)\nif ((a)) (b)\n";
+    assert_eq!(tergo_format(input, None).unwrap(), input);
+}
+
+#[test]
+fn syntax_error_points_at_the_offending_line() {
+    log_init();
+    let error = find_syntax_error("a <- 1\nf <- function(x) {\n  x <- )\n}\n").unwrap();
+    assert_eq!(error.line, 3);
+    assert!(find_syntax_error("a <- 1\n").is_none());
+}
+
+#[test]
+fn syntax_error_tells_the_expected_tokens() {
+    log_init();
+    let error = find_syntax_error("x <- c(1, 2\n").unwrap();
+    assert_eq!((error.line, error.column), (1, 12));
+    assert_eq!(error.token, None);
+    assert_eq!(error.expected, ["`,`", "`)`"]);
+    let error = find_syntax_error("for (i 1:10) print(i)").unwrap();
+    assert_eq!(error.token.as_deref(), Some("1"));
+    assert_eq!(error.expected, ["`in`"]);
+    assert_eq!(
+        error.message(),
+        "Could not parse the code at line 1, column 8: unexpected `1`, expected `in`"
+    );
+    let error = find_syntax_error("y <- 1 +").unwrap();
+    assert_eq!(error.token, None);
+    assert_eq!(error.expected, ["an expression"]);
+}
+
+#[test]
+fn syntax_error_renders_the_line_with_a_caret() {
+    log_init();
+    let error = find_syntax_error("a <- 1\nif x > 1 {\n}\n").unwrap();
+    assert_eq!(error.snippet, "  |\n2 | if x > 1 {\n  |    ^");
+    assert_eq!(
+        error.to_string(),
+        format!("{}\n{}", error.message(), error.snippet)
+    );
+}
+
+#[test]
+fn format_str_reports_the_syntax_error() {
+    log_init();
+    let config = Config::builder().line_length(80).build();
+    assert_eq!(format_str("a<-1", &config).unwrap(), "a <- 1\n");
+    match format_str("a <- 1\nb <- )\n", &config) {
+        Err(Error::Syntax(error)) => assert_eq!(error.line, 2),
+        other => panic!("Expected a syntax error, got {other:?}"),
+    }
+}
+
+#[test]
+fn long_lines_blame_the_unbreakable_token() {
+    log_init();
+    let config = Config::builder().line_length(30).build();
+    let input =
+        "f(first_argument, second_argument)\nx <- \"a string which is far too long\"\n# short\n";
+    let formatted = tergo_format(input, Some(&config)).unwrap();
+    let long_lines = find_long_lines(&formatted, &config);
+    assert_eq!(long_lines.len(), 1);
+    assert_eq!(long_lines[0].line, 5);
+    assert_eq!(long_lines[0].length, 37);
+    assert_eq!(
+        long_lines[0].token.as_deref(),
+        Some("\"a string which is far too long\"")
+    );
+}
+
+#[test]
+fn report_counts_the_changes_of_formatting() {
+    log_init();
+    let config = Config::builder()
+        .line_length(30)
+        .normalize_assignment(NormalizeAssignment::Arrow)
+        .remove_redundant_backticks(true)
+        .build();
+    let input = "a <- 1\nb = `mean`(x)\nc = d\nx <- \"a string which is far too long\"\n";
+    let (formatted, report) = tergo_format_with_report(input, Some(&config)).unwrap();
+    assert_eq!(formatted, tergo_format(input, Some(&config)).unwrap());
+    assert_eq!(report.changed_lines, 2);
+    assert_eq!(report.rewrites.get("normalize_assignment"), Some(&2));
+    assert_eq!(report.rewrites.get("remove_redundant_backticks"), Some(&1));
+    assert!(!report.rewrites.contains_key("normalize_numbers"));
+    assert_eq!(report.long_lines.len(), 1);
+    assert!(report.broken_groups <= report.groups);
+
+    let (_, report) = tergo_format_with_report(&formatted, Some(&config)).unwrap();
+    assert_eq!(report.changed_lines, 0);
+    assert!(report.rewrites.is_empty());
+}
+
+#[test]
+fn package_files_are_formatted_by_their_directory() {
+    log_init();
+    let root = std::env::temp_dir().join(format!("tergo-package-{}", std::process::id()));
+    let files = [
+        ("DESCRIPTION", "Package: example\n"),
+        (
+            "R/sum.R",
+            "#' @examples\n#' sum2(1,2)\nsum2 <- function(a,b) a+b\n",
+        ),
+        ("R/ok.R", "x <- 1\n"),
+        (
+            "tests/testthat/test-sum.R",
+            "#' @examples\n#' sum2(1,2)\nsum2(1,2)\n",
+        ),
+        ("vignettes/intro.Rmd", "# Intro\n\n```{r}\nsum2(1,2)\n```\n"),
+        ("inst/scripts/broken.R", "f(\n"),
+        ("inst/notes.txt", "x<-1\n"),
+        ("data-raw/make.R", "x<-1\n"),
+    ];
+    for (path, content) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    let config = Config::builder().format_roxygen_examples(true).build();
+    let results = format_package(&root, &config);
+    std::fs::remove_dir_all(&root).unwrap();
+
+    let paths: Vec<_> = results.iter().map(|result| result.path.clone()).collect();
+    assert_eq!(
+        paths,
+        [
+            "R/ok.R",
+            "R/sum.R",
+            "inst/scripts/broken.R",
+            "tests/testthat/test-sum.R",
+            "vignettes/intro.Rmd",
+        ]
+        .map(std::path::PathBuf::from)
+    );
+    assert!(!results[0].changed);
+    assert_eq!(
+        results[1].formatted.as_deref(),
+        Ok("#' @examples\n#' sum2(1, 2)\nsum2 <- function(a, b) a + b\n")
+    );
+    assert!(results[2].formatted.is_err());
+    // Only the roxygen comments of R/ are the package documentation
+    assert_eq!(
+        results[3].formatted.as_deref(),
+        Ok("#' @examples\n#' sum2(1,2)\nsum2(1, 2)\n")
+    );
+    assert_eq!(
+        results[4].formatted.as_deref(),
+        Ok("# Intro\n\n```{r}\nsum2(1, 2)\n```\n")
+    );
+    assert!(results[4].changed);
+}
+
+/// Panics on the code with `boom`.
+struct Boom;
+
+impl FormattingPlugin for Boom {
+    fn adjust_output(&self, formatted: &mut String) {
+        if formatted.contains("boom") {
+            panic!("boom");
+        }
+    }
+}
+
+#[test]
+fn package_files_the_formatter_panics_on_have_an_error() {
+    log_init();
+    let root = std::env::temp_dir().join(format!("tergo-panic-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("R")).unwrap();
+    for file in 0..8 {
+        std::fs::write(root.join(format!("R/{file}.R")), "x<-1\n").unwrap();
+    }
+    std::fs::write(root.join("R/boom.R"), "boom<-1\n").unwrap();
+    let results = format_package(&root, &Config::builder().plugin(Boom).build());
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(results.len(), 9);
+    for result in &results[..8] {
+        assert_eq!(result.formatted.as_deref(), Ok("x <- 1\n"));
+    }
+    assert_eq!(results[8].path, std::path::PathBuf::from("R/boom.R"));
+    assert_eq!(
+        results[8].formatted,
+        Err("The formatter failed: boom".to_string())
+    );
+}
+
+#[test]
+fn line_length_tolerance_keeps_the_formatting_idempotent() {
+    log_init();
+    let config = Config::builder()
+        .line_length(60)
+        .line_length_tolerance(10)
+        .allow_nl_after_assignment(true)
+        .wrap_comments(true)
+        .comment_line_length(50)
+        .build();
+    let input: String = include_str!("./test_cases/125.R")
+        .lines()
+        .take(5)
+        .map(|line| format!("{line}\n"))
+        .collect();
+    let formatted = tergo_format(&input, Some(&config)).unwrap();
+    assert!(formatted.contains("f <-\n  function(x) {\n"), "{formatted}");
+    assert_eq!(tergo_format(&formatted, Some(&config)).unwrap(), formatted);
+}
+
+#[test]
+fn code_over_the_limits_is_not_formatted() {
+    log_init();
+    let deep = format!("x <- {}1{}", "f(".repeat(500), ")".repeat(500));
+    let error = tergo_format(&deep, None).unwrap_err();
+    assert!(error.contains("max_expression_depth"), "{error}");
+    let config = Config::builder().max_expression_depth(2).build();
+    assert!(tergo_format("f(g(h(x)))", Some(&config)).is_err());
+    let config = Config::builder().max_expression_depth(0).build();
+    assert!(tergo_format("f(g(h(x)))", Some(&config)).is_ok());
+    let config = Config::builder().max_file_size(10).build();
+    assert!(check_limits("a <- 1\nb <- 2\n", &config).is_err());
+    assert!(check_limits("a <- 1\n", &config).is_ok());
+}
+
+#[test]
+fn aligned_assignments_end_at_the_blank_lines_between_functions() {
+    log_init();
+    let config = Config::builder()
+        .align_assignments(true)
+        .blank_lines_between_functions(1)
+        .build();
+    let formatted = tergo_format(
+        "x <- 1\nf <- function() 1\nlonger_name <- 2\ny <- 3\n",
+        Some(&config),
+    )
+    .unwrap();
+    assert_eq!(
+        formatted,
+        "x <- 1\n\nf <- function() 1\n\nlonger_name <- 2\ny           <- 3\n"
+    );
+    for input in [
+        formatted.as_str(),
+        include_str!("test_cases/111.R"),
+        include_str!("test_cases/137.R"),
+        include_str!("test_cases/138.R"),
+        include_str!("test_cases/142.R"),
+        include_str!("test_cases/143.R"),
+        include_str!("test_cases/145.R"),
+    ] {
+        check_idempotent(input, Some(&config)).unwrap();
+    }
+}
+
+#[test]
+fn bench_counts_the_allocations_only_with_the_counting_allocator() {
+    log_init();
+    let timings = bench::format_timings("f <- function(x) {x+1}", &Config::default()).unwrap();
+    assert_eq!(timings.allocations, None);
+    assert!(bench::format_timings("f <- function(", &Config::default()).is_err());
+}
+
+#[test]
+fn incremental_formatting_matches_formatting_the_whole_code() {
+    log_init();
+    let config = Config::default();
+    let mut formatter = IncrementalFormatter::new();
+    for input in [
+        include_str!("test_cases/real_life_001.R"),
+        include_str!("test_cases/real_life_002.R"),
+        include_str!("test_cases/111.R"),
+    ] {
+        assert_eq!(
+            formatter.format(input, &config),
+            tergo_format(input, Some(&config))
+        );
+        let edited = input.replacen("<-", "<<-", 1);
+        assert_eq!(
+            formatter.format(&edited, &config),
+            tergo_format(&edited, Some(&config))
+        );
+    }
+}
+
+/// The documents of the layout are reference counted without atomics
+/// unless the `sync` feature is on, but they never leave a call, so
+/// the formatter runs on any thread, e.g. in a rayon pool or a tokio task.
+#[test]
+fn formatter_is_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    log_init();
+    let config = Config::builder().plugin(Header).build();
+    let mut formatter = IncrementalFormatter::new();
+    assert_send_sync(&config);
+    assert_send_sync(&formatter);
+    std::thread::scope(|scope| {
+        let formatted = scope.spawn(|| tergo_format("a<-1", Some(&config)));
+        let formatted_incrementally = scope.spawn(|| formatter.format("a<-1", &config));
+        let expected = Ok("# Formatted\na <- 1\n".to_string());
+        assert_eq!(formatted.join().unwrap(), expected);
+        assert_eq!(formatted_incrementally.join().unwrap(), expected);
+    });
+}
+
+#[test]
+fn partial_formatting_keeps_the_statements_which_do_not_parse() {
+    log_init();
+    let input = "a<-1\nf <- function(x) {\n  y <- )\n  z<-2\n}\n\nb<-2\nc <- c(1,\n";
+    let (formatted, skipped) = tergo_format_partial(input, None).unwrap();
+    assert_eq!(
+        formatted,
+        "a <- 1\nf <- function(x) {\n  y <- )\n  z<-2\n}\n\nb <- 2\nc <- c(1,\n"
+    );
+    assert_eq!(skipped, [1..5, 7..8]);
+    let (formatted, skipped) = tergo_format_partial("a<-1\n", None).unwrap();
+    assert_eq!(formatted, "a <- 1\n");
+    assert!(skipped.is_empty());
+}
+
+#[test]
+fn token_audit_finds_the_dropped_comments() {
+    log_init();
+    let input = "a <- 1 # one\n#' @examples\n#' f(1,2)\nf <- function(x, y) x\n";
+    let formatted = tergo_format(input, None).unwrap();
+    assert!(check_tokens_kept(input, &formatted).is_ok());
+    assert!(
+        check_tokens_kept(
+            input,
+            "a <- 1\n#' @examples\n#' f(1, 2)\nf <- function(x, y) x\n"
+        )
+        .is_err()
+    );
+    assert!(check_tokens_kept(input, &format!("{formatted}a <- 1\n")).is_err());
+}
+
+/// Drops the top-level `browser` statements.
+struct RemoveBrowser;
+
+impl FormattingPlugin for RemoveBrowser {
+    fn rewrite(&self, expression: &mut Expression<'_>) {
+        if let Expression::Term(term) = expression {
+            term.term.retain(|statement| {
+                !matches!(statement, Expression::Symbol(token) if token.token == Token::Symbol("browser"))
+            });
+        }
+    }
+}
+
+/// Adds a header to the formatted code.
+struct Header;
+
+impl FormattingPlugin for Header {
+    fn adjust_output(&self, formatted: &mut String) {
+        formatted.insert_str(0, "# Formatted\n");
+    }
+}
+
+#[test]
+fn plugins_run_in_the_registration_order() {
+    log_init();
+    let config = Config::builder()
+        .plugin(RemoveBrowser)
+        .plugin(Header)
+        .build();
+    let formatted = tergo_format("x<-1\nbrowser\ny<-2\n", Some(&config)).unwrap();
+    assert_eq!(formatted, "# Formatted\nx <- 1\ny <- 2\n");
+}
+
+#[test]
+fn formats_the_parsed_code_serialized_to_json() {
+    log_init();
+    let input = "# Sum\nf<-function(x,y=2){\n  if(x>1) x+y else{y} # inline\n}\nfor(i in 1:10)print(l[[i]])\n";
+    let json = parse_to_ast_json(input).unwrap();
+    assert_eq!(
+        format_ast_json(&json, None).unwrap(),
+        tergo_format(input, None).unwrap()
+    );
+}
+
+#[test]
+fn formats_generated_json() {
+    log_init();
+    let json = r#"[{"FunctionCall": {
+        "function_ref": {"Symbol": {"token": {"Symbol": "print"}}},
+        "args": {
+            "left_delimeter": {"Paren": {"token": "LParen"}},
+            "args": [{"Proper": [{"Literal": {"token": {"Literal": "\"hello\""}}}, null]}],
+            "right_delimeter": {"Paren": {"token": "RParen"}}
+        }
+    }}]"#;
+    assert_eq!(format_ast_json(json, None).unwrap(), "print(\"hello\")\n");
+    assert!(format_ast_json(r#"[{"Unknown": 1}]"#, None).is_err());
+}
+
+#[test]
+fn formatting_the_test_cases_is_idempotent() {
+    log_init();
+    let mut cases: Vec<_> = std::fs::read_dir("tests/test_cases")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "R"))
+        .collect();
+    cases.sort();
+    let mut failures = vec![];
+    for case in cases {
+        let input = std::fs::read_to_string(&case).unwrap();
+        for (name, config) in [
+            ("the long line config", long_line_config()),
+            ("the default config", Config::default()),
+        ] {
+            if let Err(e) = check_idempotent(&input, Some(&config)) {
+                failures.push(format!("{case:?} with {name}: {e}"));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "tree-sitter")]
+
+use tergo_lib::{Config, cross_check_parsers, tergo_format};
+
+#[test]
+fn code_the_native_parser_rejects_is_parsed_with_tree_sitter() {
+    let config = Config::default();
+    assert_eq!(
+        tergo_format("while(a)(b)", Some(&config)).unwrap(),
+        "while (a) (b)\n"
+    );
+}
+
+#[test]
+fn parsers_read_the_test_cases_the_same() {
+    // The native parser reads `x$f(y)` as `x$(f(y))` and gives `|>` the precedence of `~`
+    let known_differences = ["073.R", "097.R", "129.R", "real_life_003.R"];
+    // Only the native parser accepts `for (1 in x)` and a top-level `else` on a new line
+    let invalid = ["052.R", "134.R"];
+    let mut cases: Vec<_> = std::fs::read_dir("tests/test_cases")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "R"))
+        .collect();
+    cases.sort();
+    for case in cases {
+        let name = case.file_name().unwrap().to_str().unwrap();
+        let code = std::fs::read_to_string(&case).unwrap();
+        let checked = cross_check_parsers(&code);
+        if known_differences.contains(&name) || invalid.contains(&name) {
+            assert!(checked.is_err(), "{name} is read the same now");
+        } else {
+            assert_eq!(checked, Ok(()), "{name}");
+        }
+    }
+}
+
+#[test]
+fn cross_check_points_at_the_line_read_differently() {
+    assert_eq!(cross_check_parsers("a <- 1\nb <- -2\n"), Ok(()));
+    assert_eq!(
+        cross_check_parsers("a <- 1\n\nx$f(y)\n"),
+        Err("The native parser and tree-sitter-r read the code at line 3 differently".to_string())
+    );
+}
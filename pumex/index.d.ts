@@ -0,0 +1,56 @@
+/**
+ * The formatting options. The missing options take their default values.
+ *
+ * The options are the same as the keys of `tergo.toml`, in camel case.
+ */
+export interface FormatOptions {
+  indent?: number;
+  continuationIndent?: number;
+  hangingBinaryOperations?: boolean;
+  lineLength?: number;
+  lineLengthTolerance?: number;
+  embracingOpNoNl?: boolean;
+  allowNlAfterAssignment?: boolean;
+  breakAfterAssignment?: boolean;
+  spaceBeforeComplexRhsInFormula?: boolean;
+  stripSuffixWhitespaceInFunctionDefs?: boolean;
+  functionLineBreaks?: "hanging" | "double" | "single";
+  insertNewlineInQuoteCall?: boolean;
+  minAccessChainLength?: number;
+  alignNamedArguments?: boolean;
+  alignAssignments?: boolean;
+  dataTableDialect?: boolean;
+  lastBracedArgumentHeuristic?: boolean;
+  closureArgumentFunctions?: string[];
+  expandedCallFunctions?: string[];
+  maxBlankLinesInBlocks?: number;
+  blankLinesBetweenFunctions?: number;
+  spaceAfterKeyword?: boolean;
+  spaceAfterFunction?: boolean;
+  trailingComma?: "preserve" | "never";
+  collapseIfBlocks?: boolean;
+  collapseLoopBlocks?: boolean;
+  collapseFunctionBlocks?: boolean;
+  expandEmptyBlocks?: boolean;
+  padSectionComments?: boolean;
+  wrapComments?: boolean;
+  commentLineLength?: number;
+  normalizeAssignment?: false | "none" | "arrow" | "equal";
+  parenthesizeMixedLogicalOperators?: boolean;
+  removeRedundantBackticks?: boolean;
+  normalizeNumbers?: boolean;
+  formatRoxygenExamples?: boolean;
+  formatGlueStrings?: boolean;
+  formatShebangScripts?: boolean;
+  verify?: boolean;
+  maxFileSize?: number;
+  maxExpressionDepth?: number;
+  generatedFileMarkers?: string[];
+  exclusionList?: string[];
+}
+
+/**
+ * Formats the R code. Throws an error if the options are invalid
+ * or the code cannot be parsed.
+ */
+export function format(code: string, options?: FormatOptions | null): string;
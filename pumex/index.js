@@ -0,0 +1,18 @@
+const { existsSync } = require("fs");
+const { join } = require("path");
+
+// `napi build --platform` names the module after the platform, e.g. tergo.linux-x64-gnu.node
+const candidates = [
+  `tergo.${process.platform}-${process.arch}.node`,
+  `tergo.${process.platform}-${process.arch}-gnu.node`,
+  `tergo.${process.platform}-${process.arch}-musl.node`,
+  `tergo.${process.platform}-${process.arch}-msvc.node`,
+  "tergo.node",
+].map((name) => join(__dirname, name));
+
+const binding = candidates.find((candidate) => existsSync(candidate));
+if (!binding) {
+  throw new Error(`tergo-node does not support ${process.platform}-${process.arch}`);
+}
+
+module.exports = require(binding);
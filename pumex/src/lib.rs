@@ -0,0 +1,88 @@
+//! A Node.js native module formatting R code, built with `napi-rs`.
+//!
+//! It runs the formatter natively, so it is faster than the WebAssembly
+//! build on servers, in build tools and in the VS Code extension host.
+
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use serde_json::{Map, Value};
+use tergo_lib::{Config, tergo_format};
+
+/// Formats the R code.
+///
+/// `options` is an object with the same keys as `tergo.toml`, in camel case,
+/// e.g. `{ lineLength: 100 }`. The missing options take their default values.
+/// Throws an error if the options are invalid or the code cannot be parsed.
+#[napi(ts_args_type = "code: string, options?: FormatOptions | null")]
+pub fn format(code: String, options: Option<Value>) -> Result<String> {
+    let config = parse_options(options).map_err(|e| Error::new(Status::InvalidArg, e))?;
+    tergo_format(&code, Some(&config)).map_err(|e| Error::new(Status::GenericFailure, e))
+}
+
+/// Parses the options of [`format`] into a configuration. Missing options
+/// are the default configuration.
+pub fn parse_options(options: Option<Value>) -> std::result::Result<Config, String> {
+    let options = match options {
+        None | Some(Value::Null) => return Ok(Config::default()),
+        Some(Value::Object(options)) => options,
+        Some(_) => return Err("Invalid options: expected an object".to_string()),
+    };
+    let options: Map<String, Value> = options
+        .into_iter()
+        .map(|(key, value)| (snake_case(&key), value))
+        .collect();
+    serde_json::from_value(Value::Object(options)).map_err(|e| format!("Invalid options: {e}"))
+}
+
+/// Turns a camel case key, e.g. `lineLength`, into the key of `tergo.toml`.
+fn snake_case(key: &str) -> String {
+    let mut snake = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn formats_with_the_camel_case_options() {
+        let config = parse_options(Some(json!({
+            "indent": 4,
+            "functionLineBreaks": "double",
+            "blankLinesBetweenFunctions": 1,
+            "closureArgumentFunctions": ["map"],
+        })))
+        .unwrap();
+        assert_eq!(config.indent.0, 4);
+        assert_eq!(config.blank_lines_between_functions.0, 1);
+        assert_eq!(config.closure_argument_functions.0, vec!["map"]);
+        let formatted = tergo_format("f<-function(x){x}", Some(&config)).unwrap();
+        assert_eq!(formatted, "f <- function(x) {\n    x\n}\n");
+        assert_eq!(tergo_format(&formatted, Some(&config)).unwrap(), formatted);
+    }
+
+    #[test]
+    fn missing_options_are_the_default_configuration() {
+        let default = tergo_format("a<-1", None).unwrap();
+        for options in [None, Some(Value::Null), Some(json!({}))] {
+            let config = parse_options(options).unwrap();
+            assert_eq!(tergo_format("a<-1", Some(&config)).unwrap(), default);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(parse_options(Some(json!({ "lineLenght": 80 }))).is_err());
+        assert!(parse_options(Some(json!({ "functionLineBreaks": "triple" }))).is_err());
+        assert!(parse_options(Some(json!(80))).is_err());
+    }
+}
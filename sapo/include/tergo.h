@@ -0,0 +1,36 @@
+#ifndef TERGO_H
+#define TERGO_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Returned if the code was formatted. */
+#define TERGO_OK 0
+/* Returned if the code could not be formatted. The error message is in `err`. */
+#define TERGO_ERROR 1
+/* Returned if `code`, `out` or `err` is a null pointer. */
+#define TERGO_INVALID_ARGUMENT 2
+
+/*
+ * Formats the R code.
+ *
+ * `config_json` is a JSON object with the same keys as `tergo.toml`,
+ * e.g. `{"indent": 4}`, or NULL for the default configuration.
+ *
+ * On success, returns TERGO_OK and stores the formatted code in `*out`
+ * and NULL in `*err`. On failure, returns TERGO_ERROR and stores
+ * the error message in `*err` and NULL in `*out`. A panic of the formatter
+ * is a failure, too, instead of unwinding into the host.
+ * The stored strings must be released with `tergo_free_string`.
+ */
+int tergo_format(const char *code, const char *config_json, char **out, char **err);
+
+/* Releases a string returned by tergo. Does nothing for NULL. */
+void tergo_free_string(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TERGO_H */
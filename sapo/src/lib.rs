@@ -0,0 +1,108 @@
+//! A C interface to `tergo`, so C and C++ hosts, e.g. IDEs,
+//! can embed the formatter. The declarations are in `include/tergo.h`.
+//!
+//! The strings returned by the functions are owned by the caller,
+//! who releases them with [tergo_free_string].
+
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr,
+};
+
+use tergo_lib::{Config, tergo_format as format_code};
+
+/// Returned if the code was formatted.
+pub const TERGO_OK: c_int = 0;
+/// Returned if the code could not be formatted. The error message is in `err`.
+pub const TERGO_ERROR: c_int = 1;
+/// Returned if `code`, `out` or `err` is a null pointer.
+pub const TERGO_INVALID_ARGUMENT: c_int = 2;
+
+/// Formats the R code.
+///
+/// `config_json` is a JSON object with the same keys as `tergo.toml`,
+/// e.g. `{"indent": 4}`, or a null pointer for the default configuration.
+///
+/// On success, returns [TERGO_OK] and stores the formatted code in `*out`
+/// and a null pointer in `*err`. On failure, returns [TERGO_ERROR]
+/// and stores the error message in `*err` and a null pointer in `*out`.
+/// A panic of the formatter is a failure, too, instead of unwinding
+/// into the host.
+/// The stored strings must be released with [tergo_free_string].
+///
+/// # Safety
+///
+/// `code` and `config_json`, if not null, must point to null-terminated
+/// strings. `out` and `err` must point to memory writable as a `char*`.
+#[no_mangle]
+pub unsafe extern "C" fn tergo_format(
+    code: *const c_char,
+    config_json: *const c_char,
+    out: *mut *mut c_char,
+    err: *mut *mut c_char,
+) -> c_int {
+    if code.is_null() || out.is_null() || err.is_null() {
+        return TERGO_INVALID_ARGUMENT;
+    }
+    *out = ptr::null_mut();
+    *err = ptr::null_mut();
+    let config_json = (!config_json.is_null()).then(|| CStr::from_ptr(config_json));
+    let formatted = catch_unwind(AssertUnwindSafe(|| {
+        format(CStr::from_ptr(code), config_json)
+    }))
+    .unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+        Err(format!("The formatter failed: {message}"))
+    });
+    match formatted {
+        Ok(formatted) => {
+            *out = into_c_string(formatted);
+            TERGO_OK
+        }
+        Err(message) => {
+            *err = into_c_string(message);
+            TERGO_ERROR
+        }
+    }
+}
+
+/// Releases a string returned by `tergo`. Does nothing for a null pointer.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by `tergo` that was not
+/// released yet.
+#[no_mangle]
+pub unsafe extern "C" fn tergo_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+fn format(code: &CStr, config_json: Option<&CStr>) -> Result<String, String> {
+    let code = code
+        .to_str()
+        .map_err(|e| format!("The code is not valid UTF-8: {e}"))?;
+    let config = match config_json {
+        Some(config_json) => {
+            let config_json = config_json
+                .to_str()
+                .map_err(|e| format!("The configuration is not valid UTF-8: {e}"))?;
+            serde_json::from_str::<Config>(config_json)
+                .map_err(|e| format!("Invalid configuration: {e}"))?
+        }
+        None => Config::default(),
+    };
+    format_code(code, Some(&config))
+}
+
+fn into_c_string(string: String) -> *mut c_char {
+    // The input has no null bytes, but the messages might quote them
+    let string = CString::new(string.replace('\0', "\\0")).unwrap_or_default();
+    string.into_raw()
+}
@@ -0,0 +1,75 @@
+use std::{
+    ffi::{CStr, CString, c_char},
+    ptr,
+};
+
+use tergo_ffi::{TERGO_ERROR, TERGO_INVALID_ARGUMENT, TERGO_OK, tergo_format, tergo_free_string};
+
+fn call(code: &str, config_json: Option<&str>) -> (i32, Option<String>, Option<String>) {
+    let code = CString::new(code).unwrap();
+    let config_json = config_json.map(|config| CString::new(config).unwrap());
+    let mut out: *mut c_char = ptr::null_mut();
+    let mut err: *mut c_char = ptr::null_mut();
+    let status = unsafe {
+        tergo_format(
+            code.as_ptr(),
+            config_json
+                .as_ref()
+                .map_or(ptr::null(), |config| config.as_ptr()),
+            &mut out,
+            &mut err,
+        )
+    };
+    let take = |string: *mut c_char| {
+        (!string.is_null()).then(|| unsafe {
+            let owned = CStr::from_ptr(string).to_string_lossy().into_owned();
+            tergo_free_string(string);
+            owned
+        })
+    };
+    (status, take(out), take(err))
+}
+
+#[test]
+fn formats_the_code() {
+    let (status, out, err) = call("f<-function(){1}", Some(r#"{"indent": 4}"#));
+    assert_eq!(status, TERGO_OK);
+    assert_eq!(out.as_deref(), Some("f <- function() {\n    1\n}\n"));
+    assert_eq!(err, None);
+}
+
+#[test]
+fn reports_invalid_configuration() {
+    let (status, out, err) = call("a", Some(r#"{"indnet": 4}"#));
+    assert_eq!(status, TERGO_ERROR);
+    assert_eq!(out, None);
+    assert!(err.unwrap().contains("indnet"));
+}
+
+#[test]
+fn rejects_null_arguments() {
+    let status =
+        unsafe { tergo_format(ptr::null(), ptr::null(), ptr::null_mut(), ptr::null_mut()) };
+    assert_eq!(status, TERGO_INVALID_ARGUMENT);
+
+    let mut out: *mut c_char = ptr::null_mut();
+    let mut err: *mut c_char = ptr::null_mut();
+    let status = unsafe { tergo_format(ptr::null(), ptr::null(), &mut out, &mut err) };
+    assert_eq!(status, TERGO_INVALID_ARGUMENT);
+    assert!(out.is_null() && err.is_null());
+}
+
+#[test]
+fn reports_invalid_utf8() {
+    let code = CString::new(b"a <- \"\xff\"".to_vec()).unwrap();
+    let mut out: *mut c_char = ptr::null_mut();
+    let mut err: *mut c_char = ptr::null_mut();
+    let status = unsafe { tergo_format(code.as_ptr(), ptr::null(), &mut out, &mut err) };
+    assert_eq!(status, TERGO_ERROR);
+    assert!(out.is_null());
+    let message = unsafe { CStr::from_ptr(err) }
+        .to_string_lossy()
+        .into_owned();
+    unsafe { tergo_free_string(err) };
+    assert!(message.contains("not valid UTF-8"));
+}
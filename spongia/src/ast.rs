@@ -3,6 +3,7 @@ use tokenizer::tokens::CommentedToken;
 use crate::Input;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expression<'a> {
     Symbol(&'a CommentedToken<'a>),
     Literal(&'a CommentedToken<'a>),
@@ -76,6 +77,7 @@ impl std::fmt::Display for Expression<'_> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ExpressionsBuffer<'a>(pub &'a [Expression<'a>]);
 impl std::fmt::Display for ExpressionsBuffer<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -94,6 +96,7 @@ impl std::fmt::Display for ExpressionsBuffer<'_> {
 
 // Term
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TermExpr<'a> {
     pub pre_delimiters: Option<&'a CommentedToken<'a>>,
     pub term: Vec<Expression<'a>>,
@@ -148,6 +151,7 @@ impl std::fmt::Display for TermExpr<'_> {
 // The formatter needs comments and some of them might end up squeezed into
 // the comma token
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Arg<'a> {
     Proper(Option<Expression<'a>>, Option<Expression<'a>>), // Argument, comma
     EmptyEqual(
@@ -178,6 +182,7 @@ impl std::fmt::Display for Arg<'_> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Delimiter<'a> {
     Paren(&'a CommentedToken<'a>),
     SingleBracket(&'a CommentedToken<'a>),
@@ -196,6 +201,7 @@ impl std::fmt::Display for Delimiter<'_> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Args<'a> {
     pub left_delimeter: Delimiter<'a>,
     pub args: Vec<Arg<'a>>,
@@ -232,6 +238,7 @@ impl std::fmt::Display for Args<'_> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FunctionDefinition<'a> {
     pub keyword: &'a CommentedToken<'a>,
     pub arguments: Args<'a>,
@@ -263,6 +270,7 @@ impl std::fmt::Display for FunctionDefinition<'_> {
 
 // If expression
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IfConditional<'a> {
     pub keyword: &'a CommentedToken<'a>,
     pub left_delimiter: &'a CommentedToken<'a>,
@@ -272,18 +280,21 @@ pub struct IfConditional<'a> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ElseIfConditional<'a> {
     pub else_keyword: &'a CommentedToken<'a>,
     pub if_conditional: IfConditional<'a>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TrailingElse<'a> {
     pub else_keyword: &'a CommentedToken<'a>,
     pub body: Box<Expression<'a>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IfExpression<'a> {
     pub if_conditional: IfConditional<'a>,
     pub else_ifs: Vec<ElseIfConditional<'a>>,
@@ -329,6 +340,7 @@ impl std::fmt::Display for IfExpression<'_> {
 
 // While expression
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct WhileExpression<'a> {
     pub while_keyword: &'a CommentedToken<'a>,
     pub condition: Box<Expression<'a>>,
@@ -346,6 +358,7 @@ impl std::fmt::Display for WhileExpression<'_> {
 
 // Repeat expresssion
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RepeatExpression<'a> {
     pub repeat_keyword: &'a CommentedToken<'a>,
     pub body: Box<Expression<'a>>,
@@ -359,6 +372,7 @@ impl std::fmt::Display for RepeatExpression<'_> {
 
 // Function call
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FunctionCall<'a> {
     pub function_ref: Box<Expression<'a>>,
     pub args: Args<'a>,
@@ -372,6 +386,7 @@ impl std::fmt::Display for FunctionCall<'_> {
 
 // Subset expression
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SubsetExpression<'a> {
     pub object_ref: Box<Expression<'a>>,
     pub args: Args<'a>,
@@ -385,6 +400,7 @@ impl std::fmt::Display for SubsetExpression<'_> {
 
 // For loop
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ForLoop<'a> {
     pub keyword: &'a CommentedToken<'a>,
     pub left_delim: Delimiter<'a>,
@@ -412,6 +428,7 @@ impl std::fmt::Display for ForLoop<'_> {
 
 // Lambda
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Lambda<'a> {
     pub keyword: &'a CommentedToken<'a>,
     pub args: Args<'a>,
@@ -13,7 +13,6 @@ use crate::{
         IfConditional, IfExpression, Lambda, RepeatExpression, TrailingElse, WhileExpression,
     },
     expressions::{expr, expr_with_newlines, unary_term},
-    program::statement_or_expr,
     token_parsers::*,
 };
 
@@ -256,7 +255,7 @@ pub(crate) fn lambda_function<'a, 'b: 'a>(
             many0(newline),
             delimited_comma_sep_exprs(map(lparen, Delimiter::Paren), map(rparen, Delimiter::Paren)),
             many0(newline),
-            statement_or_expr,
+            expr,
         ),
         |(keyword, _, args, _, body)| {
             Expression::LambdaFunction(Lambda {
@@ -268,3 +267,30 @@ pub(crate) fn lambda_function<'a, 'b: 'a>(
     )
     .parse(tokens)
 }
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::Tokenizer;
+
+    use crate::{Input, ast::Expression, parse, pre_parse};
+
+    fn blank_lines(code: &str) -> usize {
+        let mut tokenizer = Tokenizer::new(code);
+        let mut commented_tokens = tokenizer.tokenize();
+        let tokens = pre_parse(&mut commented_tokens);
+        parse(Input(&tokens))
+            .unwrap()
+            .iter()
+            .filter(|expression| matches!(expression, Expression::Whitespace(_)))
+            .count()
+    }
+
+    #[test]
+    fn lambda_body_leaves_the_newlines_after_it() {
+        // Parsing the body as a statement took the newline ending it,
+        // so the blank line after the lambda was lost
+        assert_eq!(blank_lines("f <- function(x) x\n\ny\n"), 1);
+        assert_eq!(blank_lines("f <- \\(x) x\n\ny\n"), 1);
+        assert_eq!(blank_lines("f <- \\(x) {\n  x\n}\n\ny\n"), 1);
+    }
+}
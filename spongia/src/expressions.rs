@@ -84,8 +84,10 @@ enum Tail<'a> {
     SingleSubset(Args<'a>),
 }
 
+/// The unary operators other than `~`, which starts a formula. Trying `~` again
+/// after the formula failed would make parsing exponential in the number of `~`.
 fn unary_op<'a, 'b: 'a>(tokens: Input<'a, 'b>) -> IResult<Input<'a, 'b>, &'b CommentedToken<'a>> {
-    alt((minus, plus, unary_not, tilde, help)).parse(tokens)
+    alt((minus, plus, unary_not, help)).parse(tokens)
 }
 
 pub(crate) fn unary_term<'a, 'b: 'a>(
@@ -222,7 +224,7 @@ fn precedence(token: &CommentedToken) -> u8 {
     }
 }
 
-fn is_binary_operator(token: &CommentedToken) -> bool {
+pub(crate) fn is_binary_operator(token: &CommentedToken) -> bool {
     matches!(
         &token.token,
         Help | RAssign
@@ -331,7 +333,7 @@ pub(crate) fn expr_with_newlines<'a, 'b: 'a>(
     }
 }
 
-fn bop_to_multibop(bop: Expression) -> Expression {
+pub(crate) fn bop_to_multibop(bop: Expression) -> Expression {
     match bop {
         Expression::Bop(op, lhs, rhs) => {
             let mut multibop = vec![(op, rhs)];
@@ -339,7 +341,9 @@ fn bop_to_multibop(bop: Expression) -> Expression {
             let mut lhs = lhs;
             while let Expression::Bop(op, lhs_, rhs) = *lhs {
                 if original_precedence != precedence(op) {
-                    lhs = Box::new(Expression::Bop(op, lhs_, rhs));
+                    // The chain of other operators, e.g. the pipeline
+                    // of `x %>% f() -> y`, is flattened too
+                    lhs = Box::new(bop_to_multibop(Expression::Bop(op, lhs_, rhs)));
                     break;
                 }
                 multibop.push((op, rhs));
@@ -351,3 +355,36 @@ fn bop_to_multibop(bop: Expression) -> Expression {
         _ => bop,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokenizer::Tokenizer;
+
+    use crate::{Input, ast::Expression, parse, pre_parse};
+
+    #[test]
+    fn repeated_tildes_are_nested_formulae() {
+        let code = format!("{}x", "~".repeat(64));
+        let mut tokenizer = Tokenizer::new(&code);
+        let mut commented_tokens = tokenizer.tokenize();
+        let tokens = pre_parse(&mut commented_tokens);
+        let mut expression = &parse(Input(&tokens)).unwrap()[0];
+        let mut depth = 0;
+        while let Expression::Formula(_, term) = expression {
+            expression = term;
+            depth += 1;
+        }
+        assert_eq!(depth, 64);
+    }
+
+    #[test]
+    fn repeated_tildes_fail_to_parse_in_linear_time() {
+        // A failed formula used to be parsed again as a unary `~`,
+        // doubling the work with every `~`
+        let code = format!("{})", "~".repeat(64));
+        let mut tokenizer = Tokenizer::new(&code);
+        let mut commented_tokens = tokenizer.tokenize();
+        let tokens = pre_parse(&mut commented_tokens);
+        assert!(parse(Input(&tokens)).is_err());
+    }
+}
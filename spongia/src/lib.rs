@@ -6,14 +6,19 @@ pub(crate) mod pre_parsing_hooks;
 use std::{iter::Cloned, slice::Iter};
 
 use nom::Needed;
-pub use parser::parse;
+pub use parser::{ParseFailure, error_offset, parse, parse_failure, parse_with_token_ranges};
 pub use pre_parsing_hooks::pre_parse;
 use tokenizer::tokens::CommentedToken;
+#[cfg(feature = "tree-sitter")]
+pub use tree_sitter_frontend::parse_with_tree_sitter;
 pub(crate) mod program;
 pub(crate) mod token_parsers;
+#[cfg(feature = "tree-sitter")]
+pub(crate) mod tree_sitter_frontend;
 pub(crate) mod whitespace;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Input<'a, 'b: 'a>(pub &'b [&'a CommentedToken<'a>]);
 
 impl<'a, 'b> std::ops::Deref for Input<'a, 'b> {
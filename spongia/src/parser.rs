@@ -1,22 +1,120 @@
+use std::{cell::RefCell, ops::Range};
+
 use log::{debug, trace};
 use tokenizer::Token;
 
 use crate::{Input, ast::Expression};
 
-pub fn parse<'a, 'b: 'a>(mut tokens: Input<'a, 'b>) -> Result<Vec<Expression<'a>>, String> {
+pub fn parse<'a, 'b: 'a>(tokens: Input<'a, 'b>) -> Result<Vec<Expression<'a>>, String> {
+    Ok(parse_with_token_ranges(tokens)?
+        .into_iter()
+        .map(|(expression, _)| expression)
+        .collect())
+}
+
+/// Parses the tokens like [parse] and returns the range of the tokens
+/// each of the top-level expressions was parsed from.
+pub fn parse_with_token_ranges<'a, 'b: 'a>(
+    tokens: Input<'a, 'b>,
+) -> Result<Vec<(Expression<'a>, Range<usize>)>, String> {
+    parse_statements(tokens).map_err(|(_, message)| message)
+}
+
+/// Returns the offset of the token at which parsing fails,
+/// or `None` if the tokens parse.
+pub fn error_offset<'a, 'b: 'a>(tokens: Input<'a, 'b>) -> Option<usize> {
+    parse_failure(tokens).map(|failure| failure.offset)
+}
+
+/// Returns where and why parsing fails, or `None` if the tokens parse.
+pub fn parse_failure<'a, 'b: 'a>(tokens: Input<'a, 'b>) -> Option<ParseFailure> {
+    parse_statements(tokens).err().map(|(failure, _)| failure)
+}
+
+/// The token at which parsing fails and the tokens the parser expected there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFailure {
+    /// The offset of the token in the code.
+    pub offset: usize,
+    /// The offset of the first token of the top-level statement which fails.
+    /// The statements before it parse.
+    pub statement_offset: usize,
+    /// The descriptions of the expected tokens, e.g. "`)`" or "a symbol",
+    /// in the order the parser tried them.
+    pub expected: Vec<&'static str>,
+}
+
+thread_local! {
+    /// The furthest failure of the token parsers in the code being parsed.
+    ///
+    /// The alternatives of the parser hide the failures of each other,
+    /// so the token no alternative could get past is recorded here.
+    /// It is kept across the statements, because a statement which fails
+    /// late can backtrack to a shorter one, e.g. `x <- c(1, 2` to `x <- c`.
+    ///
+    /// Holds the offset of the token and the descriptions of the expected tokens.
+    static FURTHEST_FAILURE: RefCell<Option<(usize, Vec<&'static str>)>> = const { RefCell::new(None) };
+}
+
+/// Records that a token parser expected something else at the start of the input.
+pub(crate) fn record_expected(input: &Input, expected: &'static str) {
+    let Some(token) = input.first() else {
+        return;
+    };
+    FURTHEST_FAILURE.with_borrow_mut(|furthest| match furthest {
+        Some((offset, _)) if *offset > token.offset => {}
+        Some((offset, descriptions)) if *offset == token.offset => {
+            if !descriptions.contains(&expected) {
+                descriptions.push(expected);
+            }
+        }
+        _ => *furthest = Some((token.offset, vec![expected])),
+    });
+}
+
+type ParseError = (ParseFailure, String);
+
+fn parse_statements<'a, 'b: 'a>(
+    mut tokens: Input<'a, 'b>,
+) -> Result<Vec<(Expression<'a>, Range<usize>)>, ParseError> {
     let mut expressions = vec![];
+    let all_tokens = tokens.len();
+    FURTHEST_FAILURE.with_borrow_mut(|furthest| *furthest = None);
 
     while !tokens.is_empty() && !matches!(tokens.first().unwrap().token, Token::EOF) {
         trace!("Main parse function, remaining tokens: {}", &tokens);
-        let (new_remaining_tokens, expr) = crate::program::statement_or_expr(tokens)
-            .map_err(|err| format!("Could not parse: {:?}", err))?;
-        expressions.push(expr);
+        let start = all_tokens - tokens.len();
+        let (new_remaining_tokens, expr) = crate::program::statement_or_expr(tokens.clone())
+            .map_err(|err| {
+                // The token the parser stopped at, or the start of the statement
+                let failed_at = match &err {
+                    nom::Err::Error(e) | nom::Err::Failure(e) => e.input.first(),
+                    nom::Err::Incomplete(_) => None,
+                };
+                let offset = failed_at.or(tokens.first()).map_or(0, |token| token.offset);
+                let statement_offset = tokens
+                    .iter()
+                    .find(|token| !matches!(token.token, Token::Newline | Token::Semicolon))
+                    .map_or(offset, |token| token.offset);
+                let (offset, expected) = FURTHEST_FAILURE
+                    .take()
+                    .filter(|(furthest, _)| *furthest >= offset)
+                    .unwrap_or((offset, vec![]));
+                let failure = ParseFailure {
+                    offset,
+                    statement_offset,
+                    expected,
+                };
+                (failure, format!("Could not parse: {:?}", err))
+            })?;
         tokens = new_remaining_tokens;
+        expressions.push((expr, start..all_tokens - tokens.len()));
         debug!("Remaining tokens length: {}", &tokens.len());
         debug!("Current expressions length: {}", expressions.len());
         trace!("New remaining tokens: {}", &tokens);
     }
-    expressions.push(Expression::EOF(tokens[0]));
+    let eof = all_tokens - tokens.len();
+    expressions.push((Expression::EOF(tokens[0]), eof..eof + 1));
 
     Ok(expressions)
 }
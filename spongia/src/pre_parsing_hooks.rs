@@ -56,12 +56,25 @@ pub fn pre_parse<'a>(tokens: &'a mut [CommentedToken<'a>]) -> Vec<&'a CommentedT
             tokens[it].leading_comments = Some(comments);
             tokens_without_comments.push(it);
         } else if let Token::InlineComment(comment) = tokens[it].token {
-            tokens[it - 1].inline_comment = Some(comment);
+            // The parser drops the semicolons ending the statements,
+            // so the comment goes to the statement before them
+            let mut target = it - 1;
+            while target > 0
+                && matches!(tokens[target].token, Token::Semicolon)
+                && !matches!(
+                    tokens[target - 1].token,
+                    Token::Newline | Token::Comment(_) | Token::InlineComment(_)
+                )
+            {
+                target -= 1;
+            }
+            tokens[target].inline_comment = Some(comment);
         } else {
             tokens_without_comments.push(it);
         }
         it += 1;
     }
+    join_else(tokens, &mut tokens_without_comments);
 
     tokens_without_comments
         .into_iter()
@@ -69,6 +82,65 @@ pub fn pre_parse<'a>(tokens: &'a mut [CommentedToken<'a>]) -> Vec<&'a CommentedT
         .collect()
 }
 
+/// Puts `else` on the line of the end of the `if` body and gathers
+/// the comments around `else` in its leading comments.
+///
+/// A line break before `else` ends the `if` expression at the top level,
+/// so the comments between the `if` body and `else`, and the comments
+/// after `else`, e.g. `} # end\nelse # other\n{`, are all printed after
+/// `else`, before its body. This way the comments never separate `else`
+/// from the `if` body and the code always re-parses.
+fn join_else(tokens: &mut [CommentedToken], tokens_without_comments: &mut Vec<usize>) {
+    let is_newline = |token: &CommentedToken| matches!(token.token, Token::Newline);
+    let mut position = 0;
+    while position < tokens_without_comments.len() {
+        let else_token = tokens_without_comments[position];
+        if !matches!(tokens[else_token].token, Token::Else) {
+            position += 1;
+            continue;
+        }
+        let mut before = position;
+        while before > 0 && is_newline(&tokens[tokens_without_comments[before - 1]]) {
+            before -= 1;
+        }
+        let mut after = position + 1;
+        while after < tokens_without_comments.len()
+            && is_newline(&tokens[tokens_without_comments[after]])
+        {
+            after += 1;
+        }
+        let mut comments = vec![];
+        if before > 0 && before < position {
+            comments.extend(
+                tokens[tokens_without_comments[before - 1]]
+                    .inline_comment
+                    .take(),
+            );
+        }
+        comments.extend(
+            tokens[else_token]
+                .leading_comments
+                .take()
+                .into_iter()
+                .flatten()
+                .filter(|comment| !comment.is_empty()),
+        );
+        comments.extend(tokens[else_token].inline_comment.take());
+        if after < tokens_without_comments.len() {
+            let body = &mut tokens[tokens_without_comments[after]];
+            let body_comments = body.leading_comments.take().into_iter().flatten();
+            comments.extend(body_comments.filter(|comment| !comment.is_empty()));
+        }
+        let else_token = &mut tokens[else_token];
+        if !comments.is_empty() {
+            else_token.leading_comments = Some(comments);
+        }
+        tokens_without_comments.drain(position + 1..after);
+        tokens_without_comments.drain(before..position);
+        position = before + 1;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,4 +172,25 @@ mod tests {
             "Inline comment"
         ));
     }
+
+    #[test]
+    fn test_pre_parse_joins_else() {
+        let mut tokens = commented_tokens![
+            Token::RBrace,
+            Token::InlineComment("# end"),
+            Token::Newline,
+            Token::Else,
+            Token::InlineComment("# other"),
+            Token::Newline,
+            Token::LBrace
+        ];
+        let commented_tokens = pre_parse(&mut tokens);
+        let tokens: Vec<&Token> = commented_tokens.iter().map(|token| &token.token).collect();
+        assert_eq!(tokens, [&Token::RBrace, &Token::Else, &Token::LBrace]);
+        assert_eq!(commented_tokens[0].inline_comment, None);
+        assert_eq!(
+            commented_tokens[1].leading_comments,
+            Some(vec!["# end", "# other"])
+        );
+    }
 }
@@ -4,7 +4,7 @@ use tokenizer::Token::*;
 use tokenizer::tokens::CommentedToken;
 
 macro_rules! token_parser {
-    ($name:ident, $token:pat) => {
+    ($name:ident, $token:pat, $description:literal) => {
         pub(crate) fn $name<'a, 'b>(
             input: Input<'a, 'b>,
         ) -> IResult<Input<'a, 'b>, &'b CommentedToken<'a>>
@@ -15,45 +15,48 @@ macro_rules! token_parser {
                 [token @ CommentedToken { token: $token, .. }, rest @ ..] => {
                     Ok((Input(rest), token))
                 }
-                _ => Err(nom::Err::Error(nom::error::Error::new(
-                    input,
-                    nom::error::ErrorKind::Tag,
-                ))),
+                _ => {
+                    crate::parser::record_expected(&input, $description);
+                    Err(nom::Err::Error(nom::error::Error::new(
+                        input,
+                        nom::error::ErrorKind::Tag,
+                    )))
+                }
             }
         }
     };
 }
 
-token_parser!(symbol, Symbol(_));
-token_parser!(literal, Literal(_));
-token_parser!(semicolon, Semicolon);
-token_parser!(newline, Newline);
-token_parser!(lparen, LParen);
-token_parser!(rparen, RParen);
-token_parser!(lbrace, LBrace);
-token_parser!(rbrace, RBrace);
-token_parser!(lbracket, LBracket);
-token_parser!(rbracket, RBracket);
-token_parser!(comma, Comma);
+token_parser!(symbol, Symbol(_), "a symbol");
+token_parser!(literal, Literal(_), "a literal");
+token_parser!(semicolon, Semicolon, "`;`");
+token_parser!(newline, Newline, "a line break");
+token_parser!(lparen, LParen, "`(`");
+token_parser!(rparen, RParen, "`)`");
+token_parser!(lbrace, LBrace, "`{`");
+token_parser!(rbrace, RBrace, "`}`");
+token_parser!(lbracket, LBracket, "`[`");
+token_parser!(rbracket, RBracket, "`]`");
+token_parser!(comma, Comma, "`,`");
 
 // Reserved
-token_parser!(continue_token, Continue);
-token_parser!(break_token, Break);
+token_parser!(continue_token, Continue, "`continue`");
+token_parser!(break_token, Break, "`break`");
 
 // Compound
-token_parser!(if_token, If);
-token_parser!(else_token, Else);
-token_parser!(while_token, While);
-token_parser!(for_token, For);
-token_parser!(repeat, Repeat);
-token_parser!(in_token, In);
-token_parser!(function, Function);
-token_parser!(lambda, Lambda);
+token_parser!(if_token, If, "`if`");
+token_parser!(else_token, Else, "`else`");
+token_parser!(while_token, While, "`while`");
+token_parser!(for_token, For, "`for`");
+token_parser!(repeat, Repeat, "`repeat`");
+token_parser!(in_token, In, "`in`");
+token_parser!(function, Function, "`function`");
+token_parser!(lambda, Lambda, "`\\`");
 
 // Binary operators
 // token_parser!(lassign, LAssign);
 // token_parser!(rassign, RAssign);
-token_parser!(old_assign, OldAssign);
+token_parser!(old_assign, OldAssign, "`=`");
 // token_parser!(equal, Equal);
 // token_parser!(not_equal, NotEqual);
 // token_parser!(lower_than, LowerThan);
@@ -75,11 +78,11 @@ token_parser!(old_assign, OldAssign);
 // token_parser!(colon, Colon);
 
 // Unary operators
-token_parser!(unary_not, UnaryNot);
-token_parser!(minus, Minus);
-token_parser!(plus, Plus);
-token_parser!(tilde, Tilde);
-token_parser!(help, Help);
+token_parser!(unary_not, UnaryNot, "`!`");
+token_parser!(minus, Minus, "`-`");
+token_parser!(plus, Plus, "`+`");
+token_parser!(tilde, Tilde, "`~`");
+token_parser!(help, Help, "`?`");
 
 // Comments
 // token_parser!(inline_comment, InlineComment(_));
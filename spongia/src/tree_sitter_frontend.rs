@@ -0,0 +1,512 @@
+//! Builds the syntax tree from the parse tree of tree-sitter-r.
+//!
+//! The tree is built from the same tokens and in the same shape as the tree
+//! of [crate::parse], e.g. with the chains of the operators of the same
+//! precedence flattened, so the formatter can print it and the two trees can
+//! be compared. The structure of the expressions follows the grammar of
+//! tree-sitter-r.
+use tokenizer::Token;
+use tokenizer::tokens::CommentedToken;
+use tree_sitter::{Node, Parser};
+
+use crate::Input;
+use crate::ast::{
+    Arg, Args, Delimiter, ElseIfConditional, Expression, ForLoop, FunctionCall, FunctionDefinition,
+    IfConditional, IfExpression, Lambda, RepeatExpression, SubsetExpression, TermExpr,
+    TrailingElse, WhileExpression,
+};
+use crate::expressions::{bop_to_multibop, is_binary_operator};
+
+/// Parses the code with tree-sitter-r and builds the syntax tree from the tokens
+/// of the code, after [crate::pre_parse].
+///
+/// Returns an error if tree-sitter-r cannot parse the code or the code uses
+/// a construct the formatter does not support.
+pub fn parse_with_tree_sitter<'a, 'b: 'a>(
+    code: &str,
+    tokens: Input<'a, 'b>,
+) -> Result<Vec<Expression<'a>>, String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_r::LANGUAGE.into())
+        .map_err(|err| err.to_string())?;
+    let tree = parser
+        .parse(code, None)
+        .ok_or("tree-sitter-r did not parse the code")?;
+    let program = tree.root_node();
+    if let Some(error) = first_error(program) {
+        let position = error.start_position();
+        return Err(format!(
+            "tree-sitter-r could not parse the code at line {}, column {}",
+            position.row + 1,
+            position.column + 1
+        ));
+    }
+    let eof = match tokens.last() {
+        Some(token) if matches!(token.token, Token::EOF) => tokens.len() - 1,
+        _ => return Err("The tokens do not end with EOF".to_string()),
+    };
+    let builder = Builder { tokens: tokens.0 };
+    let mut expressions = builder.statements(statements(program), 0, eof)?;
+    expressions.push(Expression::EOF(tokens[eof]));
+    Ok(expressions)
+}
+
+fn first_error(node: Node) -> Option<Node> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let error = node.children(&mut cursor).find_map(first_error);
+    error
+}
+
+/// The statements of a program or a braced expression, without the comments.
+fn statements(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .filter(|child| child.kind() != "comment")
+        .collect()
+}
+
+struct Builder<'a, 'b: 'a> {
+    tokens: &'b [&'a CommentedToken<'a>],
+}
+
+impl<'a, 'b: 'a> Builder<'a, 'b> {
+    /// The index of the token the node starts with.
+    fn index(&self, node: Node) -> Result<usize, String> {
+        self.tokens
+            .binary_search_by_key(&node.start_byte(), |token| token.offset)
+            .map_err(|_| {
+                format!(
+                    "No token starts the {} at line {}",
+                    node.kind(),
+                    node.start_position().row + 1
+                )
+            })
+    }
+
+    /// The index of the first token after the node.
+    fn end(&self, node: Node) -> usize {
+        self.tokens
+            .partition_point(|token| token.offset < node.end_byte())
+    }
+
+    fn token(&self, node: Node) -> Result<&'a CommentedToken<'a>, String> {
+        Ok(self.tokens[self.index(node)?])
+    }
+
+    /// The statements between the tokens `position` and `end`. Each statement
+    /// takes the line break or the semicolon ending it and the other line
+    /// breaks are the whitespace between the statements.
+    fn statements(
+        &self,
+        statements: Vec<Node>,
+        mut position: usize,
+        end: usize,
+    ) -> Result<Vec<Expression<'a>>, String> {
+        let mut expressions = vec![];
+        for statement in statements {
+            self.whitespace(position, self.index(statement)?, &mut expressions);
+            expressions.push(self.expression(statement)?);
+            position = self.end(statement);
+            if position < end
+                && matches!(
+                    self.tokens[position].token,
+                    Token::Newline | Token::Semicolon
+                )
+            {
+                position += 1;
+            }
+        }
+        self.whitespace(position, end, &mut expressions);
+        Ok(expressions)
+    }
+
+    fn whitespace(&self, start: usize, end: usize, expressions: &mut Vec<Expression<'a>>) {
+        let newlines = self.tokens[start..end]
+            .split(|token| !matches!(token.token, Token::Newline))
+            .filter(|newlines| !newlines.is_empty());
+        expressions.extend(newlines.map(|newlines| Expression::Whitespace(Input(newlines))));
+    }
+
+    fn expression(&self, node: Node) -> Result<Expression<'a>, String> {
+        let field = |name| field(node, name);
+        let start = self.index(node)?;
+        if self.end(node) == start + 1 {
+            let token = self.tokens[start];
+            return match token.token {
+                Token::Symbol(_) => Ok(Expression::Symbol(token)),
+                Token::Literal(_) => Ok(Expression::Literal(token)),
+                Token::Break => Ok(Expression::Break(token)),
+                Token::Continue => Ok(Expression::Continue(token)),
+                _ => Err(self.unsupported(node)),
+            };
+        }
+        match node.kind() {
+            "binary_operator" | "extract_operator" | "namespace_operator" => {
+                Ok(bop_to_multibop(self.operation(node)?))
+            }
+            "unary_operator" => {
+                let operator = self.token(field("operator")?)?;
+                let operand = Box::new(self.expression(field("rhs")?)?);
+                match operator.token {
+                    Token::Tilde => Ok(Expression::Formula(operator, operand)),
+                    _ => Ok(Expression::Unary(operator, operand)),
+                }
+            }
+            "parenthesized_expression" => Ok(Expression::Term(Box::new(TermExpr::new(
+                Some(self.token(field("open")?)?),
+                vec![self.expression(field("body")?)?],
+                Some(self.token(field("close")?)?),
+            )))),
+            "braced_expression" => {
+                let open = self.index(field("open")?)?;
+                let close = self.index(field("close")?)?;
+                let mut position = open + 1;
+                while position < close && matches!(self.tokens[position].token, Token::Newline) {
+                    position += 1;
+                }
+                Ok(Expression::Term(Box::new(TermExpr::new(
+                    Some(self.tokens[open]),
+                    self.statements(statements(node), position, close)?,
+                    Some(self.tokens[close]),
+                ))))
+            }
+            "call" => Ok(Expression::FunctionCall(FunctionCall {
+                function_ref: Box::new(self.expression(field("function")?)?),
+                args: self.arguments(field("arguments")?)?,
+            })),
+            "subset" | "subset2" => Ok(Expression::SubsetExpression(SubsetExpression {
+                object_ref: Box::new(self.expression(field("function")?)?),
+                args: self.arguments(field("arguments")?)?,
+            })),
+            "function_definition" => {
+                let keyword = self.token(field("name")?)?;
+                let args = self.arguments(field("parameters")?)?;
+                let body = Box::new(self.expression(field("body")?)?);
+                match keyword.token {
+                    Token::Lambda => Ok(Expression::LambdaFunction(Lambda {
+                        keyword,
+                        args,
+                        body,
+                    })),
+                    _ => Ok(Expression::FunctionDef(FunctionDefinition::new(
+                        keyword, args, body,
+                    ))),
+                }
+            }
+            "if_statement" => self.if_expression(node),
+            "for_statement" => Ok(Expression::ForLoopExpression(ForLoop {
+                keyword: self.token(node)?,
+                left_delim: Delimiter::Paren(self.token(field("open")?)?),
+                identifier: Box::new(self.expression(field("variable")?)?),
+                in_keyword: self.token(child(node, "in")?)?,
+                collection: Box::new(self.expression(field("sequence")?)?),
+                right_delim: Delimiter::Paren(self.token(field("close")?)?),
+                body: Box::new(self.expression(field("body")?)?),
+            })),
+            "while_statement" => Ok(Expression::WhileExpression(WhileExpression {
+                while_keyword: self.token(node)?,
+                condition: Box::new(Expression::Term(Box::new(TermExpr::new(
+                    Some(self.token(field("open")?)?),
+                    vec![self.expression(field("condition")?)?],
+                    Some(self.token(field("close")?)?),
+                )))),
+                body: Box::new(self.expression(field("body")?)?),
+            })),
+            "repeat_statement" => Ok(Expression::RepeatExpression(RepeatExpression {
+                repeat_keyword: self.token(node)?,
+                body: Box::new(self.expression(field("body")?)?),
+            })),
+            _ => Err(self.unsupported(node)),
+        }
+    }
+
+    /// The binary operation, with the operations on the left not flattened yet.
+    fn operation(&self, node: Node) -> Result<Expression<'a>, String> {
+        let operator = self.token(field(node, "operator")?)?;
+        if !is_binary_operator(operator) {
+            return Err(self.unsupported(node));
+        }
+        let lhs = field(node, "lhs")?;
+        let lhs = match lhs.kind() {
+            "binary_operator" | "extract_operator" | "namespace_operator"
+                if self.end(lhs) > self.index(lhs)? + 1 =>
+            {
+                self.operation(lhs)?
+            }
+            _ => self.expression(lhs)?,
+        };
+        let rhs = self.expression(field(node, "rhs")?)?;
+        Ok(Expression::Bop(operator, Box::new(lhs), Box::new(rhs)))
+    }
+
+    /// The arguments of a call or a subset, or the parameters of a function.
+    fn arguments(&self, node: Node) -> Result<Args<'a>, String> {
+        let open = field(node, "open")?;
+        let kind = open.kind();
+        let open = self.index(open)?;
+        let close = self.index(field(node, "close")?)?;
+        let (left_delimiter, right_delimiter) = match kind {
+            "[[" => (
+                Delimiter::DoubleBracket((self.tokens[open], self.tokens[open + 1])),
+                Delimiter::DoubleBracket((self.tokens[close], self.tokens[close + 1])),
+            ),
+            "[" => (
+                Delimiter::SingleBracket(self.tokens[open]),
+                Delimiter::SingleBracket(self.tokens[close]),
+            ),
+            _ => (
+                Delimiter::Paren(self.tokens[open]),
+                Delimiter::Paren(self.tokens[close]),
+            ),
+        };
+        let mut args: Vec<Arg<'a>> = vec![];
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            match child.kind() {
+                "comma" => {
+                    let comma = Some(Expression::Literal(self.token(child)?));
+                    match args.last_mut() {
+                        Some(Arg::Proper(_, last_comma @ None))
+                        | Some(Arg::EmptyEqual(_, _, last_comma @ None)) => *last_comma = comma,
+                        _ => args.push(Arg::Proper(None, comma)),
+                    }
+                }
+                "argument" | "parameter" => args.push(self.argument(child)?),
+                _ => {}
+            }
+        }
+        if matches!(
+            args.last(),
+            Some(Arg::Proper(_, Some(_)) | Arg::EmptyEqual(_, _, Some(_)))
+        ) {
+            args.push(Arg::Proper(None, None));
+        }
+        Ok(Args::new(left_delimiter, args, right_delimiter))
+    }
+
+    fn argument(&self, node: Node) -> Result<Arg<'a>, String> {
+        let value = node
+            .child_by_field_name("value")
+            .or_else(|| node.child_by_field_name("default"));
+        let Some(name) = node.child_by_field_name("name") else {
+            let value = value.ok_or_else(|| self.unsupported(node))?;
+            return Ok(Arg::Proper(Some(self.expression(value)?), None));
+        };
+        let name = self.expression(name)?;
+        let Ok(equal) = child(node, "=") else {
+            return Ok(Arg::Proper(Some(name), None));
+        };
+        let equal = self.token(equal)?;
+        match value {
+            Some(value) => Ok(Arg::Proper(
+                Some(bop_to_multibop(Expression::Bop(
+                    equal,
+                    Box::new(name),
+                    Box::new(self.expression(value)?),
+                ))),
+                None,
+            )),
+            None => Ok(Arg::EmptyEqual(name, equal, None)),
+        }
+    }
+
+    fn if_expression(&self, node: Node) -> Result<Expression<'a>, String> {
+        let if_conditional = self.if_conditional(node)?;
+        let mut else_ifs = vec![];
+        let mut trailing_else = None;
+        let mut node = node;
+        while let Some(alternative) = node.child_by_field_name("alternative") {
+            let else_keyword = self.token(child(node, "else")?)?;
+            if alternative.kind() == "if_statement" {
+                else_ifs.push(ElseIfConditional {
+                    else_keyword,
+                    if_conditional: self.if_conditional(alternative)?,
+                });
+                node = alternative;
+            } else {
+                trailing_else = Some(TrailingElse {
+                    else_keyword,
+                    body: Box::new(self.expression(alternative)?),
+                });
+                break;
+            }
+        }
+        Ok(Expression::IfExpression(IfExpression {
+            if_conditional,
+            else_ifs,
+            trailing_else,
+        }))
+    }
+
+    fn if_conditional(&self, node: Node) -> Result<IfConditional<'a>, String> {
+        Ok(IfConditional {
+            keyword: self.token(node)?,
+            left_delimiter: self.token(field(node, "open")?)?,
+            condition: Box::new(self.expression(field(node, "condition")?)?),
+            right_delimiter: self.token(field(node, "close")?)?,
+            body: Box::new(self.expression(field(node, "consequence")?)?),
+        })
+    }
+
+    fn unsupported(&self, node: Node) -> String {
+        format!(
+            "Unsupported {} at line {}",
+            node.kind(),
+            node.start_position().row + 1
+        )
+    }
+}
+
+fn field<'tree>(node: Node<'tree>, name: &str) -> Result<Node<'tree>, String> {
+    node.child_by_field_name(name).ok_or_else(|| {
+        format!(
+            "The {} at line {} has no {name}",
+            node.kind(),
+            node.start_position().row + 1
+        )
+    })
+}
+
+fn child<'tree>(node: Node<'tree>, kind: &str) -> Result<Node<'tree>, String> {
+    let mut cursor = node.walk();
+    let child = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == kind);
+    child.ok_or_else(|| {
+        format!(
+            "The {} at line {} has no {kind}",
+            node.kind(),
+            node.start_position().row + 1
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, pre_parse};
+    use tokenizer::Tokenizer;
+
+    fn assert_same_trees(codes: &[&str]) {
+        for code in codes {
+            let mut tokenizer = Tokenizer::new(code);
+            let mut commented_tokens = tokenizer.tokenize();
+            let tokens = pre_parse(&mut commented_tokens);
+            let native = parse(Input(&tokens)).unwrap();
+            let tree_sitter = parse_with_tree_sitter(code, Input(&tokens)).unwrap();
+            assert_eq!(native, tree_sitter, "{code}");
+        }
+    }
+
+    #[test]
+    fn operators() {
+        assert_same_trees(&[
+            "a + b * c - d",
+            "a <- b <- c",
+            "x = y = 2",
+            "5 -> x -> y",
+            "a^b^c",
+            "a %>% b %in% c",
+            "y ~ x + z",
+            "~ a + b",
+            "!a",
+            "a && !b || c",
+            "-1 + +2",
+            "x$a$b@c",
+            "pkg::f(x)",
+            "a ? b",
+            "a <<- b",
+            "a +\n  b",
+        ]);
+    }
+
+    #[test]
+    fn calls_and_subsets() {
+        assert_same_trees(&[
+            "f()",
+            "f(a, b = 1, c = , ...)",
+            "f(a, , b)",
+            "f(a, b, )",
+            "f(\n  a,\n  b\n)",
+            "f(\"a\" = 1, `b` = 2)",
+            "f()()",
+            "x[1]",
+            "x[a, , drop = FALSE]",
+            "x[[1]][2]",
+            "x[[i]] <- 1",
+            "x |> f(y = _)",
+        ]);
+    }
+
+    #[test]
+    fn functions() {
+        assert_same_trees(&[
+            "function() NULL",
+            "function(x, y = 2, ...) {\n  x + y\n}",
+            "f <- function(x)\n  x",
+            "\\(x) x + 1",
+            "(function(x) x)(1)",
+            "function(x = function() 1) x",
+        ]);
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_same_trees(&[
+            "if (a) b",
+            "if (a) b else c",
+            "x <- if (a) b else c",
+            "if (a) {\n  b\n} else if (c) {\n  d\n} else {\n  e\n}",
+            "{\n  if (a) b\n  else c\n}",
+            "for (i in 1:10) {\n  next\n}",
+            "while (TRUE) break",
+            "repeat {\n  break\n}",
+        ]);
+    }
+
+    #[test]
+    fn statements_and_blank_lines() {
+        assert_same_trees(&[
+            "",
+            "a; b",
+            "{a; b}",
+            "{ }",
+            "\n\na\n\n\nb\n\n",
+            "{\n\n  a\n\n  b\n\n}",
+            "# comment\na # inline\n# trailing\n",
+        ]);
+    }
+
+    #[test]
+    fn unary_operators_bind_looser_than_in_the_native_parser() {
+        let code = "-a^2";
+        let mut tokenizer = Tokenizer::new(code);
+        let mut commented_tokens = tokenizer.tokenize();
+        let tokens = pre_parse(&mut commented_tokens);
+        let native = parse(Input(&tokens)).unwrap();
+        let tree_sitter = parse_with_tree_sitter(code, Input(&tokens)).unwrap();
+        assert!(
+            matches!(&native[0], Expression::MultiBop(lhs, _) if matches!(**lhs, Expression::Unary(..)))
+        );
+        assert!(
+            matches!(&tree_sitter[0], Expression::Unary(_, operand) if matches!(**operand, Expression::MultiBop(..)))
+        );
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_line() {
+        let code = "a <- 1\nf(x))\n";
+        let mut tokenizer = Tokenizer::new(code);
+        let mut commented_tokens = tokenizer.tokenize();
+        let tokens = pre_parse(&mut commented_tokens);
+        let err = parse_with_tree_sitter(code, Input(&tokens)).unwrap_err();
+        assert!(err.contains("line 2"), "{err}");
+    }
+}
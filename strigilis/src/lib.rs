@@ -0,0 +1,37 @@
+//! A JavaScript API of `tergo`, compiled to WebAssembly with `wasm-bindgen`.
+//!
+//! The formatting pipeline never touches the filesystem, so the same code
+//! runs in the browser, in WebR and in VS Code for the Web.
+
+use tergo_lib::{Config, tergo_format};
+use wasm_bindgen::prelude::*;
+
+/// Formats the R code.
+///
+/// `configJson` is a JSON object with the same keys as `tergo.toml`,
+/// e.g. `{"indent": 4}`. The missing keys take their default values.
+/// Throws an error if the configuration is invalid or the code
+/// cannot be parsed.
+#[wasm_bindgen]
+pub fn format(
+    code: &str,
+    #[wasm_bindgen(js_name = configJson)] config_json: Option<String>,
+) -> Result<String, JsError> {
+    let config = parse_config(config_json.as_deref()).map_err(|e| JsError::new(&e))?;
+    tergo_format(code, Some(&config)).map_err(|e| JsError::new(&e))
+}
+
+/// Returns the default configuration as a JSON object.
+#[wasm_bindgen(js_name = defaultConfig)]
+pub fn default_config() -> String {
+    serde_json::to_string(&Config::default()).unwrap_or_default()
+}
+
+/// Parses the configuration from JSON. An empty or missing configuration
+/// is the default configuration.
+pub fn parse_config(config_json: Option<&str>) -> Result<Config, String> {
+    match config_json.map(str::trim) {
+        None | Some("") => Ok(Config::default()),
+        Some(json) => serde_json::from_str(json).map_err(|e| format!("Invalid configuration: {e}")),
+    }
+}
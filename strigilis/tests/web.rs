@@ -0,0 +1,27 @@
+//! Run with `wasm-pack test --node strigilis`.
+#![cfg(target_arch = "wasm32")]
+
+use tergo_wasm::{default_config, format};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn formats_the_code() {
+    assert_eq!(format("a<-1", None).unwrap(), "a <- 1\n");
+}
+
+#[wasm_bindgen_test]
+fn formats_the_code_with_the_configuration() {
+    let formatted = format("f<-function(x){x}", Some(r#"{"indent": 4}"#.to_string())).unwrap();
+    assert_eq!(formatted, "f <- function(x) {\n    x\n}\n");
+}
+
+#[wasm_bindgen_test]
+fn fails_on_an_invalid_configuration_or_code() {
+    assert!(format("a", Some("{".to_string())).is_err());
+    assert!(format("a <- )", None).is_err());
+}
+
+#[wasm_bindgen_test]
+fn default_configuration_is_valid() {
+    assert_eq!(format("a<-1", Some(default_config())).unwrap(), "a <- 1\n");
+}
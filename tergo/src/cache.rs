@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tergo_lib::Config;
+use xxhash_rust::xxh3::Xxh3;
+
+/// The default location of the cache file, relative to the working directory.
+pub(crate) const DEFAULT_CACHE_LOCATION: &str = ".tergo_cache";
+
+/// The cache is invalidated whenever `tergo` changes, because the same code
+/// and configuration can format differently between the versions.
+const CACHE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: String,
+    /// The hashes of the content and the configuration of the files
+    /// that were already formatted, keyed by their absolute paths.
+    files: HashMap<PathBuf, String>,
+}
+
+/// Remembers the files that are already formatted, so the following runs
+/// can skip them without formatting them again.
+///
+/// A file is skipped only if neither its content nor its configuration
+/// changed since it was recorded.
+#[derive(Debug)]
+pub(crate) struct FormatCache {
+    location: PathBuf,
+    files: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl FormatCache {
+    /// Reads the cache file. A missing, corrupted or outdated cache file
+    /// results in an empty cache.
+    pub(crate) fn load(location: PathBuf) -> Self {
+        let files = match std::fs::read_to_string(&location) {
+            Ok(content) => match serde_json::from_str::<CacheFile>(&content) {
+                Ok(cache) if cache.version == CACHE_VERSION => cache.files,
+                Ok(cache) => {
+                    debug!("Discarding the cache from tergo {}", cache.version);
+                    HashMap::new()
+                }
+                Err(e) => {
+                    warn!("Ignoring the corrupted cache file {location:?}: {e}");
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            location,
+            files: Mutex::new(files),
+        }
+    }
+
+    /// Returns the key identifying the content of a file formatted
+    /// with the configuration.
+    pub(crate) fn key(content: &str, config: &Config) -> String {
+        let mut hasher = Xxh3::new();
+        hasher.update(toml::to_string(config).unwrap_or_default().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(content.as_bytes());
+        format!("{:016x}", hasher.digest())
+    }
+
+    pub(crate) fn is_formatted(&self, file: &Path, key: &str) -> bool {
+        self.files
+            .lock()
+            .unwrap()
+            .get(&absolute_path(file))
+            .is_some_and(|cached| cached == key)
+    }
+
+    pub(crate) fn mark_formatted(&self, file: &Path, key: String) {
+        self.files.lock().unwrap().insert(absolute_path(file), key);
+    }
+
+    pub(crate) fn save(&self) -> Result<(), String> {
+        let cache = CacheFile {
+            version: CACHE_VERSION.to_string(),
+            files: self.files.lock().unwrap().clone(),
+        };
+        let content = serde_json::to_string(&cache).map_err(|e| e.to_string())?;
+        if let Some(parent) = self.location.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+        }
+        std::fs::write(&self.location, content).map_err(|e| e.to_string())
+    }
+}
+
+fn absolute_path(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
@@ -0,0 +1,473 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use std::{ops::Range, path::PathBuf};
+
+use crate::{
+    cache,
+    diff::ColorChoice,
+    report::OutputFormat,
+    stats,
+    styler::{self, Style},
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
+    /// Running `tergo` without a subcommand is the same as running `tergo fmt`.
+    #[command(flatten)]
+    pub(crate) format: FormatArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Format R files in a file or a directory tree.
+    Fmt(FormatArgs),
+    /// Check whether files are formatted, without changing them.
+    ///
+    /// The same as `tergo fmt --check`.
+    Check(FormatArgs),
+    /// Print the effective configuration for a path.
+    Config(ConfigArgs),
+    /// Create a `tergo.toml` with all the options and their defaults.
+    Init(InitArgs),
+    /// Manage the git hooks running `tergo`.
+    #[command(subcommand)]
+    Hook(HookCommand),
+    /// Check tergo against a corpus of R files without changing them.
+    #[command(subcommand)]
+    Corpus(CorpusCommand),
+    /// Run a background process formatting code on requests sent to a socket.
+    ///
+    /// The daemon keeps the configuration loaded between the requests,
+    /// so editors formatting many small files avoid the startup cost.
+    Daemon(DaemonArgs),
+    /// Run a language server formatting R code in the editors.
+    ///
+    /// The server communicates over the standard input and output.
+    Lsp,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct InitArgs {
+    /// The directory to create the configuration file in.
+    #[arg(default_value = ".")]
+    pub(crate) directory: PathBuf,
+
+    /// Take the line length and the indentation from the `.lintr` file
+    /// and the styler pre-commit hook in the directory.
+    #[arg(long)]
+    pub(crate) import: bool,
+
+    /// Overwrite an existing configuration file.
+    #[arg(long)]
+    pub(crate) force: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DaemonArgs {
+    /// The path of the Unix socket to listen on.
+    ///
+    /// Defaults to `tergo.sock` in `$XDG_RUNTIME_DIR` or the temporary directory.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) socket: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum CorpusCommand {
+    /// Format every file in a directory, check that the formatted code
+    /// means the same and that formatting it again changes nothing,
+    /// and write a JSON report.
+    ///
+    /// The files are not changed. Exits with a non-zero status if any file
+    /// reveals a bug in tergo; syntax errors in the files do not count.
+    Run(CorpusRunArgs),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CorpusRunArgs {
+    /// The directory with the R files.
+    pub(crate) directory: PathBuf,
+
+    /// The path to the configuration file. Defaults to the `tergo.toml`
+    /// files found in the directories of each file.
+    #[arg(long)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Write the report to this file instead of the standard output.
+    #[arg(short, long, value_name = "PATH")]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum HookCommand {
+    /// Install a git pre-commit hook formatting the staged files.
+    Install {
+        /// Overwrite an existing pre-commit hook.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct FormatArgs {
+    /// The file or directory to format. Use `-` to read from the standard input.
+    #[arg(default_value = ".")]
+    pub(crate) path: String,
+
+    /// The path to the configuration file.
+    ///
+    /// If not provided, `tergo` uses the closest `tergo.toml` found in the directory
+    /// of each formatted file or any of its parent directories.
+    pub(crate) config: Option<String>,
+
+    #[command(flatten)]
+    pub(crate) overrides: ConfigOverrides,
+
+    /// Print a unified diff of the changes instead of rewriting the files.
+    ///
+    /// Exits with a non-zero status if any file would be reformatted.
+    #[arg(long)]
+    pub(crate) diff: bool,
+
+    /// Report the files that would be reformatted instead of rewriting them.
+    ///
+    /// Exits with a non-zero status if any file would be reformatted.
+    #[arg(long)]
+    pub(crate) check: bool,
+
+    /// Report the files that would be reformatted, but never change them.
+    ///
+    /// Unlike `--check`, exits with a zero status.
+    #[arg(long, conflicts_with = "diff")]
+    pub(crate) dry_run: bool,
+
+    /// Keep a copy of every reformatted file with the suffix added to its name,
+    /// e.g. `--backup=.orig`. The suffix defaults to `.bak`.
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak"
+    )]
+    pub(crate) backup: Option<String>,
+
+    /// Print a machine-readable report of the check to the standard output.
+    ///
+    /// Implies `--check`. The report lists the status, the diff and
+    /// the parse errors of every file.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        conflicts_with_all = ["diff", "stdin", "watch"]
+    )]
+    pub(crate) output_format: OutputFormat,
+
+    /// When to colorize the diff and the status of the files.
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub(crate) color: ColorChoice,
+
+    /// Print nothing if formatting succeeds, only the failures.
+    #[arg(long, short = 'q', conflicts_with = "verbose")]
+    pub(crate) quiet: bool,
+
+    /// Print every file with the time spent formatting it,
+    /// including the unchanged and the skipped files.
+    #[arg(long, short = 'v')]
+    pub(crate) verbose: bool,
+
+    /// Read the code from the standard input and write the formatted code
+    /// to the standard output. The same as passing `-` as the path.
+    #[arg(long)]
+    pub(crate) stdin: bool,
+
+    /// The path of the file passed via the standard input.
+    ///
+    /// `tergo` treats the code as if it lived at this path, e.g. it is
+    /// matched against the exclusion list and used in diffs.
+    #[arg(long, value_name = "PATH")]
+    pub(crate) stdin_filename: Option<String>,
+
+    /// Only format files matching this glob. Can be repeated.
+    ///
+    /// Defaults to `*.R`, `*.r`, `*.Rmd`, `*.qmd`, `*.Rnw` and `*.ipynb`.
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) include: Vec<String>,
+
+    /// Skip files matching this glob. Can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) exclude: Vec<String>,
+
+    /// Format the files matched by `.gitignore` and `.tergoignore` files, too.
+    #[arg(long)]
+    pub(crate) no_ignore: bool,
+
+    /// Skip the files left out of the R package by its `.Rbuildignore`,
+    /// e.g. `revdep/` or `docs/`.
+    #[arg(long)]
+    pub(crate) respect_rbuildignore: bool,
+
+    /// Format the files generated by a tool, too, e.g. `R/RcppExports.R`.
+    ///
+    /// The generated files are recognized by the `generated_file_markers`
+    /// of the configuration.
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// The number of files formatted in parallel.
+    ///
+    /// Defaults to the number of logical CPUs.
+    #[arg(long, short = 'j', value_name = "N")]
+    pub(crate) jobs: Option<usize>,
+
+    /// Skip the files that did not change since they were last formatted.
+    ///
+    /// The hashes of the formatted files and their configuration
+    /// are kept in the cache file.
+    #[arg(long)]
+    pub(crate) cache: bool,
+
+    /// Only format the files that differ from a git revision, e.g. `--changed=main`.
+    ///
+    /// Without a revision, the files are compared to the git index.
+    /// The new files not known to git are formatted, too.
+    #[arg(
+        long,
+        value_name = "REV",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    pub(crate) changed: Option<String>,
+
+    /// Only format the statements overlapping the changed lines of the files.
+    ///
+    /// Only R scripts can be formatted this way.
+    #[arg(long, requires = "changed", conflicts_with = "cache")]
+    pub(crate) lines: bool,
+
+    /// Only format the statements overlapping the lines, e.g. `--range 10:40`.
+    ///
+    /// The lines are counted from 1 and both ends are included.
+    /// Requires a single R script or the standard input.
+    #[arg(
+        long,
+        value_name = "START:END",
+        value_parser = parse_line_range,
+        conflicts_with_all = ["changed", "cache", "watch"]
+    )]
+    pub(crate) range: Option<Range<usize>>,
+
+    /// Only format the statements overlapping the modified lines of a single
+    /// R script and print the edits to the standard output as JSON.
+    ///
+    /// The modified lines are read from a unified diff on the standard input,
+    /// or are the lines changed since `--since`. The file is not changed,
+    /// so code review bots can suggest the edits.
+    #[arg(
+        long,
+        conflicts_with_all = ["stdin", "range", "changed", "staged", "watch", "cache", "diff"]
+    )]
+    pub(crate) modified_lines_only: bool,
+
+    /// The git revision the modified lines are compared to, e.g. `--since=main`.
+    #[arg(long, value_name = "REV", requires = "modified_lines_only")]
+    pub(crate) since: Option<String>,
+
+    /// Only format the staged files and stage the formatted files again.
+    ///
+    /// The files with unstaged changes are skipped, so formatting
+    /// does not stage them by accident.
+    #[arg(long, conflicts_with_all = ["stdin", "changed"])]
+    pub(crate) staged: bool,
+
+    /// Keep running and format the files again whenever they change.
+    #[arg(long, conflicts_with_all = ["stdin", "changed", "staged"])]
+    pub(crate) watch: bool,
+
+    /// Format the formatted code again and fail if that changes it.
+    ///
+    /// Formatting must be stable, so a file changing again is a bug in `tergo`.
+    #[arg(long, conflicts_with_all = ["stdin", "range", "lines"])]
+    pub(crate) check_idempotent: bool,
+
+    /// Fail if formatting an R script drops or duplicates any of its tokens,
+    /// e.g. a comment or a string.
+    ///
+    /// A debugging aid: the line breaks and the semicolons are not compared.
+    #[arg(long)]
+    pub(crate) audit_tokens: bool,
+
+    /// Format the valid statements of the R scripts with syntax errors
+    /// and keep the statements which do not parse as they are.
+    ///
+    /// The kept lines are reported as warnings.
+    #[arg(long)]
+    pub(crate) skip_syntax_errors: bool,
+
+    /// Print the statistics of the run to the standard error, listing
+    /// the N files that took the longest to format (5 by default).
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = stats::DEFAULT_SLOWEST_FILES,
+        conflicts_with = "stdin"
+    )]
+    pub(crate) stats: Option<usize>,
+
+    /// Print the time spent in each stage of formatting the R scripts
+    /// and the number of the allocations to the standard error.
+    #[arg(long, hide = true)]
+    pub(crate) profile: bool,
+
+    /// The path of the cache file.
+    #[arg(long, value_name = "FILE", default_value = cache::DEFAULT_CACHE_LOCATION)]
+    pub(crate) cache_location: PathBuf,
+}
+
+/// Parses `START:END`, both counted from 1 and included,
+/// into a range of lines counted from 0.
+fn parse_line_range(range: &str) -> Result<Range<usize>, String> {
+    let (start, end) = range
+        .split_once(':')
+        .ok_or_else(|| "expected START:END, e.g. 10:40".to_string())?;
+    let parse = |line: &str| {
+        line.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("invalid line number {line:?}: {e}"))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start == 0 || end < start {
+        return Err("the lines are counted from 1 and END cannot precede START".to_string());
+    }
+    Ok(start - 1..end)
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub(crate) struct ConfigArgs {
+    #[command(subcommand)]
+    pub(crate) command: Option<ConfigCommand>,
+
+    /// The file or directory to resolve the configuration for.
+    #[arg(default_value = ".")]
+    pub(crate) path: String,
+
+    /// The path to the configuration file. Disables looking up `tergo.toml` files.
+    #[arg(long, value_name = "FILE")]
+    pub(crate) config: Option<String>,
+
+    /// The format of the printed configuration.
+    #[arg(long, value_enum, default_value_t = ConfigFormat::Toml)]
+    pub(crate) format: ConfigFormat,
+
+    #[command(flatten)]
+    pub(crate) overrides: ConfigOverrides,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigCommand {
+    /// Print the JSON Schema of `tergo.toml`, so the editors can validate
+    /// and autocomplete the configuration file.
+    Schema,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+/// The configuration values that can be set on the command line.
+#[derive(Args, Debug)]
+pub(crate) struct ConfigOverrides {
+    /// The maximum line length. Overrides the configuration file.
+    #[arg(long, value_name = "N")]
+    pub(crate) line_length: Option<i32>,
+
+    /// The number of spaces in a single indent. Overrides the configuration file.
+    #[arg(long, value_name = "N")]
+    pub(crate) indent: Option<i32>,
+
+    /// Use the settings closest to another formatter. Overrides the configuration file.
+    ///
+    /// `styler` follows `styler::tidyverse_style()`, so migrating from `styler`
+    /// changes less code.
+    #[arg(long, value_enum)]
+    pub(crate) style: Option<Style>,
+
+    /// The arguments passed to `styler::tidyverse_style()`,
+    /// e.g. `"indent_by = 4, strict = FALSE"`. Implies `--style styler`.
+    #[arg(long, value_name = "ARGS")]
+    pub(crate) styler_args: Option<String>,
+
+    /// Skip checking that formatting did not change the meaning of the code.
+    /// Overrides the configuration file.
+    #[arg(long)]
+    pub(crate) no_verify: bool,
+}
+
+impl ConfigOverrides {
+    pub(crate) fn to_table(&self) -> toml::Table {
+        let mut overrides = toml::Table::new();
+        if let Some(style) = self.style {
+            overrides.extend(styler::style_table(style));
+        }
+        if let Some(arguments) = &self.styler_args {
+            overrides.extend(styler::style_table(Style::Styler));
+            overrides.extend(styler::translate_arguments(arguments));
+        }
+        if let Some(line_length) = self.line_length {
+            overrides.insert("line_length".to_string(), line_length.into());
+        }
+        if let Some(indent) = self.indent {
+            overrides.insert("indent".to_string(), indent.into());
+        }
+        if self.no_verify {
+            overrides.insert("verify".to_string(), false.into());
+        }
+        overrides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, error::ErrorKind};
+
+    use super::*;
+
+    #[test]
+    fn line_ranges_are_counted_from_one_and_include_the_end() {
+        assert_eq!(parse_line_range("10:40"), Ok(9..40));
+        assert_eq!(parse_line_range("5:5"), Ok(4..5));
+        assert_eq!(parse_line_range(" 1 : 2 "), Ok(0..2));
+    }
+
+    #[test]
+    fn invalid_line_ranges_are_an_error() {
+        for range in ["0", "0:5", "10:5", "a:b", "5:", ":5", "-1:5", ""] {
+            assert!(parse_line_range(range).is_err(), "{range}");
+        }
+    }
+
+    #[test]
+    fn invalid_line_ranges_are_reported_by_clap() {
+        for range in ["0", "10:5", "a:b", "5:"] {
+            let error = Cli::try_parse_from(["tergo", "file.R", "--range", range]).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ValueValidation, "{range}");
+        }
+        let cli = Cli::try_parse_from(["tergo", "file.R", "--range", "2:3"]).unwrap();
+        assert_eq!(cli.format.range, Some(1..3));
+    }
+
+    #[test]
+    fn command_line_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, trace, warn};
+use tergo_lib::Config;
+
+/// The name of the configuration file looked up next to the formatted files.
+pub(crate) const CONFIG_FILE_NAME: &str = "tergo.toml";
+
+/// The environment variable with the path to the configuration file.
+const CONFIG_PATH_VARIABLE: &str = "TERGO_CONFIG_PATH";
+
+/// The prefix of the environment variables setting the configuration keys,
+/// e.g. `TERGO_LINE_LENGTH` sets `line_length`.
+const VARIABLE_PREFIX: &str = "TERGO_";
+
+/// Resolves the configuration used to format a file.
+///
+/// If the user passed a configuration file explicitly, on the command line
+/// or in `TERGO_CONFIG_PATH`, it is used for all the files. Otherwise, `tergo`
+/// collects every `tergo.toml` found in the directory of the formatted file
+/// and its ancestors.
+///
+/// The precedence, from the lowest to the highest, is:
+/// 1. the default configuration,
+/// 2. the configuration files, starting with the outermost one, so
+///    a `tergo.toml` in `tests/` overrides the keys it sets in the project
+///    root configuration,
+/// 3. the environment variables, e.g. `TERGO_LINE_LENGTH`,
+/// 4. the command line overrides.
+pub(crate) struct ConfigResolver {
+    explicit: Option<PathBuf>,
+    environment: Result<toml::Table, String>,
+    overrides: toml::Table,
+    by_directory: HashMap<PathBuf, Result<Config, String>>,
+}
+
+impl ConfigResolver {
+    pub(crate) fn new(explicit: Option<PathBuf>, overrides: toml::Table) -> Self {
+        let explicit = explicit.or_else(|| {
+            std::env::var_os(CONFIG_PATH_VARIABLE)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        });
+        Self {
+            explicit,
+            environment: environment_table(),
+            overrides,
+            by_directory: HashMap::new(),
+        }
+    }
+
+    /// Returns the configuration for a file, which does not need to exist.
+    ///
+    /// Returns an error if any of the configuration files cannot be read
+    /// or contains unknown or invalid keys.
+    pub(crate) fn resolve(&mut self, file: &Path) -> Result<&Config, String> {
+        let directory = absolute_directory(file);
+        if !self.by_directory.contains_key(&directory) {
+            let config_files = self.config_files(&directory);
+            debug!("Configuration files for {file:?}: {config_files:?}");
+            let config = self.environment.clone().and_then(|environment| {
+                merge_config_files(&config_files, &environment, &self.overrides)
+            });
+            self.by_directory.insert(directory.clone(), config);
+        }
+        self.by_directory[&directory].as_ref().map_err(Clone::clone)
+    }
+
+    /// Forgets the resolved configurations, so they are read again
+    /// from the configuration files.
+    pub(crate) fn clear(&mut self) {
+        self.by_directory.clear();
+    }
+
+    /// Returns the configuration files applying to the files in `directory`,
+    /// from the lowest to the highest precedence.
+    pub(crate) fn config_files(&self, directory: &Path) -> Vec<PathBuf> {
+        match &self.explicit {
+            Some(explicit) => vec![explicit.clone()],
+            None => find_config_files(directory),
+        }
+    }
+
+    /// Returns the paths the configuration files applying to the files in
+    /// `directory` are read from, including the ones which do not exist, so
+    /// the configuration can be read again when any of them is created
+    /// or deleted.
+    pub(crate) fn candidate_config_files(&self, directory: &Path) -> Vec<PathBuf> {
+        match &self.explicit {
+            Some(explicit) => vec![explicit.clone()],
+            None => directory
+                .ancestors()
+                .map(|ancestor| ancestor.join(CONFIG_FILE_NAME))
+                .collect(),
+        }
+    }
+}
+
+pub(crate) fn absolute_directory(file: &Path) -> PathBuf {
+    let directory = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    directory
+        .canonicalize()
+        .unwrap_or_else(|_| directory.to_path_buf())
+}
+
+/// Returns the configuration files in the ancestors of `directory`,
+/// the outermost first.
+fn find_config_files(directory: &Path) -> Vec<PathBuf> {
+    let mut config_files: Vec<PathBuf> = directory
+        .ancestors()
+        .map(|ancestor| ancestor.join(CONFIG_FILE_NAME))
+        .filter(|candidate| candidate.is_file())
+        .collect();
+    config_files.reverse();
+    config_files
+}
+
+fn merge_config_files(
+    config_files: &[PathBuf],
+    environment: &toml::Table,
+    overrides: &toml::Table,
+) -> Result<Config, String> {
+    let mut merged = toml::Table::new();
+    for config_file in config_files {
+        merged.extend(read_table(config_file)?);
+    }
+    merged.extend(environment.clone());
+    merged.extend(overrides.clone());
+    merged
+        .try_into()
+        .map_err(|e| format!("Invalid configuration: {e}"))
+}
+
+/// Reads a single configuration file. The file is validated on its own,
+/// so the errors point at the file with the offending key.
+fn read_table(path: &Path) -> Result<toml::Table, String> {
+    let config_file = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read the configuration file {path:?}: {e}"))?;
+    let table = config_file
+        .parse::<toml::Table>()
+        .map_err(|e| format!("Failed to parse the configuration file {path:?}: {e}"))?;
+    trace!("Configuration from {path:?}: {table:?}");
+    table
+        .clone()
+        .try_into::<Config>()
+        .map_err(|e| format!("Invalid configuration file {path:?}: {e}"))?;
+    Ok(table)
+}
+
+/// Collects the configuration keys set by the environment variables.
+///
+/// Every key of the configuration has a variable, e.g. `TERGO_LINE_LENGTH`
+/// for `line_length`. The values are read as TOML values, e.g. `true`,
+/// `80` or `["./data"]`, and as strings if they are not valid TOML,
+/// so `TERGO_FUNCTION_LINE_BREAKS=double` needs no quotes.
+fn environment_table() -> Result<toml::Table, String> {
+    variables_table(std::env::vars_os())
+}
+
+fn variables_table(
+    variables: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<toml::Table, String> {
+    let keys = toml::Table::try_from(Config::default()).map_err(|e| e.to_string())?;
+    let mut table = toml::Table::new();
+    for (variable, value) in variables {
+        let (Some(variable), Some(value)) = (variable.to_str(), value.to_str()) else {
+            continue;
+        };
+        let Some(key) = variable
+            .strip_prefix(VARIABLE_PREFIX)
+            .map(str::to_lowercase)
+            .filter(|key| keys.contains_key(key))
+        else {
+            continue;
+        };
+        let value = parse_value(value);
+        trace!("Configuration from {variable}: {value:?}");
+        table.insert(key, value);
+    }
+    table
+        .clone()
+        .try_into::<Config>()
+        .map_err(|e| format!("Invalid configuration in the environment variables: {e}"))?;
+    Ok(table)
+}
+
+/// Reads a value as a TOML value, or as a string if it is not valid TOML.
+fn parse_value(value: &str) -> toml::Value {
+    format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.trim().to_string()))
+}
+
+/// Applies the configuration in the header comment of the file,
+/// e.g. `# tergo: line_length = 100, indent = 4`, to the configuration.
+///
+/// The header is the first line of the file, or the second one after
+/// a shebang. It takes precedence over all the other configuration,
+/// because it describes the file itself, e.g. a generated file.
+/// A header which is not a list of `key = value` settings, e.g.
+/// `# tergo: formatted`, is ignored with a warning.
+pub(crate) fn with_header<'a>(
+    config: &'a Config,
+    content: &str,
+) -> Result<Cow<'a, Config>, String> {
+    let mut lines = content.lines();
+    let header = match lines.next() {
+        Some(shebang) if shebang.starts_with("#!") => lines.next(),
+        first => first,
+    };
+    let Some(settings) = header.and_then(|header| {
+        header
+            .strip_prefix('#')?
+            .trim_start()
+            .strip_prefix("tergo:")
+    }) else {
+        return Ok(Cow::Borrowed(config));
+    };
+    let settings: Option<Vec<_>> = split_settings(settings).into_iter().map(setting).collect();
+    let Some(settings) = settings.filter(|settings| !settings.is_empty()) else {
+        warn!("Ignoring the tergo header, which is not a list of `key = value` settings");
+        return Ok(Cow::Borrowed(config));
+    };
+    let mut table = toml::Table::try_from(config).map_err(|e| e.to_string())?;
+    for (key, value) in settings {
+        table.insert(key.to_string(), parse_value(value));
+    }
+    table
+        .try_into::<Config>()
+        .map(Cow::Owned)
+        .map_err(|e| format!("Invalid configuration in the tergo header: {e}"))
+}
+
+/// Splits a setting of the header into its key and value,
+/// if it is a `key = value` setting.
+fn setting(setting: &str) -> Option<(&str, &str)> {
+    let (key, value) = setting.split_once('=')?;
+    let key = key.trim();
+    let is_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|character| character.is_ascii_lowercase() || character == '_');
+    (is_key && !value.trim().is_empty()).then_some((key, value))
+}
+
+/// Splits the settings at the commas outside of the brackets and the quotes.
+fn split_settings(settings: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (index, character) in settings.char_indices() {
+        match (quote, character) {
+            (Some(open), _) if character == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(character),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&settings[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&settings[start..]);
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use tergo_lib::FunctionLineBreaks;
+
+    use super::*;
+
+    #[test]
+    fn header_settings_override_the_configuration() {
+        let config = Config::default();
+        let content = "#!/usr/bin/env Rscript\n# tergo: line_length = 100, exclusion_list = [\"a, b\"]\nx <- 1\n";
+        let header_config = with_header(&config, content).unwrap();
+        assert_eq!(header_config.line_length.0, 100);
+        assert_eq!(header_config.exclusion_list.0, vec!["a, b".to_string()]);
+        assert_eq!(header_config.indent.0, config.indent.0);
+    }
+
+    #[test]
+    fn headers_which_are_not_settings_are_ignored() {
+        let config = Config::default();
+        for content in [
+            "# tergo: formatted by tergo\nx <- 1\n",
+            "# tergo: see https://example.com/?a=b\nx <- 1\n",
+            "# tergo:\nx <- 1\n",
+            "# tergo: indent = 4, formatted\nx <- 1\n",
+        ] {
+            let header_config = with_header(&config, content).unwrap();
+            assert!(matches!(header_config, Cow::Borrowed(_)), "{content}");
+        }
+    }
+
+    #[test]
+    fn header_with_an_unknown_key_is_an_error() {
+        let config = Config::default();
+        let error = with_header(&config, "# tergo: line_lenght = 100\n").unwrap_err();
+        assert!(error.contains("line_lenght"), "{error}");
+    }
+
+    #[test]
+    fn values_are_read_as_toml_or_as_strings() {
+        assert_eq!(parse_value("80"), toml::Value::Integer(80));
+        assert_eq!(parse_value("true"), toml::Value::Boolean(true));
+        assert_eq!(
+            parse_value("\"double\""),
+            toml::Value::String("double".to_string())
+        );
+        assert_eq!(
+            parse_value("[\"./data\", \"./renv\"]"),
+            toml::Value::Array(vec!["./data".into(), "./renv".into()])
+        );
+        assert_eq!(
+            parse_value("double"),
+            toml::Value::String("double".to_string())
+        );
+        assert_eq!(
+            parse_value(" ./data "),
+            toml::Value::String("./data".to_string())
+        );
+        assert_eq!(
+            parse_value("80 columns"),
+            toml::Value::String("80 columns".to_string())
+        );
+    }
+
+    fn variables(variables: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        variables
+            .iter()
+            .map(|(variable, value)| (variable.into(), value.into()))
+            .collect()
+    }
+
+    #[test]
+    fn environment_overrides_the_files_and_the_command_line_the_environment() {
+        let directory =
+            std::env::temp_dir().join(format!("tergo-config-precedence-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let config_file = directory.join(CONFIG_FILE_NAME);
+        std::fs::write(
+            &config_file,
+            "indent = 4\nline_length = 100\nfunction_line_breaks = \"single\"\n",
+        )
+        .unwrap();
+        let environment = variables_table(variables(&[
+            ("TERGO_LINE_LENGTH", "90"),
+            ("TERGO_FUNCTION_LINE_BREAKS", "double"),
+            ("TERGO_NOT_A_KEY", "1"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .unwrap();
+        assert_eq!(environment.len(), 2);
+        let mut overrides = toml::Table::new();
+        overrides.insert("line_length".to_string(), parse_value("80"));
+
+        let config = merge_config_files(&[config_file], &environment, &overrides).unwrap();
+        assert_eq!(config.indent.0, 4);
+        assert_eq!(config.line_length.0, 80);
+        assert_eq!(config.function_line_breaks, FunctionLineBreaks::Double);
+        let config = merge_config_files(&[], &environment, &toml::Table::new()).unwrap();
+        assert_eq!(config.line_length.0, 90);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn invalid_environment_values_are_an_error() {
+        let error = variables_table(variables(&[("TERGO_INDENT", "two")])).unwrap_err();
+        assert!(error.contains("environment variables"), "{error}");
+    }
+}
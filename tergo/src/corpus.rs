@@ -0,0 +1,195 @@
+//! Checking tergo against a corpus of R files without changing them.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use log::{info, warn};
+use rayon::prelude::*;
+use serde_json::{Value, json};
+use tergo_lib::Config;
+
+use crate::{
+    check_idempotent,
+    config::{self, ConfigResolver},
+    encoding,
+    files::{self, FileFilter, FileKind},
+    format_code, is_excluded, syntax_error,
+};
+
+/// The outcome of checking a single file of the corpus.
+#[derive(Debug)]
+enum Outcome {
+    /// The formatted code means the same as the original code
+    /// and formatting it again does not change it.
+    Ok,
+    /// The file is not valid R code, so it is not formatted.
+    SyntaxError { line: usize, column: usize },
+    /// The formatted code does not mean the same as the original code.
+    NotEquivalent,
+    /// Formatting the formatted code changes it.
+    NotIdempotent(String),
+    /// The file could not be read or formatted.
+    Failed(String),
+    /// The file is excluded by its configuration.
+    Skipped,
+}
+
+impl Outcome {
+    fn status(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::SyntaxError { .. } => "syntax-error",
+            Outcome::NotEquivalent => "not-equivalent",
+            Outcome::NotIdempotent(_) => "not-idempotent",
+            Outcome::Failed(_) => "failed",
+            Outcome::Skipped => "skipped",
+        }
+    }
+
+    /// Whether the outcome is a bug in tergo rather than a problem of the file.
+    fn is_bug(&self) -> bool {
+        matches!(
+            self,
+            Outcome::NotEquivalent | Outcome::NotIdempotent(_) | Outcome::Failed(_)
+        )
+    }
+
+    fn to_json(&self, path: &Path) -> Value {
+        let mut entry = json!({
+            "path": path.to_string_lossy(),
+            "status": self.status(),
+        });
+        match self {
+            Outcome::SyntaxError { line, column } => {
+                entry["line"] = json!(line);
+                entry["column"] = json!(column);
+            }
+            Outcome::NotIdempotent(message) | Outcome::Failed(message) => {
+                entry["message"] = json!(message);
+            }
+            Outcome::Ok | Outcome::NotEquivalent | Outcome::Skipped => {}
+        }
+        entry
+    }
+}
+
+/// Checks every supported file under `directory`, writes the report
+/// to `output` or the standard output, and fails if any file reveals a bug.
+pub(crate) fn run(directory: &Path, config: Option<PathBuf>, output: Option<&Path>) -> ExitCode {
+    if !directory.is_dir() {
+        warn!("{directory:?} is not a directory");
+        return ExitCode::FAILURE;
+    }
+    let filter = match FileFilter::new(&[], &[]) {
+        Ok(filter) => filter,
+        Err(e) => {
+            warn!("Invalid glob pattern: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut configs = ConfigResolver::new(config, toml::Table::new());
+    // The configurations are resolved upfront, because the resolver caches
+    // them and cannot be shared between the threads
+    let files: Vec<(PathBuf, Result<Config, String>)> = files::list_files(directory, &filter, true)
+        .into_iter()
+        .map(|file| {
+            let config = configs.resolve(&file).cloned();
+            (file, config)
+        })
+        .collect();
+    let outcomes: Vec<(PathBuf, Outcome)> = files
+        .into_par_iter()
+        .map(|(file, config)| {
+            let outcome = match config {
+                Ok(config) => check_file(&file, config),
+                Err(e) => Outcome::Failed(e),
+            };
+            (file, outcome)
+        })
+        .collect();
+
+    let mut summary: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for (file, outcome) in &outcomes {
+        *summary.entry(outcome.status()).or_default() += 1;
+        if outcome.is_bug() {
+            warn!("{file:?}: {}", outcome.status());
+        }
+    }
+    let report = json!({
+        "files": outcomes
+            .iter()
+            .map(|(file, outcome)| outcome.to_json(file))
+            .collect::<Vec<_>>(),
+        "summary": summary,
+    });
+    let report = serde_json::to_string_pretty(&report).unwrap_or_default() + "\n";
+    match output {
+        Some(output) => {
+            if let Err(e) = std::fs::write(output, report) {
+                warn!("Failed to write the report to {output:?}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{report}"),
+    }
+    info!(
+        "Checked {} files: {}",
+        outcomes.len(),
+        summary
+            .iter()
+            .map(|(status, count)| format!("{count} {status}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    if outcomes.iter().any(|(_, outcome)| outcome.is_bug()) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn check_file(file: &Path, config: Config) -> Outcome {
+    if is_excluded(file, &config)
+        || (file.extension().is_none()
+            && !config.format_shebang_scripts.0
+            && files::has_rscript_shebang(file))
+    {
+        return Outcome::Skipped;
+    }
+    let content = match encoding::read(file) {
+        Ok(decoded) => decoded.content,
+        Err(e) => return Outcome::Failed(e),
+    };
+    let mut config = match config::with_header(&config, &content) {
+        Ok(header_config) => header_config.into_owned(),
+        Err(e) => return Outcome::Failed(e),
+    };
+    let kind = FileKind::from_path(file);
+    if let Some(error) = syntax_error(&content, kind) {
+        return Outcome::SyntaxError {
+            line: error.line,
+            column: error.column,
+        };
+    }
+    config.verify.0 = true;
+    let formatted = match format_code(&content, kind, &config) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            // Formatting without the check tells the changed meaning
+            // apart from the other failures
+            config.verify.0 = false;
+            return match format_code(&content, kind, &config) {
+                Ok(_) => Outcome::NotEquivalent,
+                Err(_) => Outcome::Failed(e),
+            };
+        }
+    };
+    match check_idempotent(&formatted, kind, &config) {
+        Ok(()) => Outcome::Ok,
+        Err(e) => Outcome::NotIdempotent(e),
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tergo_lib::{Config, IncrementalFormatter};
+
+use crate::{
+    FileStatus, FormatOptions, Output,
+    config::{self, ConfigResolver, absolute_directory},
+    files::FileKind,
+    format_code_incremental, format_file, is_excluded, lock, syntax_error,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FORMATTING_FAILED: i64 = -32000;
+
+/// The most formatters the daemon keeps. The content of a file whose
+/// formatter was dropped is laid out again in full.
+const MAX_FORMATTERS: usize = 64;
+
+/// Returns the socket the daemon listens on if the user did not pass one.
+pub(crate) fn default_socket() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("tergo.sock")
+}
+
+/// A request of a client, one JSON-RPC 2.0 message per line.
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormatParams {
+    path: PathBuf,
+    /// The code to format. If missing, the file at `path` is formatted in place.
+    content: Option<String>,
+}
+
+/// The state kept warm between the requests.
+struct Daemon {
+    configs: Mutex<ConfigResolver>,
+    /// The modification times of the configuration files the resolved
+    /// configurations were read from, `None` for the candidates which
+    /// do not exist.
+    config_stamps: Mutex<HashMap<PathBuf, Option<SystemTime>>>,
+    formatters: Mutex<Formatters>,
+}
+
+/// The formatters of the content sent by the clients, by the path
+/// of the file, so only the statements edited since are laid out again.
+///
+/// Only the [MAX_FORMATTERS] most recently used are kept, so the daemon
+/// does not grow with every file it ever formatted.
+#[derive(Default)]
+struct Formatters(VecDeque<(PathBuf, IncrementalFormatter)>);
+
+impl Formatters {
+    /// Takes the formatter of the file out, or returns a new one.
+    fn take(&mut self, path: &Path) -> IncrementalFormatter {
+        self.0
+            .iter()
+            .position(|(formatted, _)| formatted == path)
+            .and_then(|index| self.0.remove(index))
+            .map(|(_, formatter)| formatter)
+            .unwrap_or_default()
+    }
+
+    /// Puts the formatter of the file back as the most recently used one,
+    /// dropping the least recently used one if there are too many.
+    fn put(&mut self, path: PathBuf, formatter: IncrementalFormatter) {
+        // Another client may have formatted the same file meanwhile
+        self.0.retain(|(formatted, _)| *formatted != path);
+        self.0.push_back((path, formatter));
+        if self.0.len() > MAX_FORMATTERS {
+            self.0.pop_front();
+        }
+    }
+}
+
+impl Daemon {
+    fn new() -> Self {
+        Self {
+            configs: Mutex::new(ConfigResolver::new(None, toml::Table::new())),
+            config_stamps: Mutex::new(HashMap::new()),
+            formatters: Mutex::default(),
+        }
+    }
+
+    /// Handles a single line of the input. Returns `None` for notifications.
+    fn handle(&self, line: &str) -> Option<Value> {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        debug!("Request: {} {}", request.method, request.params);
+        let result = match request.method.as_str() {
+            "format" => match serde_json::from_value(request.params) {
+                Ok(params) => self.format(params).map_err(|e| (FORMATTING_FAILED, e)),
+                Err(e) => Err((INVALID_PARAMS, e.to_string())),
+            },
+            "reload" => {
+                lock(&self.configs).clear();
+                Ok(Value::Null)
+            }
+            method => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        };
+        if request.id.is_null() {
+            return None;
+        }
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request.id, "result": result }),
+            Err((code, message)) => error_response(request.id, code, message),
+        })
+    }
+
+    fn format(&self, params: FormatParams) -> Result<Value, String> {
+        let FormatParams { path, content } = params;
+        let config = self.config(&path)?;
+        let kind = FileKind::from_path(&path);
+        let ignored = is_excluded(&path, &config);
+        match content {
+            Some(content) if ignored => Ok(json!({ "formatted": content, "changed": false })),
+            Some(content) => {
+                let config = config::with_header(&config, &content)?;
+                // The formatter is taken out, so formatting does not block the other clients
+                let mut formatter = lock(&self.formatters).take(&path);
+                let formatted = format_code_incremental(&content, kind, &config, &mut formatter);
+                lock(&self.formatters).put(path, formatter);
+                let formatted = formatted
+                    .map_err(|e| syntax_error(&content, kind).map_or(e, |error| error.message()))?;
+                let changed = formatted != content;
+                Ok(json!({ "formatted": formatted, "changed": changed }))
+            }
+            None if ignored => Ok(json!({ "changed": false })),
+            None => {
+                let options = FormatOptions {
+                    output: Output::InPlace,
+                    cache: None,
+                    changed_lines: None,
+                    backup: None,
+                    range: None,
+                    report: None,
+                    printer: None,
+                    stats: None,
+                    check_idempotent: false,
+                    audit_tokens: false,
+                    skip_syntax_errors: false,
+                    profile: false,
+                    force: false,
+                };
+                let status = format_file(&path, &config, options)
+                    .map_err(|e| format!("Failed to format {path:?}. Error: {e:?}"))?;
+                Ok(json!({ "changed": status == FileStatus::Changed }))
+            }
+        }
+    }
+
+    /// Resolves the configuration of the file, reading the configuration
+    /// files again if any of them changed, was created or was deleted
+    /// since it was last resolved.
+    fn config(&self, path: &Path) -> Result<Config, String> {
+        let mut configs = lock(&self.configs);
+        let mut stamps = lock(&self.config_stamps);
+        let config_files = configs.candidate_config_files(&absolute_directory(path));
+        let mut stale = false;
+        for config_file in config_files {
+            let modified = std::fs::metadata(&config_file)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            if stamps.insert(config_file, modified) != Some(modified) {
+                stale = true;
+            }
+        }
+        if stale {
+            configs.clear();
+        }
+        configs.resolve(path).cloned()
+    }
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Runs the daemon, serving the clients connecting to the socket
+/// until the process is stopped.
+#[cfg(unix)]
+pub(crate) fn run(socket: &Path) -> ExitCode {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::{UnixListener, UnixStream},
+        sync::Arc,
+    };
+
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            warn!("Another daemon is already listening on {socket:?}");
+            return ExitCode::FAILURE;
+        }
+        // A socket left over by a daemon that did not shut down cleanly
+        let _ = std::fs::remove_file(socket);
+    }
+    let listener = match UnixListener::bind(socket) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Failed to listen on {socket:?}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    info!("Listening on {socket:?}");
+    let daemon = Arc::new(Daemon::new());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept a connection: {e}");
+                continue;
+            }
+        };
+        let daemon = Arc::clone(&daemon);
+        // Every client gets its own thread, so a slow client does not block the others
+        std::thread::spawn(move || {
+            let mut writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(e) => {
+                    warn!("Failed to open the connection for writing: {e}");
+                    return;
+                }
+            };
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let Some(response) = daemon.handle(&line) else {
+                    continue;
+                };
+                if writeln!(writer, "{response}").is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run(_: &Path) -> ExitCode {
+    warn!("The daemon is only supported on Unix systems");
+    ExitCode::FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("tergo-daemon-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn format_request(path: &Path, content: &str) -> String {
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "format", "params": { "path": path, "content": content } })
+            .to_string()
+    }
+
+    #[test]
+    fn handle_answers_with_the_json_rpc_errors() {
+        let daemon = Daemon::new();
+        let code = |line: &str| daemon.handle(line).unwrap()["error"]["code"].clone();
+        assert_eq!(code("not json"), json!(PARSE_ERROR));
+        assert_eq!(
+            code(r#"{"id": 1, "method": "lint"}"#),
+            json!(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            code(r#"{"id": 1, "method": "format", "params": {"content": "x"}}"#),
+            json!(INVALID_PARAMS)
+        );
+        assert_eq!(
+            code(&format_request(Path::new("file.R"), "x <- (")),
+            json!(FORMATTING_FAILED)
+        );
+    }
+
+    #[test]
+    fn handle_does_not_answer_notifications() {
+        let daemon = Daemon::new();
+        assert_eq!(daemon.handle(r#"{"method": "reload"}"#), None);
+        assert_eq!(
+            daemon.handle(r#"{"id": 7, "method": "reload"}"#),
+            Some(json!({ "jsonrpc": "2.0", "id": 7, "result": null }))
+        );
+    }
+
+    #[test]
+    fn handle_formats_the_content() {
+        let daemon = Daemon::new();
+        let response = daemon
+            .handle(&format_request(Path::new("file.R"), "x<-1"))
+            .unwrap();
+        assert_eq!(response["id"], json!(1));
+        assert_eq!(
+            response["result"],
+            json!({ "formatted": "x <- 1\n", "changed": true })
+        );
+    }
+
+    #[test]
+    fn configuration_is_read_again_when_a_config_file_is_created_or_deleted() {
+        let directory = test_directory("config");
+        let file = directory.join("file.R");
+        let daemon = Daemon::new();
+        let code = "f <- function(x) {\n  x\n}\n";
+        let formatted = |daemon: &Daemon| {
+            daemon.handle(&format_request(&file, code)).unwrap()["result"]["formatted"].clone()
+        };
+        assert_eq!(formatted(&daemon), json!(code));
+
+        std::fs::write(directory.join("tergo.toml"), "indent = 4\n").unwrap();
+        assert_eq!(formatted(&daemon), json!("f <- function(x) {\n    x\n}\n"));
+
+        std::fs::remove_file(directory.join("tergo.toml")).unwrap();
+        assert_eq!(formatted(&daemon), json!(code));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn formatters_keep_the_most_recently_used() {
+        let mut formatters = Formatters::default();
+        let path = |index: usize| PathBuf::from(format!("{index}.R"));
+        for index in 0..MAX_FORMATTERS {
+            formatters.put(path(index), IncrementalFormatter::default());
+        }
+        // Using the oldest formatter makes it the most recently used one
+        let formatter = formatters.take(&path(0));
+        assert_eq!(formatters.0.len(), MAX_FORMATTERS - 1);
+        formatters.put(path(0), formatter);
+        formatters.put(path(MAX_FORMATTERS), IncrementalFormatter::default());
+
+        let paths: Vec<_> = formatters.0.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(paths.len(), MAX_FORMATTERS);
+        assert!(!paths.contains(&path(1)));
+        assert_eq!(paths[paths.len() - 2..], [path(0), path(MAX_FORMATTERS)]);
+    }
+
+    #[test]
+    fn formatters_keep_one_formatter_per_file() {
+        let mut formatters = Formatters::default();
+        formatters.put(PathBuf::from("a.R"), IncrementalFormatter::default());
+        formatters.put(PathBuf::from("a.R"), IncrementalFormatter::default());
+        assert_eq!(formatters.0.len(), 1);
+    }
+}
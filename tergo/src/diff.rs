@@ -0,0 +1,104 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use similar::{ChangeTag, TextDiff};
+
+pub(crate) const RED: &str = "\x1b[31m";
+pub(crate) const GREEN: &str = "\x1b[32m";
+pub(crate) const YELLOW: &str = "\x1b[33m";
+pub(crate) const CYAN: &str = "\x1b[36m";
+pub(crate) const DIM: &str = "\x1b[2m";
+pub(crate) const BOLD: &str = "\x1b[1m";
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// The number of unchanged lines printed around every change.
+const CONTEXT_RADIUS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ColorChoice {
+    Always,
+    Never,
+    Auto,
+}
+
+impl ColorChoice {
+    /// Resolves `auto` to `always` if the standard output is a terminal.
+    pub(crate) fn should_colorize(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::io::stdout().is_terminal(),
+        }
+    }
+
+    /// Resolves `auto` to `always` if the standard error is a terminal.
+    pub(crate) fn should_colorize_stderr(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::io::stderr().is_terminal(),
+        }
+    }
+}
+
+/// Renders a unified diff between the original and the formatted content
+/// of a file.
+///
+/// Returns an empty string if the contents are the same.
+pub(crate) fn unified_diff(
+    file_name: &str,
+    original: &str,
+    formatted: &str,
+    color: bool,
+) -> String {
+    if original == formatted {
+        return String::new();
+    }
+    let diff = TextDiff::from_lines(original, formatted);
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut output = String::new();
+    output.push_str(&paint(BOLD, &format!("--- {file_name}")));
+    output.push('\n');
+    output.push_str(&paint(BOLD, &format!("+++ {file_name}")));
+    output.push('\n');
+    for hunk in diff
+        .unified_diff()
+        .context_radius(CONTEXT_RADIUS)
+        .iter_hunks()
+    {
+        output.push_str(&paint(CYAN, &hunk.header().to_string()));
+        output.push('\n');
+        for change in hunk.iter_changes() {
+            let (sign, style) = match change.tag() {
+                ChangeTag::Delete => ("-", Some(RED)),
+                ChangeTag::Insert => ("+", Some(GREEN)),
+                ChangeTag::Equal => (" ", None),
+            };
+            let mut line = format!("{sign}{}", change.value().trim_end_matches('\n'));
+            // The carriage return of a changed line is shown, so a change
+            // of only the line ending does not look like identical lines
+            if line.ends_with('\r') {
+                line.pop();
+                if change.tag() != ChangeTag::Equal {
+                    line.push('␍');
+                }
+            }
+            match style {
+                Some(style) => output.push_str(&paint(style, &line)),
+                None => output.push_str(&line),
+            }
+            output.push('\n');
+            if change.missing_newline() {
+                output.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    output
+}
@@ -0,0 +1,208 @@
+//! Reading and writing the files in their own encoding.
+//!
+//! The formatter works on UTF-8 text, but many older R packages are
+//! written in Latin-1. Such files are transcoded to UTF-8 for formatting
+//! and written back in their original encoding.
+
+use std::path::Path;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
+
+/// The text of a file and how to write it back.
+#[derive(Debug)]
+pub(crate) struct Decoded {
+    pub(crate) content: String,
+    pub(crate) encoding: &'static Encoding,
+    /// Whether the file starts with a byte order mark.
+    pub(crate) bom: bool,
+}
+
+/// Reads the file and decodes it. See [decode].
+pub(crate) fn read(path: &Path) -> Result<Decoded, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    decode(&bytes, path)
+}
+
+/// Decodes the content of the file at `path`.
+///
+/// The encoding is, in this order:
+/// 1. the one of the byte order mark,
+/// 2. UTF-8, if the content is valid UTF-8,
+/// 3. the `Encoding` field of the `DESCRIPTION` file of the package with the file,
+/// 4. the encoding guessed from the content.
+pub(crate) fn decode(bytes: &[u8], path: &Path) -> Result<Decoded, String> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return decode_with(&bytes[bom_length..], encoding, true);
+    }
+    if let Ok(content) = std::str::from_utf8(bytes) {
+        return Ok(Decoded {
+            content: content.to_string(),
+            encoding: UTF_8,
+            bom: false,
+        });
+    }
+    if let Some(encoding) = description_encoding(path).filter(|encoding| *encoding != UTF_8) {
+        return decode_with(bytes, encoding, false);
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    decode_with(bytes, detector.guess(None, true), false)
+}
+
+fn decode_with(bytes: &[u8], encoding: &'static Encoding, bom: bool) -> Result<Decoded, String> {
+    let content = encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .ok_or_else(|| format!("The file is not valid {}", encoding.name()))?;
+    Ok(Decoded {
+        content: content.into_owned(),
+        encoding,
+        bom,
+    })
+}
+
+/// Encodes the text, failing if a character cannot be represented in the encoding.
+pub(crate) fn encode(
+    content: &str,
+    encoding: &'static Encoding,
+    bom: bool,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    // encoding_rs only encodes to UTF-8 when asked for UTF-16
+    if encoding == UTF_16LE {
+        if bom {
+            bytes.extend([0xFF, 0xFE]);
+        }
+        bytes.extend(content.encode_utf16().flat_map(u16::to_le_bytes));
+        return Ok(bytes);
+    }
+    if encoding == UTF_16BE {
+        if bom {
+            bytes.extend([0xFE, 0xFF]);
+        }
+        bytes.extend(content.encode_utf16().flat_map(u16::to_be_bytes));
+        return Ok(bytes);
+    }
+    if bom && encoding == UTF_8 {
+        bytes.extend([0xEF, 0xBB, 0xBF]);
+    }
+    let (encoded, _, had_errors) = encoding.encode(content);
+    if had_errors {
+        return Err(format!(
+            "The formatted code cannot be encoded in {}",
+            encoding.name()
+        ));
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+/// Returns the encoding declared in the `DESCRIPTION` file of the package
+/// the file belongs to, e.g. `Encoding: latin1`.
+fn description_encoding(path: &Path) -> Option<&'static Encoding> {
+    let description = path
+        .ancestors()
+        .skip(1)
+        .map(|directory| directory.join("DESCRIPTION"))
+        .find(|description| description.is_file())?;
+    // The DESCRIPTION file may itself be in the declared encoding
+    let bytes = std::fs::read(description).ok()?;
+    String::from_utf8_lossy(&bytes).lines().find_map(|line| {
+        let label = line.strip_prefix("Encoding:")?.trim();
+        Encoding::for_label(label.as_bytes())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{ISO_8859_2, WINDOWS_1252};
+
+    use super::*;
+
+    fn round_trip(bytes: &[u8], path: &Path) -> Decoded {
+        let decoded = decode(bytes, path).unwrap();
+        assert_eq!(
+            encode(&decoded.content, decoded.encoding, decoded.bom).unwrap(),
+            bytes
+        );
+        decoded
+    }
+
+    #[test]
+    fn utf8_files_keep_their_byte_order_mark() {
+        let decoded = round_trip(b"x <- \"caf\xC3\xA9\"\n", Path::new("file.R"));
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding, decoded.bom),
+            ("x <- \"café\"\n", UTF_8, false)
+        );
+
+        let decoded = round_trip(b"\xEF\xBB\xBFx <- 1\n", Path::new("file.R"));
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding, decoded.bom),
+            ("x <- 1\n", UTF_8, true)
+        );
+    }
+
+    #[test]
+    fn utf16_files_are_read_by_their_byte_order_mark() {
+        let little_endian: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("x <- \"é\"\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let decoded = round_trip(&little_endian, Path::new("file.R"));
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding, decoded.bom),
+            ("x <- \"é\"\n", UTF_16LE, true)
+        );
+
+        let big_endian: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain("x <- \"é\"\n".encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        let decoded = round_trip(&big_endian, Path::new("file.R"));
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding, decoded.bom),
+            ("x <- \"é\"\n", UTF_16BE, true)
+        );
+    }
+
+    #[test]
+    fn latin1_files_are_guessed_from_the_content() {
+        let decoded = round_trip(b"x <- \"caf\xE9\"\n", Path::new("file.R"));
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding, decoded.bom),
+            ("x <- \"café\"\n", WINDOWS_1252, false)
+        );
+    }
+
+    #[test]
+    fn encoding_of_the_description_file_takes_precedence_over_the_guess() {
+        let package =
+            std::env::temp_dir().join(format!("tergo-encoding-package-{}", std::process::id()));
+        std::fs::create_dir_all(package.join("R")).unwrap();
+        std::fs::write(
+            package.join("DESCRIPTION"),
+            "Package: pkg\nEncoding: latin1\n",
+        )
+        .unwrap();
+        let bytes = b"x <- \"\xB9\"\n";
+
+        let decoded = round_trip(bytes, &package.join("R").join("file.R"));
+        assert_eq!(
+            (decoded.content.as_str(), decoded.encoding),
+            ("x <- \"¹\"\n", WINDOWS_1252)
+        );
+        let decoded = decode(bytes, Path::new("file.R")).unwrap();
+        assert_eq!(decoded.encoding, ISO_8859_2);
+        std::fs::remove_dir_all(&package).unwrap();
+    }
+
+    #[test]
+    fn characters_missing_in_the_encoding_are_an_error() {
+        assert!(encode("x <- \"ą\"\n", WINDOWS_1252, false).is_err());
+        assert_eq!(
+            encode("x <- \"é\"\n", WINDOWS_1252, false).unwrap(),
+            b"x <- \"\xE9\"\n"
+        );
+    }
+}
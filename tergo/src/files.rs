@@ -0,0 +1,349 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use log::{trace, warn};
+use regex::{Regex, RegexBuilder};
+
+/// The name of `tergo`'s own ignore files. Follows the `.gitignore` syntax.
+const IGNORE_FILE_NAME: &str = ".tergoignore";
+
+/// The files picked up when no `--include` glob is passed.
+const DEFAULT_INCLUDES: [&str; 6] = ["*.R", "*.r", "*.Rmd", "*.qmd", "*.Rnw", "*.ipynb"];
+
+/// The name of the file listing the files left out of an R package.
+const RBUILDIGNORE_FILE_NAME: &str = ".Rbuildignore";
+
+/// The number of bytes read when looking for a shebang.
+const MAX_SHEBANG_LENGTH: u64 = 256;
+
+/// The kinds of files `tergo` knows how to find.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FileKind {
+    R,
+    RMarkdown,
+    Quarto,
+    Sweave,
+    Notebook,
+}
+
+impl FileKind {
+    pub(crate) fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("Rmd") | Some("rmd") => FileKind::RMarkdown,
+            Some("qmd") => FileKind::Quarto,
+            Some("Rnw") | Some("rnw") => FileKind::Sweave,
+            Some("ipynb") => FileKind::Notebook,
+            _ => FileKind::R,
+        }
+    }
+}
+
+/// Returns true if the first line of the file is a shebang running `Rscript`,
+/// e.g. `#!/usr/bin/env Rscript` or `#!/usr/bin/Rscript --vanilla`.
+pub(crate) fn has_rscript_shebang(path: &Path) -> bool {
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut first_line = String::new();
+    // A binary file may have no line breaks, so the line is read in a limited chunk
+    if BufReader::new(file.take(MAX_SHEBANG_LENGTH))
+        .read_line(&mut first_line)
+        .is_err()
+    {
+        return false;
+    }
+    first_line.strip_prefix("#!").is_some_and(|command| {
+        command.split_whitespace().any(|word| {
+            Path::new(word)
+                .file_name()
+                .is_some_and(|name| name == "Rscript")
+        })
+    })
+}
+
+/// Decides which of the walked files get formatted.
+#[derive(Debug)]
+pub(crate) struct FileFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+    /// Whether to pick up the R scripts without an extension.
+    /// Custom `--include` globs turn it off.
+    shebang_scripts: bool,
+    rbuildignore: Option<RBuildIgnore>,
+}
+
+impl FileFilter {
+    pub(crate) fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        let shebang_scripts = include.is_empty();
+        let include = if include.is_empty() {
+            build_glob_set(DEFAULT_INCLUDES.iter().copied())?
+        } else {
+            build_glob_set(include.iter().map(String::as_str))?
+        };
+        let exclude = build_glob_set(exclude.iter().map(String::as_str))?;
+        Ok(Self {
+            include,
+            exclude,
+            shebang_scripts,
+            rbuildignore: None,
+        })
+    }
+
+    /// Skips the files left out by the `.Rbuildignore` of the R package
+    /// containing `root`, too, e.g. `revdep/` or `docs/`.
+    pub(crate) fn respecting_rbuildignore(mut self, root: &Path) -> Self {
+        self.rbuildignore = RBuildIgnore::find(root);
+        self
+    }
+
+    /// `path` is matched relative to the directory in which the walk started,
+    /// so globs such as `tests/*.R` work as expected.
+    fn matches(&self, path: &Path, root: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if self.exclude.is_match(relative)
+            || self
+                .rbuildignore
+                .as_ref()
+                .is_some_and(|rbuildignore| rbuildignore.is_ignored(path))
+        {
+            return false;
+        }
+        self.include.is_match(relative)
+            || (self.shebang_scripts && path.extension().is_none() && has_rscript_shebang(path))
+    }
+}
+
+/// The files left out of an R package by its `.Rbuildignore`. Each line
+/// is a regular expression matched case-insensitively against the paths
+/// relative to the root of the package, e.g. `^revdep$`. The files
+/// in an ignored directory are ignored, too.
+#[derive(Debug)]
+struct RBuildIgnore {
+    package_root: PathBuf,
+    patterns: Vec<Regex>,
+}
+
+impl RBuildIgnore {
+    /// Reads the `.Rbuildignore` in `root` or in the nearest directory
+    /// containing it, if any.
+    fn find(root: &Path) -> Option<Self> {
+        let root = root.canonicalize().ok()?;
+        let package_root = root
+            .ancestors()
+            .find(|directory| directory.join(RBUILDIGNORE_FILE_NAME).is_file())?;
+        let path = package_root.join(RBUILDIGNORE_FILE_NAME);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                warn!("Failed to read {path:?}: {err}");
+                return None;
+            }
+        };
+        let patterns = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                match RegexBuilder::new(line.trim())
+                    .case_insensitive(true)
+                    .build()
+                {
+                    Ok(pattern) => Some(pattern),
+                    Err(err) => {
+                        warn!("Invalid pattern in {path:?}: {err}");
+                        None
+                    }
+                }
+            })
+            .collect();
+        trace!("Read {path:?}");
+        Some(Self {
+            package_root: package_root.to_path_buf(),
+            patterns,
+        })
+    }
+
+    /// Whether the file, or a directory containing it, is ignored.
+    fn is_ignored(&self, file: &Path) -> bool {
+        let Some(relative) = file.canonicalize().ok().and_then(|file| {
+            file.strip_prefix(&self.package_root)
+                .ok()
+                .map(Path::to_path_buf)
+        }) else {
+            return false;
+        };
+        let mut prefix = String::new();
+        relative.components().any(|component| {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(&component.as_os_str().to_string_lossy());
+            self.patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&prefix))
+        })
+    }
+}
+
+fn build_glob_set<'a>(globs: impl Iterator<Item = &'a str>) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build()
+}
+
+/// Lists the files under `root` (or `root` itself if it is a file)
+/// that pass the filter. The files are sorted, so the output
+/// of `tergo` is deterministic.
+///
+/// Without `--include` globs, the files without an extension starting
+/// with an `Rscript` shebang are listed, too. Whether they are formatted
+/// depends on their configuration.
+///
+/// If `respect_ignore_files` is true, the files matched by `.gitignore`
+/// and `.tergoignore` files are skipped, e.g. `renv/` or `packrat/`.
+pub(crate) fn list_files(
+    root: &Path,
+    filter: &FileFilter,
+    respect_ignore_files: bool,
+) -> Vec<PathBuf> {
+    trace!("List files in a path: {root:?}");
+    if root.is_file() {
+        // Files passed explicitly are always formatted
+        return vec![root.to_path_buf()];
+    }
+    let mut walker = WalkBuilder::new(root);
+    walker.standard_filters(false);
+    if respect_ignore_files {
+        walker
+            .parents(true)
+            .git_ignore(true)
+            .git_exclude(true)
+            .require_git(false)
+            .add_custom_ignore_filename(IGNORE_FILE_NAME);
+    }
+    let mut files: Vec<PathBuf> = walker
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                warn!("Failed to read a directory entry: {err}");
+                None
+            }
+        })
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| entry.into_path())
+        .filter(|path| filter.matches(path, root))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Replaces the content of the file without ever leaving it truncated.
+///
+/// The content is written to a temporary file next to the file first,
+/// which is then renamed over the file. The permissions of the file are kept.
+/// A symbolic link is followed, so the file it points to is replaced.
+pub(crate) fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let path = &std::fs::canonicalize(path)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{file_name}.tergo-{}", std::process::id()));
+    let result = std::fs::write(&temporary, content)
+        .and_then(|_| {
+            let permissions = std::fs::metadata(path)?.permissions();
+            std::fs::set_permissions(&temporary, permissions)
+        })
+        .and_then(|_| std::fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, rbuildignore: &str, files: &[&str]) -> PathBuf {
+        let package = std::env::temp_dir().join(format!("tergo-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&package);
+        std::fs::create_dir_all(&package).unwrap();
+        std::fs::write(package.join(RBUILDIGNORE_FILE_NAME), rbuildignore).unwrap();
+        for file in files {
+            let file = package.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, "x <- 1\n").unwrap();
+        }
+        package
+    }
+
+    #[test]
+    fn rbuildignore_patterns_match_the_relative_paths() {
+        let package = package(
+            "rbuildignore-anchored",
+            "^README\\.Rmd$\n^R/.*_scratch\\.R$\n\n",
+            &[
+                "README.Rmd",
+                "Readme.rmd",
+                "readme.rmd.R",
+                "R/README.Rmd",
+                "R/a_scratch.R",
+                "R/a.R",
+            ],
+        );
+        let rbuildignore = RBuildIgnore::find(&package.join("R")).unwrap();
+        assert!(rbuildignore.is_ignored(&package.join("README.Rmd")));
+        assert!(rbuildignore.is_ignored(&package.join("Readme.rmd")));
+        assert!(!rbuildignore.is_ignored(&package.join("readme.rmd.R")));
+        assert!(!rbuildignore.is_ignored(&package.join("R/README.Rmd")));
+        assert!(rbuildignore.is_ignored(&package.join("R/a_scratch.R")));
+        assert!(!rbuildignore.is_ignored(&package.join("R/a.R")));
+        std::fs::remove_dir_all(&package).unwrap();
+    }
+
+    #[test]
+    fn rbuildignore_ignores_the_files_in_the_ignored_directories() {
+        let package = package(
+            "rbuildignore-directories",
+            "^revdep$\n^docs/\ndata\n",
+            &[
+                "revdep/check.R",
+                "revdeps.R",
+                "docs/site.R",
+                "R/data_utils.R",
+                "R/a.R",
+            ],
+        );
+        let rbuildignore = RBuildIgnore::find(&package).unwrap();
+        assert!(rbuildignore.is_ignored(&package.join("revdep/check.R")));
+        assert!(!rbuildignore.is_ignored(&package.join("revdeps.R")));
+        assert!(rbuildignore.is_ignored(&package.join("docs/site.R")));
+        // Like in R, a pattern without anchors matches anywhere in the path
+        assert!(rbuildignore.is_ignored(&package.join("R/data_utils.R")));
+        assert!(!rbuildignore.is_ignored(&package.join("R/a.R")));
+        std::fs::remove_dir_all(&package).unwrap();
+    }
+
+    #[test]
+    fn rbuildignore_skips_the_invalid_patterns() {
+        let package = package(
+            "rbuildignore-invalid",
+            "^[revdep\n^scratch\\.R$\n",
+            &["scratch.R", "[revdep.R"],
+        );
+        let rbuildignore = RBuildIgnore::find(&package).unwrap();
+        assert_eq!(rbuildignore.patterns.len(), 1);
+        assert!(rbuildignore.is_ignored(&package.join("scratch.R")));
+        assert!(!rbuildignore.is_ignored(&package.join("[revdep.R")));
+        std::fs::remove_dir_all(&package).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use log::trace;
+
+/// What the files are compared to when looking for changes.
+#[derive(Debug, Clone)]
+pub(crate) enum Baseline {
+    /// The staged content of the files.
+    Index,
+    /// A commit, a branch or any other git revision.
+    Revision(String),
+}
+
+impl Baseline {
+    pub(crate) fn new(revision: &str) -> Self {
+        if revision.is_empty() {
+            Baseline::Index
+        } else {
+            Baseline::Revision(revision.to_string())
+        }
+    }
+
+    fn args(&self) -> Vec<&str> {
+        match self {
+            Baseline::Index => vec![],
+            Baseline::Revision(revision) => vec![revision.as_str()],
+        }
+    }
+}
+
+fn run_git(directory: &Path, args: &[&str]) -> Result<String, String> {
+    trace!("Running git {args:?} in {directory:?}");
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|e| format!("Invalid output of git: {e}"))
+}
+
+fn directory_of(path: &Path) -> &Path {
+    if path.is_dir() {
+        path
+    } else {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    }
+}
+
+/// Returns the absolute paths of the files under `root` that differ
+/// from the baseline, including the new files not known to git yet.
+pub(crate) fn changed_files(root: &Path, baseline: &Baseline) -> Result<Vec<PathBuf>, String> {
+    let top_level = top_level(root)?;
+    let mut diff_args = vec!["diff", "--name-only", "--diff-filter=d", "-z"];
+    diff_args.extend(baseline.args());
+    let changed = run_git(&top_level, &diff_args)?;
+    let untracked = run_git(
+        &top_level,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )?;
+    Ok(changed
+        .split('\0')
+        .chain(untracked.split('\0'))
+        .filter(|name| !name.is_empty())
+        .map(|name| top_level.join(name))
+        .collect())
+}
+
+/// Returns the absolute paths of the files under `root` with staged changes.
+/// The deleted files are left out.
+pub(crate) fn staged_files(root: &Path) -> Result<Vec<PathBuf>, String> {
+    let top_level = top_level(root)?;
+    let staged = run_git(
+        &top_level,
+        &["diff", "--cached", "--name-only", "--diff-filter=d", "-z"],
+    )?;
+    Ok(staged
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(|name| top_level.join(name))
+        .collect())
+}
+
+/// Returns true if the file has changes that are not staged.
+pub(crate) fn has_unstaged_changes(file: &Path) -> Result<bool, String> {
+    let unstaged = run_git(
+        directory_of(file),
+        &["diff", "--name-only", "--", &file.to_string_lossy()],
+    )?;
+    Ok(!unstaged.trim().is_empty())
+}
+
+/// Stages the current content of the files.
+pub(crate) fn stage(root: &Path, files: &[PathBuf]) -> Result<(), String> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec!["add".to_string(), "--".to_string()];
+    args.extend(files.iter().map(|file| file.to_string_lossy().into_owned()));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    run_git(&top_level(root)?, &args).map(|_| ())
+}
+
+/// Returns the path of the directory with the git hooks of the repository
+/// containing `root`.
+pub(crate) fn hooks_directory(root: &Path) -> Result<PathBuf, String> {
+    let directory = directory_of(root);
+    let hooks = run_git(directory, &["rev-parse", "--git-path", "hooks"])?;
+    Ok(directory.join(hooks.trim()))
+}
+
+fn top_level(root: &Path) -> Result<PathBuf, String> {
+    Ok(PathBuf::from(
+        run_git(directory_of(root), &["rev-parse", "--show-toplevel"])?.trim(),
+    ))
+}
+
+/// Returns the lines of the file, counted from 0, that differ from
+/// the baseline. All the lines of a file not known to git are new.
+pub(crate) fn changed_lines(file: &Path, baseline: &Baseline) -> Result<Vec<Range<usize>>, String> {
+    let directory = directory_of(file);
+    let file_name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid file name: {file:?}"))?;
+    let tracked = run_git(directory, &["ls-files", "--", file_name])?;
+    if tracked.trim().is_empty() {
+        return Ok(std::iter::once(0..usize::MAX).collect());
+    }
+    let mut diff_args = vec!["diff", "--unified=0", "--no-color", "--no-ext-diff"];
+    diff_args.extend(baseline.args());
+    diff_args.extend(["--", file_name]);
+    Ok(parse_hunk_lines(&run_git(directory, &diff_args)?))
+}
+
+/// Returns the modified lines of the file, counted from 0, in a unified diff,
+/// e.g. of a pull request: the added lines and the lines in place of
+/// the removed ones. The unchanged lines around the hunks are left out.
+///
+/// The diff may change other files, too. A diff without the headers
+/// of the files is taken to change only this file.
+pub(crate) fn diff_lines(diff: &str, file: &Path) -> Vec<Range<usize>> {
+    let file: PathBuf = file
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+    let is_file = |name: &str| {
+        let name = Path::new(name);
+        file.ends_with(name) || name.ends_with(&file)
+    };
+    let mut in_file = !diff.lines().any(|line| line.starts_with("+++ "));
+    let mut modified: Vec<Range<usize>> = vec![];
+    let mut mark = |lines: Range<usize>| match modified.last_mut() {
+        Some(last) if last.end >= lines.start => last.end = last.end.max(lines.end),
+        _ => modified.push(lines),
+    };
+    // The next line of the new file and the old and new lines left in the hunk
+    let (mut line, mut old, mut new): (usize, usize, usize) = (0, 0, 0);
+    for text in diff.lines() {
+        if old > 0 || new > 0 {
+            match text.as_bytes().first() {
+                Some(b'+') => {
+                    if in_file {
+                        mark(line..line + 1);
+                    }
+                    line += 1;
+                    new = new.saturating_sub(1);
+                }
+                Some(b'-') => {
+                    if in_file {
+                        mark(line..line + 1);
+                    }
+                    old = old.saturating_sub(1);
+                }
+                Some(b'\\') => {}
+                _ => {
+                    line += 1;
+                    old = old.saturating_sub(1);
+                    new = new.saturating_sub(1);
+                }
+            }
+        } else if let Some(name) = text.strip_prefix("+++ ") {
+            // The name may be followed by a tab and the modification time
+            let name = name.split('\t').next().unwrap_or_default().trim();
+            in_file = name != "/dev/null"
+                && (is_file(name) || name.strip_prefix("b/").is_some_and(is_file));
+        } else if let Some((old_lines, new_start, new_lines)) = parse_hunk_header(text) {
+            // A hunk with no new lines starts after the line it names
+            let line_index = match new_lines {
+                0 => new_start,
+                _ => new_start.saturating_sub(1),
+            };
+            (line, old, new) = (line_index, old_lines, new_lines);
+        }
+    }
+    modified
+}
+
+/// Reads the number of the old lines, the first new line, counted from 1,
+/// and the number of the new lines from a hunk header,
+/// e.g. `@@ -10,2 +12,3 @@`.
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = header.strip_prefix("@@ ")?.split_whitespace();
+    let range = |part: Option<&str>, sign: char| -> Option<(usize, usize)> {
+        let mut numbers = part?.strip_prefix(sign)?.split(',');
+        let start = numbers.next()?.parse().ok()?;
+        let count = numbers.next().map_or(Some(1), |count| count.parse().ok())?;
+        Some((start, count))
+    };
+    let (_, old_lines) = range(parts.next(), '-')?;
+    let (new_start, new_lines) = range(parts.next(), '+')?;
+    Some((old_lines, new_start, new_lines))
+}
+
+/// Reads the new lines from the hunk headers of a diff,
+/// e.g. `@@ -10,2 +12,3 @@` means the lines 12-14, counted from 1.
+fn parse_hunk_lines(diff: &str) -> Vec<Range<usize>> {
+    diff.lines()
+        .filter_map(parse_hunk_header)
+        .map(|(_, start, count)| {
+            // A hunk removing lines only has no new lines; the code around it
+            // is the one that changed
+            let start = start.saturating_sub(1);
+            start..start + count.max(1)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunk_headers_may_omit_the_counts() {
+        assert_eq!(parse_hunk_header("@@ -10,2 +12,3 @@"), Some((2, 12, 3)));
+        assert_eq!(
+            parse_hunk_header("@@ -10 +12 @@ f <- function() {"),
+            Some((1, 12, 1))
+        );
+        assert_eq!(parse_hunk_header("@@ -3,2 +2,0 @@"), Some((2, 2, 0)));
+        assert_eq!(parse_hunk_header("@@ -1 +1 @@\r"), Some((1, 1, 1)));
+        assert_eq!(parse_hunk_header("@@ -a +1 @@"), None);
+        assert_eq!(parse_hunk_header("+++ b/file.R"), None);
+    }
+
+    #[test]
+    fn diff_lines_are_the_added_lines_of_the_file() {
+        let diff = "\
+diff --git a/R/other.R b/R/other.R
+--- a/R/other.R
++++ b/R/other.R
+@@ -1 +1 @@
+-x<-1
++x <- 1
+diff --git a/R/file.R b/R/file.R
+--- a/R/file.R
++++ b/R/file.R
+@@ -1,4 +1,5 @@
+ a <- 1
+-b<-2
++b <- 2
++c <- 3
+ d <- 4
+ e <- 5
+@@ -20 +21 @@
+-z<-26
++z <- 26
+";
+        assert_eq!(
+            diff_lines(diff, Path::new("./R/file.R")),
+            vec![1..3, 20..21]
+        );
+        assert_eq!(diff_lines(diff, Path::new("R/other.R")), vec![0..1]);
+        assert!(diff_lines(diff, Path::new("R/missing.R")).is_empty());
+    }
+
+    #[test]
+    fn diff_lines_of_a_diff_without_file_headers() {
+        let diff = "@@ -2 +2,2 @@\n-b<-2\n+b <- 2\n+c <- 3\n";
+        assert_eq!(diff_lines(diff, Path::new("file.R")), vec![1..3]);
+    }
+
+    #[test]
+    fn diff_lines_of_a_deleted_file_are_empty() {
+        let diff = "--- a/file.R\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-a <- 1\n-b <- 2\n";
+        assert!(diff_lines(diff, Path::new("file.R")).is_empty());
+    }
+
+    #[test]
+    fn diff_lines_of_removed_lines_are_the_lines_in_their_place() {
+        let diff = "+++ b/file.R\n@@ -3,2 +2,0 @@\n-c <- 3\n-d <- 4\n";
+        assert_eq!(diff_lines(diff, Path::new("file.R")), vec![2..3]);
+        let diff = "+++ b/file.R\n@@ -2,4 +2,2 @@\n b <- 2\n-c <- 3\n-d <- 4\n e <- 5\n";
+        assert_eq!(diff_lines(diff, Path::new("file.R")), vec![2..3]);
+    }
+
+    #[test]
+    fn diff_lines_skip_the_missing_newline_markers() {
+        let diff = "\
++++ b/file.R
+@@ -1,2 +1,3 @@
+ a <- 1
+-b<-2
+\\ No newline at end of file
++b <- 2
++c <- 3
+\\ No newline at end of file
+";
+        assert_eq!(diff_lines(diff, Path::new("file.R")), vec![1..3]);
+    }
+
+    #[test]
+    fn diff_lines_of_a_diff_with_windows_line_endings() {
+        let diff =
+            "--- a/file.R\r\n+++ b/file.R\r\n@@ -1,2 +1,2 @@\r\n a <- 1\r\n-b<-2\r\n+b <- 2\r\n";
+        assert_eq!(diff_lines(diff, Path::new("file.R")), vec![1..2]);
+    }
+}
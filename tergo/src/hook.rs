@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use log::info;
+
+use crate::git;
+
+/// Marks the hooks written by `tergo`, so they can be overwritten safely.
+const HOOK_MARKER: &str = "# Installed by `tergo hook install`.";
+
+/// Writes a git pre-commit hook formatting the staged files.
+///
+/// An existing hook is overwritten only if `tergo` installed it or `force` is set.
+pub(crate) fn install(root: &Path, force: bool) -> Result<(), String> {
+    let hooks = git::hooks_directory(root)?;
+    let hook = hooks.join("pre-commit");
+    if let Ok(existing) = std::fs::read_to_string(&hook) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            return Err(format!(
+                "The pre-commit hook {hook:?} already exists. Pass --force to overwrite it"
+            ));
+        }
+    }
+    std::fs::create_dir_all(&hooks)
+        .map_err(|e| format!("Failed to create the hooks directory {hooks:?}: {e}"))?;
+    let script = format!("#!/bin/sh\n{HOOK_MARKER}\nexec tergo fmt --staged\n");
+    std::fs::write(&hook, script).map_err(|e| format!("Failed to write {hook:?}: {e}"))?;
+    make_executable(&hook)?;
+    info!("Installed the pre-commit hook: {hook:?}");
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Failed to make {path:?} executable: {e}"))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &Path) -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use std::path::Path;
+
+use log::info;
+use tergo_lib::Config;
+
+use crate::config::CONFIG_FILE_NAME;
+
+/// The comments written above the keys of the scaffolded configuration file,
+/// in the order of the keys in the file.
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    (
+        "indent",
+        "The number of spaces in a single level of indentation.",
+    ),
+    (
+        "continuation_indent",
+        "The indentation of the lines continuing a broken binary operation or pipe. If 0, `indent` is used.",
+    ),
+    (
+        "hanging_binary_operations",
+        "Whether to align the operands of a broken arithmetic or logical operation under the first one.",
+    ),
+    (
+        "line_length",
+        "The maximum length of a line. Longer lines are broken if possible.",
+    ),
+    (
+        "line_length_tolerance",
+        "The percentage of line_length by which a line may overflow instead of being broken.",
+    ),
+    (
+        "embracing_op_no_nl",
+        "Whether to keep the embracing operator `{{ x }}` on a single line.",
+    ),
+    (
+        "allow_nl_after_assignment",
+        "Whether to break the line after `<-` if the assignment does not fit a line.",
+    ),
+    (
+        "break_after_assignment",
+        "Whether to break the line after `<-` rather than the value if the value then fits a line.",
+    ),
+    (
+        "space_before_complex_rhs_in_formula",
+        "Whether to put a space after `~` if the right hand side is not a single symbol.",
+    ),
+    (
+        "strip_suffix_whitespace_in_function_defs",
+        "Whether to remove the blank lines before the closing brace of a function.",
+    ),
+    (
+        "function_line_breaks",
+        "The indentation of the broken function arguments: \"single\", \"double\" or \"hanging\".",
+    ),
+    (
+        "insert_newline_in_quote_call",
+        "Whether to break the line after `quote(` in long calls to `quote`.",
+    ),
+    (
+        "min_access_chain_length",
+        "The number of `$`, `@`, `[` or `[[` accesses in a chain from which it is broken into lines.",
+    ),
+    (
+        "align_named_arguments",
+        "Whether to align the `=` of the named arguments of a call broken one argument per line.",
+    ),
+    (
+        "align_assignments",
+        "Whether to align the `<-` of the consecutive assignments. If false, the aligned ones are kept.",
+    ),
+    (
+        "data_table_dialect",
+        "Whether to keep the empty `i` of the `data.table` subsetting `DT[, j, by]` after the bracket.",
+    ),
+    (
+        "last_braced_argument_heuristic",
+        "Whether to keep the braced last argument of any call on the line of the call.",
+    ),
+    (
+        "closure_argument_functions",
+        "The functions whose braced last argument stays on the line of the call if the heuristic is off.",
+    ),
+    (
+        "expanded_call_functions",
+        "The functions whose calls put each argument on its own line, e.g. \"fluidPage\".",
+    ),
+    (
+        "max_blank_lines_in_blocks",
+        "The maximum number of consecutive blank lines kept in braces, e.g. in function bodies.",
+    ),
+    (
+        "blank_lines_between_functions",
+        "The number of blank lines around the top-level function definitions. If 0, the code's are kept.",
+    ),
+    (
+        "space_after_keyword",
+        "Whether to put a space between `if`, `for` and `while` and their parenthesis: `if (x)`.",
+    ),
+    (
+        "space_after_function",
+        "Whether to put a space between `function` and its arguments: `function (x)`.",
+    ),
+    (
+        "trailing_comma",
+        "What to do with a comma after the last argument of a call: \"preserve\" or \"never\".",
+    ),
+    (
+        "collapse_if_blocks",
+        "Whether to keep the braced bodies of `if` with a single expression on one line if it fits.",
+    ),
+    (
+        "collapse_loop_blocks",
+        "Whether to keep the braced body of a loop with a single expression on one line if it fits.",
+    ),
+    (
+        "collapse_function_blocks",
+        "Whether to keep the braced body of a function with a single expression on one line if it fits.",
+    ),
+    (
+        "expand_empty_blocks",
+        "Whether to put the closing brace of an empty block on its own line.",
+    ),
+    (
+        "pad_section_comments",
+        "Whether to pad the section comments, e.g. `# Setup ----`, with their marker to comment_line_length.",
+    ),
+    (
+        "wrap_comments",
+        "Whether to split the long prose comments on their own lines into more lines.",
+    ),
+    (
+        "comment_line_length",
+        "The maximum length of the wrapped and padded comments, 0 for line_length.",
+    ),
+    (
+        "normalize_assignment",
+        "Which operator the assignment statements use: \"none\" (or false), \"arrow\" (<-) or \"equal\" (=).",
+    ),
+    (
+        "parenthesize_mixed_logical_operators",
+        "Whether to put the parentheses around the `&&` operations in the operands of `||`.",
+    ),
+    (
+        "remove_redundant_backticks",
+        "Whether to remove the backticks around the names which are syntactic without them.",
+    ),
+    (
+        "normalize_numbers",
+        "Whether to normalize the exponents of the numbers, e.g. 1e+03 to 1e3.",
+    ),
+    (
+        "format_roxygen_examples",
+        "Whether to format the code in the `@examples` sections of roxygen comments.",
+    ),
+    (
+        "format_glue_strings",
+        "Whether to format the code in the `{...}` interpolations of the `glue()` strings.",
+    ),
+    (
+        "format_shebang_scripts",
+        "Whether to format the files without an extension starting with `#!/usr/bin/env Rscript`.",
+    ),
+    (
+        "verify",
+        "Whether to check that formatting did not change the meaning of the code.",
+    ),
+    (
+        "max_file_size",
+        "The size in bytes of the largest file formatted. 0 formats the files of any size.",
+    ),
+    (
+        "max_expression_depth",
+        "The deepest nesting of brackets in the code formatted. 0 formats any depth.",
+    ),
+    (
+        "generated_file_markers",
+        "The comments marking the generated files, which are not formatted.",
+    ),
+    (
+        "exclusion_list",
+        "The paths of the files and directories that are not formatted.",
+    ),
+];
+
+/// Writes a commented `tergo.toml` with all the options into `directory`.
+///
+/// If `import` is set, the options are taken from the `.lintr` file
+/// and the styler hook in `.pre-commit-config.yaml`, where they exist.
+pub(crate) fn init(directory: &Path, import: bool, force: bool) -> Result<(), String> {
+    let path = directory.join(CONFIG_FILE_NAME);
+    if path.exists() && !force {
+        return Err(format!(
+            "The configuration file {path:?} already exists. Pass --force to overwrite it"
+        ));
+    }
+    let mut config = Config::default();
+    if import {
+        import_lintr(directory, &mut config);
+        import_styler(directory, &mut config);
+    }
+    std::fs::write(&path, scaffold(&config)?)
+        .map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+    info!("Created the configuration file: {path:?}");
+    Ok(())
+}
+
+/// Returns the content of a configuration file setting all the options
+/// of `config`, each preceded by a comment describing it.
+fn scaffold(config: &Config) -> Result<String, String> {
+    let mut table = toml::Table::try_from(config)
+        .map_err(|e| format!("Failed to serialize the configuration: {e}"))?;
+    let mut scaffold = String::from(
+        "# The configuration of tergo, the R code formatter.\n\
+         # Removing an option sets it to its default value.\n",
+    );
+    for (key, description) in DESCRIPTIONS {
+        if let Some(value) = table.remove(*key) {
+            scaffold.push_str(&format!("\n# {description}\n{key} = {value}\n"));
+        }
+    }
+    for (key, value) in table {
+        scaffold.push_str(&format!("\n{key} = {value}\n"));
+    }
+    Ok(scaffold)
+}
+
+/// Reads the line length and the indentation from the linters
+/// configured in `.lintr`, e.g. `line_length_linter(100)`.
+fn import_lintr(directory: &Path, config: &mut Config) {
+    let Ok(lintr) = std::fs::read_to_string(directory.join(".lintr")) else {
+        return;
+    };
+    if let Some(line_length) = linter_argument(&lintr, "line_length_linter", "length") {
+        info!("Imported from .lintr: line_length = {line_length}");
+        config.line_length.0 = line_length;
+    }
+    if let Some(indent) = linter_argument(&lintr, "indentation_linter", "indent") {
+        info!("Imported from .lintr: indent = {indent}");
+        config.indent.0 = indent;
+    }
+}
+
+/// Reads the indentation from the `indent_by` argument of the styler
+/// hook in `.pre-commit-config.yaml`, e.g. `args: [--indent_by=4]`.
+fn import_styler(directory: &Path, config: &mut Config) {
+    let Ok(pre_commit) = std::fs::read_to_string(directory.join(".pre-commit-config.yaml")) else {
+        return;
+    };
+    if let Some(indent) = argument_value(&pre_commit, "indent_by") {
+        info!("Imported from the styler hook: indent = {indent}");
+        config.indent.0 = indent;
+    }
+}
+
+/// Returns the integer passed to the linter in `lintr`, either
+/// as the first positional argument or as the `argument`.
+fn linter_argument(lintr: &str, linter: &str, argument: &str) -> Option<i32> {
+    let start = lintr.find(&format!("{linter}("))? + linter.len() + 1;
+    let arguments = &lintr[start..start + lintr[start..].find(')')?];
+    let first = arguments.split(',').next()?.trim();
+    parse_integer(first).or_else(|| argument_value(arguments, argument))
+}
+
+/// Returns the integer following `name` and `=`. The name is not a part
+/// of a longer one, e.g. `indent` in `hanging_indent_style`.
+fn argument_value(text: &str, name: &str) -> Option<i32> {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    text.match_indices(name).find_map(|(start, _)| {
+        if text[..start].ends_with(is_identifier) {
+            return None;
+        }
+        let value = text[start + name.len()..]
+            .trim_start()
+            .strip_prefix('=')?
+            .trim_start();
+        let end = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        value[..end].parse().ok()
+    })
+}
+
+/// Parses an R integer literal, e.g. `100` or `100L`.
+fn parse_integer(literal: &str) -> Option<i32> {
+    literal.strip_suffix('L').unwrap_or(literal).parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINTR: &str = r#"linters: linters_with_defaults(
+    line_length_linter(120),
+    indentation_linter(hanging_indent_style = "tidy", indent = 4L),
+    object_name_linter = NULL
+  )
+encoding: "UTF-8"
+"#;
+
+    const PRE_COMMIT_CONFIG: &str = r#"repos:
+-   repo: https://github.com/lorenzwalthert/precommit
+    rev: v0.4.3
+    hooks:
+    -   id: style-files
+        args: [--style_pkg=styler, --style_fun=tidyverse_style, --indent_by=4]
+    -   id: lintr
+"#;
+
+    #[test]
+    fn linter_arguments_are_read_from_the_lintr_file() {
+        assert_eq!(
+            linter_argument(LINTR, "line_length_linter", "length"),
+            Some(120)
+        );
+        assert_eq!(
+            linter_argument(LINTR, "indentation_linter", "indent"),
+            Some(4)
+        );
+        assert_eq!(
+            linter_argument(LINTR, "object_usage_linter", "indent"),
+            None
+        );
+    }
+
+    #[test]
+    fn linter_arguments_may_be_named_or_missing() {
+        let lintr = "linters: linters_with_defaults(line_length_linter(length = 100L), indentation_linter())";
+        assert_eq!(
+            linter_argument(lintr, "line_length_linter", "length"),
+            Some(100)
+        );
+        assert_eq!(linter_argument(lintr, "indentation_linter", "indent"), None);
+    }
+
+    #[test]
+    fn indentation_is_read_from_the_styler_hook() {
+        assert_eq!(argument_value(PRE_COMMIT_CONFIG, "indent_by"), Some(4));
+        assert_eq!(argument_value("args: [--indent_by=two]", "indent_by"), None);
+        assert_eq!(
+            argument_value("args: [--style_pkg=styler]", "indent_by"),
+            None
+        );
+    }
+
+    #[test]
+    fn argument_names_are_not_matched_inside_longer_names() {
+        assert_eq!(
+            argument_value("hanging_indent = 8, indent = 2", "indent"),
+            Some(2)
+        );
+        assert_eq!(argument_value("hanging_indent = 8", "indent"), None);
+    }
+}
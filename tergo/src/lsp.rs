@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+};
+
+use log::{debug, warn};
+use tergo_lib::{Config, IncrementalFormatter, diff_edits, is_spin_script, tergo_format_range};
+use tower_lsp::{
+    Client, LanguageServer, LspService, Server, jsonrpc,
+    lsp_types::{
+        DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+        DocumentFormattingParams, DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams,
+        DocumentRangeFormattingParams, InitializeParams, InitializeResult, MessageType, OneOf,
+        Position, Range, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
+        TextDocumentSyncKind, TextEdit, Url,
+    },
+};
+
+use crate::{
+    config::{self, ConfigResolver},
+    files::FileKind,
+    format_code_incremental, is_excluded, lock,
+};
+
+/// A language server formatting R code in the editors.
+///
+/// The server keeps the content of the open documents and resolves
+/// the configuration of each document the same way `tergo fmt` does.
+struct Backend {
+    client: Client,
+    documents: Mutex<HashMap<Url, String>>,
+    /// The formatters of the open documents, so only the statements
+    /// edited since the last formatting are laid out again.
+    formatters: Mutex<HashMap<Url, IncrementalFormatter>>,
+    configs: Mutex<ConfigResolver>,
+}
+
+/// What part of a document to format.
+enum Scope {
+    Document,
+    Lines(std::ops::Range<usize>),
+}
+
+impl Backend {
+    fn format(&self, uri: &Url, scope: Scope) -> Result<Option<Vec<TextEdit>>, String> {
+        let Some(content) = lock(&self.documents).get(uri).cloned() else {
+            return Ok(None);
+        };
+        let path = uri
+            .to_file_path()
+            .unwrap_or_else(|_| PathBuf::from(uri.path()));
+        let config = lock(&self.configs).resolve(&path)?.clone();
+        if is_excluded(&path, &config) {
+            debug!("Ignoring: {path:?}");
+            return Ok(None);
+        }
+        let config = config::with_header(&config, &content)?;
+        let formatted = match scope {
+            Scope::Document => {
+                let mut formatter = lock(&self.formatters).remove(uri).unwrap_or_default();
+                let kind = FileKind::from_path(&path);
+                let formatted = format_code_incremental(&content, kind, &config, &mut formatter);
+                let mut formatters = lock(&self.formatters);
+                // The formatter of a document closed meanwhile would never be removed
+                if lock(&self.documents).contains_key(uri) {
+                    formatters.insert(uri.clone(), formatter);
+                }
+                Some(formatted?)
+            }
+            Scope::Lines(lines) => format_lines(&content, &path, lines, &config)?,
+        };
+        Ok(formatted.map(|formatted| text_edits(&content, &formatted)))
+    }
+
+    async fn respond(&self, uri: &Url, scope: Scope) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        match self.format(uri, scope) {
+            Ok(edits) => Ok(edits),
+            Err(e) => {
+                warn!("Failed to format {uri}: {e}");
+                self.client
+                    .log_message(MessageType::WARNING, format!("Failed to format {uri}: {e}"))
+                    .await;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Only plain R scripts can be formatted in parts.
+fn format_lines(
+    content: &str,
+    path: &Path,
+    lines: std::ops::Range<usize>,
+    config: &Config,
+) -> Result<Option<String>, String> {
+    if FileKind::from_path(path) != FileKind::R || is_spin_script(content) {
+        return Ok(None);
+    }
+    tergo_format_range(content, lines, Some(config)).map(Some)
+}
+
+/// Returns the edits turning the original content into the formatted one.
+///
+/// The edits replace only the changed lines, so the editor keeps
+/// the cursor position and the undo history of the rest of the document.
+fn text_edits(original: &str, formatted: &str) -> Vec<TextEdit> {
+    diff_edits(original, formatted)
+        .into_iter()
+        .map(|edit| TextEdit {
+            range: Range::new(
+                position(original, edit.range.start),
+                position(original, edit.range.end),
+            ),
+            new_text: edit.new_text,
+        })
+        .collect()
+}
+
+/// Converts a byte offset into a position, whose column counts UTF-16 code
+/// units as the language server protocol requires.
+fn position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let column = before[line_start..].encode_utf16().count();
+    Position::new(line as u32, column as u32)
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, _: InitializeParams) -> jsonrpc::Result<InitializeResult> {
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::FULL,
+                )),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "}".to_string(),
+                    more_trigger_character: Some(vec!["\n".to_string()]),
+                }),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: "tergo".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
+        })
+    }
+
+    async fn shutdown(&self) -> jsonrpc::Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        lock(&self.documents).insert(params.text_document.uri, params.text_document.text);
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        // The server asks for the full content on every change
+        if let Some(change) = params.content_changes.into_iter().last() {
+            lock(&self.documents).insert(params.text_document.uri, change.text);
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        lock(&self.documents).remove(&params.text_document.uri);
+        lock(&self.formatters).remove(&params.text_document.uri);
+    }
+
+    async fn formatting(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        self.respond(&params.text_document.uri, Scope::Document)
+            .await
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let range = params.range;
+        let start = range.start.line as usize;
+        // A selection ending at the start of a line does not include the line
+        let end = if range.end.character == 0 && range.end.line > range.start.line {
+            range.end.line as usize
+        } else {
+            range.end.line as usize + 1
+        };
+        self.respond(&params.text_document.uri, Scope::Lines(start..end))
+            .await
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let line = params.text_document_position.position.line as usize;
+        // After a new line, the statement to format ends on the previous line
+        let line = if params.ch == "\n" {
+            line.saturating_sub(1)
+        } else {
+            line
+        };
+        self.respond(
+            &params.text_document_position.text_document.uri,
+            Scope::Lines(line..line + 1),
+        )
+        .await
+    }
+}
+
+/// Runs the language server over the standard input and output.
+pub(crate) fn run() -> ExitCode {
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            warn!("Failed to start the language server: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    runtime.block_on(async {
+        let (service, socket) = LspService::new(|client| Backend {
+            client,
+            documents: Mutex::new(HashMap::new()),
+            formatters: Mutex::new(HashMap::new()),
+            configs: Mutex::new(ConfigResolver::new(None, toml::Table::new())),
+        });
+        Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
+            .serve(service)
+            .await;
+    });
+    ExitCode::SUCCESS
+}
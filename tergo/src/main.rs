@@ -1,120 +1,627 @@
+mod cache;
+mod cli;
+mod config;
+mod corpus;
+mod daemon;
+mod diff;
+mod encoding;
+mod files;
+mod git;
+mod hook;
+mod init;
+mod lsp;
+mod modified;
+mod printer;
+mod report;
+mod stats;
+mod styler;
+mod watch;
+
 use std::{
-    ffi::OsStr,
+    collections::HashSet,
+    io::{Read, Write},
+    ops::Range,
     path::{Path, PathBuf},
+    process::ExitCode,
     str::FromStr,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Instant,
 };
 
-use clap::{arg, Parser};
+use cache::FormatCache;
+use clap::Parser;
+use cli::{
+    Cli, Command, ConfigArgs, ConfigCommand, ConfigFormat, CorpusCommand, CorpusRunArgs,
+    DaemonArgs, FormatArgs, HookCommand, InitArgs,
+};
+use config::ConfigResolver;
+use files::{FileFilter, FileKind};
 use log::{debug, info, trace, warn};
-use tergo_lib::{tergo_format, Config};
+use printer::{Printer, Verbosity};
+use rayon::prelude::*;
+use report::{OutputFormat, Report};
+use stats::Stats;
+use tergo_lib::{
+    Config, IncrementalFormatter, SyntaxError, Timings, bench, check_limits, check_tokens_kept,
+    find_long_lines, find_syntax_error, first_different_line, is_generated_file, is_spin_script,
+    tergo_format_notebook, tergo_format_partial, tergo_format_quarto, tergo_format_range,
+    tergo_format_rmarkdown, tergo_format_spin, tergo_format_sweave, tergo_format_timed,
+};
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Cli {
-    #[arg(default_value = ".")]
-    path: String,
+// Counts the allocations for `--profile`
+#[global_allocator]
+static ALLOCATOR: bench::CountingAllocator = bench::CountingAllocator;
 
-    #[arg(default_value = "tergo.toml")]
-    config: String,
+/// Decides what happens with the formatted code.
+#[derive(Debug, Clone, Copy)]
+enum Output {
+    /// Overwrite the file with the formatted code.
+    InPlace,
+    /// Print a diff between the file and the formatted code.
+    Diff { color: bool },
+    /// Only report whether the file would be reformatted.
+    Check,
+}
+
+/// The options of formatting the files, shared by all the threads.
+#[derive(Debug, Clone, Copy)]
+struct FormatOptions<'a> {
+    output: Output,
+    cache: Option<&'a FormatCache>,
+    /// If set, only the lines changed since the baseline are formatted.
+    changed_lines: Option<&'a git::Baseline>,
+    /// If set, the original content of a reformatted file is kept
+    /// in a file with this suffix added to its name.
+    backup: Option<&'a str>,
+    /// If set, only these lines, counted from 0, are formatted.
+    range: Option<&'a Range<usize>>,
+    /// If set, the results are collected into the report.
+    report: Option<&'a Report>,
+    /// If set, a line is printed for each file formatted.
+    printer: Option<&'a Printer>,
+    /// If set, the sizes and the formatting times of the files are collected.
+    stats: Option<&'a Stats>,
+    /// If set, the formatted code is formatted again and must not change.
+    check_idempotent: bool,
+    /// If set, formatting R scripts must keep every token of the code.
+    audit_tokens: bool,
+    /// If set, the statements of R scripts with syntax errors are kept
+    /// as they are and the rest of the code is formatted.
+    skip_syntax_errors: bool,
+    /// If set, the stages of formatting R scripts are measured and printed.
+    profile: bool,
+    /// If set, the generated files are formatted, too.
+    force: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FileStatus {
+    Unchanged,
+    Changed,
+    Skipped,
+    /// Skipped, because the code exceeds the limits of the configuration.
+    TooLarge,
+    /// Skipped, because a tool generated the code.
+    Generated,
+}
+
+/// Counts of the outcomes of formatting multiple files.
+#[derive(Debug, Default)]
+struct Summary {
+    changed: usize,
+    unchanged: usize,
+    skipped: usize,
+    too_large: usize,
+    /// The generated files skipped, listed after the counts.
+    generated: Vec<PathBuf>,
+    failed: usize,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} changed, {} unchanged, {} skipped, ",
+            self.changed, self.unchanged, self.skipped
+        )?;
+        if self.too_large > 0 {
+            write!(f, "{} too large, ", self.too_large)?;
+        }
+        if !self.generated.is_empty() {
+            write!(f, "{} generated, ", self.generated.len())?;
+        }
+        write!(f, "{} failed", self.failed)
+    }
 }
 
 #[derive(Debug)]
 enum Error {
     ReadFileToString,
     WriteToFile,
+    ReadStdin,
+    WriteStdout,
+    Configuration,
     Formatting,
+    Git,
 }
 
-fn get_config(path: &Path) -> Config {
-    match std::fs::read_to_string(path) {
-        Ok(config_file) => {
-            let config: Config = toml::from_str(&config_file).unwrap_or_else(|_| {
-                warn!(
-                    "Failed to deserialize the configuration file to Config. Using the default \
-                     configuration."
-                );
-                Config::default()
-            });
-            config
+fn format_file(path: &Path, config: &Config, options: FormatOptions) -> Result<FileStatus, Error> {
+    use Error::*;
+    let FormatOptions {
+        output,
+        cache,
+        report,
+        stats,
+        ..
+    } = options;
+    let kind = FileKind::from_path(path);
+    let encoding::Decoded {
+        content,
+        encoding,
+        bom,
+    } = encoding::read(path).map_err(|e| {
+        warn!("{e}");
+        ReadFileToString
+    })?;
+    if encoding != encoding_rs::UTF_8 {
+        debug!("Reading {path:?} as {}", encoding.name());
+    }
+    let config = &*config::with_header(config, &content).map_err(|e| {
+        warn!("{path:?}: {e}");
+        Configuration
+    })?;
+    if !options.force && is_generated_file(&content, config) {
+        debug!("Skipping {path:?}: the file is generated");
+        return Ok(FileStatus::Generated);
+    }
+    if let Some(cache) = cache {
+        if cache.is_formatted(path, &FormatCache::key(&content, config)) {
+            debug!("Already formatted according to the cache: {path:?}");
+            return Ok(FileStatus::Unchanged);
         }
-        Err(_) => {
-            debug!("Configuration file not found. Using the default configuration.");
-            Config::default()
+    }
+    if kind == FileKind::R {
+        if let Err(e) = check_limits(&content, config) {
+            warn!("Skipping {path:?}: {e}");
+            return Ok(FileStatus::TooLarge);
         }
     }
+    let partial = options.changed_lines.is_some() || options.range.is_some();
+    if partial && (kind != FileKind::R || is_spin_script(&content)) {
+        debug!("Skipping {path:?}: formatting parts of {kind:?} files is not supported");
+        return Ok(FileStatus::Skipped);
+    }
+    let started = Instant::now();
+    let formatted = match (options.changed_lines, options.range) {
+        (Some(baseline), _) => {
+            let lines = git::changed_lines(path, baseline).map_err(|e| {
+                warn!("{e}");
+                Git
+            })?;
+            format_lines(&content, &lines, config).map(|formatted| (formatted, None))
+        }
+        (None, Some(range)) => format_lines(&content, std::slice::from_ref(range), config)
+            .map(|formatted| (formatted, None)),
+        (None, None)
+            if options.skip_syntax_errors && kind == FileKind::R && !is_spin_script(&content) =>
+        {
+            format_valid_statements(&content, config, &path.display().to_string())
+                .map(|formatted| (formatted, None))
+        }
+        (None, None) => format_code_timed(&content, kind, config),
+    }
+    .and_then(|formatted| {
+        if options.check_idempotent && !partial {
+            check_idempotent(&formatted.0, kind, config)?;
+        }
+        if options.audit_tokens && kind == FileKind::R {
+            check_tokens_kept(&content, &formatted.0)?;
+        }
+        Ok(formatted)
+    })
+    .map_err(|e| {
+        trace!("Error when formatting: {e}");
+        let location = syntax_error(&content, kind);
+        match &location {
+            Some(error) => warn!("{path:?}: {error}"),
+            // Not a syntax error, but a failed check of the formatted code
+            None if kind == FileKind::R => warn!("{path:?}: {e}"),
+            None => {}
+        }
+        if let Some(report) = report {
+            let message = location.as_ref().map_or(e, SyntaxError::message);
+            report.record_error(path, message, location);
+        }
+        Formatting
+    })?;
+    let (formatted, timings) = formatted;
+    if kind == FileKind::R && !partial {
+        for long_line in find_long_lines(&formatted, config) {
+            warn!("{}:{}: {long_line}", path.display(), long_line.line);
+        }
+    }
+    if options.profile && kind == FileKind::R && !partial {
+        print_profile(&path.display().to_string(), &content, config);
+    }
+    if let Some(stats) = stats {
+        stats.record(path, &content, started.elapsed(), timings);
+    }
+    trace!("Formatted code:\n:{}", formatted);
+    if formatted == content {
+        if let Some(cache) = cache {
+            cache.mark_formatted(path, FormatCache::key(&content, config));
+        }
+        return Ok(FileStatus::Unchanged);
+    }
+    if let Some(report) = report {
+        report.record_changes(path, &content, &formatted);
+    }
+    match output {
+        Output::InPlace => {
+            if let Some(suffix) = options.backup {
+                let mut backup = path.as_os_str().to_owned();
+                backup.push(suffix);
+                std::fs::copy(path, &backup).map_err(|e| {
+                    warn!("Failed to back up {path:?} to {backup:?}: {e}");
+                    WriteToFile
+                })?;
+            }
+            let bytes = encoding::encode(&formatted, encoding, bom).map_err(|e| {
+                warn!("{path:?}: {e}");
+                WriteToFile
+            })?;
+            files::write_atomically(path, &bytes).map_err(|e| {
+                trace!("Error writing to file {e}");
+                WriteToFile
+            })?;
+            if let Some(cache) = cache {
+                cache.mark_formatted(path, FormatCache::key(&formatted, config));
+            }
+        }
+        Output::Diff { color } => {
+            // A single `print!` keeps the diff in one piece when formatting in parallel
+            print!(
+                "{}",
+                diff::unified_diff(&path.to_string_lossy(), &content, &formatted, color)
+            );
+        }
+        Output::Check => {}
+    }
+    Ok(FileStatus::Changed)
 }
 
-fn format_file_in_place(path: &Path, config: &Config) -> Result<(), Error> {
+/// Formats the code from the standard input.
+///
+/// The formatted code (or the diff) is written to the standard output.
+/// Excluded files are echoed back unchanged.
+fn format_stdin(
+    stdin_filename: Option<&Path>,
+    configs: &mut ConfigResolver,
+    output: Output,
+    range: Option<&Range<usize>>,
+    skip_syntax_errors: bool,
+    profile: bool,
+) -> Result<FileStatus, Error> {
     use Error::*;
-    let content = std::fs::read_to_string(path).map_err(|e| {
-        trace!("Error when reading the file {e}");
-        ReadFileToString
+    let mut bytes = vec![];
+    std::io::stdin().read_to_end(&mut bytes).map_err(|e| {
+        trace!("Error when reading the standard input {e}");
+        ReadStdin
     })?;
-    let formatted = tergo_format(&content, Some(config)).map_err(|e| {
-        trace!("Error when formatting: {e}");
-        Formatting
+    let encoding::Decoded {
+        content,
+        encoding,
+        bom,
+    } = encoding::decode(&bytes, stdin_filename.unwrap_or(Path::new("<stdin>"))).map_err(|e| {
+        warn!("{e}");
+        ReadStdin
     })?;
-    trace!("Formatted code:\n:{}", formatted);
-    std::fs::write(path, formatted).map_err(|e| {
-        trace!("Error writing to file {e}");
-        WriteToFile
+    let config = configs
+        .resolve(stdin_filename.unwrap_or(Path::new("<stdin>")))
+        .and_then(|config| config::with_header(config, &content))
+        .map_err(|e| {
+            warn!("{e}");
+            Configuration
+        })?;
+    let config = &*config;
+    let file_name = stdin_filename
+        .map(|file| file.to_string_lossy())
+        .unwrap_or("<stdin>".into());
+    let is_r_script = stdin_filename.is_none_or(|file| FileKind::from_path(file) == FileKind::R);
+    let exceeded_limits = is_r_script
+        .then(|| check_limits(&content, config).err())
+        .flatten();
+    let formatted = match stdin_filename {
+        Some(file) if is_excluded(file, config) => {
+            info!("Ignoring: {file:?}");
+            content.clone()
+        }
+        _ if exceeded_limits.is_some() => {
+            let error = exceeded_limits.as_deref().unwrap_or_default();
+            warn!("Skipping {file_name}: {error}");
+            content.clone()
+        }
+        _ => {
+            let kind = stdin_filename.map_or(FileKind::R, FileKind::from_path);
+            match range {
+                Some(range) if kind == FileKind::R => {
+                    format_lines(&content, std::slice::from_ref(range), config)
+                }
+                Some(_) => {
+                    warn!("Only the lines of R scripts can be formatted");
+                    return Err(Formatting);
+                }
+                None if skip_syntax_errors && kind == FileKind::R && !is_spin_script(&content) => {
+                    format_valid_statements(&content, config, &file_name)
+                }
+                None => format_code(&content, kind, config),
+            }
+            .map_err(|e| {
+                trace!("Error when formatting: {e}");
+                if let Some(error) = syntax_error(&content, kind) {
+                    warn!("<stdin>: {error}");
+                }
+                Formatting
+            })?
+        }
+    };
+    if profile && is_r_script && exceeded_limits.is_none() && range.is_none() {
+        print_profile(&file_name, &content, config);
+    }
+    let mut stdout = std::io::stdout().lock();
+    match output {
+        Output::InPlace => {
+            let bytes = encoding::encode(&formatted, encoding, bom).map_err(|e| {
+                warn!("{e}");
+                WriteStdout
+            })?;
+            stdout.write_all(&bytes)
+        }
+        Output::Diff { color } => {
+            stdout.write_all(diff::unified_diff(&file_name, &content, &formatted, color).as_bytes())
+        }
+        Output::Check => Ok(()),
+    }
+    .map_err(|e| {
+        trace!("Error writing to the standard output {e}");
+        WriteStdout
     })?;
-    Ok(())
+    if formatted == content {
+        Ok(FileStatus::Unchanged)
+    } else {
+        Ok(FileStatus::Changed)
+    }
+}
+
+/// Formats an R script, keeping the statements with syntax errors as they are
+/// and warning about them.
+fn format_valid_statements(content: &str, config: &Config, name: &str) -> Result<String, String> {
+    let (formatted, skipped) = tergo_format_partial(content, Some(config))?;
+    for lines in skipped {
+        let start = lines.start + 1;
+        if lines.end == start {
+            warn!("{name}:{start}: Kept the line as it is, because it does not parse");
+        } else {
+            warn!(
+                "{name}:{start}: Kept the lines {start}-{} as they are, because they do not parse",
+                lines.end
+            );
+        }
+    }
+    Ok(formatted)
+}
+
+/// Formats the code of a file of the given kind.
+fn format_code(content: &str, kind: FileKind, config: &Config) -> Result<String, String> {
+    format_code_timed(content, kind, config).map(|(formatted, _)| formatted)
+}
+
+/// Formats the code like [format_code], reusing the top-level statements
+/// of R scripts which the formatter formatted before.
+fn format_code_incremental(
+    content: &str,
+    kind: FileKind,
+    config: &Config,
+    formatter: &mut IncrementalFormatter,
+) -> Result<String, String> {
+    match kind {
+        FileKind::R if !is_spin_script(content) => formatter.format(content, config),
+        _ => format_code(content, kind, config),
+    }
+}
+
+/// Locks the state the daemon and the language server keep between
+/// the requests. The state is never left half updated, so it is still
+/// used after a request panicked holding the lock.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Formats the code like [format_code]. Measures the stages of formatting
+/// of R scripts, too.
+fn format_code_timed(
+    content: &str,
+    kind: FileKind,
+    config: &Config,
+) -> Result<(String, Option<Timings>), String> {
+    let formatted = match kind {
+        FileKind::R if is_spin_script(content) => tergo_format_spin(content, Some(config)),
+        FileKind::R => {
+            return tergo_format_timed(content, Some(config))
+                .map(|(formatted, timings)| (formatted, Some(timings)));
+        }
+        FileKind::RMarkdown => tergo_format_rmarkdown(content, Some(config)),
+        FileKind::Quarto => tergo_format_quarto(content, Some(config)),
+        FileKind::Sweave => tergo_format_sweave(content, Some(config)),
+        FileKind::Notebook => tergo_format_notebook(content, Some(config)),
+    };
+    formatted.map(|formatted| (formatted, None))
+}
+
+/// Formats the formatted code again and fails if that changes it.
+fn check_idempotent(formatted: &str, kind: FileKind, config: &Config) -> Result<(), String> {
+    let formatted_again = format_code(formatted, kind, config)?;
+    match first_different_line(formatted, &formatted_again) {
+        None => Ok(()),
+        Some(line) => Err(format!(
+            "Formatting the formatted code changes line {line}. This is a bug in tergo; \
+             please report it with the code at https://github.com/kpagacz/tergo/issues"
+        )),
+    }
 }
 
-fn list_r_files(path: &Path) -> Vec<PathBuf> {
-    trace!("List R files in a path: {path:?}");
-    match path.read_dir() {
-        Ok(paths) => {
-            let mut r_files = vec![];
-            for path in paths.flatten() {
-                r_files.extend(list_r_files(&path.path()));
+/// Returns the syntax error in an R script.
+///
+/// The formatter's own message is a dump of the tokens, so the diagnostic
+/// is what gets reported to the users.
+fn syntax_error(content: &str, kind: FileKind) -> Option<SyntaxError> {
+    (kind == FileKind::R)
+        .then(|| find_syntax_error(content))
+        .flatten()
+}
+
+/// Formats the R script again, measuring the stages of formatting,
+/// and prints the measurements to the standard error.
+fn print_profile(name: &str, content: &str, config: &Config) {
+    match bench::format_timings(content, config) {
+        Ok(timings) => eprintln!("{name}: {timings}"),
+        Err(e) => trace!("Failed to profile {name}: {e}"),
+    }
+}
+
+/// Formats only the statements overlapping the lines, counted from 0.
+fn format_lines(content: &str, lines: &[Range<usize>], config: &Config) -> Result<String, String> {
+    let mut formatted = content.to_string();
+    // Formatting the last lines first keeps the numbers of the preceding lines valid
+    for lines in lines.iter().rev() {
+        formatted = tergo_format_range(&formatted, lines.clone(), Some(config))?;
+    }
+    Ok(formatted)
+}
+
+fn is_excluded(file: &Path, config: &Config) -> bool {
+    config
+        .exclusion_list
+        .0
+        .iter()
+        .any(|ignored_path| file.starts_with(ignored_path))
+}
+
+impl Summary {
+    fn record(&mut self, file: &Path, result: &Result<FileStatus, Error>) {
+        match result {
+            Ok(FileStatus::Changed) => self.changed += 1,
+            Ok(FileStatus::Unchanged) => self.unchanged += 1,
+            Ok(FileStatus::Skipped) => self.skipped += 1,
+            Ok(FileStatus::TooLarge) => self.too_large += 1,
+            Ok(FileStatus::Generated) => self.generated.push(file.to_path_buf()),
+            Err(_) => self.failed += 1,
+        }
+    }
+}
+
+/// Formats the files in parallel.
+///
+/// The results are logged as soon as each file is done, so their order
+/// depends on the scheduling of the threads.
+fn format_files(
+    files: Vec<PathBuf>,
+    configs: &mut ConfigResolver,
+    options: FormatOptions,
+) -> Summary {
+    // The configurations are resolved upfront, because the resolver caches
+    // them and cannot be shared between the threads
+    let files: Vec<(PathBuf, Result<Config, String>)> = files
+        .into_iter()
+        .map(|file| {
+            let config = configs.resolve(&file).cloned();
+            (file, config)
+        })
+        .collect();
+    let results: Vec<Result<FileStatus, Error>> = files
+        .par_iter()
+        .map(|(file, config)| {
+            let started = Instant::now();
+            let result = format_listed_file(file, config.as_ref(), options);
+            if let Some(report) = options.report {
+                report.record_status(file, &result);
             }
-            r_files
-        }
-        Err(_) => {
-            trace!("{path:?} is not a directory");
-            match path.extension() {
-                Some(extension) => {
-                    if extension == OsStr::new("R") || extension == OsStr::new("r") {
-                        vec![path.to_path_buf()]
-                    } else {
-                        vec![]
-                    }
-                }
-                None => {
-                    vec![]
-                }
+            if let Some(printer) = options.printer {
+                printer.file(file, &result, started.elapsed());
             }
+            result
+        })
+        .collect();
+    let mut summary = Summary::default();
+    for ((file, _), result) in files.iter().zip(&results) {
+        summary.record(file, result);
+    }
+    summary
+}
+
+fn format_listed_file(
+    file: &Path,
+    config: Result<&Config, &String>,
+    options: FormatOptions,
+) -> Result<FileStatus, Error> {
+    let config = config.map_err(|e| {
+        warn!("Failed to format {file:?}. {e}");
+        if let Some(report) = options.report {
+            report.record_error(file, e.clone(), None);
         }
+        Error::Configuration
+    })?;
+    if is_excluded(file, config) {
+        debug!("Ignoring: {file:?}");
+        return Ok(FileStatus::Skipped);
     }
+    if file.extension().is_none()
+        && !config.format_shebang_scripts.0
+        && files::has_rscript_shebang(file)
+    {
+        debug!("Skipping {file:?}: format_shebang_scripts is disabled");
+        return Ok(FileStatus::Skipped);
+    }
+    debug!("Formatting: {file:?}");
+    let result = format_file(file, config, options);
+    if let Err(e) = &result {
+        warn!("Failed to format {:?}. Error: {e:?}", file);
+        trace!("Error was: {e:?}");
+    }
+    result
 }
 
-fn format_r_files(path: &Path, config_path: &Path) {
-    let r_files = list_r_files(path);
-    let config = get_config(config_path);
-    let ignored_paths: Vec<&Path> = config.exclusion_list.0.iter().map(Path::new).collect();
-    debug!("Ignored paths: {ignored_paths:?}");
-    for file in r_files {
-        if ignored_paths
-            .iter()
-            .any(|&ignored_path| file.starts_with(ignored_path))
-        {
-            info!("Ignoring: {file:?}");
+/// Returns the files with staged changes and without unstaged changes.
+fn staged_files(root: &Path, files: Vec<PathBuf>) -> Result<Vec<PathBuf>, String> {
+    let staged: HashSet<PathBuf> = git::staged_files(root)?
+        .into_iter()
+        .map(|file| canonical(&file))
+        .collect();
+    let mut result = vec![];
+    for file in files {
+        if !staged.contains(&canonical(&file)) {
             continue;
         }
-        debug!("Formatting: {file:?}");
-        match format_file_in_place(&file, &config) {
-            Ok(_) => info!("Formatted: {:?}", &file),
-            Err(e) => {
-                warn!("Failed to format {:?}. Error: {e:?}", &file);
-                trace!("Error was: {e:?}");
-            }
+        if git::has_unstaged_changes(&file)? {
+            warn!("Skipping {file:?}: it has unstaged changes");
+            continue;
         }
+        result.push(file);
+    }
+    Ok(result)
+}
+
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
+
+fn save_cache(cache: &FormatCache, location: &Path) {
+    if let Err(e) = cache.save() {
+        warn!("Failed to save the cache to {location:?}: {e}");
     }
 }
 
-fn main() {
+fn main() -> ExitCode {
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
     }
@@ -123,8 +630,275 @@ fn main() {
         Err(err) => println!("Failed to initialize logger: {:?}", err),
     }
     let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Fmt(args)) => fmt(args),
+        Some(Command::Check(mut args)) => {
+            args.check = true;
+            fmt(args)
+        }
+        Some(Command::Config(ConfigArgs {
+            command: Some(ConfigCommand::Schema),
+            ..
+        })) => print_config_schema(),
+        Some(Command::Config(args)) => print_config(args),
+        Some(Command::Init(InitArgs {
+            directory,
+            import,
+            force,
+        })) => match init::init(&directory, import, force) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                warn!("{e}");
+                ExitCode::FAILURE
+            }
+        },
+        Some(Command::Hook(HookCommand::Install { force })) => {
+            match hook::install(Path::new("."), force) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    warn!("{e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Some(Command::Corpus(CorpusCommand::Run(CorpusRunArgs {
+            directory,
+            config,
+            output,
+        }))) => corpus::run(&directory, config, output.as_deref()),
+        Some(Command::Daemon(DaemonArgs { socket })) => {
+            daemon::run(&socket.unwrap_or_else(daemon::default_socket))
+        }
+        Some(Command::Lsp) => lsp::run(),
+        None => fmt(cli.format),
+    }
+}
+
+fn print_config_schema() -> ExitCode {
+    match serde_json::to_string_pretty(&Config::json_schema()) {
+        Ok(schema) => {
+            println!("{schema}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            warn!("Failed to serialize the schema of the configuration: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_config(args: ConfigArgs) -> ExitCode {
+    let mut configs = ConfigResolver::new(
+        args.config.as_ref().map(PathBuf::from),
+        args.overrides.to_table(),
+    );
+    let path = PathBuf::from(&args.path);
+    // The configuration of a directory is the configuration of the files inside it
+    let file = if path.is_dir() {
+        path.join(config::CONFIG_FILE_NAME)
+    } else {
+        path
+    };
+    for config_file in configs.config_files(&config::absolute_directory(&file)) {
+        info!("Using the configuration file: {config_file:?}");
+    }
+    let config = match configs.resolve(&file) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let printed = match args.format {
+        ConfigFormat::Toml => toml::to_string(config).map_err(|e| e.to_string()),
+        ConfigFormat::Json => serde_json::to_string_pretty(config)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+    };
+    match printed {
+        Ok(printed) => {
+            print!("{printed}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            warn!("Failed to serialize the configuration: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let path = PathBuf::from_str(&cli.path).unwrap();
-    let config_path = PathBuf::from_str(&cli.config).unwrap();
-    format_r_files(&path, &config_path);
+fn fmt(args: FormatArgs) -> ExitCode {
+    let path = PathBuf::from_str(&args.path).unwrap();
+    let mut configs = ConfigResolver::new(
+        args.config.as_ref().map(PathBuf::from),
+        args.overrides.to_table(),
+    );
+    let output = if args.diff {
+        Output::Diff {
+            color: args.color.should_colorize(),
+        }
+    } else if args.check || args.dry_run || args.output_format != OutputFormat::Text {
+        Output::Check
+    } else {
+        Output::InPlace
+    };
+    // Checking fails on the files which would be reformatted
+    let changes_fail = matches!(output, Output::Diff { .. } | Output::Check) && !args.dry_run;
+    if args.quiet {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    let success = if args.modified_lines_only {
+        if !path.is_file() {
+            warn!("--modified-lines-only needs a single file, but {path:?} is not a file");
+            return ExitCode::FAILURE;
+        }
+        match modified::format_modified_lines(
+            &path,
+            args.since.as_deref(),
+            args.force,
+            &mut configs,
+        ) {
+            Ok(FileStatus::Changed) => !changes_fail,
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Failed to format the modified lines of {path:?}. Error: {e:?}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if args.stdin || args.path == "-" {
+        let stdin_filename = args.stdin_filename.as_ref().map(PathBuf::from);
+        match format_stdin(
+            stdin_filename.as_deref(),
+            &mut configs,
+            output,
+            args.range.as_ref(),
+            args.skip_syntax_errors,
+            args.profile,
+        ) {
+            Ok(FileStatus::Changed) => !changes_fail,
+            Ok(FileStatus::Unchanged)
+            | Ok(FileStatus::Skipped)
+            | Ok(FileStatus::TooLarge)
+            | Ok(FileStatus::Generated) => true,
+            Err(e) => {
+                warn!("Failed to format the standard input. Error: {e:?}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        if args.range.is_some() && !path.is_file() {
+            warn!("--range needs a single file, but {path:?} is not a file");
+            return ExitCode::FAILURE;
+        }
+        let filter = match FileFilter::new(&args.include, &args.exclude) {
+            Ok(filter) if args.respect_rbuildignore => filter.respecting_rbuildignore(&path),
+            Ok(filter) => filter,
+            Err(e) => {
+                warn!("Invalid glob pattern: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let pool = match rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs.unwrap_or(0))
+            .build()
+        {
+            Ok(pool) => pool,
+            Err(e) => {
+                warn!("Failed to start the worker threads: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let cache = args
+            .cache
+            .then(|| FormatCache::load(args.cache_location.clone()));
+        let baseline = args.changed.as_deref().map(git::Baseline::new);
+        let mut files = files::list_files(&path, &filter, !args.no_ignore);
+        if let Some(baseline) = &baseline {
+            let changed: HashSet<PathBuf> = match git::changed_files(&path, baseline) {
+                Ok(changed) => changed.into_iter().map(|file| canonical(&file)).collect(),
+                Err(e) => {
+                    warn!("Failed to find the changed files: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            files.retain(|file| changed.contains(&canonical(file)));
+        }
+        if args.staged {
+            files = match staged_files(&path, files) {
+                Ok(files) => files,
+                Err(e) => {
+                    warn!("Failed to find the staged files: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+        }
+        let report = Report::default();
+        let stats = Stats::default();
+        let printer = Printer {
+            verbosity: if args.quiet {
+                Verbosity::Quiet
+            } else if args.verbose {
+                Verbosity::Verbose
+            } else {
+                Verbosity::Normal
+            },
+            color: args.color.should_colorize_stderr(),
+            output,
+        };
+        let started = Instant::now();
+        let options = FormatOptions {
+            output,
+            cache: cache.as_ref(),
+            changed_lines: baseline.as_ref().filter(|_| args.lines),
+            backup: args.backup.as_deref(),
+            range: args.range.as_ref(),
+            report: (args.output_format != OutputFormat::Text).then_some(&report),
+            printer: Some(&printer),
+            stats: args.stats.is_some().then_some(&stats),
+            check_idempotent: args.check_idempotent,
+            audit_tokens: args.audit_tokens,
+            skip_syntax_errors: args.skip_syntax_errors,
+            profile: args.profile,
+            force: args.force,
+        };
+        let summary = pool.install(|| format_files(files.clone(), &mut configs, options));
+        if args.staged && matches!(output, Output::InPlace) {
+            if let Err(e) = git::stage(&path, &files) {
+                warn!("Failed to stage the formatted files: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        if let Some(cache) = &cache {
+            save_cache(cache, &args.cache_location);
+        }
+        let success = !(changes_fail && summary.changed > 0) && summary.failed == 0;
+        printer.summary(&summary, started.elapsed(), success);
+        if let Some(slowest) = args.stats {
+            eprint!("{}", stats.render(&summary, started.elapsed(), slowest));
+        }
+        if let Some(rendered) = report.render(args.output_format, &summary) {
+            print!("{rendered}");
+        }
+        if args.watch {
+            let watched = watch::Watched {
+                root: &path,
+                filter: &filter,
+                respect_ignore_files: !args.no_ignore,
+                cache_location: &args.cache_location,
+            };
+            return match watch::watch(watched, &mut configs, options, &pool) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    warn!("Failed to watch {path:?}: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        success
+    };
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
 }
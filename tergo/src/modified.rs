@@ -0,0 +1,115 @@
+//! Formatting only the modified lines of a file, e.g. for the code review bots
+//! which must not touch the code outside of the reviewed changes.
+
+use std::{io::Read, path::Path};
+
+use log::{info, trace, warn};
+use serde_json::json;
+use tergo_lib::{check_limits, diff_edits, is_generated_file, is_spin_script};
+
+use crate::{
+    Error, FileStatus,
+    config::{self, ConfigResolver},
+    encoding,
+    files::FileKind,
+    format_lines, git, is_excluded, syntax_error,
+};
+
+/// Formats the statements of the R script overlapping its modified lines
+/// and prints the edits turning the script into the formatted code as JSON.
+///
+/// The modified lines are the lines changed since the git revision `since`,
+/// or else the new lines of the unified diff read from the standard input.
+/// The script itself is not changed.
+///
+/// The edits replace whole lines. Their `start` and `end` are the offsets
+/// of the bytes of the script, their `start_line` and `end_line` are counted
+/// from 1 and both included, so an edit inserting lines ends on the line
+/// before its start.
+pub(crate) fn format_modified_lines(
+    path: &Path,
+    since: Option<&str>,
+    force: bool,
+    configs: &mut ConfigResolver,
+) -> Result<FileStatus, Error> {
+    use Error::*;
+    let kind = FileKind::from_path(path);
+    let encoding::Decoded { content, .. } = encoding::read(path).map_err(|e| {
+        warn!("{e}");
+        ReadFileToString
+    })?;
+    if kind != FileKind::R || is_spin_script(&content) {
+        warn!("Only the modified lines of R scripts can be formatted, but {path:?} is not one");
+        return Err(Formatting);
+    }
+    let config = configs
+        .resolve(path)
+        .and_then(|config| config::with_header(config, &content))
+        .map_err(|e| {
+            warn!("{path:?}: {e}");
+            Configuration
+        })?;
+    let config = &*config;
+    let lines = match since {
+        Some(revision) => git::changed_lines(path, &git::Baseline::new(revision)).map_err(|e| {
+            warn!("{e}");
+            Git
+        })?,
+        None => {
+            let mut diff = String::new();
+            std::io::stdin().read_to_string(&mut diff).map_err(|e| {
+                trace!("Error when reading the standard input {e}");
+                ReadStdin
+            })?;
+            git::diff_lines(&diff, path)
+        }
+    };
+    let status = if is_excluded(path, config) {
+        info!("Ignoring: {path:?}");
+        FileStatus::Skipped
+    } else if !force && is_generated_file(&content, config) {
+        info!("Skipping {path:?}: the file is generated");
+        FileStatus::Generated
+    } else if let Err(e) = check_limits(&content, config) {
+        warn!("Skipping {path:?}: {e}");
+        FileStatus::TooLarge
+    } else {
+        FileStatus::Unchanged
+    };
+    let formatted = if status == FileStatus::Unchanged {
+        format_lines(&content, &lines, config).map_err(|e| {
+            trace!("Error when formatting: {e}");
+            if let Some(error) = syntax_error(&content, kind) {
+                warn!("{path:?}: {error}");
+            }
+            Formatting
+        })?
+    } else {
+        content.clone()
+    };
+    let edits: Vec<_> = diff_edits(&content, &formatted)
+        .into_iter()
+        .map(|edit| {
+            let start_line = content[..edit.range.start].matches('\n').count() + 1;
+            let end_line = start_line + content[edit.range.clone()].lines().count() - 1;
+            json!({
+                "start": edit.range.start,
+                "end": edit.range.end,
+                "start_line": start_line,
+                "end_line": end_line,
+                "new_text": edit.new_text,
+            })
+        })
+        .collect();
+    let changed = !edits.is_empty();
+    let printed = json!({ "path": path, "edits": edits });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&printed).unwrap_or_default()
+    );
+    if changed {
+        Ok(FileStatus::Changed)
+    } else {
+        Ok(status)
+    }
+}
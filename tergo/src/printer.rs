@@ -0,0 +1,113 @@
+//! The lines printed for the formatted files and the summary of the run.
+
+use std::{path::Path, time::Duration};
+
+use crate::{
+    Error, FileStatus, Output, Summary,
+    diff::{BOLD, CYAN, DIM, GREEN, RED, RESET, YELLOW},
+    stats::millis,
+};
+
+/// How much is printed about the formatted files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verbosity {
+    /// Only the failures.
+    Quiet,
+    /// The changed and the failed files, and the summary.
+    Normal,
+    /// Every file with the time spent formatting it, and the summary.
+    Verbose,
+}
+
+/// Prints the status of each formatted file and the summary of the run
+/// to the standard error, so they do not mix with the diffs and the reports.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Printer {
+    pub(crate) verbosity: Verbosity,
+    pub(crate) color: bool,
+    pub(crate) output: Output,
+}
+
+impl Printer {
+    /// Prints the status of a formatted file, e.g. `formatted R/utils.R`.
+    pub(crate) fn file(&self, path: &Path, result: &Result<FileStatus, Error>, elapsed: Duration) {
+        let (label, style) = match result {
+            Ok(FileStatus::Changed) => match self.output {
+                Output::InPlace => ("formatted", GREEN),
+                Output::Diff { .. } | Output::Check => ("would reformat", YELLOW),
+            },
+            Ok(FileStatus::Unchanged) => ("unchanged", DIM),
+            Ok(FileStatus::Skipped) => ("skipped", CYAN),
+            Ok(FileStatus::TooLarge) => ("too large", CYAN),
+            Ok(FileStatus::Generated) => ("generated", CYAN),
+            Err(_) => ("failed", RED),
+        };
+        let printed = match (self.verbosity, result) {
+            (Verbosity::Verbose, _) | (_, Err(_)) => true,
+            (Verbosity::Normal, Ok(status)) => *status == FileStatus::Changed,
+            (Verbosity::Quiet, Ok(_)) => false,
+        };
+        if !printed {
+            return;
+        }
+        let mut line = format!(
+            "{} {}",
+            self.paint(style, &format!("{label:>14}")),
+            path.display()
+        );
+        if self.verbosity == Verbosity::Verbose {
+            line.push_str(&self.paint(DIM, &format!(" ({})", millis(elapsed))));
+        }
+        // A single `eprintln!` keeps the line in one piece when formatting in parallel
+        eprintln!("{line}");
+    }
+
+    /// Prints the counts of the outcomes, the skipped generated files
+    /// and the time of the run. Nothing is printed for a `success`ful run
+    /// if quiet.
+    pub(crate) fn summary(&self, summary: &Summary, elapsed: Duration, success: bool) {
+        if self.verbosity == Verbosity::Quiet && success {
+            return;
+        }
+        let (changed, changed_style) = match self.output {
+            Output::InPlace => ("formatted", GREEN),
+            Output::Diff { .. } | Output::Check => ("would be reformatted", YELLOW),
+        };
+        let counts = [
+            (summary.changed, changed, changed_style),
+            (summary.unchanged, "unchanged", ""),
+            (summary.skipped, "skipped", CYAN),
+            (summary.too_large, "too large", CYAN),
+            (summary.generated.len(), "generated", CYAN),
+            (summary.failed, "failed", RED),
+        ];
+        let mut block = self.paint(BOLD, "Summary");
+        block.push('\n');
+        for (count, outcome, style) in counts.into_iter().filter(|(count, ..)| *count > 0) {
+            let files = if count == 1 { "file" } else { "files" };
+            block.push_str(&format!(
+                "  {count} {files} {}\n",
+                self.paint(style, outcome)
+            ));
+        }
+        if counts.iter().all(|(count, ..)| *count == 0) {
+            block.push_str("  No files found\n");
+        }
+        if !summary.generated.is_empty() {
+            block.push_str("  The generated files were skipped, pass --force to format them:\n");
+            for file in &summary.generated {
+                block.push_str(&format!("    {}\n", file.display()));
+            }
+        }
+        block.push_str(&format!("Done in {}", millis(elapsed)));
+        eprintln!("{block}");
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color && !style.is_empty() {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use similar::TextDiff;
+use tergo_lib::SyntaxError;
+
+use crate::{Error, FileStatus, Summary, diff};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// Log the results for humans.
+    Text,
+    /// Print a JSON report with the status, the diff and the errors of every file.
+    Json,
+    /// Print a SARIF 2.1.0 log, understood by code scanning tools.
+    Sarif,
+}
+
+const FORMATTING_RULE: &str = "formatting";
+const SYNTAX_ERROR_RULE: &str = "syntax-error";
+
+#[derive(Debug, Default)]
+struct FileReport {
+    status: &'static str,
+    diff: Option<String>,
+    /// The ranges of the lines that change, counted from 1, both ends included.
+    changes: Vec<(usize, usize)>,
+    error: Option<FileError>,
+}
+
+#[derive(Debug)]
+struct FileError {
+    message: String,
+    location: Option<SyntaxError>,
+}
+
+/// Collects the results of checking the files, so they can be printed
+/// together once all the files are checked.
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    files: Mutex<BTreeMap<PathBuf, FileReport>>,
+}
+
+impl Report {
+    pub(crate) fn record_status(&self, path: &Path, result: &Result<FileStatus, Error>) {
+        let status = match result {
+            Ok(FileStatus::Changed) => "changed",
+            Ok(FileStatus::Unchanged) => "unchanged",
+            Ok(FileStatus::Skipped) => "skipped",
+            Ok(FileStatus::TooLarge) => "too-large",
+            Ok(FileStatus::Generated) => "generated",
+            Err(_) => "failed",
+        };
+        self.update(path, |file| file.status = status);
+    }
+
+    pub(crate) fn record_changes(&self, path: &Path, original: &str, formatted: &str) {
+        let diff = diff::unified_diff(&path.to_string_lossy(), original, formatted, false);
+        let changes = TextDiff::from_lines(original, formatted)
+            .grouped_ops(0)
+            .iter()
+            .filter_map(|group| {
+                let start = group.first()?.old_range().start;
+                let end = group.last()?.old_range().end;
+                // Insertions have no lines in the original, so they point at the next line
+                Some((start + 1, end.max(start + 1)))
+            })
+            .collect();
+        self.update(path, |file| {
+            file.diff = Some(diff);
+            file.changes = changes;
+        });
+    }
+
+    pub(crate) fn record_error(&self, path: &Path, message: String, location: Option<SyntaxError>) {
+        self.update(path, |file| {
+            file.error = Some(FileError { message, location })
+        });
+    }
+
+    fn update(&self, path: &Path, update: impl FnOnce(&mut FileReport)) {
+        let mut files = self.files.lock().unwrap();
+        update(files.entry(path.to_path_buf()).or_default());
+    }
+
+    /// Renders the report in the format. Returns `None` for the text format,
+    /// which is logged while formatting.
+    pub(crate) fn render(&self, format: OutputFormat, summary: &Summary) -> Option<String> {
+        let report = match format {
+            OutputFormat::Text => return None,
+            OutputFormat::Json => self.to_json(summary),
+            OutputFormat::Sarif => self.to_sarif(),
+        };
+        Some(serde_json::to_string_pretty(&report).unwrap_or_default() + "\n")
+    }
+
+    fn to_json(&self, summary: &Summary) -> Value {
+        let files = self.files.lock().unwrap();
+        let files: Vec<Value> = files
+            .iter()
+            .map(|(path, file)| {
+                let mut entry = json!({ "path": path, "status": file.status });
+                if let Some(diff) = &file.diff {
+                    entry["diff"] = json!(diff);
+                }
+                if let Some(error) = &file.error {
+                    entry["error"] = json!({
+                        "message": error.message,
+                        "line": error.location.as_ref().map(|location| location.line),
+                        "column": error.location.as_ref().map(|location| location.column),
+                        "expected": error.location.as_ref().map(|location| &location.expected),
+                    });
+                }
+                entry
+            })
+            .collect();
+        json!({
+            "files": files,
+            "summary": {
+                "changed": summary.changed,
+                "unchanged": summary.unchanged,
+                "skipped": summary.skipped,
+                "too_large": summary.too_large,
+                "generated": summary.generated.len(),
+                "failed": summary.failed,
+            },
+        })
+    }
+
+    fn to_sarif(&self) -> Value {
+        let files = self.files.lock().unwrap();
+        let mut results = vec![];
+        for (path, file) in files.iter() {
+            let uri = artifact_uri(path);
+            for (start, end) in &file.changes {
+                results.push(json!({
+                    "ruleId": FORMATTING_RULE,
+                    "level": "warning",
+                    "message": { "text": "The code is not formatted." },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": { "startLine": start, "endLine": end },
+                        },
+                    }],
+                }));
+            }
+            if let Some(error) = &file.error {
+                let mut location = json!({ "artifactLocation": { "uri": uri } });
+                if let Some(position) = &error.location {
+                    location["region"] = json!({
+                        "startLine": position.line,
+                        "startColumn": position.column,
+                    });
+                }
+                results.push(json!({
+                    "ruleId": SYNTAX_ERROR_RULE,
+                    "level": "error",
+                    "message": { "text": error.message },
+                    "locations": [{ "physicalLocation": location }],
+                }));
+            }
+        }
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "tergo",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": "https://github.com/kpagacz/tergo",
+                        "rules": [
+                            {
+                                "id": FORMATTING_RULE,
+                                "shortDescription": { "text": "The code is not formatted." },
+                            },
+                            {
+                                "id": SYNTAX_ERROR_RULE,
+                                "shortDescription": { "text": "The code could not be formatted." },
+                            },
+                        ],
+                    },
+                },
+                "results": results,
+            }],
+        })
+    }
+}
+
+/// Returns the path as a relative URI reference, as SARIF expects.
+fn artifact_uri(path: &Path) -> String {
+    let uri = path.to_string_lossy().replace('\\', "/");
+    uri.strip_prefix("./").unwrap_or(&uri).to_string()
+}
@@ -0,0 +1,98 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use tergo_lib::Timings;
+
+use crate::Summary;
+
+/// The number of the slowest files listed if `--stats` has no value.
+pub(crate) const DEFAULT_SLOWEST_FILES: &str = "5";
+
+#[derive(Debug)]
+struct FileStats {
+    path: PathBuf,
+    lines: usize,
+    elapsed: Duration,
+    /// Only known for R scripts.
+    timings: Option<Timings>,
+}
+
+/// Collects the sizes and the formatting times of the files.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    files: Mutex<Vec<FileStats>>,
+}
+
+impl Stats {
+    pub(crate) fn record(
+        &self,
+        path: &Path,
+        content: &str,
+        elapsed: Duration,
+        timings: Option<Timings>,
+    ) {
+        self.files.lock().unwrap().push(FileStats {
+            path: path.to_path_buf(),
+            lines: content.lines().count(),
+            elapsed,
+            timings,
+        });
+    }
+
+    /// Renders the statistics of the run, listing the `slowest` files
+    /// that took the longest to format.
+    pub(crate) fn render(&self, summary: &Summary, wall_time: Duration, slowest: usize) -> String {
+        let mut files = self.files.lock().unwrap();
+        let scanned = summary.changed
+            + summary.unchanged
+            + summary.skipped
+            + summary.too_large
+            + summary.generated.len()
+            + summary.failed;
+        let lines: usize = files.iter().map(|file| file.lines).sum();
+        let elapsed: Duration = files.iter().map(|file| file.elapsed).sum();
+        let parsing: Duration = files
+            .iter()
+            .filter_map(|file| file.timings)
+            .map(|timings| timings.parsing)
+            .sum();
+        let layout: Duration = files
+            .iter()
+            .filter_map(|file| file.timings)
+            .map(|timings| timings.layout)
+            .sum();
+
+        let mut rendered = format!("Files scanned: {scanned} ({summary})\n");
+        rendered.push_str(&format!("Lines formatted: {lines}\n"));
+        rendered.push_str(&format!(
+            "Time: {} wall clock, {} formatting across all threads\n",
+            millis(wall_time),
+            millis(elapsed)
+        ));
+        rendered.push_str(&format!(
+            "R scripts: {} parsing, {} layout\n",
+            millis(parsing),
+            millis(layout)
+        ));
+        files.sort_by_key(|file| std::cmp::Reverse(file.elapsed));
+        if slowest > 0 && !files.is_empty() {
+            rendered.push_str("Slowest files:\n");
+            for file in files.iter().take(slowest) {
+                rendered.push_str(&format!(
+                    "  {:>10}  {:?} ({} lines)\n",
+                    millis(file.elapsed),
+                    file.path,
+                    file.lines
+                ));
+            }
+        }
+        rendered
+    }
+}
+
+pub(crate) fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,114 @@
+use clap::ValueEnum;
+use log::warn;
+
+/// The style the configuration starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Style {
+    /// The defaults of `tergo`.
+    Tergo,
+    /// The style closest to `styler::tidyverse_style()`.
+    Styler,
+}
+
+/// The line length of the tidyverse style guide, which `styler` follows.
+const TIDYVERSE_LINE_LENGTH: i64 = 80;
+
+/// Returns the configuration values of the style.
+pub(crate) fn style_table(style: Style) -> toml::Table {
+    let mut table = toml::Table::new();
+    if style == Style::Styler {
+        table.insert("line_length".to_string(), TIDYVERSE_LINE_LENGTH.into());
+    }
+    table
+}
+
+/// Translates the arguments of `styler::tidyverse_style()`,
+/// e.g. `indent_by = 4, strict = FALSE`, into configuration values.
+///
+/// The arguments without a counterpart in `tergo` are reported and ignored.
+pub(crate) fn translate_arguments(arguments: &str) -> toml::Table {
+    let mut table = toml::Table::new();
+    for argument in arguments
+        .split(',')
+        .filter(|argument| !argument.trim().is_empty())
+    {
+        let Some((name, value)) = argument.split_once('=') else {
+            warn!("Ignoring the styler argument {argument:?}: expected name = value");
+            continue;
+        };
+        let (name, value) = (name.trim(), unwrap_value(value.trim()));
+        match name {
+            "indent_by" => match value.strip_suffix('L').unwrap_or(value).parse::<i64>() {
+                Ok(indent) => {
+                    table.insert("indent".to_string(), indent.into());
+                }
+                Err(_) => {
+                    warn!("Ignoring the styler argument indent_by: {value:?} is not a number")
+                }
+            },
+            "scope" if matches!(value, "tokens" | "line_breaks") => {}
+            "scope" => warn!(
+                "Ignoring the styler argument scope = {value:?}: tergo always styles the whole code"
+            ),
+            "strict" if matches!(value, "TRUE" | "T") => {}
+            "strict" => warn!(
+                "Ignoring the styler argument strict = {value}: tergo always applies the rules strictly"
+            ),
+            _ => warn!("Ignoring the styler argument {name}: tergo has no equivalent option"),
+        }
+    }
+    table
+}
+
+/// Strips the quotes and the `I()` or `c()` wrapping a single value.
+fn unwrap_value(value: &str) -> &str {
+    let value = ["I(", "c("]
+        .iter()
+        .find_map(|call| value.strip_prefix(call)?.strip_suffix(')'))
+        .unwrap_or(value)
+        .trim();
+    value.trim_matches(|c| c == '"' || c == '\'')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indent_is_translated_from_integers_and_doubles() {
+        for arguments in [
+            "indent_by = 4",
+            "indent_by = 4L",
+            "indent_by=I(4L)",
+            "indent_by = c(4)",
+        ] {
+            assert_eq!(
+                translate_arguments(arguments).get("indent"),
+                Some(&4.into()),
+                "{arguments}"
+            );
+        }
+        assert!(translate_arguments("indent_by = four").is_empty());
+        assert!(translate_arguments("indent_by = 4.5").is_empty());
+    }
+
+    #[test]
+    fn values_are_unwrapped_from_the_calls_and_the_quotes() {
+        assert_eq!(unwrap_value("I(\"tokens\")"), "tokens");
+        assert_eq!(unwrap_value("c('line_breaks')"), "line_breaks");
+        assert_eq!(unwrap_value("I( 2L )"), "2L");
+        assert_eq!(unwrap_value("TRUE"), "TRUE");
+        assert_eq!(unwrap_value("list(2)"), "list(2)");
+    }
+
+    #[test]
+    fn arguments_without_an_equivalent_are_ignored() {
+        let table = translate_arguments(
+            "scope = I(\"tokens\"), strict = FALSE, math_token_spacing = NULL, indent_by = 4L, reindention",
+        );
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get("indent"), Some(&4.into()));
+        assert!(translate_arguments("").is_empty());
+        assert!(translate_arguments("scope = \"spaces\", strict = TRUE").is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::{debug, info, warn};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+use rayon::ThreadPool;
+
+use crate::{
+    FormatOptions,
+    config::{CONFIG_FILE_NAME, ConfigResolver},
+    files::{self, FileFilter},
+    format_files, save_cache,
+};
+
+/// How long to wait for the changes to settle before formatting.
+/// Editors often write a file in several steps.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The files picked up by the watch.
+pub(crate) struct Watched<'a> {
+    pub(crate) root: &'a Path,
+    pub(crate) filter: &'a FileFilter,
+    pub(crate) respect_ignore_files: bool,
+    pub(crate) cache_location: &'a Path,
+}
+
+/// Formats the files under the root whenever they change. Runs until
+/// the watcher fails or the process is interrupted.
+///
+/// The watcher reports reading the files, too, so the files are formatted
+/// only if their modification time changed. Otherwise, formatting a file
+/// would trigger formatting it again.
+pub(crate) fn watch(
+    watched: Watched<'_>,
+    configs: &mut ConfigResolver,
+    options: FormatOptions,
+    pool: &ThreadPool,
+) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, sender).map_err(|e| e.to_string())?;
+    debouncer
+        .watcher()
+        .watch(watched.root, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+    info!("Watching {:?} for changes", watched.root);
+    let mut modified: HashMap<PathBuf, SystemTime> = HashMap::new();
+
+    for events in receiver {
+        let events = match events {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Failed to watch the files: {e}");
+                continue;
+            }
+        };
+        let changed: HashSet<PathBuf> = events
+            .into_iter()
+            .filter_map(|event| event.path.canonicalize().ok())
+            .collect();
+        if changed.iter().any(|path| {
+            path.file_name()
+                .is_some_and(|name| name == CONFIG_FILE_NAME)
+        }) {
+            debug!("A configuration file changed");
+            configs.clear();
+        }
+        // Listing the files again applies the filters and the ignore files
+        // to the changed paths
+        let files: Vec<PathBuf> =
+            files::list_files(watched.root, watched.filter, watched.respect_ignore_files)
+                .into_iter()
+                .filter(|file| {
+                    file.canonicalize()
+                        .is_ok_and(|file| changed.contains(&file))
+                })
+                .filter(|file| {
+                    let last_modified = modification_time(file);
+                    last_modified.is_none() || modified.get(file) != last_modified.as_ref()
+                })
+                .collect();
+        if files.is_empty() {
+            continue;
+        }
+        let started = Instant::now();
+        let summary = pool.install(|| format_files(files.clone(), configs, options));
+        for file in files {
+            if let Some(last_modified) = modification_time(&file) {
+                modified.insert(file, last_modified);
+            }
+        }
+        if let Some(cache) = options.cache {
+            save_cache(cache, watched.cache_location);
+        }
+        if let Some(printer) = options.printer {
+            printer.summary(&summary, started.elapsed(), true);
+        }
+    }
+    Ok(())
+}
+
+fn modification_time(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
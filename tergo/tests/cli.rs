@@ -0,0 +1,82 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Creates an empty directory for a test, removing the one left by a previous run.
+fn test_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("tergo-cli-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn tergo(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tergo"))
+        .current_dir(directory)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn fails_on_a_file_which_does_not_parse() {
+    let directory = test_directory("unparsable");
+    std::fs::write(directory.join("formatted.R"), "x <- 1\n").unwrap();
+    std::fs::write(directory.join("broken.R"), "x <- )\n").unwrap();
+
+    assert!(!tergo(&directory, &["check", "."]).status.success());
+    assert!(!tergo(&directory, &["fmt", "."]).status.success());
+    assert_eq!(
+        std::fs::read_to_string(directory.join("broken.R")).unwrap(),
+        "x <- )\n"
+    );
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn succeeds_on_formatted_files() {
+    let directory = test_directory("formatted");
+    std::fs::write(directory.join("formatted.R"), "x <- 1\n").unwrap();
+
+    assert!(tergo(&directory, &["check", "."]).status.success());
+    assert!(tergo(&directory, &["fmt", "."]).status.success());
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn diff_shows_a_change_of_the_line_ending() {
+    let directory = test_directory("line-ending");
+    std::fs::write(directory.join("crlf.R"), "a <- 1\r\nb <- 2\n").unwrap();
+
+    let output = tergo(&directory, &["fmt", "--diff", "crlf.R"]);
+    let diff = String::from_utf8(output.stdout).unwrap();
+    assert!(diff.contains("-a <- 1␍\n+a <- 1\n b <- 2\n"), "{diff}");
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn formatting_a_symbolic_link_formats_the_file_it_points_to() {
+    let directory = test_directory("symlink");
+    std::fs::create_dir(directory.join("code")).unwrap();
+    std::fs::write(directory.join("code/target.R"), "x<-1\n").unwrap();
+    std::os::unix::fs::symlink("code/target.R", directory.join("link.R")).unwrap();
+
+    assert!(tergo(&directory, &["fmt", "link.R"]).status.success());
+    assert!(
+        std::fs::symlink_metadata(directory.join("link.R"))
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(
+        std::fs::read_to_string(directory.join("code/target.R")).unwrap(),
+        "x <- 1\n"
+    );
+    assert_eq!(
+        std::fs::read_dir(directory.join("code")).unwrap().count(),
+        1
+    );
+    std::fs::remove_dir_all(directory).unwrap();
+}
@@ -1,25 +1,27 @@
+use crate::comments::{LayoutDirective, is_commented_code, layout_directive};
 use crate::format::CommonProperties;
 use crate::{
-    config::{FormattingConfig, FunctionLineBreaks},
+    config::{FormattingConfig, FunctionLineBreaks, TrailingComma},
     format::DocAlgebra,
 };
 
 use parser::ast::{Arg, Args, Delimiter, Expression, IfConditional, TermExpr};
 use tokenizer::tokens::CommentedToken;
 
+use crate::format::DocRc;
 use crate::format::{Doc, InlineCommentPosition, ShouldBreak};
-use std::{ops::Deref, rc::Rc};
+use std::ops::Deref;
 use tokenizer::Token;
 
 pub(crate) trait Code {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc>;
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc>;
 }
 
 impl<T> Code for Option<T>
 where
     T: Code,
 {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc> {
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc> {
         match self {
             Some(inner) => inner.to_docs(config, doc_ref),
             None => text!(""),
@@ -32,7 +34,7 @@ pub(crate) trait CodeWithoutLeadingComments {
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> Rc<Doc>;
+    ) -> DocRc<Doc>;
 }
 
 impl<T> CodeWithoutLeadingComments for Option<T>
@@ -43,10 +45,10 @@ where
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> Rc<Doc> {
+    ) -> DocRc<Doc> {
         match self {
             Some(code) => code.to_docs_without_leading_comments(config, doc_ref),
-            None => Rc::new(Doc::Nil),
+            None => DocRc::new(Doc::Nil),
         }
     }
 }
@@ -60,7 +62,7 @@ pub(crate) trait DocAlgebraWithSeparateComments {
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> (Rc<Doc>, Option<Rc<Doc>>);
+    ) -> (DocRc<Doc>, Option<DocRc<Doc>>);
 }
 
 impl<T> DocAlgebraWithSeparateComments for Option<T>
@@ -71,10 +73,10 @@ where
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> (Rc<Doc>, Option<Rc<Doc>>) {
+    ) -> (DocRc<Doc>, Option<DocRc<Doc>>) {
         match self {
             Some(code) => code.to_docs_with_separate_comments(config, doc_ref),
-            None => (Rc::new(Doc::Nil), None),
+            None => (DocRc::new(Doc::Nil), None),
         }
     }
 }
@@ -82,38 +84,37 @@ where
 // Macro that creates a Doc::Break
 macro_rules! nl {
     ($txt:expr) => {
-        Rc::new(Doc::Break($txt))
+        DocRc::new(Doc::Break($txt))
     };
 }
-pub(crate) use nl;
 
 // Macro that creates a Doc::Text
 macro_rules! text {
     ($txt:expr) => {{
         let txt: &str = $txt;
-        Rc::new(Doc::Text(
-            Rc::from(txt),
+        DocRc::new(Doc::Text(
+            $crate::intern::intern(txt),
             txt.len(),
-            CommonProperties(InlineCommentPosition::No, 0),
+            CommonProperties(InlineCommentPosition::No, 0, Some(txt.len())),
         ))
     }};
     ($txt:expr, $size:expr) => {{
         let txt: &str = $txt;
         let size: usize = $size;
-        Rc::new(Doc::Text(
-            Rc::from(txt),
+        DocRc::new(Doc::Text(
+            $crate::intern::intern(txt),
             size,
-            CommonProperties(InlineCommentPosition::No, 0),
+            CommonProperties(InlineCommentPosition::No, 0, Some(size)),
         ))
     }};
     ($txt:expr, $size:expr, $comment_position:expr) => {{
         let txt: &str = $txt;
         let size: usize = $size;
         let position: InlineCommentPosition = $comment_position;
-        Rc::new(Doc::Text(
-            Rc::from(txt),
+        DocRc::new(Doc::Text(
+            $crate::intern::intern(txt),
             size,
-            CommonProperties(position, 0),
+            CommonProperties(position, 0, Some(size)),
         ))
     }};
 }
@@ -121,13 +122,19 @@ pub(crate) use text;
 
 // Macro that creates a HardBreak
 macro_rules! hardbreak {
-    () => {{ Rc::new(Doc::HardBreak) }};
+    () => {{ DocRc::new(Doc::HardBreak) }};
 }
-pub(crate) use hardbreak;
 
 impl Code for Token<'_> {
-    fn to_docs(&self, _: &impl FormattingConfig, _: &mut usize) -> Rc<Doc> {
+    fn to_docs(&self, _: &impl FormattingConfig, _: &mut usize) -> DocRc<Doc> {
         match self {
+            Token::Symbol(s) | Token::Literal(s) if s.contains('\n') => {
+                // The lines of a string are printed as they are, without indentation
+                s.split('\n')
+                    .map(|line| text!(line))
+                    .reduce(|first, second| first.cons(DocRc::new(Doc::LiteralLine)).cons(second))
+                    .expect("split returns at least one line")
+            }
             Token::Symbol(s) | Token::Literal(s) => text!(*s),
             Token::Semicolon => text!(";"),
             Token::Newline => text!("\n"),
@@ -188,7 +195,7 @@ impl Code for Token<'_> {
 }
 
 impl Code for CommentedToken<'_> {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc> {
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc> {
         match (&self.leading_comments, self.inline_comment) {
             (None, None) => self.token.to_docs(config, doc_ref),
             (None, Some(inline_comment)) => self
@@ -221,7 +228,7 @@ impl Code for CommentedToken<'_> {
                             .to_docs(config, doc_ref)
                             .to_group(ShouldBreak::No, doc_ref),
                     )
-                    .to_group(ShouldBreak::Yes, doc_ref)
+                    .to_group(leading_comments_break(self), doc_ref)
             }
             (Some(leading_comments), Some(inline_comment)) => {
                 let mut leading_comments_it = leading_comments.iter();
@@ -247,12 +254,31 @@ impl Code for CommentedToken<'_> {
     }
 }
 
+/// How the group of the token with its leading comments breaks.
+///
+/// The comments go on their own lines, but may follow the code before
+/// them if it does not break, e.g. `f(a, # b\n  c)`. The commented-out
+/// code breaks the code around it instead, so it stays on its own line
+/// and cannot be mistaken for a comment on the code before it.
+fn leading_comments_break(token: &CommentedToken) -> ShouldBreak {
+    let has_commented_code = token
+        .leading_comments
+        .iter()
+        .flatten()
+        .any(|comment| is_commented_code(comment));
+    if has_commented_code {
+        ShouldBreak::Propagate
+    } else {
+        ShouldBreak::Yes
+    }
+}
+
 impl CodeWithoutLeadingComments for CommentedToken<'_> {
     fn to_docs_without_leading_comments(
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> Rc<Doc> {
+    ) -> DocRc<Doc> {
         match self.inline_comment {
             None => self.token.to_docs(config, doc_ref),
             Some(inline_comment) => self
@@ -269,7 +295,7 @@ impl DocAlgebraWithSeparateComments for CommentedToken<'_> {
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> (Rc<Doc>, Option<Rc<Doc>>) {
+    ) -> (DocRc<Doc>, Option<DocRc<Doc>>) {
         match (&self.leading_comments, self.inline_comment) {
             (None, None) => (self.token.to_docs(config, doc_ref), None),
             (None, Some(inline_comment)) => (
@@ -301,7 +327,7 @@ impl DocAlgebraWithSeparateComments for CommentedToken<'_> {
                                 .to_docs(config, doc_ref)
                                 .to_group(ShouldBreak::No, doc_ref),
                         )
-                        .to_group(ShouldBreak::Yes, doc_ref),
+                        .to_group(leading_comments_break(self), doc_ref),
                     None,
                 )
             }
@@ -326,7 +352,7 @@ impl DocAlgebraWithSeparateComments for CommentedToken<'_> {
 }
 
 impl Code for Delimiter<'_> {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc> {
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc> {
         match self {
             Delimiter::Paren(single) | Delimiter::SingleBracket(single) => {
                 single.to_docs(config, doc_ref)
@@ -343,7 +369,7 @@ impl DocAlgebraWithSeparateComments for Delimiter<'_> {
         &self,
         config: &impl FormattingConfig,
         doc_ref: &mut usize,
-    ) -> (Rc<Doc>, Option<Rc<Doc>>) {
+    ) -> (DocRc<Doc>, Option<DocRc<Doc>>) {
         match self {
             Delimiter::Paren(token) | Delimiter::SingleBracket(token) => {
                 token.to_docs_with_separate_comments(config, doc_ref)
@@ -360,26 +386,26 @@ impl DocAlgebraWithSeparateComments for Delimiter<'_> {
 /// Returns a Doc::Group
 fn join_docs<I, F>(
     docs: I,
-    separator: Rc<Doc>,
+    separator: DocRc<Doc>,
     should_break: ShouldBreak,
     _config: &F,
     doc_ref: &mut usize,
-) -> Rc<Doc>
+) -> DocRc<Doc>
 where
-    I: IntoIterator<Item = Rc<Doc>>,
+    I: IntoIterator<Item = DocRc<Doc>>,
     F: FormattingConfig,
 {
     join_docs_ungroupped(docs, separator, _config).to_group(should_break, doc_ref)
 }
 
 /// Returns a Doc::Cons
-fn join_docs_ungroupped<I, F>(docs: I, separator: Rc<Doc>, _config: &F) -> Rc<Doc>
+fn join_docs_ungroupped<I, F>(docs: I, separator: DocRc<Doc>, _config: &F) -> DocRc<Doc>
 where
-    I: IntoIterator<Item = Rc<Doc>>,
+    I: IntoIterator<Item = DocRc<Doc>>,
     F: FormattingConfig,
 {
     let mut docs = docs.into_iter();
-    let mut res = Rc::new(Doc::Nil);
+    let mut res = DocRc::new(Doc::Nil);
 
     if let Some(first_doc) = docs.next() {
         if !matches!(*first_doc, Doc::Nil) {
@@ -397,7 +423,7 @@ where
 }
 
 impl Code for Expression<'_> {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc> {
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc> {
         match self {
             Expression::Symbol(token)
             | Expression::Literal(token)
@@ -411,73 +437,20 @@ impl Code for Expression<'_> {
                     pre_delimiters: Some(pre_delim),
                     term,
                     post_delimiters: Some(post_delim),
-                } if config.embracing_op_no_nl()
-                    && matches!(pre_delim.token, Token::LBrace)
-                    && term.len() == 1
-                    && matches!(term[0], Expression::Term { .. }) =>
-                {
-                    match &term[0] {
-                        Expression::Term(inner_term_expr) => {
-                            if inner_term_expr
-                                .pre_delimiters
-                                .is_some_and(|delim| matches!(delim.token, Token::LBrace))
-                            {
-                                let inner_docs: Vec<_> = inner_term_expr
-                                    .term
-                                    .iter()
-                                    .map(|t| t.to_docs(config, doc_ref))
-                                    .collect();
-                                let inner_docs = join_docs(
-                                    inner_docs,
-                                    Rc::new(Doc::Nil),
-                                    ShouldBreak::No,
-                                    config,
-                                    doc_ref,
-                                );
-                                pre_delim
-                                    .to_docs(config, doc_ref)
-                                    .cons(
-                                        inner_term_expr
-                                            .pre_delimiters
-                                            .as_ref()
-                                            .expect(
-                                                "Already checked this pre delimiter to be an l \
-                                                 brace",
-                                            )
-                                            .to_docs(config, doc_ref),
-                                    )
-                                    .cons(text!(" "))
-                                    .cons(inner_docs)
-                                    .cons(text!(" "))
-                                    .cons(
-                                        inner_term_expr
-                                            .post_delimiters
-                                            .as_ref()
-                                            .unwrap()
-                                            .to_docs(config, doc_ref),
-                                    )
-                                    .cons(post_delim.to_docs(config, doc_ref))
-                                    .to_group(ShouldBreak::No, doc_ref)
-                            } else {
-                                let docs: Vec<_> =
-                                    term.iter().map(|t| t.to_docs(config, doc_ref)).collect();
-                                let inner = join_docs(
-                                    docs,
-                                    Rc::new(Doc::Nil),
-                                    ShouldBreak::No,
-                                    config,
-                                    doc_ref,
-                                );
-                                pre_delim
-                                    .to_docs(config, doc_ref)
-                                    .cons(nl!(" ").cons(inner).nest(config.indent()))
-                                    .cons(nl!(" "))
-                                    .cons(post_delim.to_docs(config, doc_ref))
-                                    .to_group(ShouldBreak::Propagate, doc_ref)
-                            }
-                        }
-                        _ => unreachable!("Already checked that term[0] is a Term"),
-                    }
+                } if config.embracing_op_no_nl() && is_term_embracing_op(term_expr) => {
+                    let Expression::Term(inner_term_expr) = &term[0] else {
+                        unreachable!("Already checked that term[0] is a Term")
+                    };
+                    let inner_docs = inner_term_expr.term[0].to_docs(config, doc_ref);
+                    pre_delim
+                        .to_docs(config, doc_ref)
+                        .cons(text!("{"))
+                        .cons(text!(" "))
+                        .cons(inner_docs)
+                        .cons(text!(" "))
+                        .cons(text!("}"))
+                        .cons(post_delim.to_docs(config, doc_ref))
+                        .embrace()
                 }
                 // Normal { }
                 TermExpr {
@@ -486,23 +459,12 @@ impl Code for Expression<'_> {
                     post_delimiters: Some(post_delim),
                 } if matches!(pre_delim.token, Token::LBrace) => {
                     if term.is_empty() {
-                        pre_delim
-                            .to_docs(config, doc_ref)
-                            .cons(nl!(""))
-                            .nest(config.indent())
-                            .cons(post_delim.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref)
+                        empty_block_to_docs(pre_delim, post_delim, config, doc_ref)
                     } else {
-                        let docs = term
-                            .iter()
-                            .map(|t| {
-                                t.to_docs(config, doc_ref)
-                                    .to_group(ShouldBreak::No, doc_ref)
-                            })
-                            .collect::<Vec<_>>();
+                        let docs = statements_to_docs(term, false, config, doc_ref);
                         let inner = join_docs(
                             docs,
-                            Rc::new(Doc::Nil),
+                            DocRc::new(Doc::Nil),
                             ShouldBreak::Propagate,
                             config,
                             doc_ref,
@@ -522,16 +484,10 @@ impl Code for Expression<'_> {
                     term,
                     post_delimiters: None,
                 } => {
-                    let docs = term
-                        .iter()
-                        .map(|t| {
-                            t.to_docs(config, doc_ref)
-                                .to_group(ShouldBreak::No, doc_ref)
-                        })
-                        .collect::<Vec<_>>();
+                    let docs = statements_to_docs(term, true, config, doc_ref);
                     join_docs(
                         docs,
-                        Rc::new(Doc::Nil),
+                        DocRc::new(Doc::Nil),
                         ShouldBreak::Propagate,
                         config,
                         doc_ref,
@@ -558,7 +514,7 @@ impl Code for Expression<'_> {
                             .map(|t| t.to_docs(config, doc_ref))
                             .collect::<Vec<_>>();
                         let inner =
-                            join_docs(docs, Rc::new(Doc::Nil), ShouldBreak::No, config, doc_ref);
+                            join_docs(docs, DocRc::new(Doc::Nil), ShouldBreak::No, config, doc_ref);
                         pre_delim
                             .to_docs(config, doc_ref)
                             .cons(inner)
@@ -570,7 +526,7 @@ impl Code for Expression<'_> {
                             .map(|t| t.to_docs(config, doc_ref))
                             .collect::<Vec<_>>();
                         let inner =
-                            join_docs(docs, Rc::new(Doc::Nil), ShouldBreak::No, config, doc_ref);
+                            join_docs(docs, DocRc::new(Doc::Nil), ShouldBreak::No, config, doc_ref);
                         delimited_content_to_docs(
                             pre_delim,
                             inner,
@@ -586,15 +542,34 @@ impl Code for Expression<'_> {
             Expression::Unary(op, expr) => op
                 .to_docs(config, doc_ref)
                 .cons(expr.to_docs(config, doc_ref)),
+            Expression::Bop(..) | Expression::MultiBop(..)
+                if is_long_access_chain(self, config) =>
+            {
+                access_chain_to_docs(self, config, doc_ref)
+            }
+            Expression::Bop(tilde, lhs, rhs)
+                if tilde.token == Token::Tilde && is_formula_terms(rhs) =>
+            {
+                formula_to_docs(lhs, tilde, rhs, config, doc_ref)
+            }
+            Expression::MultiBop(lhs, other)
+                if other.len() == 1
+                    && other[0].0.token == Token::Tilde
+                    && is_formula_terms(&other[0].1) =>
+            {
+                let (tilde, rhs) = &other[0];
+                formula_to_docs(lhs, tilde, rhs, config, doc_ref)
+            }
             Expression::Bop(op, lhs, rhs) => match op.token {
-                Token::OldAssign | Token::LAssign | Token::ColonAssign | Token::SuperAssign
-                    if !config.allow_nl_after_assignment() =>
-                {
-                    lhs.to_docs(config, doc_ref)
+                _ if keeps_rhs_on_line(op, config) => {
+                    assignment_target_to_docs(lhs, op, config, doc_ref)
                         .cons(text!(" "))
                         .cons(op.to_docs(config, doc_ref))
-                        .cons(text!(" "))
-                        .cons(rhs.to_docs(config, doc_ref).nest(config.indent()))
+                        .cons(assigned_value_to_docs(
+                            op,
+                            rhs.to_docs(config, doc_ref).nest(config.indent()),
+                            config,
+                        ))
                 }
                 Token::RAssign
                 | Token::Equal
@@ -614,16 +589,14 @@ impl Code for Expression<'_> {
                 | Token::Pipe
                 | Token::Modulo
                 | Token::Tilde
-                | Token::Special(_) => lhs
-                    .to_docs(config, doc_ref)
-                    .cons(text!(" "))
-                    .cons(op.to_docs(config, doc_ref))
-                    .to_group(ShouldBreak::No, doc_ref)
-                    .cons(
-                        nl!(" ")
-                            .cons(rhs.to_docs(config, doc_ref))
-                            .nest(config.indent()),
-                    ),
+                | Token::Special(_) => {
+                    let lhs = lhs
+                        .to_docs(config, doc_ref)
+                        .cons(text!(" "))
+                        .cons(op.to_docs(config, doc_ref))
+                        .to_group(ShouldBreak::No, doc_ref);
+                    broken_operation_to_docs(lhs, rhs.to_docs(config, doc_ref), op, config)
+                }
                 Token::Dollar
                 | Token::NsGet
                 | Token::NsGetInt
@@ -640,6 +613,13 @@ impl Code for Expression<'_> {
                     &op
                 ),
             },
+            Expression::Formula(tilde, term) if is_formula_terms(term) => tilde
+                .to_docs(config, doc_ref)
+                .cons(text!(" "))
+                .cons(
+                    formula_terms_to_docs(term, config, doc_ref).nest(config.continuation_indent()),
+                )
+                .to_group(ShouldBreak::No, doc_ref),
             Expression::Formula(tilde, term) => tilde
                 .to_docs(config, doc_ref)
                 .cons(if matches!(**term, Expression::Symbol(_)) {
@@ -648,7 +628,7 @@ impl Code for Expression<'_> {
                     text!(" ")
                 })
                 .cons(term.to_docs(config, doc_ref)),
-            Expression::Newline(_) => Rc::new(Doc::Break("\n")),
+            Expression::Newline(_) => DocRc::new(Doc::Break("\n")),
             Expression::EOF(eof) => eof.to_docs(config, doc_ref),
             Expression::Whitespace(_) => text!(""),
             Expression::FunctionDef(function_def) => {
@@ -657,80 +637,54 @@ impl Code for Expression<'_> {
                     &function_def.arguments,
                     &function_def.body,
                 );
-                match config.function_line_breaks() {
+                // Reserves the reference of the group of the arguments
+                let mut observed_doc = *doc_ref;
+                *doc_ref += 1;
+                let args_group = match config.function_line_breaks() {
+                    _ if args.args.is_empty() => empty_args_to_docs(args, config, doc_ref),
                     FunctionLineBreaks::Hanging => {
-                        let args_doc = join_docs_ungroupped(
-                            args.args.iter().map(|arg| {
-                                arg.to_docs(config, doc_ref)
-                                    .to_group(ShouldBreak::No, doc_ref)
-                            }),
-                            Rc::new(Doc::Nil),
-                            config,
-                        );
-                        let args_group = args
-                            .left_delimeter
-                            .to_docs(config, doc_ref)
-                            .cons(args_doc.nest_hanging())
-                            .cons(args.right_delimeter.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref);
-                        keyword
-                            .to_docs(config, doc_ref)
-                            .cons(args_group)
-                            .cons(text!(" "))
-                            .cons(body.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref)
-                    }
-                    FunctionLineBreaks::Double => {
-                        let args_doc = join_docs_ungroupped(
-                            args.args.iter().map(|arg| {
-                                arg.to_docs(config, doc_ref)
-                                    .to_group(ShouldBreak::No, doc_ref)
-                            }),
-                            Rc::new(Doc::Nil),
-                            config,
-                        );
-                        let args_group = args
-                            .left_delimeter
+                        let (args_doc, hugs_closure) =
+                            formals_to_docs(args, None, observed_doc + 1, config, doc_ref);
+                        let args_doc = if hugs_closure {
+                            args_doc.nest_hanging_if_break(observed_doc + 1)
+                        } else {
+                            args_doc.nest_hanging()
+                        };
+                        args.left_delimeter
                             .to_docs(config, doc_ref)
-                            .cons(nl!(""))
                             .cons(args_doc)
-                            .nest(2 * config.indent())
-                            .cons(nl!(""))
                             .cons(args.right_delimeter.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref);
-                        keyword
-                            .to_docs(config, doc_ref)
-                            .cons(args_group)
-                            .cons(text!(" "))
-                            .cons(body.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref)
                     }
-                    FunctionLineBreaks::Single => {
-                        let args_doc = join_docs_ungroupped(
-                            args.args.iter().map(|arg| {
-                                arg.to_docs(config, doc_ref)
-                                    .to_group(ShouldBreak::No, doc_ref)
-                            }),
-                            Rc::new(Doc::Nil),
-                            config,
-                        );
-                        let args_group = args
-                            .left_delimeter
+                    FunctionLineBreaks::Double | FunctionLineBreaks::Single => {
+                        let indent = if config.function_line_breaks() == FunctionLineBreaks::Double
+                        {
+                            2 * config.indent()
+                        } else {
+                            config.indent()
+                        };
+                        let (args_doc, _) =
+                            formals_to_docs(args, Some(indent), observed_doc + 1, config, doc_ref);
+                        args.left_delimeter
                             .to_docs(config, doc_ref)
                             .cons(nl!(""))
                             .cons(args_doc)
-                            .nest(config.indent())
+                            .nest(indent)
                             .cons(nl!(""))
                             .cons(args.right_delimeter.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref);
-                        keyword
-                            .to_docs(config, doc_ref)
-                            .cons(args_group)
-                            .cons(text!(" "))
-                            .cons(body.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref)
                     }
-                }
+                };
+                keyword
+                    .to_docs(config, doc_ref)
+                    .cons(optional_space(config.space_after_function()))
+                    .cons(args_group.to_group(ShouldBreak::No, &mut observed_doc))
+                    .cons(text!(" "))
+                    .cons(body_to_docs(
+                        body,
+                        config.collapse_function_blocks(),
+                        config,
+                        doc_ref,
+                    ))
+                    .to_group(ShouldBreak::No, doc_ref)
             }
             Expression::IfExpression(if_expression) => {
                 let (if_conditional, else_ifs, trailing_else) = (
@@ -739,29 +693,38 @@ impl Code for Expression<'_> {
                     &if_expression.trailing_else,
                 );
 
+                // The bodies are kept on one line only all together
+                let collapse = config.collapse_if_blocks()
+                    && std::iter::once(if_conditional)
+                        .chain(else_ifs.iter().map(|else_if| &else_if.if_conditional))
+                        .all(|conditional| {
+                            conditional.right_delimiter.inline_comment.is_none()
+                                && single_expression_block(&conditional.body).is_some()
+                        })
+                    && else_ifs
+                        .iter()
+                        .all(|else_if| else_if.else_keyword.leading_comments.is_none())
+                    && trailing_else.as_ref().is_none_or(|trailing_else| {
+                        trailing_else.else_keyword.leading_comments.is_none()
+                            && single_expression_block(&trailing_else.body).is_some()
+                    });
                 let if_conditional_to_docs =
                     |if_conditional: &IfConditional<'_>, doc_ref: &mut usize| {
-                        let (keyword, left_delim, condition, right_delim, body) = (
-                            if_conditional.keyword,
+                        let header = condition_to_docs(
                             if_conditional.left_delimiter,
                             &if_conditional.condition,
                             if_conditional.right_delimiter,
-                            &if_conditional.body,
+                            config,
+                            doc_ref,
                         );
-                        let condition_docs = left_delim
-                            .to_docs(config, doc_ref)
-                            .cons(nl!(""))
-                            .cons(condition.to_docs(config, doc_ref))
-                            .nest(config.indent())
-                            .cons(nl!(""))
-                            .cons(right_delim.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref);
-                        keyword
-                            .to_docs(config, doc_ref)
-                            .cons(text!(" "))
-                            .cons(condition_docs)
-                            .cons(text!(" "))
-                            .cons(body.to_docs(config, doc_ref))
+                        compound_to_docs(
+                            if_conditional.keyword,
+                            Some(header),
+                            &if_conditional.body,
+                            collapse,
+                            config,
+                            doc_ref,
+                        )
                     };
                 let mut docs = if_conditional_to_docs(if_conditional, doc_ref);
                 for else_if in else_ifs {
@@ -769,19 +732,27 @@ impl Code for Expression<'_> {
                         (else_if.else_keyword, &else_if.if_conditional);
                     docs = docs
                         .cons(text!(" "))
-                        .cons(else_keyword.to_docs(config, doc_ref))
-                        .cons(text!(" "))
+                        .cons(else_to_docs(else_keyword, config, doc_ref))
+                        // `if` after the comments is not indented like a body
+                        .cons(match else_keyword.leading_comments {
+                            Some(_) => nl!(""),
+                            None => text!(" "),
+                        })
                         .cons(if_conditional_to_docs(conditional, doc_ref));
                 }
                 if let Some(trailing_else) = trailing_else {
                     let (else_keyword, body) = (&trailing_else.else_keyword, &trailing_else.body);
                     docs = docs
                         .cons(text!(" "))
-                        .cons(else_keyword.to_docs(config, doc_ref))
+                        .cons(else_to_docs(else_keyword, config, doc_ref))
                         .cons(text!(" "))
-                        .cons(body.to_docs(config, doc_ref));
+                        .cons(body_to_docs(body, collapse, config, doc_ref));
+                }
+                if collapse {
+                    docs.to_group(ShouldBreak::No, doc_ref)
+                } else {
+                    docs
                 }
-                docs
             }
             Expression::WhileExpression(while_expression) => {
                 let (keyword, condition, body) = (
@@ -789,34 +760,38 @@ impl Code for Expression<'_> {
                     &while_expression.condition,
                     &while_expression.body,
                 );
-                keyword
-                    .to_docs(config, doc_ref)
-                    .cons(text!(" "))
-                    .cons(condition.to_docs(config, doc_ref))
-                    .cons(text!(" "))
-                    .cons(body.to_docs(config, doc_ref))
+                // The parser keeps the parentheses around the condition
+                let header = match condition.as_ref() {
+                    Expression::Term(term) if term.term.len() == 1 => {
+                        match (term.pre_delimiters, term.post_delimiters) {
+                            (Some(left_delim), Some(right_delim)) => Some(condition_to_docs(
+                                left_delim,
+                                &term.term[0],
+                                right_delim,
+                                config,
+                                doc_ref,
+                            )),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+                .unwrap_or_else(|| (condition.to_docs(config, doc_ref), None));
+                let collapse = config.collapse_loop_blocks() && header.1.is_none();
+                compound_to_docs(keyword, Some(header), body, collapse, config, doc_ref)
                     .to_group(ShouldBreak::No, doc_ref)
             }
             Expression::RepeatExpression(repeat_expression) => {
                 let (keyword, body) = (&repeat_expression.repeat_keyword, &repeat_expression.body);
-                let is_body_lbraced = if let Expression::Term(term_expr) = &**body {
-                    let pre_delimiters = &term_expr.pre_delimiters;
-                    pre_delimiters.is_some_and(|delimiter| matches!(delimiter.token, Token::LBrace))
-                } else {
-                    false
-                };
-                if is_body_lbraced {
-                    keyword
-                        .to_docs(config, doc_ref)
-                        .cons(text!(" "))
-                        .cons(body.to_docs(config, doc_ref))
-                        .to_group(ShouldBreak::No, doc_ref)
-                } else {
-                    keyword
-                        .to_docs(config, doc_ref)
-                        .cons(body.to_docs(config, doc_ref))
-                        .to_group(ShouldBreak::No, doc_ref)
-                }
+                compound_to_docs(
+                    keyword,
+                    None,
+                    body,
+                    config.collapse_loop_blocks(),
+                    config,
+                    doc_ref,
+                )
+                .to_group(ShouldBreak::No, doc_ref)
             }
             Expression::FunctionCall(function_call) => {
                 let (function_ref, args) = (&function_call.function_ref, &function_call.args);
@@ -831,7 +806,17 @@ impl Code for Expression<'_> {
                         false
                     }
                 };
-                let inner_docs = args.to_docs(config, doc_ref);
+                let inner_docs = grouped_args_to_docs(
+                    args,
+                    hugged_argument(function_ref, config),
+                    if is_expanded_call(function_ref, config) {
+                        ShouldBreak::Propagate
+                    } else {
+                        ShouldBreak::No
+                    },
+                    config,
+                    doc_ref,
+                );
                 if is_function_ref_quote && args.args.len() == 1 {
                     if let Arg::Proper(arg, _) = args.args.first().unwrap() {
                         if arg
@@ -865,6 +850,9 @@ impl Code for Expression<'_> {
                     function_ref.to_docs(config, doc_ref).cons(inner_docs)
                 }
             }
+            Expression::SubsetExpression(_) if is_long_access_chain(self, config) => {
+                access_chain_to_docs(self, config, doc_ref)
+            }
             Expression::SubsetExpression(subset_expression) => {
                 let (object_ref, args) = (&subset_expression.object_ref, &subset_expression.args);
                 object_ref
@@ -882,25 +870,47 @@ impl Code for Expression<'_> {
                     &for_loop.right_delim,
                     &for_loop.body,
                 );
-                keyword
+                let (right_delim, inline_comment) =
+                    right_delim.to_docs_with_separate_comments(config, doc_ref);
+                // Reserves the reference of the group of the header
+                let mut observed_doc = *doc_ref;
+                *doc_ref += 1;
+                // The header breaks inside the call of the sequence if it fits
+                // up to the opening parenthesis of the call, e.g.
+                // for (item in seq_len(
+                //   n
+                // )) {
+                // The arguments are then indented like a body, not like the header
+                let collection = match collection.as_ref() {
+                    Expression::FunctionCall(_) => collection
+                        .to_docs(config, doc_ref)
+                        .nest(-config.indent())
+                        .nest_if_break(config.indent(), observed_doc + 1)
+                        .fits_until_l_paren(),
+                    _ => collection.to_docs(config, doc_ref),
+                };
+                let header = left_delim
                     .to_docs(config, doc_ref)
-                    .cons(
-                        text!(" ")
-                            .cons(left_delim.to_docs(config, doc_ref))
-                            .cons(nl!(""))
-                            .cons(identifier.to_docs(config, doc_ref))
-                            .cons(text!(" "))
-                            .cons(in_keyword.to_docs(config, doc_ref))
-                            .cons(nl!(" "))
-                            .cons(collection.to_docs(config, doc_ref))
-                            .nest(config.indent()),
-                    )
                     .cons(nl!(""))
-                    .cons(right_delim.to_docs(config, doc_ref))
-                    .to_group(ShouldBreak::No, doc_ref)
+                    .cons(identifier.to_docs(config, doc_ref))
                     .cons(text!(" "))
-                    .cons(body.to_docs(config, doc_ref))
-                    .to_group(ShouldBreak::No, doc_ref)
+                    .cons(in_keyword.to_docs(config, doc_ref))
+                    .cons(nl!(" "))
+                    .cons(collection)
+                    .nest(config.indent())
+                    .cons(nl!(""))
+                    .cons(right_delim)
+                    .to_group(ShouldBreak::No, &mut observed_doc);
+                let collapse = config.collapse_loop_blocks() && inline_comment.is_none();
+                compound_to_docs(
+                    keyword,
+                    Some((header, inline_comment)),
+                    body,
+                    collapse,
+                    config,
+                    doc_ref,
+                )
+                .to_group(ShouldBreak::No, doc_ref)
             }
             Expression::LambdaFunction(lambda) => {
                 let (keyword, args, body) = (&lambda.keyword, &lambda.args, &lambda.body);
@@ -911,22 +921,22 @@ impl Code for Expression<'_> {
                             .to_group(ShouldBreak::No, doc_ref),
                     )
                     .cons(text!(" "))
-                    .cons(body.to_docs(config, doc_ref))
+                    .cons(body_to_docs(
+                        body,
+                        config.collapse_function_blocks(),
+                        config,
+                        doc_ref,
+                    ))
                     .to_group(ShouldBreak::No, doc_ref)
             }
             Expression::MultiBop(lhs, other) => {
                 assert!(!other.is_empty());
                 let mut last_op: Option<&CommentedToken> = None;
-                let mut acc_rhs: Rc<Doc> = Rc::new(Doc::Nil);
+                let mut acc_rhs: DocRc<Doc> = DocRc::new(Doc::Nil);
                 for (op, rhs) in other.iter().rev() {
                     match last_op {
                         Some(last_op_token) => match last_op_token.token {
-                            Token::OldAssign
-                            | Token::LAssign
-                            | Token::ColonAssign
-                            | Token::SuperAssign
-                                if !config.allow_nl_after_assignment() =>
-                            {
+                            _ if keeps_rhs_on_line(last_op_token, config) => {
                                 acc_rhs = rhs
                                     .to_docs(config, doc_ref)
                                     .cons(text!(" "))
@@ -995,20 +1005,13 @@ impl Code for Expression<'_> {
                     }
                 }
                 if let Some(last_op) = last_op {
+                    let lhs = assignment_target_to_docs(lhs, last_op, config, doc_ref);
                     match last_op.token {
-                        Token::OldAssign
-                        | Token::LAssign
-                        | Token::ColonAssign
-                        | Token::SuperAssign
-                            if !config.allow_nl_after_assignment() =>
-                        {
-                            lhs.to_docs(config, doc_ref)
-                                .cons(text!(" "))
-                                .cons(last_op.to_docs(config, doc_ref))
-                                .cons(text!(" "))
-                                .cons(acc_rhs)
-                                .to_group(ShouldBreak::No, doc_ref)
-                        }
+                        _ if keeps_rhs_on_line(last_op, config) => lhs
+                            .cons(text!(" "))
+                            .cons(last_op.to_docs(config, doc_ref))
+                            .cons(assigned_value_to_docs(last_op, acc_rhs, config))
+                            .to_group(ShouldBreak::No, doc_ref),
                         Token::OldAssign
                         | Token::LAssign
                         | Token::ColonAssign
@@ -1031,13 +1034,14 @@ impl Code for Expression<'_> {
                         | Token::Pipe
                         | Token::Modulo
                         | Token::Tilde
-                        | Token::Special(_) => lhs
-                            .to_docs(config, doc_ref)
-                            .cons(text!(" "))
-                            .cons(last_op.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::No, doc_ref)
-                            .cons(nl!(" ").cons(acc_rhs).nest(config.indent()))
-                            .to_group(ShouldBreak::No, doc_ref),
+                        | Token::Special(_) => {
+                            let lhs = lhs
+                                .cons(text!(" "))
+                                .cons(last_op.to_docs(config, doc_ref))
+                                .to_group(ShouldBreak::No, doc_ref);
+                            broken_operation_to_docs(lhs, acc_rhs, last_op, config)
+                                .to_group(ShouldBreak::No, doc_ref)
+                        }
                         Token::Dollar
                         | Token::NsGet
                         | Token::NsGetInt
@@ -1045,7 +1049,6 @@ impl Code for Expression<'_> {
                         | Token::Slot
                         | Token::Power
                         | Token::Help => lhs
-                            .to_docs(config, doc_ref)
                             .cons(last_op.to_docs(config, doc_ref))
                             .cons(acc_rhs)
                             .to_group(ShouldBreak::No, doc_ref),
@@ -1064,108 +1067,356 @@ impl Code for Expression<'_> {
 }
 
 impl Code for Args<'_> {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc> {
-        let mut observed_doc = *doc_ref;
-        // Hoist up the comment, so it's not part of the args group
-        // This prevents line breaks in these situations:
-        // c(1, 2, 3) # Comment
-        //
-        // We want the above instead of:
-        // c(
-        //   1,
-        //   2,
-        //   3
-        // ) # Comment
-        //
-        // The latter might happen because the inline comment
-        // is followed by a hard break, but at the same time
-        // it should not impact the fits calculations of the line.
-        let (right_delim, inline_comment) = self
-            .right_delimeter
-            .to_docs_with_separate_comments(config, doc_ref);
-        match self.args.split_last() {
-            Some((last_arg, other_args)) => {
-                let other_args = other_args
-                    .iter()
-                    .map(|arg| {
-                        arg.to_docs(config, doc_ref)
-                            .to_group(ShouldBreak::No, doc_ref)
-                    })
-                    .collect::<Vec<_>>();
-                let last_arg = std::iter::once(match &last_arg {
-                    Arg::Proper(expression, _)
-                        if is_expression_bracketed_term_or_function_def(expression) =>
-                    {
-                        last_arg
-                            .to_docs(config, doc_ref)
-                            .to_group(ShouldBreak::No, doc_ref)
-                            .nest(-config.indent())
-                            .nest_if_break(config.indent(), observed_doc + 1)
-                            .fits_until_l_bracket()
-                    }
-                    _ => last_arg
-                        .to_docs(config, doc_ref)
-                        .to_group(ShouldBreak::No, doc_ref),
-                });
-                let inside_delims = other_args
-                .into_iter()
-                .chain(last_arg)
-                .reduce(|first, second| first.cons(nl!(" ")).cons(second))
-                .expect(
-                    "There is at least last_arg doc, otherwise we should be in the None match arm",
-                )
-                .to_group(ShouldBreak::No, &mut observed_doc);
-                if let Some(inline) = inline_comment {
-                    self.left_delimeter
-                        .to_docs(config, doc_ref)
-                        .cons(nl!("").cons(inside_delims).nest(config.indent()))
-                        .cons(nl!(""))
-                        .cons(right_delim)
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc> {
+        grouped_args_to_docs(
+            self,
+            HuggedArgument::from_heuristic(config),
+            ShouldBreak::No,
+            config,
+            doc_ref,
+        )
+    }
+}
+
+/// Returns the docs of the arguments with their brackets, grouped,
+/// and the inline comment after the closing bracket.
+///
+/// The directive comment after the closing bracket, e.g. `# tergo: expand`,
+/// pins the layout of the arguments, see [layout_directive]. The arguments
+/// which cannot be on one line, e.g. with comments, ignore `collapse`.
+fn grouped_args_to_docs(
+    args: &Args<'_>,
+    hugged: HuggedArgument,
+    should_break: ShouldBreak,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let directive = match args.right_delimeter {
+        Delimiter::Paren(token)
+        | Delimiter::SingleBracket(token)
+        | Delimiter::DoubleBracket((_, token)) => token.inline_comment.and_then(layout_directive),
+    };
+    let mut observed_doc = *doc_ref;
+    let (args, inline_comment) = args_to_docs(args, hugged, config, doc_ref, observed_doc + 1);
+    // The arguments break together with their brackets, so a call
+    // which does not fit puts each of its arguments on its own line
+    // before any of the calls inside it breaks
+    let args = match directive {
+        Some(LayoutDirective::Expand) => args.to_group(ShouldBreak::Propagate, &mut observed_doc),
+        Some(LayoutDirective::Collapse) => match flat_docs(&args) {
+            Some(flat_args) => flat_args,
+            None => args.to_group(should_break, &mut observed_doc),
+        },
+        None => args.to_group(should_break, &mut observed_doc),
+    };
+    match inline_comment {
+        Some(inline) => args.cons(text!(" ")).cons(inline),
+        None => args,
+    }
+}
+
+/// The functions whose arguments after the expression are the handlers
+/// of the conditions, e.g. `error = function(e) { ... }`.
+const CONDITION_HANDLING_FUNCTIONS: [&str; 2] = ["tryCatch", "withCallingHandlers"];
+
+/// The functions which call the function of their first argument
+/// with the list of their second argument, e.g. `do.call(rbind, list(...))`.
+const FUNCTION_APPLYING_FUNCTIONS: [&str; 2] = ["do.call", "Reduce"];
+
+/// The last argument of a call which stays on the line of the call,
+/// with its contents indented like the arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HuggedArgument {
+    None,
+    /// A braced block or a function, e.g. of `test_that("", { ... })`.
+    Closure,
+    /// A closure or a handler with a braced body,
+    /// e.g. `error = function(e) {` of `tryCatch`.
+    ClosureOrHandler,
+    /// The second and last argument if it is a call,
+    /// e.g. the `list(` of `do.call(rbind, list(`.
+    Call,
+}
+
+impl HuggedArgument {
+    fn from_heuristic(config: &impl FormattingConfig) -> Self {
+        if config.last_braced_argument_heuristic() {
+            Self::Closure
+        } else {
+            Self::None
+        }
+    }
+
+    fn hugs(self, expression: &Option<Expression>, position: usize) -> bool {
+        match self {
+            Self::None => false,
+            Self::Closure => is_expression_bracketed_term_or_function_def(expression),
+            Self::ClosureOrHandler => {
+                is_expression_bracketed_term_or_function_def(expression)
+                    || is_braced_handler(expression)
+            }
+            Self::Call => position == 1 && matches!(expression, Some(Expression::FunctionCall(_))),
+        }
+    }
+}
+
+/// Returns the name of the called function without its namespace,
+/// e.g. `fluidPage` of `shiny::fluidPage(...)`.
+fn function_name<'a>(function_ref: &Expression<'a>) -> Option<&'a str> {
+    let Expression::Symbol(token) = function_ref else {
+        return None;
+    };
+    match token.token {
+        Token::Symbol(name) => name.rsplit(':').next(),
+        _ => None,
+    }
+}
+
+/// Whether the arguments of the call of the function are always
+/// on their own lines, e.g. of `fluidPage` in the user interface of an app.
+fn is_expanded_call(function_ref: &Expression, config: &impl FormattingConfig) -> bool {
+    function_name(function_ref).is_some_and(|name| {
+        config
+            .expanded_call_functions()
+            .iter()
+            .any(|function| function == name)
+    })
+}
+
+/// Whether the argument is a handler with a braced body,
+/// e.g. `error = function(e) { ... }`.
+fn is_braced_handler(expr: &Option<Expression>) -> bool {
+    let Some(Expression::MultiBop(_, other)) = expr else {
+        return false;
+    };
+    other.len() == 1
+        && matches!(other[0].0.token, Token::OldAssign)
+        && matches!(other[0].1.as_ref(), Expression::FunctionDef(function_def)
+            if is_closure_with_brackets(&function_def.body))
+}
+
+/// Returns which last argument of the call of the function stays
+/// on the line of the call, e.g. the closure of `test_that("", { ... })`.
+fn hugged_argument(function_ref: &Expression, config: &impl FormattingConfig) -> HuggedArgument {
+    // The function may come from a namespace, e.g. shiny::reactive
+    let name = function_name(function_ref);
+    let hugs_closure = config.last_braced_argument_heuristic()
+        || name.is_some_and(|name| {
+            config
+                .closure_argument_functions()
+                .iter()
+                .any(|function| function == name)
+        });
+    match name {
+        Some(name) if FUNCTION_APPLYING_FUNCTIONS.contains(&name) => HuggedArgument::Call,
+        Some(name) if hugs_closure && CONDITION_HANDLING_FUNCTIONS.contains(&name) => {
+            HuggedArgument::ClosureOrHandler
+        }
+        _ if hugs_closure => HuggedArgument::Closure,
+        _ => HuggedArgument::None,
+    }
+}
+
+/// Returns the docs of the arguments with their brackets, not grouped,
+/// and the inline comment after the closing bracket.
+///
+/// The `hugged` last argument stays on the line of the call. `observed_doc`
+/// is the reference of the group which indents it if it breaks.
+fn args_to_docs(
+    args: &Args<'_>,
+    hugged: HuggedArgument,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+    observed_doc: usize,
+) -> (DocRc<Doc>, Option<DocRc<Doc>>) {
+    // Hoist up the comment, so it's not part of the args group
+    // This prevents line breaks in these situations:
+    // c(1, 2, 3) # Comment
+    //
+    // We want the above instead of:
+    // c(
+    //   1,
+    //   2,
+    //   3
+    // ) # Comment
+    //
+    // The latter might happen because the inline comment
+    // is followed by a hard break, but at the same time
+    // it should not impact the fits calculations of the line.
+    // The comments before the closing bracket are indented like the arguments
+    let closing_token = match args.right_delimeter {
+        Delimiter::Paren(token)
+        | Delimiter::SingleBracket(token)
+        | Delimiter::DoubleBracket((token, _)) => token,
+    };
+    let closing_comments = closing_token.leading_comments.as_deref().map(|comments| {
+        closing_comments_to_docs(comments, doc_ref).to_group(ShouldBreak::Propagate, doc_ref)
+    });
+    let uncommented_token = CommentedToken {
+        leading_comments: None,
+        ..closing_token.clone()
+    };
+    let (right_delim, inline_comment) = match args.right_delimeter {
+        Delimiter::Paren(_) => Delimiter::Paren(&uncommented_token),
+        Delimiter::SingleBracket(_) => Delimiter::SingleBracket(&uncommented_token),
+        Delimiter::DoubleBracket((_, second)) => {
+            Delimiter::DoubleBracket((&uncommented_token, second))
+        }
+    }
+    .to_docs_with_separate_comments(config, doc_ref);
+    let without_comma;
+    let call_args = match without_trailing_comma(args) {
+        Some(call_args) if config.trailing_comma() == TrailingComma::Never => {
+            without_comma = remove_last_comma(call_args);
+            without_comma.as_deref().unwrap_or(call_args)
+        }
+        Some(call_args) => call_args,
+        None => &args.args,
+    };
+    let paddings = if config.align_named_arguments() {
+        named_argument_paddings(call_args)
+    } else {
+        vec![0; call_args.len()]
+    };
+    match call_args.split_last() {
+        Some((last_arg, other_args)) => {
+            let other_args = other_args
+                .iter()
+                .zip(&paddings)
+                .map(|(arg, &padding)| {
+                    aligned_arg_to_docs(arg, padding, observed_doc, config, doc_ref)
                         .to_group(ShouldBreak::No, doc_ref)
-                        .cons(text!(" "))
-                        .cons(inline)
-                } else {
-                    self.left_delimeter
+                })
+                .collect::<Vec<_>>();
+            let last_arg = std::iter::once(match &last_arg {
+                Arg::Proper(expression, _) if hugged.hugs(expression, other_args.len()) => {
+                    let last_arg = last_arg
                         .to_docs(config, doc_ref)
-                        .cons(nl!("").cons(inside_delims).nest(config.indent()))
-                        .cons(nl!(""))
-                        .cons(right_delim)
-                }
-            }
-            None => match self.right_delimeter {
-                Delimiter::SingleBracket(commented_token) | Delimiter::Paren(commented_token) => {
-                    if commented_token.leading_comments.is_some() {
-                        self.left_delimeter
-                            .to_docs(config, doc_ref)
-                            .cons(nl!("").nest(config.indent()))
-                            .cons(self.right_delimeter.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::Yes, doc_ref)
+                        .to_group(ShouldBreak::No, doc_ref)
+                        .nest(-config.indent())
+                        .nest_if_break(config.indent(), observed_doc);
+                    if hugged == HuggedArgument::Call {
+                        last_arg.fits_until_l_paren()
                     } else {
-                        self.left_delimeter
-                            .to_docs(config, doc_ref)
-                            .cons(self.right_delimeter.to_docs(config, doc_ref))
+                        last_arg.fits_until_l_bracket()
                     }
                 }
-                Delimiter::DoubleBracket((first_commented_token, _)) => {
-                    if first_commented_token.leading_comments.is_some() {
-                        self.left_delimeter
-                            .to_docs(config, doc_ref)
-                            .cons(nl!("").nest(config.indent()))
-                            .cons(self.right_delimeter.to_docs(config, doc_ref))
-                            .to_group(ShouldBreak::Yes, doc_ref)
-                    } else {
-                        self.left_delimeter
-                            .to_docs(config, doc_ref)
-                            .cons(self.right_delimeter.to_docs(config, doc_ref))
+                _ => aligned_arg_to_docs(
+                    last_arg,
+                    paddings[other_args.len()],
+                    observed_doc,
+                    config,
+                    doc_ref,
+                )
+                .to_group(ShouldBreak::No, doc_ref),
+            });
+            let mut arg_docs = other_args.into_iter().chain(last_arg);
+            // The empty `i` of data.table stays after the bracket: DT[, j, by]
+            let mut left_delim = args.left_delimeter.to_docs(config, doc_ref);
+            let mut first_break = nl!("");
+            if config.data_table_dialect()
+                && matches!(args.left_delimeter, Delimiter::SingleBracket(_))
+            {
+                let empty = call_args
+                    .iter()
+                    .take_while(|arg| matches!(arg, Arg::Proper(None, Some(_))))
+                    .count();
+                if empty < call_args.len() {
+                    for (index, empty_arg) in arg_docs.by_ref().take(empty).enumerate() {
+                        let separator = if index == 0 { text!("") } else { text!(" ") };
+                        left_delim = left_delim.cons(separator).cons(empty_arg);
+                    }
+                    if empty > 0 {
+                        first_break = nl!(" ");
                     }
                 }
-            },
+            }
+            let mut inside_delims = arg_docs
+                .reduce(|first, second| first.cons(nl!(" ")).cons(second))
+                .expect(
+                    "There is at least last_arg doc, otherwise we should be in the None match arm",
+                );
+            if let Some(closing_comments) = closing_comments {
+                inside_delims = inside_delims.cons(nl!("")).cons(closing_comments);
+            }
+            let docs = left_delim
+                .cons(first_break.cons(inside_delims).nest(config.indent()))
+                .cons(nl!(""))
+                .cons(right_delim);
+            (docs, inline_comment)
+        }
+        None => (empty_args_to_docs(args, config, doc_ref), None),
+    }
+}
+
+/// Returns the docs of the brackets without arguments, e.g. `f()`,
+/// with the comments between them on their own lines.
+fn empty_args_to_docs(
+    args: &Args<'_>,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let (left_token, right_token) = match (&args.left_delimeter, &args.right_delimeter) {
+        (Delimiter::Paren(left), Delimiter::Paren(right))
+        | (Delimiter::SingleBracket(left), Delimiter::SingleBracket(right))
+        | (Delimiter::DoubleBracket((_, left)), Delimiter::DoubleBracket((right, _))) => {
+            (left, right)
+        }
+        _ => unreachable!("The brackets of the arguments match"),
+    };
+    let left = args.left_delimeter.to_docs(config, doc_ref);
+    let right = args.right_delimeter.to_docs(config, doc_ref);
+    if right_token.leading_comments.is_some() {
+        // The comments go on their own lines, indented like the arguments
+        left.cons(nl!("").nest(config.indent()))
+            .cons(right)
+            .to_group(ShouldBreak::Yes, doc_ref)
+    } else if left_token.inline_comment.is_some() {
+        // The closing bracket goes after the comment, e.g. `c( # none\n)`
+        left.cons(nl!(""))
+            .cons(right)
+            .to_group(ShouldBreak::Yes, doc_ref)
+    } else {
+        left.cons(right)
+    }
+}
+
+/// Returns the arguments of the call without the empty one after
+/// the trailing comma, e.g. the one after `2,` in `tribble(~a, 1, 2,)`,
+/// or `None` if the call has no trailing comma.
+///
+/// The empty argument takes no line of its own, so the call is broken
+/// the same with or without the comma. The subsets, e.g. `x[1, ]`, keep it.
+fn without_trailing_comma<'a, 'b>(args: &'b Args<'a>) -> Option<&'b [Arg<'a>]> {
+    match args.args.split_last() {
+        Some((Arg::Proper(None, None), call_args))
+            if matches!(args.left_delimeter, Delimiter::Paren(_))
+                && matches!(call_args.last(), Some(Arg::Proper(_, Some(_)))) =>
+        {
+            Some(call_args)
         }
+        _ => None,
+    }
+}
+
+/// Returns the arguments without the comma after the last one,
+/// or `None` if the comma has comments, which would be lost.
+fn remove_last_comma<'a>(args: &[Arg<'a>]) -> Option<Vec<Arg<'a>>> {
+    let (Arg::Proper(Some(expression), Some(Expression::Literal(comma))), other_args) =
+        args.split_last()?
+    else {
+        return None;
+    };
+    if comma.leading_comments.is_some() || comma.inline_comment.is_some() {
+        return None;
     }
+    let mut args = other_args.to_vec();
+    args.push(Arg::Proper(Some(expression.clone()), None));
+    Some(args)
 }
 
 impl Code for Arg<'_> {
-    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> Rc<Doc> {
+    fn to_docs(&self, config: &impl FormattingConfig, doc_ref: &mut usize) -> DocRc<Doc> {
         match self {
             Arg::Proper(expr, comma) => {
                 if let Some(comma) = comma {
@@ -1185,6 +1436,664 @@ impl Code for Arg<'_> {
     }
 }
 
+/// Whether the expression is the terms of a formula joined by `+` or `-`,
+/// e.g. the `a + b + c` of `y ~ a + b + c`.
+fn is_formula_terms(expression: &Expression) -> bool {
+    matches!(expression, Expression::MultiBop(_, other)
+        if other.iter().all(|(op, _)| matches!(op.token, Token::Plus | Token::Minus)))
+}
+
+/// Formats a formula with many terms, e.g. of a model, keeping its
+/// response and `~` on the line of the first term.
+fn formula_to_docs(
+    lhs: &Expression,
+    tilde: &CommentedToken,
+    rhs: &Expression,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    lhs.to_docs(config, doc_ref)
+        .cons(text!(" "))
+        .cons(tilde.to_docs(config, doc_ref))
+        .cons(text!(" "))
+        .cons(formula_terms_to_docs(rhs, config, doc_ref).nest(config.continuation_indent()))
+        .to_group(ShouldBreak::No, doc_ref)
+}
+
+/// Returns the docs of the terms of a formula, which fill the lines:
+/// a term goes to the next line, after the `+` or `-`, only if it does
+/// not fit in the line, e.g.
+/// lm(
+///   y ~ age + height + weight +
+///     income + region,
+///   data = df
+/// )
+fn formula_terms_to_docs(
+    terms: &Expression,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let Expression::MultiBop(first, other) = terms else {
+        return terms.to_docs(config, doc_ref);
+    };
+    other
+        .iter()
+        .fold(first.to_docs(config, doc_ref), |docs, (op, term)| {
+            docs.cons(text!(" "))
+                .cons(op.to_docs(config, doc_ref))
+                .cons(
+                    nl!(" ")
+                        .cons(term.to_docs(config, doc_ref))
+                        .to_group(ShouldBreak::No, doc_ref),
+                )
+        })
+}
+
+/// The left-hand side of the operation, ending with the operator, and
+/// the right-hand side on the next line if the operation breaks.
+/// The right-hand side is indented by the continuation indent or, with
+/// `hanging_binary_operations`, aligned under the left-hand side.
+fn broken_operation_to_docs(
+    lhs: DocRc<Doc>,
+    rhs: DocRc<Doc>,
+    op: &CommentedToken,
+    config: &impl FormattingConfig,
+) -> DocRc<Doc> {
+    let hangs = config.hanging_binary_operations()
+        && !matches!(
+            op.token,
+            Token::OldAssign
+                | Token::LAssign
+                | Token::ColonAssign
+                | Token::SuperAssign
+                | Token::RAssign
+                | Token::Pipe
+                | Token::Special(_)
+        );
+    if hangs {
+        lhs.cons(nl!(" ")).cons(rhs).nest_hanging()
+    } else {
+        lhs.cons(nl!(" ").cons(rhs).nest(config.continuation_indent()))
+    }
+}
+
+/// The space and the value after the operator of an assignment kept
+/// on the line of the operator. With `break_after_assignment`, the value
+/// of `<-`, `<<-` or `:=` goes on the next line instead if it fits there
+/// but not on the line.
+fn assigned_value_to_docs(
+    op: &CommentedToken,
+    value: DocRc<Doc>,
+    config: &impl FormattingConfig,
+) -> DocRc<Doc> {
+    // Not `=`, which mostly names an argument
+    let is_assignment = matches!(
+        op.token,
+        Token::LAssign | Token::ColonAssign | Token::SuperAssign
+    );
+    // E.g. a function definition breaks anyway, so it stays after `<-`
+    if config.break_after_assignment()
+        && is_assignment
+        && op.inline_comment.is_none()
+        && !has_forced_line_breaks(&value, false)
+    {
+        value.break_before_if_fits(config.continuation_indent())
+    } else {
+        text!(" ").cons(value)
+    }
+}
+
+/// The docs of the left-hand side of the binary operator. The call or
+/// the subsetting assigned to, e.g. `names(x)` in `names(x) <- value`,
+/// never breaks, so only the value does.
+fn assignment_target_to_docs(
+    lhs: &Expression,
+    op: &CommentedToken,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let docs = lhs.to_docs(config, doc_ref);
+    let is_replacement = matches!(
+        op.token,
+        Token::OldAssign | Token::LAssign | Token::ColonAssign | Token::SuperAssign
+    ) && matches!(
+        lhs,
+        Expression::FunctionCall(_) | Expression::SubsetExpression(_)
+    );
+    if is_replacement {
+        flat_docs(&docs).unwrap_or(docs)
+    } else {
+        docs
+    }
+}
+
+/// The docs printed on one line, or `None` if they must break,
+/// e.g. because of a comment.
+fn flat_docs(doc: &DocRc<Doc>) -> Option<DocRc<Doc>> {
+    match doc.deref() {
+        Doc::Nil => Some(DocRc::clone(doc)),
+        Doc::Text(_, _, props) if props.0 == InlineCommentPosition::No => Some(DocRc::clone(doc)),
+        Doc::Break(s) => Some(text!(s)),
+        Doc::Cons(first, second, _) => Some(flat_docs(first)?.cons(flat_docs(second)?)),
+        Doc::Nest(_, inner, _)
+        | Doc::NestIfBreak(_, inner, _, _)
+        | Doc::NestHanging(inner, _)
+        | Doc::NestHangingIfBreak(inner, _, _)
+        | Doc::FitsUntilLBracket(inner, _, _)
+        | Doc::Embrace(inner, _) => flat_docs(inner),
+        Doc::BreakBeforeIfFits(_, inner, _) => Some(text!(" ").cons(flat_docs(inner)?)),
+        Doc::Group(group_props, props)
+            if group_props.1 == ShouldBreak::No && props.0 != InlineCommentPosition::Middle =>
+        {
+            flat_docs(&group_props.0)
+        }
+        Doc::Text(..) | Doc::Align(..) | Doc::LiteralLine | Doc::Group(..) | Doc::HardBreak => None,
+    }
+}
+
+/// Whether the right-hand side of the binary operator stays on the line
+/// of the operator. The target of a right assignment always does, so
+/// a pipeline ending with `-> result` breaks like the pipeline alone.
+fn keeps_rhs_on_line(op: &CommentedToken, config: &impl FormattingConfig) -> bool {
+    match op.token {
+        Token::RAssign => true,
+        Token::OldAssign | Token::LAssign | Token::ColonAssign | Token::SuperAssign => {
+            !config.allow_nl_after_assignment()
+        }
+        _ => false,
+    }
+}
+
+/// Returns the braces and the expression of a braced block with a single
+/// expression and no comments, e.g. `{ b }`.
+fn single_expression_block<'a, 'b>(
+    body: &'b Expression<'a>,
+) -> Option<(
+    &'a CommentedToken<'a>,
+    &'b Expression<'a>,
+    &'a CommentedToken<'a>,
+)> {
+    let Expression::Term(term_expr) = body else {
+        return None;
+    };
+    let (Some(left_brace), Some(right_brace)) =
+        (term_expr.pre_delimiters, term_expr.post_delimiters)
+    else {
+        return None;
+    };
+    if left_brace.token != Token::LBrace
+        || left_brace.inline_comment.is_some()
+        || right_brace.leading_comments.is_some()
+    {
+        return None;
+    }
+    let mut expressions = term_expr.term.iter().filter(|expression| {
+        !matches!(
+            expression,
+            Expression::Newline(_) | Expression::Whitespace(_)
+        )
+    });
+    match (expressions.next(), expressions.next()) {
+        (Some(expression), None)
+            if !matches!(expression, Expression::Comment(_))
+                && first_token(expression).is_none_or(|token| token.leading_comments.is_none()) =>
+        {
+            Some((left_brace, expression, right_brace))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the docs of the condition of an `if` or a `while` in its
+/// parentheses, and the inline comment after them separately.
+fn condition_to_docs(
+    left_delim: &CommentedToken,
+    condition: &Expression,
+    right_delim: &CommentedToken,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> (DocRc<Doc>, Option<DocRc<Doc>>) {
+    let (right_delim, inline_comment) = right_delim.to_docs_with_separate_comments(config, doc_ref);
+    let condition = left_delim
+        .to_docs(config, doc_ref)
+        .cons(nl!(""))
+        .cons(condition.to_docs(config, doc_ref))
+        .nest(config.indent())
+        .cons(nl!(""))
+        .cons(right_delim)
+        .to_group(ShouldBreak::No, doc_ref);
+    (condition, inline_comment)
+}
+
+/// Returns the docs of an `if`, a `while`, a `for` or a `repeat` with
+/// the header after the keyword, e.g. the condition, and the body.
+///
+/// The inline comment after the header stays outside of its group,
+/// so it does not break the header.
+fn compound_to_docs(
+    keyword: &CommentedToken,
+    header: Option<(DocRc<Doc>, Option<DocRc<Doc>>)>,
+    body: &Expression,
+    collapse: bool,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let mut docs = keyword.to_docs(config, doc_ref);
+    // `repeat(x)` is kept like a call
+    let is_parenthesized = matches!(
+        body,
+        Expression::Term(term) if term.pre_delimiters.is_some_and(|delim| delim.token == Token::LParen)
+    );
+    let separator = if header.is_none() && is_parenthesized {
+        text!("")
+    } else {
+        text!(" ")
+    };
+    let mut body = body_to_docs(body, collapse, config, doc_ref);
+    if let Some((header, inline_comment)) = header {
+        docs = docs
+            .cons(optional_space(config.space_after_keyword()))
+            .cons(header);
+        if let Some(inline_comment) = inline_comment {
+            docs = docs.cons(text!(" ")).cons(inline_comment);
+            // The comment breaks the line, but not the body
+            body = body.to_group(ShouldBreak::No, doc_ref);
+        }
+    }
+    docs.cons(separator).cons(body)
+}
+
+/// Returns the docs of the body of an `if`, a loop or a function.
+///
+/// If `collapse`, a braced body with a single expression is not grouped,
+/// so it stays on one line, e.g. `if (a) { b }`, if the group around it fits.
+fn body_to_docs(
+    body: &Expression,
+    collapse: bool,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    match single_expression_block(body).filter(|_| collapse) {
+        Some((left_brace, expression, right_brace)) => left_brace
+            .to_docs(config, doc_ref)
+            .cons(
+                nl!(" ")
+                    .cons(expression.to_docs(config, doc_ref))
+                    .nest(config.indent()),
+            )
+            .cons(nl!(" "))
+            .cons(right_brace.to_docs(config, doc_ref)),
+        None => body.to_docs(config, doc_ref),
+    }
+}
+
+/// Formats `else` with the comments around it, which the parser gathers
+/// in its leading comments. The comments follow `else`, each on its own
+/// line, and the body goes on the next line, e.g. `} else # end\n{`,
+/// because a line break before `else` would end the `if` expression.
+fn else_to_docs(
+    else_keyword: &CommentedToken,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let Some(comments) = &else_keyword.leading_comments else {
+        return else_keyword.to_docs(config, doc_ref);
+    };
+    let mut comments_it = comments.iter();
+    let mut docs = else_keyword
+        .token
+        .to_docs(config, doc_ref)
+        .cons(text!(" "))
+        .cons(text!(
+            comments_it.next().unwrap(),
+            0,
+            InlineCommentPosition::End
+        ));
+    for comment in comments_it {
+        docs = docs
+            .cons(nl!(""))
+            .cons(text!(comment, 0, InlineCommentPosition::End));
+    }
+    docs
+}
+
+/// The space, if any, between a keyword, e.g. `if`, and its opening parenthesis.
+fn optional_space(space: bool) -> DocRc<Doc> {
+    if space { text!(" ") } else { text!("") }
+}
+
+/// Returns the docs of the formal arguments of a function, not grouped,
+/// and whether the last one stays on the line of `function(` because
+/// its default is a closure, e.g. `function(x, f = function(y) {`.
+///
+/// The closure is indented by `indent`, or hanging, only if the group
+/// of the arguments, `observed_doc`, breaks.
+fn formals_to_docs(
+    args: &Args<'_>,
+    indent: Option<i32>,
+    observed_doc: usize,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> (DocRc<Doc>, bool) {
+    let hugs_closure = config.last_braced_argument_heuristic()
+        && matches!(
+            args.args.last(),
+            Some(Arg::Proper(Some(Expression::MultiBop(_, other)), _))
+                if other.len() == 1
+                    && matches!(other[0].0.token, Token::OldAssign)
+                    && is_braced_closure(&other[0].1)
+        );
+    let last = args.args.len().saturating_sub(1);
+    let formals = args.args.iter().enumerate().map(|(index, arg)| {
+        let formal = arg
+            .to_docs(config, doc_ref)
+            .to_group(ShouldBreak::No, doc_ref);
+        match indent {
+            _ if !hugs_closure || index != last => formal,
+            Some(indent) => formal
+                .nest(-indent)
+                .nest_if_break(indent, observed_doc)
+                .fits_until_l_bracket(),
+            None => formal.fits_until_l_bracket(),
+        }
+    });
+    let docs = join_docs_ungroupped(formals.collect::<Vec<_>>(), DocRc::new(Doc::Nil), config);
+    (docs, hugs_closure)
+}
+
+/// Whether the expression is a block or a function with a block as its body.
+fn is_braced_closure(expression: &Expression) -> bool {
+    match expression {
+        Expression::FunctionDef(function_def) => is_closure_with_brackets(&function_def.body),
+        expression => is_closure_with_brackets(expression),
+    }
+}
+
+/// Returns the width of the name of a named argument, e.g. `width = 12`.
+fn named_argument_width(arg: &Arg) -> Option<usize> {
+    let name: &Expression = match arg {
+        Arg::Proper(Some(Expression::MultiBop(name, other)), _)
+            if other.len() == 1 && matches!(other[0].0.token, Token::OldAssign) =>
+        {
+            name
+        }
+        Arg::EmptyEqual(name, _, _) => name,
+        _ => return None,
+    };
+    name_width(name)
+}
+
+fn name_width(name: &Expression) -> Option<usize> {
+    match name {
+        Expression::Symbol(token) | Expression::Literal(token) => match token.token {
+            Token::Symbol(text) | Token::Literal(text) => Some(text.chars().count()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the number of spaces after the name of each of the arguments,
+/// which align the `=` of the consecutive named arguments.
+fn named_argument_paddings(args: &[Arg]) -> Vec<usize> {
+    let widths = args.iter().map(named_argument_width).collect::<Vec<_>>();
+    widths
+        .chunk_by(|first, second| first.is_some() == second.is_some())
+        .flat_map(|run| {
+            let widest = run.iter().flatten().max().copied().unwrap_or_default();
+            run.iter()
+                .map(move |width| width.map_or(0, |width| widest - width))
+        })
+        .collect()
+}
+
+/// Formats the argument with the spaces after its name, printed if
+/// the group of the arguments `observed_doc` breaks.
+fn aligned_arg_to_docs(
+    arg: &Arg,
+    padding: usize,
+    observed_doc: usize,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    if padding == 0 {
+        return arg.to_docs(config, doc_ref);
+    }
+    let align = DocRc::new(Doc::Align(padding, observed_doc));
+    match arg {
+        Arg::Proper(Some(Expression::MultiBop(name, other)), comma) => {
+            let (equal_sign, value) = &other[0];
+            aligned_assignment_to_docs(name, equal_sign, value, align, config, doc_ref)
+                .cons(comma.to_docs(config, doc_ref))
+        }
+        Arg::EmptyEqual(name, equal_sign, comma) => name
+            .to_docs(config, doc_ref)
+            .cons(align)
+            .cons(text!(" "))
+            .cons(equal_sign.to_docs(config, doc_ref))
+            .cons(text!(" "))
+            .cons(comma.to_docs(config, doc_ref)),
+        _ => arg.to_docs(config, doc_ref),
+    }
+}
+
+/// Formats an assignment, or a named argument, like a binary expression
+/// with `align` between the name and the operator.
+fn aligned_assignment_to_docs(
+    name: &Expression,
+    op: &CommentedToken,
+    value: &Expression,
+    align: DocRc<Doc>,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let value = value
+        .to_docs(config, doc_ref)
+        .to_group(ShouldBreak::No, doc_ref);
+    let name = name
+        .to_docs(config, doc_ref)
+        .cons(align)
+        .cons(text!(" "))
+        .cons(op.to_docs(config, doc_ref));
+    let assignment = if config.allow_nl_after_assignment() {
+        name.to_group(ShouldBreak::No, doc_ref)
+            .cons(nl!(" ").cons(value).nest(config.continuation_indent()))
+    } else {
+        name.cons(assigned_value_to_docs(op, value, config))
+    };
+    assignment.to_group(ShouldBreak::No, doc_ref)
+}
+
+/// Returns the width of the assigned name of an assignment, e.g. `x <- 1`,
+/// and the distance from the name to the operator in the code.
+fn assignment_name_width(statement: &Expression) -> Option<(usize, usize)> {
+    match statement {
+        Expression::MultiBop(name, other)
+            if other.len() == 1
+                && matches!(
+                    other[0].0.token,
+                    Token::LAssign | Token::SuperAssign | Token::OldAssign
+                ) =>
+        {
+            let width = name_width(name)?;
+            let (Expression::Symbol(token) | Expression::Literal(token)) = &**name else {
+                return None;
+            };
+            Some((width, other[0].0.offset.saturating_sub(token.offset)))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the number of spaces after the assigned name of each of the statements,
+/// which align the operators of the consecutive assignments.
+///
+/// Unless [FormattingConfig::align_assignments] is set, only the assignments
+/// aligned in the code are aligned, so the alignment made by hand is kept.
+/// If `around_functions`, the function definitions are not aligned with
+/// their neighbours, as the blank lines inserted around them would end
+/// the alignment when formatted again.
+fn assignment_paddings(
+    statements: &[Expression],
+    around_functions: bool,
+    config: &impl FormattingConfig,
+) -> Vec<usize> {
+    let widths = statements
+        .iter()
+        .map(|statement| {
+            let separate = around_functions && is_function_definition(statement);
+            (assignment_name_width(statement), separate)
+        })
+        .collect::<Vec<_>>();
+    widths
+        .chunk_by(|(first, first_separate), (second, second_separate)| {
+            !first_separate
+                && !second_separate
+                && match (first, second) {
+                    (Some(_), Some(_)) if config.align_assignments() => true,
+                    (Some((_, first)), Some((_, second))) => first == second,
+                    (first, second) => first.is_none() && second.is_none(),
+                }
+        })
+        .flat_map(|run| {
+            let widest = run
+                .iter()
+                .filter_map(|(width, _)| width.as_ref())
+                .map(|(width, _)| *width)
+                .max()
+                .unwrap_or_default();
+            run.iter()
+                .map(move |(width, _)| width.map_or(0, |(width, _)| widest - width))
+        })
+        .collect()
+}
+
+/// Returns the docs of the statements of a block, or of the file
+/// if `top_level`, each in its own group.
+///
+/// The blank lines between the statements are limited to
+/// [FormattingConfig::max_blank_lines_in_blocks] in a block and to one
+/// at the top level, where [FormattingConfig::blank_lines_between_functions]
+/// sets them around the function definitions.
+fn statements_to_docs(
+    statements: &[Expression],
+    top_level: bool,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> Vec<DocRc<Doc>> {
+    let max_blank_lines = if top_level {
+        1
+    } else {
+        usize::try_from(config.max_blank_lines_in_blocks()).unwrap_or_default()
+    };
+    let between_functions = usize::try_from(config.blank_lines_between_functions())
+        .ok()
+        .filter(|&lines| top_level && lines > 0);
+    let mut docs = vec![];
+    let mut blank_lines = 0;
+    let mut previous: Option<&Expression> = None;
+    for (statement, padding) in statements.iter().zip(assignment_paddings(
+        statements,
+        between_functions.is_some(),
+        config,
+    )) {
+        if let Expression::Whitespace(lines) = statement {
+            blank_lines += lines.len();
+            continue;
+        }
+        let kept_blank_lines = std::mem::take(&mut blank_lines).min(max_blank_lines);
+        let blank_lines_before = match (between_functions, previous) {
+            (Some(lines), Some(previous))
+                if !matches!(statement, Expression::EOF(_))
+                    && (is_function_definition(previous) || is_function_definition(statement)) =>
+            {
+                // A blank line before the comments is kept with them
+                lines - usize::from(starts_with_blank_line(statement))
+            }
+            _ => kept_blank_lines,
+        };
+        docs.extend(std::iter::repeat_with(|| text!("")).take(blank_lines_before));
+        docs.push(
+            aligned_statement_to_docs(statement, padding, config, doc_ref)
+                .to_group(ShouldBreak::No, doc_ref),
+        );
+        previous = Some(statement);
+    }
+    docs.extend(std::iter::repeat_with(|| text!("")).take(blank_lines.min(max_blank_lines)));
+    docs
+}
+
+/// Whether the statement assigns a function, e.g. `f <- function() 1`.
+fn is_function_definition(statement: &Expression) -> bool {
+    let is_assignment = |op: &CommentedToken| {
+        matches!(
+            op.token,
+            Token::LAssign | Token::SuperAssign | Token::OldAssign
+        )
+    };
+    match statement {
+        Expression::FunctionDef(_) => true,
+        Expression::Bop(op, _, rhs) => is_assignment(op) && is_function_definition(rhs),
+        Expression::MultiBop(_, other) => other
+            .last()
+            .is_some_and(|(op, rhs)| is_assignment(op) && is_function_definition(rhs)),
+        _ => false,
+    }
+}
+
+/// Whether the statement starts with comments after a blank line.
+fn starts_with_blank_line(statement: &Expression) -> bool {
+    first_token(statement)
+        .and_then(|token| token.leading_comments.as_ref())
+        .is_some_and(|comments| comments.first() == Some(&""))
+}
+
+/// The first token of the expression, which holds its leading comments.
+fn first_token<'a>(expression: &'a Expression<'a>) -> Option<&'a CommentedToken<'a>> {
+    match expression {
+        Expression::Symbol(token)
+        | Expression::Literal(token)
+        | Expression::Comment(token)
+        | Expression::Unary(token, _)
+        | Expression::Formula(token, _)
+        | Expression::Newline(token)
+        | Expression::EOF(token)
+        | Expression::Break(token)
+        | Expression::Continue(token) => Some(token),
+        Expression::Term(term) => match term.pre_delimiters {
+            Some(delimiter) => Some(delimiter),
+            None => term.term.first().and_then(first_token),
+        },
+        Expression::Bop(_, lhs, _) | Expression::MultiBop(lhs, _) => first_token(lhs),
+        Expression::FunctionDef(function_def) => Some(function_def.keyword),
+        Expression::LambdaFunction(lambda) => Some(lambda.keyword),
+        Expression::IfExpression(if_expression) => Some(if_expression.if_conditional.keyword),
+        Expression::WhileExpression(while_expression) => Some(while_expression.while_keyword),
+        Expression::RepeatExpression(repeat_expression) => Some(repeat_expression.repeat_keyword),
+        Expression::FunctionCall(call) => first_token(&call.function_ref),
+        Expression::SubsetExpression(subset) => first_token(&subset.object_ref),
+        Expression::ForLoopExpression(for_loop) => Some(for_loop.keyword),
+        Expression::Whitespace(_) => None,
+    }
+}
+
+/// Formats the statement with the spaces after the assigned name.
+fn aligned_statement_to_docs(
+    statement: &Expression,
+    padding: usize,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    match statement {
+        Expression::MultiBop(name, other) if padding > 0 => {
+            let (op, value) = &other[0];
+            let align = text!(&" ".repeat(padding));
+            aligned_assignment_to_docs(name, op, value, align, config, doc_ref)
+        }
+        _ => statement.to_docs(config, doc_ref),
+    }
+}
+
 fn is_expression_bracketed_term_or_function_def(expr: &Option<Expression>) -> bool {
     expr.as_ref().is_some_and(|expr| match expr {
         Expression::Term(term) => {
@@ -1197,44 +2106,62 @@ fn is_expression_bracketed_term_or_function_def(expr: &Option<Expression>) -> bo
     })
 }
 
+/// Whether the term is the embracing operator `{{ }}` around a single
+/// expression, e.g. `{{ var }}`. A block with a block inside, e.g.
+/// `{ {{ var }} }`, and the braces with comments are not embracing.
 fn is_term_embracing_op(term: &TermExpr) -> bool {
-    if let Some(pre_delim) = term.pre_delimiters {
-        if matches!(pre_delim.token, Token::LBrace)
-            && !term.term.is_empty()
-            && matches!(term.term[0], Expression::Term(_))
-        {
-            let first_expr = &term.term[0];
-            if let Expression::Term(inner_term) = first_expr {
-                return inner_term
-                    .pre_delimiters
-                    .is_some_and(|pre_delim| matches!(pre_delim.token, Token::LBrace));
-            }
-        }
-    }
-    false
+    let is_l_brace = |delim: Option<&CommentedToken>| {
+        delim.is_some_and(|delim| matches!(delim.token, Token::LBrace))
+    };
+    let has_comments = |delim: Option<&CommentedToken>| {
+        delim
+            .is_some_and(|delim| delim.leading_comments.is_some() || delim.inline_comment.is_some())
+    };
+    let [Expression::Term(inner_term)] = term.term.as_slice() else {
+        return false;
+    };
+    let embraces_block = matches!(
+        inner_term.term.as_slice(),
+        [Expression::Term(embraced)] if is_l_brace(embraced.pre_delimiters)
+    );
+    is_l_brace(term.pre_delimiters)
+        && is_l_brace(inner_term.pre_delimiters)
+        && inner_term.term.len() == 1
+        && !embraces_block
+        && term
+            .pre_delimiters
+            .is_none_or(|delim| delim.inline_comment.is_none())
+        && term
+            .post_delimiters
+            .is_none_or(|delim| delim.leading_comments.is_none())
+        && !has_comments(inner_term.pre_delimiters)
+        && !has_comments(inner_term.post_delimiters)
 }
 
 /// Forced line breaks are line breaks inside a group
 /// with ShouldBreak::Yes
-fn has_forced_line_breaks(doc: &Rc<Doc>, inside_a_group_with_should_break: bool) -> bool {
+fn has_forced_line_breaks(doc: &DocRc<Doc>, inside_a_group_with_should_break: bool) -> bool {
     match doc.deref() {
         Doc::Nil => false,
         Doc::Cons(first, second, _) => {
             has_forced_line_breaks(first, inside_a_group_with_should_break)
                 || has_forced_line_breaks(second, inside_a_group_with_should_break)
         }
-        Doc::Text(_, _, _) => false,
+        Doc::Text(_, _, _) | Doc::Align(..) => false,
         Doc::Nest(_, inner, _) => has_forced_line_breaks(inner, inside_a_group_with_should_break),
         Doc::NestIfBreak(_, inner, _, _) => {
             has_forced_line_breaks(inner, inside_a_group_with_should_break)
         }
-        Doc::NestHanging(inner, _) => {
+        Doc::NestHanging(inner, _) | Doc::NestHangingIfBreak(inner, _, _) => {
             has_forced_line_breaks(inner, inside_a_group_with_should_break)
         }
-        Doc::FitsUntilLBracket(inner, _) => {
+        Doc::FitsUntilLBracket(inner, _, _)
+        | Doc::Embrace(inner, _)
+        | Doc::BreakBeforeIfFits(_, inner, _) => {
             has_forced_line_breaks(inner, inside_a_group_with_should_break)
         }
         Doc::Break(_) => inside_a_group_with_should_break,
+        Doc::LiteralLine => false,
         Doc::Group(group_props, _) => has_forced_line_breaks(
             &group_props.0,
             matches!(group_props.1, ShouldBreak::Yes)
@@ -1244,29 +2171,133 @@ fn has_forced_line_breaks(doc: &Rc<Doc>, inside_a_group_with_should_break: bool)
     }
 }
 
+fn is_access_operator(op: &CommentedToken) -> bool {
+    matches!(op.token, Token::Dollar | Token::Slot)
+}
+
+/// Returns the object at the start of a chain of `$` and `@`,
+/// e.g. `config` in `config$server$options`, and pushes the accesses
+/// of the chain with the accessed names.
+fn collect_accesses<'e, 'a>(
+    expr: &'e Expression<'a>,
+    accesses: &mut Vec<(&'e CommentedToken<'a>, &'e Expression<'a>)>,
+) -> &'e Expression<'a> {
+    match expr {
+        Expression::Bop(op, lhs, rhs) if is_access_operator(op) => {
+            let object = collect_accesses(lhs, accesses);
+            accesses.push((op, rhs));
+            object
+        }
+        Expression::MultiBop(lhs, other) if other.iter().all(|(op, _)| is_access_operator(op)) => {
+            let object = collect_accesses(lhs, accesses);
+            accesses.extend(other.iter().map(|(op, rhs)| (*op, &**rhs)));
+            object
+        }
+        _ => expr,
+    }
+}
+
+/// Returns the object at the start of a chain of `[` and `[[`,
+/// e.g. `x` in `x[["a"]][["b"]]`, and pushes the brackets of the chain.
+fn collect_subsets<'e, 'a>(
+    expr: &'e Expression<'a>,
+    subsets: &mut Vec<&'e Args<'a>>,
+) -> &'e Expression<'a> {
+    match expr {
+        Expression::SubsetExpression(subset_expression) => {
+            let object = collect_subsets(&subset_expression.object_ref, subsets);
+            subsets.push(&subset_expression.args);
+            object
+        }
+        _ => expr,
+    }
+}
+
+/// Whether the expression is a chain of accesses long enough to be broken
+/// into lines. See [FormattingConfig::min_access_chain_length].
+fn is_long_access_chain(expr: &Expression, config: &impl FormattingConfig) -> bool {
+    let length = if matches!(expr, Expression::SubsetExpression(_)) {
+        let mut subsets = vec![];
+        collect_subsets(expr, &mut subsets);
+        subsets.len()
+    } else {
+        let mut accesses = vec![];
+        collect_accesses(expr, &mut accesses);
+        accesses.len()
+    };
+    length > 0 && length as i32 >= config.min_access_chain_length()
+}
+
+/// Formats a chain of accesses, which breaks after every `$` and `@`
+/// or inside all of the brackets, e.g.
+/// config$
+///   server$
+///   options
+fn access_chain_to_docs(
+    expr: &Expression,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    let mut observed_doc = *doc_ref;
+    let chain = if matches!(expr, Expression::SubsetExpression(_)) {
+        let mut subsets = vec![];
+        let object = collect_subsets(expr, &mut subsets);
+        subsets.into_iter().fold(
+            object
+                .to_docs(config, doc_ref)
+                .to_group(ShouldBreak::No, doc_ref),
+            |chain, args| {
+                // The brackets are not grouped, so all of them break with the chain
+                match args_to_docs(
+                    args,
+                    HuggedArgument::from_heuristic(config),
+                    config,
+                    doc_ref,
+                    observed_doc + 1,
+                ) {
+                    (docs, Some(inline)) => chain.cons(docs).cons(text!(" ")).cons(inline),
+                    (docs, None) => chain.cons(docs),
+                }
+            },
+        )
+    } else {
+        let mut accesses = vec![];
+        let object = collect_accesses(expr, &mut accesses);
+        accesses.into_iter().fold(
+            object
+                .to_docs(config, doc_ref)
+                .to_group(ShouldBreak::No, doc_ref),
+            |chain, (op, name)| {
+                chain.cons(op.to_docs(config, doc_ref)).cons(
+                    nl!("")
+                        .cons(
+                            name.to_docs(config, doc_ref)
+                                .to_group(ShouldBreak::No, doc_ref),
+                        )
+                        .nest(config.indent()),
+                )
+            },
+        )
+    };
+    chain.to_group(ShouldBreak::No, &mut observed_doc)
+}
+
 /// Delimited content requires special care with comments at the end of it...
 fn delimited_content_to_docs(
     left_delim: &CommentedToken<'_>,
-    inner: Rc<Doc>,
+    inner: DocRc<Doc>,
     right_delim: &CommentedToken<'_>,
     config: &impl FormattingConfig,
     doc_ref: &mut usize,
     should_break: ShouldBreak,
-) -> Rc<Doc> {
+) -> DocRc<Doc> {
     let nl = || match left_delim.token {
         Token::LParen => nl!(""),
         Token::LBrace => nl!(" "),
         _ => unreachable!("Non parenthesis argument as the delimiter"),
     };
     if let Some(right_delim_leading_comments) = &right_delim.leading_comments {
-        let mut leading_comments_it = right_delim_leading_comments.iter();
-        let mut leading_comments = text!(leading_comments_it.next().unwrap());
-        for comment in leading_comments_it {
-            leading_comments = leading_comments.cons(nl!("")).cons(text!(comment, 0));
-        }
-        let leading_comments = leading_comments
-            .nest_hanging()
-            .to_group(ShouldBreak::Yes, doc_ref);
+        let leading_comments = closing_comments_to_docs(right_delim_leading_comments, doc_ref);
         left_delim
             .to_docs(config, doc_ref)
             .cons(
@@ -1288,6 +2319,47 @@ fn delimited_content_to_docs(
     }
 }
 
+/// Returns the docs of an empty block, `{}`, or of the block with
+/// only comments, which go on their own lines.
+fn empty_block_to_docs(
+    left_brace: &CommentedToken,
+    right_brace: &CommentedToken,
+    config: &impl FormattingConfig,
+    doc_ref: &mut usize,
+) -> DocRc<Doc> {
+    // The comments inside an empty block are the leading comments
+    // of the closing brace
+    let closing = right_brace.to_docs_without_leading_comments(config, doc_ref);
+    let opening = left_brace.to_docs(config, doc_ref);
+    match &right_brace.leading_comments {
+        Some(comments) => opening
+            .cons(
+                nl!("")
+                    .cons(closing_comments_to_docs(comments, doc_ref))
+                    .nest(config.indent()),
+            )
+            .cons(nl!(""))
+            .cons(closing)
+            .to_group(ShouldBreak::Propagate, doc_ref),
+        None if left_brace.inline_comment.is_some() || config.expand_empty_blocks() => opening
+            .cons(nl!(""))
+            .cons(closing)
+            .to_group(ShouldBreak::Yes, doc_ref),
+        None => opening.cons(closing),
+    }
+}
+
+/// Returns the docs of the comments before a closing bracket,
+/// each on its own line.
+fn closing_comments_to_docs(comments: &[&str], doc_ref: &mut usize) -> DocRc<Doc> {
+    let mut comments_it = comments.iter();
+    let mut docs = text!(comments_it.next().unwrap());
+    for comment in comments_it {
+        docs = docs.cons(nl!("")).cons(text!(comment, 0));
+    }
+    docs.nest_hanging().to_group(ShouldBreak::Yes, doc_ref)
+}
+
 fn is_closure_with_brackets(expr: &Expression) -> bool {
     if let Expression::Term(term) = expr {
         term.pre_delimiters
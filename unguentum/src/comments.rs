@@ -0,0 +1,171 @@
+//! The kinds of comments the formatter treats specially.
+
+/// The shortest run of the characters ending a section comment.
+const MIN_SECTION_MARKER_LENGTH: usize = 4;
+
+/// Returns the character of the marker ending an RStudio section comment,
+/// e.g. `-` of `# Setup ----`, or `None` if the comment is not one.
+///
+/// The marker is a run of at least four `-`, `=` or `#` after the name
+/// of the section.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_formatter::comments::section_marker;
+///
+/// assert_eq!(section_marker("# Setup ----"), Some('-'));
+/// assert_eq!(section_marker("## Models ####"), Some('#'));
+/// assert_eq!(section_marker("# x - y"), None);
+/// ```
+pub fn section_marker(comment: &str) -> Option<char> {
+    let comment = comment.trim_end();
+    if !comment.starts_with('#') || comment.starts_with("#'") {
+        return None;
+    }
+    let marker = comment
+        .chars()
+        .last()
+        .filter(|last| matches!(last, '-' | '=' | '#'))?;
+    let name = comment.trim_end_matches(marker);
+    let has_name = !name.trim_start_matches('#').trim().is_empty();
+    (has_name && comment.len() - name.len() >= MIN_SECTION_MARKER_LENGTH).then_some(marker)
+}
+
+/// Whether the comment looks like commented-out code, e.g. `# x <- f(y)`,
+/// rather than prose: it starts with a name followed by `(`, `<-` or `=`,
+/// or with a keyword of R, or it is an argument, e.g. `# b,`.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_formatter::comments::is_commented_code;
+///
+/// assert!(is_commented_code("# x <- f(y)"));
+/// assert!(is_commented_code("#print(x)"));
+/// assert!(is_commented_code("# b,"));
+/// assert!(!is_commented_code("# Prints x (and y)"));
+/// ```
+pub fn is_commented_code(comment: &str) -> bool {
+    let text = comment.trim_start_matches('#').trim();
+    if ["if (", "for (", "while (", "function(", "library(", "}"]
+        .iter()
+        .any(|start| text.starts_with(start))
+    {
+        return true;
+    }
+    let name_length = text
+        .find(|character: char| {
+            !(character.is_alphanumeric() || matches!(character, '.' | '_' | ':' | '$' | '@'))
+        })
+        .unwrap_or(text.len());
+    if name_length == 0 {
+        return false;
+    }
+    let rest = text[name_length..].trim_start();
+    rest == ","
+        || text[name_length..].starts_with('(')
+        || rest.starts_with("<-")
+        || rest.starts_with("<<-")
+        || (rest.starts_with('=') && !rest.starts_with("=="))
+}
+
+/// Whether the comment is a line of the file prologue read by other
+/// programs: a shebang, e.g. `#!/usr/bin/env Rscript`, or an editor
+/// mode line, e.g. `# -*- coding: utf-8 -*-`. These lines are kept
+/// as they are.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_formatter::comments::is_prologue_comment;
+///
+/// assert!(is_prologue_comment("#!/usr/bin/env Rscript"));
+/// assert!(is_prologue_comment("# -*- mode: R; coding: utf-8 -*-"));
+/// assert!(!is_prologue_comment("# Reads the -*- files"));
+/// ```
+pub fn is_prologue_comment(comment: &str) -> bool {
+    let text = comment.trim_start_matches('#').trim();
+    comment.starts_with("#!")
+        || (text.len() >= 6 && text.starts_with("-*-") && text.ends_with("-*-"))
+}
+
+/// The layout of a call pinned by the directive comment after it,
+/// regardless of whether the call fits on the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutDirective {
+    /// Each argument on its own line, `# tergo: expand`.
+    Expand,
+    /// All the arguments on the line of the call, `# tergo: collapse`.
+    Collapse,
+}
+
+/// Returns the layout pinned by the directive comment, e.g.
+/// `# tergo: expand`, or `None` if the comment is not a directive.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_formatter::comments::{LayoutDirective, layout_directive};
+///
+/// assert_eq!(layout_directive("# tergo: expand"), Some(LayoutDirective::Expand));
+/// assert_eq!(layout_directive("#tergo:collapse"), Some(LayoutDirective::Collapse));
+/// assert_eq!(layout_directive("# tergo expands this"), None);
+/// ```
+pub fn layout_directive(comment: &str) -> Option<LayoutDirective> {
+    let text = comment.strip_prefix('#')?.trim_start();
+    match text.strip_prefix("tergo:")?.trim() {
+        "expand" => Some(LayoutDirective::Expand),
+        "collapse" => Some(LayoutDirective::Collapse),
+        _ => None,
+    }
+}
+
+/// Whether the comment is prose, which can be split into more lines:
+/// its `#` are followed by a space and it is neither a section comment,
+/// nor commented-out code, nor a line of the file prologue.
+pub fn is_prose_comment(comment: &str) -> bool {
+    let text = comment.trim_start_matches('#');
+    text.len() < comment.len()
+        && text.starts_with(' ')
+        && section_marker(comment).is_none()
+        && !is_commented_code(comment)
+        && !is_prologue_comment(comment)
+}
+
+/// Splits the comment into comments with the same prefix, e.g. `# `,
+/// each at most `width` characters long, if it is a prose comment
+/// longer than `width`.
+///
+/// The words longer than the width are not split. The comments which
+/// are not prose, see [is_prose_comment], e.g. the roxygen comments,
+/// are returned as they are.
+///
+/// # Example
+///
+/// ```rust
+/// use tergo_formatter::comments::wrap_comment;
+///
+/// assert_eq!(wrap_comment("# one two three", 10), ["# one two", "# three"]);
+/// assert_eq!(wrap_comment("# x <- f(y, z)", 10), ["# x <- f(y, z)"]);
+/// ```
+pub fn wrap_comment(comment: &str, width: usize) -> Vec<String> {
+    if !is_prose_comment(comment) || comment.chars().count() <= width {
+        return vec![comment.to_string()];
+    }
+    let text = comment.trim_start_matches('#').trim_start();
+    let prefix = &comment[..comment.len() - text.len()];
+    let mut lines: Vec<String> = vec![];
+    let mut line = prefix.to_string();
+    for word in text.split_whitespace() {
+        let is_empty = line.len() == prefix.len();
+        if !is_empty && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::replace(&mut line, prefix.to_string()));
+        } else if !is_empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
@@ -1,18 +1,53 @@
-use serde::Deserialize;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::plugin::{FormattingPlugin, Plugins};
 
 pub trait FormattingConfig: std::fmt::Display + Clone {
     fn line_length(&self) -> i32;
+    fn line_length_tolerance(&self) -> i32;
     fn indent(&self) -> i32;
+    fn continuation_indent(&self) -> i32;
+    fn hanging_binary_operations(&self) -> bool;
     // Custom embracing behaviour: https://style.tidyverse.org/syntax.html#embracing
     fn embracing_op_no_nl(&self) -> bool;
     fn allow_nl_after_assignment(&self) -> bool;
+    fn break_after_assignment(&self) -> bool;
     fn space_before_complex_rhs_in_formulas(&self) -> bool;
     fn strip_suffix_whitespace_in_function_defs(&self) -> bool;
     fn function_line_breaks(&self) -> FunctionLineBreaks;
     fn insert_newline_in_quote_call(&self) -> bool;
+    fn min_access_chain_length(&self) -> i32;
+    fn align_named_arguments(&self) -> bool;
+    fn align_assignments(&self) -> bool;
+    fn data_table_dialect(&self) -> bool;
+    fn last_braced_argument_heuristic(&self) -> bool;
+    fn closure_argument_functions(&self) -> &[String];
+    fn expanded_call_functions(&self) -> &[String];
+    fn max_blank_lines_in_blocks(&self) -> i32;
+    fn blank_lines_between_functions(&self) -> i32;
+    fn space_after_keyword(&self) -> bool;
+    fn space_after_function(&self) -> bool;
+    fn trailing_comma(&self) -> TrailingComma;
+    fn collapse_if_blocks(&self) -> bool;
+    fn collapse_loop_blocks(&self) -> bool;
+    fn collapse_function_blocks(&self) -> bool;
+    fn expand_empty_blocks(&self) -> bool;
+    fn pad_section_comments(&self) -> bool;
+    fn wrap_comments(&self) -> bool;
+    fn comment_line_length(&self) -> i32;
+    fn normalize_assignment(&self) -> NormalizeAssignment;
+    fn parenthesize_mixed_logical_operators(&self) -> bool;
+    fn remove_redundant_backticks(&self) -> bool;
+    fn normalize_numbers(&self) -> bool;
+    fn plugins(&self) -> &[Arc<dyn FormattingPlugin>] {
+        &[]
+    }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Default, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum FunctionLineBreaks {
     #[default]
@@ -21,10 +56,67 @@ pub enum FunctionLineBreaks {
     Single,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase", try_from = "NormalizeAssignmentValue")]
+pub enum NormalizeAssignment {
+    #[default]
+    None,
+    Arrow,
+    Equal,
+}
+
+/// The values of `normalize_assignment` in the configuration files:
+/// one of the operator names or `false`, the same as `"none"`.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "schema",
+    schemars(extend("enum" = [false, "none", "arrow", "equal"]))
+)]
+#[serde(untagged)]
+enum NormalizeAssignmentValue {
+    Flag(bool),
+    Name(String),
+}
+
+impl TryFrom<NormalizeAssignmentValue> for NormalizeAssignment {
+    type Error = String;
+
+    fn try_from(value: NormalizeAssignmentValue) -> Result<Self, Self::Error> {
+        match value {
+            NormalizeAssignmentValue::Flag(false) => Ok(NormalizeAssignment::None),
+            NormalizeAssignmentValue::Flag(true) => Err(
+                "normalize_assignment = true is ambiguous, use \"arrow\" or \"equal\"".to_string(),
+            ),
+            NormalizeAssignmentValue::Name(name) => match name.as_str() {
+                "none" => Ok(NormalizeAssignment::None),
+                "arrow" => Ok(NormalizeAssignment::Arrow),
+                "equal" => Ok(NormalizeAssignment::Equal),
+                _ => Err(format!(
+                    "unknown normalize_assignment `{name}`, expected one of `none`, `arrow`, `equal` or `false`"
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingComma {
+    #[default]
+    Preserve,
+    Never,
+}
+
 /// The configuration for `tergo`.
 ///
 /// This configuration can also read from a TOML file.
-#[derive(Debug, Clone, Deserialize, Default)]
+/// Unknown keys are rejected, so typos do not go unnoticed.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// The number of characters to use for one level of indentation.
     ///
@@ -32,6 +124,36 @@ pub struct Config {
     #[serde(default)]
     pub indent: Indent,
 
+    /// The number of characters to indent the continuation lines
+    /// of the expressions broken at a binary operator or a pipe, e.g.
+    ///
+    /// ```R
+    /// result <- first_value +
+    ///     second_value # for continuation_indent = 4
+    /// ```
+    ///
+    /// The blocks in braces are still indented by `indent`.
+    /// If 0, the continuation lines are indented by `indent`.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub continuation_indent: ContinuationIndent,
+
+    /// A logical flag indicating whether to align the operands of the broken
+    /// arithmetic, comparison and logical operations under the first operand
+    /// instead of indenting them by `continuation_indent`, e.g.
+    ///
+    /// ```R
+    /// result <- first_value +
+    ///           second_value # for hanging_binary_operations = true
+    /// ```
+    ///
+    /// The pipes and the assignments are always indented.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub hanging_binary_operations: HangingBinaryOperations,
+
     /// Tha maximum number of characters in a line of the formatted
     /// code. `tergo` will ensure lines do not exceed this number
     /// if possible.
@@ -40,6 +162,15 @@ pub struct Config {
     #[serde(default)]
     pub line_length: LineLength,
 
+    /// The percentage of `line_length` by which the code may overflow
+    /// the line and still stay on one line, e.g. with `line_length = 80`
+    /// and `line_length_tolerance = 5`, a call 82 characters long
+    /// is not broken into one argument per line.
+    ///
+    /// Default: 0, which never allows the overflow.
+    #[serde(default)]
+    pub line_length_tolerance: LineLengthTolerance,
+
     /// A logical flag to determine whether to suppress line
     /// breaks for embracing operator `{{}}`.
     ///
@@ -75,6 +206,23 @@ pub struct Config {
     #[serde(default)]
     pub allow_nl_after_assignment: AllowNlAfterAssignment,
 
+    /// A logical flag indicating whether to break the line after
+    /// `<-`, `<<-` or `:=` if the assigned value then fits
+    /// on the next line, instead of breaking the value, e.g.
+    ///
+    /// ```R
+    /// result <-
+    ///   compute(first_argument, second_argument)
+    /// ```
+    ///
+    /// If the value does not fit on the next line either, it stays
+    /// on the line of the operator. It has no effect if
+    /// `allow_nl_after_assignment` is true.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub break_after_assignment: BreakAfterAssignment,
+
     /// A logical flag indicating whether to put a space before complex right hand sides of
     /// the formula operator. Example:
     ///
@@ -169,6 +317,458 @@ pub struct Config {
     #[serde(default)]
     pub insert_newline_in_quote_call: InsertNewlineInQuoteCall,
 
+    /// The minimum number of accesses with `$`, `@`, `[` or `[[` in a chain,
+    /// e.g. `config$server$options`, for the chain to be broken
+    /// into lines if it does not fit in one.
+    ///
+    /// A chain of `$` and `@` breaks after each of them, with the names indented:
+    ///
+    /// ```R
+    /// config$
+    ///   server$
+    ///   options$
+    ///   timeout
+    /// ```
+    ///
+    /// A chain of `[` and `[[` breaks inside all of its brackets at once.
+    /// The shorter chains are never broken after `$` or `@`.
+    ///
+    /// Default: 3.
+    #[serde(default)]
+    pub min_access_chain_length: MinAccessChainLength,
+
+    /// A logical flag indicating whether to align the `=` of the consecutive
+    /// named arguments of a call broken one argument per line, e.g.:
+    ///
+    /// ```R
+    /// textInput(
+    ///   inputId = "name",
+    ///   label   = "Name",
+    ///   value   = ""
+    /// )
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub align_named_arguments: AlignNamedArguments,
+
+    /// A logical flag indicating whether to align the assignment operators
+    /// of the consecutive assignments to a name, e.g.:
+    ///
+    /// ```R
+    /// x      <- 1
+    /// height <- 2
+    /// ```
+    ///
+    /// If false, only the consecutive assignments already aligned
+    /// in the code stay aligned.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub align_assignments: AlignAssignments,
+
+    /// A logical flag indicating whether to format the `[i, j, by]`
+    /// subsetting of `data.table` in its own style. An empty `i`
+    /// stays after the bracket when the subsetting is broken into lines:
+    ///
+    /// ```R
+    /// DT[,
+    ///   .(total = sum(value), count = .N),
+    ///   by = .(group)
+    /// ]
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub data_table_dialect: DataTableDialect,
+
+    /// A logical flag indicating whether to keep the braced last argument
+    /// of any call on the line of the call, e.g. a closure:
+    ///
+    /// ```R
+    /// test_that("it works", {
+    ///   expect_true(TRUE)
+    /// })
+    /// ```
+    ///
+    /// If false, only the calls of [Config::closure_argument_functions]
+    /// keep it on their line.
+    ///
+    /// Default: true.
+    #[serde(default)]
+    pub last_braced_argument_heuristic: LastBracedArgumentHeuristic,
+
+    /// The names of the functions whose braced last argument, e.g.
+    /// the body of `reactive({ ... })`, stays on the line of the call
+    /// if [Config::last_braced_argument_heuristic] is false.
+    ///
+    /// Default: `test_that`, `describe`, `it`, `reactive`, `observe`,
+    /// `observeEvent`, `eventReactive`, `tryCatch`, `withCallingHandlers`,
+    /// `with`, `local`, `map`, `lapply`, `sapply` and `vapply`.
+    #[serde(default)]
+    pub closure_argument_functions: ClosureArgumentFunctions,
+
+    /// The names of the functions whose calls put each argument
+    /// on its own line, even if the call fits in the line, e.g.
+    /// the functions of the user interface of a Shiny app:
+    ///
+    /// ```R
+    /// # If expanded_call_functions = ["fluidPage", "sidebarLayout"]
+    /// fluidPage(
+    ///   titlePanel("App"),
+    ///   sidebarLayout(
+    ///     sidebarPanel(),
+    ///     mainPanel()
+    ///   )
+    /// )
+    /// ```
+    ///
+    /// Default: none.
+    #[serde(default)]
+    pub expanded_call_functions: ExpandedCallFunctions,
+
+    /// The maximum number of consecutive blank lines kept between
+    /// the statements in braces, e.g. in the body of a function.
+    ///
+    /// Default: 1.
+    #[serde(default)]
+    pub max_blank_lines_in_blocks: MaxBlankLinesInBlocks,
+
+    /// The number of blank lines between a top-level function definition
+    /// and the statements around it, e.g. 1:
+    ///
+    /// ```R
+    /// f <- function() 1
+    ///
+    /// g <- function() 2
+    /// ```
+    ///
+    /// If 0, at most one blank line of the code is kept,
+    /// like between the other top-level statements.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub blank_lines_between_functions: BlankLinesBetweenFunctions,
+
+    /// A logical flag indicating whether to put a space between
+    /// `if`, `for` and `while` and their opening parenthesis: `if (x)`.
+    ///
+    /// Default: true.
+    #[serde(default)]
+    pub space_after_keyword: SpaceAfterKeyword,
+
+    /// A logical flag indicating whether to put a space between
+    /// `function` and its arguments: `function (x)`.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub space_after_function: SpaceAfterFunction,
+
+    /// What to do with a comma after the last argument of a call,
+    /// e.g. `tibble::tribble(~a, 1,)`. The comma never changes how
+    /// the arguments are broken into lines.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If trailing_comma = "preserve"
+    /// tibble::tribble(~a, ~b, 1, 2,)
+    ///
+    /// # If trailing_comma = "never"
+    /// tibble::tribble(~a, ~b, 1, 2)
+    /// ```
+    ///
+    /// The commas in the subsets, e.g. `x[1, ]`, are always kept.
+    ///
+    /// Default: `preserve`.
+    #[serde(default)]
+    pub trailing_comma: TrailingComma,
+
+    /// A logical flag indicating whether to keep the braced bodies
+    /// of `if` and `else` on one line if each of them has a single
+    /// expression and the whole `if` fits in the line.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If collapse_if_blocks = true
+    /// if (a) { b } else { c }
+    ///
+    /// # If collapse_if_blocks = false
+    /// if (a) {
+    ///   b
+    /// } else {
+    ///   c
+    /// }
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub collapse_if_blocks: CollapseIfBlocks,
+
+    /// A logical flag indicating whether to keep the braced body
+    /// of a `for`, `while` or `repeat` loop with a single expression
+    /// on one line if the loop fits in the line: `for (x in xs) { f(x) }`.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub collapse_loop_blocks: CollapseLoopBlocks,
+
+    /// A logical flag indicating whether to keep the braced body
+    /// of a function with a single expression on one line if the function
+    /// fits in the line: `function(x) { x + 1 }`.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub collapse_function_blocks: CollapseFunctionBlocks,
+
+    /// A logical flag indicating whether to put the closing brace of
+    /// an empty block, e.g. of a function, on its own line.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If expand_empty_blocks = true
+    /// f <- function() {
+    /// }
+    ///
+    /// # If expand_empty_blocks = false
+    /// f <- function() {}
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub expand_empty_blocks: ExpandEmptyBlocks,
+
+    /// A logical flag indicating whether to pad the RStudio section
+    /// comments, e.g. `# Setup ----`, with their marker to the
+    /// `comment_line_length`. The marker is a run of at least four `-`,
+    /// `=` or `#`. The longer section comments are kept as they are.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If pad_section_comments = true and line_length = 30
+    /// # Setup -----------------------
+    ///
+    /// # If pad_section_comments = false
+    /// # Setup ----
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub pad_section_comments: PadSectionComments,
+
+    /// A logical flag indicating whether to split the prose comments
+    /// on their own lines, which are longer than `comment_line_length`, into
+    /// more lines. The comments are never joined. The roxygen comments,
+    /// the section comments and the comments which look like code,
+    /// e.g. `# x <- f(y)`, are not changed.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If wrap_comments = true and line_length = 30
+    /// # Computes the mean of the
+    /// # values without the missing
+    /// # ones.
+    ///
+    /// # If wrap_comments = false
+    /// # Computes the mean of the values without the missing ones.
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub wrap_comments: WrapComments,
+
+    /// The maximum number of characters in a line of the comments
+    /// wrapped by `wrap_comments` and padded by `pad_section_comments`,
+    /// e.g. 100 lets the prose comments run longer than the code
+    /// with `line_length = 80`. The comments are otherwise never
+    /// wrapped, so this does not change how the code is broken.
+    ///
+    /// Default: 0, which uses `line_length`.
+    #[serde(default)]
+    pub comment_line_length: CommentLineLength,
+
+    /// Which operator the assignment statements use. `<<-`, `->`
+    /// and the `=` naming the arguments of the calls are never changed.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If normalize_assignment = "arrow"
+    /// x <- 1
+    ///
+    /// # If normalize_assignment = "equal"
+    /// x = 1
+    /// ```
+    ///
+    /// Default: `none` (or `false`), which keeps the operators of the code:
+    /// each of `=`, `<-` and `->` stays as written.
+    #[serde(default)]
+    pub normalize_assignment: NormalizeAssignment,
+
+    /// A logical flag indicating whether to put the parentheses around
+    /// the `&&` operations in the operands of `||`, which show the order
+    /// of the operations. Otherwise the formatter never adds nor removes
+    /// any parentheses.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If parenthesize_mixed_logical_operators = true
+    /// a || (b && c)
+    ///
+    /// # If parenthesize_mixed_logical_operators = false
+    /// a || b && c
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub parenthesize_mixed_logical_operators: ParenthesizeMixedLogicalOperators,
+
+    /// A logical flag indicating whether to remove the backticks around
+    /// the names which are syntactic without them. The backticks around
+    /// the reserved words, e.g. `` `if` ``, and the other non-syntactic
+    /// names, e.g. `` `my var` ``, are kept.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If remove_redundant_backticks = true
+    /// mean(x$value, na.rm = TRUE)
+    /// df$`first name`
+    ///
+    /// # If remove_redundant_backticks = false
+    /// `mean`(x$`value`, na.rm = TRUE)
+    /// df$`first name`
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub remove_redundant_backticks: RemoveRedundantBackticks,
+
+    /// A logical flag indicating whether to normalize the exponents
+    /// of the numbers: the `E` is lowercased and the `+` sign and
+    /// the leading zeros are removed. The digits before the exponent,
+    /// e.g. the trailing zeros of `1.50`, and the hexadecimal numbers
+    /// are kept.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If normalize_numbers = true
+    /// x <- c(1e3, 2.5e-7, 1.50)
+    ///
+    /// # If normalize_numbers = false
+    /// x <- c(1e+03, 2.5E-07, 1.50)
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub normalize_numbers: NormalizeNumbers,
+
+    /// A logical flag indicating whether to format the code in
+    /// the `@examples` and `@examplesIf` sections of the roxygen comments
+    /// of functions.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If format_roxygen_examples = true
+    /// #' @examples
+    /// #' x <- c(1, 2)
+    ///
+    /// # If format_roxygen_examples = false
+    /// #' @examples
+    /// #' x<-c(1,2)
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub format_roxygen_examples: FormatRoxygenExamples,
+
+    /// A logical flag indicating whether to format the code in
+    /// the `{...}` interpolations of the strings passed to `glue()`.
+    ///
+    /// The literal text of the strings is kept. The interpolations
+    /// which would span more lines are left untouched.
+    ///
+    /// Examples:
+    ///
+    /// ```R
+    /// # If format_glue_strings = true
+    /// glue("Total: {sum(x, na.rm = TRUE)}")
+    ///
+    /// # If format_glue_strings = false
+    /// glue("Total: {sum(x,na.rm=TRUE)}")
+    /// ```
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub format_glue_strings: FormatGlueStrings,
+
+    /// A logical flag indicating whether to format the files without
+    /// an extension that start with an `Rscript` shebang, e.g.:
+    ///
+    /// ```R
+    /// #!/usr/bin/env Rscript
+    /// ```
+    ///
+    /// The shebang line is kept as it is. Only used by the command line
+    /// program when looking for the files to format.
+    ///
+    /// Default: false.
+    #[serde(default)]
+    pub format_shebang_scripts: FormatShebangScripts,
+
+    /// A logical flag indicating whether to check that the formatting
+    /// did not change the meaning of the code.
+    ///
+    /// The formatted code is parsed again and compared to the original
+    /// code, ignoring the whitespace and the comments. If they differ,
+    /// formatting fails instead of returning the broken code.
+    /// The check is skipped if any [FormattingPlugin] is registered.
+    ///
+    /// Default: true.
+    #[serde(default)]
+    pub verify: Verify,
+
+    /// The size of the largest file formatted, in bytes.
+    ///
+    /// The larger files, e.g. the generated ones, are skipped
+    /// with a warning instead of taking long to format.
+    /// If 0, the files of any size are formatted.
+    ///
+    /// Default: 1000000.
+    #[serde(default)]
+    pub max_file_size: MaxFileSize,
+
+    /// The deepest nesting of parentheses, braces and brackets in the code
+    /// formatted, e.g. 2 in `f(g(x))`.
+    ///
+    /// The more deeply nested code is skipped with a warning instead
+    /// of exhausting the stack. If 0, the code is formatted at any depth.
+    ///
+    /// Default: 100.
+    #[serde(default)]
+    pub max_expression_depth: MaxExpressionDepth,
+
+    /// The comments marking the files generated by a tool, e.g. by roxygen2,
+    /// Rcpp or cpp11, which overwrites them.
+    ///
+    /// The files with one of the markers in a comment in their first lines
+    /// are skipped by `tergo`, unless run with `--force`.
+    ///
+    /// Example values:
+    ///
+    /// generated_file_markers = ["Generated by roxygen2: do not edit by hand"]
+    ///
+    /// Default: the markers of roxygen2, Rcpp and cpp11.
+    #[serde(default)]
+    pub generated_file_markers: GeneratedFileMarkers,
+
     /// A list of file paths to exclude from formatting.
     ///
     /// The file paths are relative to the directory
@@ -186,16 +786,32 @@ pub struct Config {
     /// "./target"]
     #[serde(default)]
     pub exclusion_list: ExclusionList,
+
+    /// The custom formatting rules. See [FormattingPlugin].
+    ///
+    /// Only set from code, e.g. with [ConfigBuilder::plugin].
+    #[serde(skip)]
+    pub plugins: Plugins,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Indent(pub i32);
 impl Default for Indent {
     fn default() -> Self {
         Self(2)
     }
 }
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ContinuationIndent(pub i32);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HangingBinaryOperations(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct LineLength(pub i32);
 impl Default for LineLength {
     fn default() -> Self {
@@ -203,7 +819,12 @@ impl Default for LineLength {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LineLengthTolerance(pub i32);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EmbracingOpNoNl(pub bool);
 impl Default for EmbracingOpNoNl {
     fn default() -> Self {
@@ -211,10 +832,16 @@ impl Default for EmbracingOpNoNl {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AllowNlAfterAssignment(pub bool);
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BreakAfterAssignment(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SpaceBeforeComplexRhsInFormulas(pub bool);
 impl Default for SpaceBeforeComplexRhsInFormulas {
     fn default() -> Self {
@@ -222,7 +849,8 @@ impl Default for SpaceBeforeComplexRhsInFormulas {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct StripSuffixWhitespaceInFunctionDefs(pub bool);
 impl Default for StripSuffixWhitespaceInFunctionDefs {
     fn default() -> Self {
@@ -230,7 +858,8 @@ impl Default for StripSuffixWhitespaceInFunctionDefs {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct InsertNewlineInQuoteCall(pub bool);
 impl Default for InsertNewlineInQuoteCall {
     fn default() -> Self {
@@ -238,7 +867,189 @@ impl Default for InsertNewlineInQuoteCall {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MinAccessChainLength(pub i32);
+impl Default for MinAccessChainLength {
+    fn default() -> Self {
+        Self(3)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AlignNamedArguments(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AlignAssignments(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DataTableDialect(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LastBracedArgumentHeuristic(pub bool);
+impl Default for LastBracedArgumentHeuristic {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ClosureArgumentFunctions(pub Vec<String>);
+impl Default for ClosureArgumentFunctions {
+    fn default() -> Self {
+        Self(
+            [
+                "test_that",
+                "describe",
+                "it",
+                "reactive",
+                "observe",
+                "observeEvent",
+                "eventReactive",
+                "tryCatch",
+                "withCallingHandlers",
+                "with",
+                "local",
+                "map",
+                "lapply",
+                "sapply",
+                "vapply",
+            ]
+            .map(String::from)
+            .to_vec(),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExpandedCallFunctions(pub Vec<String>);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MaxBlankLinesInBlocks(pub i32);
+impl Default for MaxBlankLinesInBlocks {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BlankLinesBetweenFunctions(pub i32);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SpaceAfterKeyword(pub bool);
+impl Default for SpaceAfterKeyword {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SpaceAfterFunction(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CollapseIfBlocks(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CollapseLoopBlocks(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CollapseFunctionBlocks(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExpandEmptyBlocks(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PadSectionComments(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WrapComments(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CommentLineLength(pub i32);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ParenthesizeMixedLogicalOperators(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RemoveRedundantBackticks(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NormalizeNumbers(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FormatRoxygenExamples(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FormatGlueStrings(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FormatShebangScripts(pub bool);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Verify(pub bool);
+impl Default for Verify {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MaxFileSize(pub i32);
+impl Default for MaxFileSize {
+    fn default() -> Self {
+        Self(1_000_000)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MaxExpressionDepth(pub i32);
+impl Default for MaxExpressionDepth {
+    fn default() -> Self {
+        Self(100)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GeneratedFileMarkers(pub Vec<String>);
+impl Default for GeneratedFileMarkers {
+    fn default() -> Self {
+        Self(vec![
+            "Generated by roxygen2: do not edit by hand".to_string(),
+            "Generated by using Rcpp::compileAttributes() -> do not edit by hand".to_string(),
+            "Generated by cpp11: do not edit by hand".to_string(),
+        ])
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ExclusionList(pub Vec<String>);
 
 impl FormattingConfig for Config {
@@ -246,10 +1057,25 @@ impl FormattingConfig for Config {
         self.line_length.0
     }
 
+    fn line_length_tolerance(&self) -> i32 {
+        self.line_length_tolerance.0
+    }
+
     fn indent(&self) -> i32 {
         self.indent.0
     }
 
+    fn continuation_indent(&self) -> i32 {
+        match self.continuation_indent.0 {
+            0 => self.indent.0,
+            indent => indent,
+        }
+    }
+
+    fn hanging_binary_operations(&self) -> bool {
+        self.hanging_binary_operations.0
+    }
+
     fn embracing_op_no_nl(&self) -> bool {
         self.embracing_op_no_nl.0
     }
@@ -258,6 +1084,10 @@ impl FormattingConfig for Config {
         self.allow_nl_after_assignment.0
     }
 
+    fn break_after_assignment(&self) -> bool {
+        self.break_after_assignment.0
+    }
+
     fn space_before_complex_rhs_in_formulas(&self) -> bool {
         self.space_before_complex_rhs_in_formula.0
     }
@@ -273,6 +1103,105 @@ impl FormattingConfig for Config {
     fn insert_newline_in_quote_call(&self) -> bool {
         self.insert_newline_in_quote_call.0
     }
+
+    fn min_access_chain_length(&self) -> i32 {
+        self.min_access_chain_length.0
+    }
+
+    fn align_named_arguments(&self) -> bool {
+        self.align_named_arguments.0
+    }
+
+    fn align_assignments(&self) -> bool {
+        self.align_assignments.0
+    }
+
+    fn data_table_dialect(&self) -> bool {
+        self.data_table_dialect.0
+    }
+
+    fn last_braced_argument_heuristic(&self) -> bool {
+        self.last_braced_argument_heuristic.0
+    }
+
+    fn closure_argument_functions(&self) -> &[String] {
+        &self.closure_argument_functions.0
+    }
+
+    fn expanded_call_functions(&self) -> &[String] {
+        &self.expanded_call_functions.0
+    }
+
+    fn max_blank_lines_in_blocks(&self) -> i32 {
+        self.max_blank_lines_in_blocks.0
+    }
+
+    fn blank_lines_between_functions(&self) -> i32 {
+        self.blank_lines_between_functions.0
+    }
+
+    fn space_after_keyword(&self) -> bool {
+        self.space_after_keyword.0
+    }
+
+    fn space_after_function(&self) -> bool {
+        self.space_after_function.0
+    }
+
+    fn trailing_comma(&self) -> TrailingComma {
+        self.trailing_comma
+    }
+
+    fn collapse_if_blocks(&self) -> bool {
+        self.collapse_if_blocks.0
+    }
+
+    fn collapse_loop_blocks(&self) -> bool {
+        self.collapse_loop_blocks.0
+    }
+
+    fn collapse_function_blocks(&self) -> bool {
+        self.collapse_function_blocks.0
+    }
+
+    fn expand_empty_blocks(&self) -> bool {
+        self.expand_empty_blocks.0
+    }
+
+    fn pad_section_comments(&self) -> bool {
+        self.pad_section_comments.0
+    }
+
+    fn wrap_comments(&self) -> bool {
+        self.wrap_comments.0
+    }
+
+    fn comment_line_length(&self) -> i32 {
+        match self.comment_line_length.0 {
+            0 => self.line_length.0,
+            line_length => line_length,
+        }
+    }
+
+    fn normalize_assignment(&self) -> NormalizeAssignment {
+        self.normalize_assignment
+    }
+
+    fn parenthesize_mixed_logical_operators(&self) -> bool {
+        self.parenthesize_mixed_logical_operators.0
+    }
+
+    fn remove_redundant_backticks(&self) -> bool {
+        self.remove_redundant_backticks.0
+    }
+
+    fn normalize_numbers(&self) -> bool {
+        self.normalize_numbers.0
+    }
+
+    fn plugins(&self) -> &[Arc<dyn FormattingPlugin>] {
+        &self.plugins.0
+    }
 }
 
 impl std::fmt::Display for Config {
@@ -286,6 +1215,8 @@ impl std::fmt::Display for Config {
 
 #[allow(clippy::too_many_arguments)]
 impl Config {
+    /// Creates a configuration with the given options. The other options
+    /// take their default values, set them with [Config::builder].
     pub fn new(
         indent: i32,
         line_length: i32,
@@ -311,6 +1242,286 @@ impl Config {
             function_line_breaks,
             insert_newline_in_quote_call: InsertNewlineInQuoteCall(insert_newline_in_quote_call),
             exclusion_list: ExclusionList(exclusion_list),
+            ..Self::default()
         }
     }
 }
+
+impl Config {
+    /// Returns a builder of a configuration, starting from the defaults.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tergo_formatter::config::Config;
+    ///
+    /// let config = Config::builder().indent(4).line_length(80).build();
+    /// assert_eq!(config.indent.0, 4);
+    /// ```
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Returns the JSON Schema of the configuration file, generated
+    /// from the options, so the editors can validate and autocomplete it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tergo_formatter::config::Config;
+    ///
+    /// let schema = Config::json_schema();
+    /// assert!(schema.get("properties").unwrap().get("line_length").is_some());
+    /// ```
+    #[cfg(feature = "schema")]
+    pub fn json_schema() -> schemars::Schema {
+        schemars::schema_for!(Config)
+    }
+}
+
+/// Builds a [Config] one option at a time. The options that are not set
+/// keep their default values.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn indent(mut self, indent: i32) -> Self {
+        self.config.indent = Indent(indent);
+        self
+    }
+
+    pub fn continuation_indent(mut self, continuation_indent: i32) -> Self {
+        self.config.continuation_indent = ContinuationIndent(continuation_indent);
+        self
+    }
+
+    pub fn hanging_binary_operations(mut self, value: bool) -> Self {
+        self.config.hanging_binary_operations = HangingBinaryOperations(value);
+        self
+    }
+
+    pub fn line_length(mut self, line_length: i32) -> Self {
+        self.config.line_length = LineLength(line_length);
+        self
+    }
+
+    pub fn line_length_tolerance(mut self, line_length_tolerance: i32) -> Self {
+        self.config.line_length_tolerance = LineLengthTolerance(line_length_tolerance);
+        self
+    }
+
+    pub fn embracing_op_no_nl(mut self, value: bool) -> Self {
+        self.config.embracing_op_no_nl = EmbracingOpNoNl(value);
+        self
+    }
+
+    pub fn allow_nl_after_assignment(mut self, value: bool) -> Self {
+        self.config.allow_nl_after_assignment = AllowNlAfterAssignment(value);
+        self
+    }
+
+    pub fn break_after_assignment(mut self, value: bool) -> Self {
+        self.config.break_after_assignment = BreakAfterAssignment(value);
+        self
+    }
+
+    pub fn space_before_complex_rhs_in_formula(mut self, value: bool) -> Self {
+        self.config.space_before_complex_rhs_in_formula = SpaceBeforeComplexRhsInFormulas(value);
+        self
+    }
+
+    pub fn strip_suffix_whitespace_in_function_defs(mut self, value: bool) -> Self {
+        self.config.strip_suffix_whitespace_in_function_defs =
+            StripSuffixWhitespaceInFunctionDefs(value);
+        self
+    }
+
+    pub fn function_line_breaks(mut self, value: FunctionLineBreaks) -> Self {
+        self.config.function_line_breaks = value;
+        self
+    }
+
+    pub fn insert_newline_in_quote_call(mut self, value: bool) -> Self {
+        self.config.insert_newline_in_quote_call = InsertNewlineInQuoteCall(value);
+        self
+    }
+
+    pub fn min_access_chain_length(mut self, value: i32) -> Self {
+        self.config.min_access_chain_length = MinAccessChainLength(value);
+        self
+    }
+
+    pub fn align_named_arguments(mut self, value: bool) -> Self {
+        self.config.align_named_arguments = AlignNamedArguments(value);
+        self
+    }
+
+    pub fn align_assignments(mut self, value: bool) -> Self {
+        self.config.align_assignments = AlignAssignments(value);
+        self
+    }
+
+    pub fn data_table_dialect(mut self, value: bool) -> Self {
+        self.config.data_table_dialect = DataTableDialect(value);
+        self
+    }
+
+    pub fn last_braced_argument_heuristic(mut self, value: bool) -> Self {
+        self.config.last_braced_argument_heuristic = LastBracedArgumentHeuristic(value);
+        self
+    }
+
+    pub fn closure_argument_functions<S: Into<String>>(
+        mut self,
+        names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.config.closure_argument_functions =
+            ClosureArgumentFunctions(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn expanded_call_functions<S: Into<String>>(
+        mut self,
+        names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.config.expanded_call_functions =
+            ExpandedCallFunctions(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn max_blank_lines_in_blocks(mut self, value: i32) -> Self {
+        self.config.max_blank_lines_in_blocks = MaxBlankLinesInBlocks(value);
+        self
+    }
+
+    pub fn blank_lines_between_functions(mut self, value: i32) -> Self {
+        self.config.blank_lines_between_functions = BlankLinesBetweenFunctions(value);
+        self
+    }
+
+    pub fn space_after_keyword(mut self, value: bool) -> Self {
+        self.config.space_after_keyword = SpaceAfterKeyword(value);
+        self
+    }
+
+    pub fn space_after_function(mut self, value: bool) -> Self {
+        self.config.space_after_function = SpaceAfterFunction(value);
+        self
+    }
+
+    pub fn trailing_comma(mut self, value: TrailingComma) -> Self {
+        self.config.trailing_comma = value;
+        self
+    }
+
+    pub fn collapse_if_blocks(mut self, value: bool) -> Self {
+        self.config.collapse_if_blocks = CollapseIfBlocks(value);
+        self
+    }
+
+    pub fn collapse_loop_blocks(mut self, value: bool) -> Self {
+        self.config.collapse_loop_blocks = CollapseLoopBlocks(value);
+        self
+    }
+
+    pub fn collapse_function_blocks(mut self, value: bool) -> Self {
+        self.config.collapse_function_blocks = CollapseFunctionBlocks(value);
+        self
+    }
+
+    pub fn expand_empty_blocks(mut self, value: bool) -> Self {
+        self.config.expand_empty_blocks = ExpandEmptyBlocks(value);
+        self
+    }
+
+    pub fn pad_section_comments(mut self, value: bool) -> Self {
+        self.config.pad_section_comments = PadSectionComments(value);
+        self
+    }
+
+    pub fn wrap_comments(mut self, value: bool) -> Self {
+        self.config.wrap_comments = WrapComments(value);
+        self
+    }
+
+    pub fn comment_line_length(mut self, comment_line_length: i32) -> Self {
+        self.config.comment_line_length = CommentLineLength(comment_line_length);
+        self
+    }
+
+    pub fn normalize_assignment(mut self, value: NormalizeAssignment) -> Self {
+        self.config.normalize_assignment = value;
+        self
+    }
+
+    pub fn parenthesize_mixed_logical_operators(mut self, value: bool) -> Self {
+        self.config.parenthesize_mixed_logical_operators = ParenthesizeMixedLogicalOperators(value);
+        self
+    }
+
+    pub fn remove_redundant_backticks(mut self, value: bool) -> Self {
+        self.config.remove_redundant_backticks = RemoveRedundantBackticks(value);
+        self
+    }
+
+    pub fn normalize_numbers(mut self, value: bool) -> Self {
+        self.config.normalize_numbers = NormalizeNumbers(value);
+        self
+    }
+
+    pub fn format_roxygen_examples(mut self, value: bool) -> Self {
+        self.config.format_roxygen_examples = FormatRoxygenExamples(value);
+        self
+    }
+
+    pub fn format_glue_strings(mut self, value: bool) -> Self {
+        self.config.format_glue_strings = FormatGlueStrings(value);
+        self
+    }
+
+    pub fn format_shebang_scripts(mut self, value: bool) -> Self {
+        self.config.format_shebang_scripts = FormatShebangScripts(value);
+        self
+    }
+
+    pub fn verify(mut self, value: bool) -> Self {
+        self.config.verify = Verify(value);
+        self
+    }
+
+    pub fn max_file_size(mut self, value: i32) -> Self {
+        self.config.max_file_size = MaxFileSize(value);
+        self
+    }
+
+    pub fn max_expression_depth(mut self, value: i32) -> Self {
+        self.config.max_expression_depth = MaxExpressionDepth(value);
+        self
+    }
+
+    pub fn generated_file_markers<S: Into<String>>(
+        mut self,
+        markers: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.config.generated_file_markers =
+            GeneratedFileMarkers(markers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn exclusion_list<S: Into<String>>(mut self, paths: impl IntoIterator<Item = S>) -> Self {
+        self.config.exclusion_list = ExclusionList(paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Registers a custom formatting rule, run after the ones registered before.
+    pub fn plugin(mut self, plugin: impl FormattingPlugin + 'static) -> Self {
+        self.config.plugins.0.push(Arc::new(plugin));
+        self
+    }
+
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
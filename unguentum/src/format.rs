@@ -1,11 +1,25 @@
 // Implementing Wadler and https://lindig.github.io/papers/strictly-pretty-2000.pdf
 use std::collections::{HashSet, VecDeque};
 use std::ops::Add;
-use std::rc::Rc;
 
 use log::trace;
 
 use crate::config::FormattingConfig;
+use crate::intern::intern;
+
+/// The reference counted pointer shared by the documents: an `Rc`, or
+/// an `Arc` with the `sync` feature, so the documents can be sent
+/// between threads.
+#[cfg(not(feature = "sync"))]
+pub(crate) use std::rc::Rc as DocRc;
+#[cfg(feature = "sync")]
+pub(crate) use std::sync::Arc as DocRc;
+
+#[cfg(feature = "sync")]
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Doc>();
+};
 
 /// ShouldBreak indicates whether a group should break
 /// regardless of the fits calculations.
@@ -24,7 +38,7 @@ pub(crate) enum ShouldBreak {
 
 /// ShouldBreak is a linebreak that propagates to the parents
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct GroupDocProperties(pub(crate) Rc<Doc>, pub(crate) ShouldBreak); // (doc, should parents break?)
+pub(crate) struct GroupDocProperties(pub(crate) DocRc<Doc>, pub(crate) ShouldBreak); // (doc, should parents break?)
 
 #[derive(Debug, Clone, PartialEq, Copy, Hash, Eq)]
 pub(crate) enum InlineCommentPosition {
@@ -49,21 +63,30 @@ impl Add for InlineCommentPosition {
     }
 }
 
-/// inlineCommentPosition, doc reference
+/// inlineCommentPosition, doc reference, flat width
+///
+/// The flat width is the width of the doc printed on one line, computed
+/// when the doc is built, so `fits` does not walk the docs inside it.
+/// It is `None` if the width is known only during the layout, e.g.
+/// for an Align, or the doc cannot be printed on one line.
 #[derive(Debug, Clone, PartialEq, Hash, Eq, Copy)]
-pub(crate) struct CommonProperties(pub(crate) InlineCommentPosition, pub(crate) usize);
+pub(crate) struct CommonProperties(
+    pub(crate) InlineCommentPosition,
+    pub(crate) usize,
+    pub(crate) Option<usize>,
+);
 impl Default for CommonProperties {
     fn default() -> Self {
-        CommonProperties(InlineCommentPosition::No, 0)
+        CommonProperties(InlineCommentPosition::No, 0, Some(0))
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub(crate) enum Doc {
     Nil,
-    Cons(Rc<Doc>, Rc<Doc>, CommonProperties),
-    Text(Rc<str>, usize, CommonProperties), // text, text length
-    Nest(i32, Rc<Doc>, CommonProperties),   // indent size, doc
+    Cons(DocRc<Doc>, DocRc<Doc>, CommonProperties),
+    Text(DocRc<str>, usize, CommonProperties), // text, text length
+    Nest(i32, DocRc<Doc>, CommonProperties),   // indent size, doc
     // This NestIfBreak supports an important layout feature of
     // tidyverse styleguide for R, e.g.
     // test_that("something", {
@@ -81,13 +104,45 @@ pub(crate) enum Doc {
     //     TRUE
     //   }
     // )
-    NestIfBreak(i32, Rc<Doc>, CommonProperties, usize), // indent size, indented doc, props, possibly broken doc
-    NestHanging(Rc<Doc>, CommonProperties),
+    NestIfBreak(i32, DocRc<Doc>, CommonProperties, usize), // indent size, indented doc, props, possibly broken doc
+    NestHanging(DocRc<Doc>, CommonProperties),
+    // Hanging nest applied only if the observed group breaks, e.g.
+    // for the formal arguments with a closure as the last default:
+    // function(x, f = function(y) {
+    //   y
+    // })
+    NestHangingIfBreak(DocRc<Doc>, CommonProperties, usize), // indented doc, props, possibly broken doc
     // This docs has fixed size, which means the fits calculations
     // will return the fixed inner length for this element instead
     // of its calculated length
-    FitsUntilLBracket(Rc<Doc>, CommonProperties), // inner docs, the fixed length, common props
+    // The left bracket is `{`, or `(` for a call, e.g. the `list(` of
+    // `do.call(rbind, list(`
+    FitsUntilLBracket(DocRc<Doc>, &'static str, CommonProperties), // inner docs, left bracket, common props
+    // The embracing operator of rlang around an expression: {{ var }}
+    // Its braces are not the left brackets of FitsUntilLBracket
+    // and never break from the expression.
+    Embrace(DocRc<Doc>, CommonProperties),
+    // A space before the doc, or a line break indented by the indent size
+    // if the doc does not fit on the line but fits flat on the next one,
+    // e.g. the value of an assignment:
+    // result <-
+    //   compute(first, second)
+    BreakBeforeIfFits(i32, DocRc<Doc>, CommonProperties), // indent size, doc
+    // Aligns the docs after it to a column, e.g. the `=` of the arguments
+    // of a call broken one argument per line:
+    // fluidRow(
+    //   id    = "row",
+    //   width = 12
+    // )
+    // It is the number of spaces printed only if the observed group breaks.
+    Align(usize, usize), // number of spaces, possibly broken doc
     Break(&'static str),
+    // A line break inside a string, e.g. an SQL query:
+    // query <- "
+    // SELECT *
+    // "
+    // It always breaks and the line after it is not indented.
+    LiteralLine,
     Group(GroupDocProperties, CommonProperties),
     // Hard break will always not fit in the line
     // essentially forcing the groups containing it
@@ -106,8 +161,17 @@ impl std::fmt::Display for Doc {
                 write!(f, "NestIfBreakRef{watched}Ind{indent}({body})")
             }
             Doc::NestHanging(body, _) => write!(f, "NestHanging({body})"),
-            Doc::FitsUntilLBracket(body, _) => write!(f, "FitsUntilLB({body})"),
+            Doc::NestHangingIfBreak(body, _, watched) => {
+                write!(f, "NestHangingIfBreakRef{watched}({body})")
+            }
+            Doc::FitsUntilLBracket(body, bracket, _) => write!(f, "FitsUntilLB{bracket}({body})"),
+            Doc::Embrace(body, _) => write!(f, "Embrace({body})"),
+            Doc::BreakBeforeIfFits(indent, body, _) => {
+                write!(f, "BreakBeforeIfFits{indent}({body})")
+            }
+            Doc::Align(spaces, watched) => write!(f, "AlignRef{watched}({spaces})"),
             Doc::Break(newline) => f.write_fmt(format_args!("NL({})", newline)),
+            Doc::LiteralLine => f.write_str("LiteralLine"),
             Doc::Group(inside, common_props) => f.write_fmt(format_args!(
                 "GROUP{}:CommPos{:?}:SB{:?}<{}>",
                 common_props.1, common_props.0, inside.1, inside.0
@@ -125,69 +189,129 @@ pub(crate) fn query_inline_position(doc: &Doc) -> InlineCommentPosition {
         Doc::Nest(_, _, props) => props.0,
         Doc::NestIfBreak(_, _, props, _) => props.0,
         Doc::NestHanging(_, props) => props.0,
-        Doc::FitsUntilLBracket(_, props) => props.0,
-        Doc::Break(_) => InlineCommentPosition::No,
+        Doc::NestHangingIfBreak(_, props, _) => props.0,
+        Doc::FitsUntilLBracket(_, _, props) => props.0,
+        Doc::Embrace(_, props) => props.0,
+        Doc::BreakBeforeIfFits(_, _, props) => props.0,
+        Doc::Align(..) => InlineCommentPosition::No,
+        Doc::Break(_) | Doc::LiteralLine => InlineCommentPosition::No,
         Doc::Group(_, props) => props.0,
         Doc::HardBreak => InlineCommentPosition::No,
     }
 }
 
+/// The width of the doc printed on one line in `fits`, if it is known
+/// before the layout. See [CommonProperties].
+pub(crate) fn annotated_width(doc: &Doc) -> Option<usize> {
+    match doc {
+        Doc::Nil => Some(0),
+        Doc::Break(s) => Some(s.len()),
+        Doc::Align(..) | Doc::LiteralLine | Doc::HardBreak => None,
+        Doc::Cons(_, _, props)
+        | Doc::Text(_, _, props)
+        | Doc::Nest(_, _, props)
+        | Doc::NestIfBreak(_, _, props, _)
+        | Doc::NestHanging(_, props)
+        | Doc::NestHangingIfBreak(_, props, _)
+        | Doc::FitsUntilLBracket(_, _, props)
+        | Doc::Embrace(_, props)
+        | Doc::BreakBeforeIfFits(_, _, props)
+        | Doc::Group(_, props) => props.2,
+    }
+}
+
 pub trait DocAlgebra {
-    fn cons(self, other: Rc<Doc>) -> Rc<Doc>;
-    fn to_group(self, should_break: ShouldBreak, doc_ref: &mut usize) -> Rc<Doc>;
-    fn nest(self, indent: i32) -> Rc<Doc>;
-    fn nest_if_break(self, indent: i32, observed_doc: usize) -> Rc<Doc>;
-    fn nest_hanging(self) -> Rc<Doc>;
-    fn fits_until_l_bracket(self) -> Rc<Doc>;
+    fn cons(self, other: DocRc<Doc>) -> DocRc<Doc>;
+    fn to_group(self, should_break: ShouldBreak, doc_ref: &mut usize) -> DocRc<Doc>;
+    fn nest(self, indent: i32) -> DocRc<Doc>;
+    fn nest_if_break(self, indent: i32, observed_doc: usize) -> DocRc<Doc>;
+    fn nest_hanging(self) -> DocRc<Doc>;
+    fn nest_hanging_if_break(self, observed_doc: usize) -> DocRc<Doc>;
+    fn fits_until_l_bracket(self) -> DocRc<Doc>;
+    fn fits_until_l_paren(self) -> DocRc<Doc>;
+    fn embrace(self) -> DocRc<Doc>;
+    fn break_before_if_fits(self, indent: i32) -> DocRc<Doc>;
 }
 
-impl DocAlgebra for Rc<Doc> {
-    fn cons(self, other: Rc<Doc>) -> Rc<Doc> {
+impl DocAlgebra for DocRc<Doc> {
+    fn cons(self, other: DocRc<Doc>) -> DocRc<Doc> {
+        let width = annotated_width(&self).zip(annotated_width(&other));
         let properties = CommonProperties(
             query_inline_position(&self) + query_inline_position(&other),
             0,
+            width.map(|(first, second)| first + second),
         );
-        Rc::new(Doc::Cons(self, other, properties))
+        DocRc::new(Doc::Cons(self, other, properties))
     }
 
-    fn to_group(self, should_break: ShouldBreak, doc_ref: &mut usize) -> Rc<Doc> {
+    fn to_group(self, should_break: ShouldBreak, doc_ref: &mut usize) -> DocRc<Doc> {
         *doc_ref += 1;
-        let properties = CommonProperties(
-            match query_inline_position(&self) {
-                InlineCommentPosition::Middle => InlineCommentPosition::InGroup,
-                InlineCommentPosition::InGroup => InlineCommentPosition::No,
-                position => position,
-            },
-            *doc_ref,
-        );
-        Rc::new(Doc::Group(
+        let position = match query_inline_position(&self) {
+            InlineCommentPosition::Middle => InlineCommentPosition::InGroup,
+            InlineCommentPosition::InGroup => InlineCommentPosition::No,
+            position => position,
+        };
+        // Such groups never fit, see `fits`
+        let width = if position == InlineCommentPosition::Middle
+            || should_break == ShouldBreak::Propagate
+        {
+            None
+        } else {
+            annotated_width(&self)
+        };
+        let properties = CommonProperties(position, *doc_ref, width);
+        DocRc::new(Doc::Group(
             GroupDocProperties(self, should_break),
             properties,
         ))
     }
 
-    fn nest(self, indent: i32) -> Rc<Doc> {
-        let properties = CommonProperties(query_inline_position(&self), 0);
-        Rc::new(Doc::Nest(indent, self, properties))
+    fn nest(self, indent: i32) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, annotated_width(&self));
+        DocRc::new(Doc::Nest(indent, self, properties))
+    }
+
+    fn nest_if_break(self, indent: i32, observed_doc: usize) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, annotated_width(&self));
+        DocRc::new(Doc::NestIfBreak(indent, self, properties, observed_doc))
+    }
+
+    fn nest_hanging(self) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, annotated_width(&self));
+        DocRc::new(Doc::NestHanging(self, properties))
+    }
+
+    fn nest_hanging_if_break(self, observed_doc: usize) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, annotated_width(&self));
+        DocRc::new(Doc::NestHangingIfBreak(self, properties, observed_doc))
+    }
+
+    fn fits_until_l_bracket(self) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, None);
+        DocRc::new(Doc::FitsUntilLBracket(self, "{", properties))
     }
 
-    fn nest_if_break(self, indent: i32, observed_doc: usize) -> Rc<Doc> {
-        let properties = CommonProperties(query_inline_position(&self), 0);
-        Rc::new(Doc::NestIfBreak(indent, self, properties, observed_doc))
+    fn fits_until_l_paren(self) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, None);
+        DocRc::new(Doc::FitsUntilLBracket(self, "(", properties))
     }
 
-    fn nest_hanging(self) -> Rc<Doc> {
-        let properties = CommonProperties(query_inline_position(&self), 0);
-        Rc::new(Doc::NestHanging(self, properties))
+    fn embrace(self) -> DocRc<Doc> {
+        let properties = CommonProperties(query_inline_position(&self), 0, annotated_width(&self));
+        DocRc::new(Doc::Embrace(self, properties))
     }
 
-    fn fits_until_l_bracket(self) -> Rc<Doc> {
-        let properties = CommonProperties(query_inline_position(&self), 0);
-        Rc::new(Doc::FitsUntilLBracket(self, properties))
+    fn break_before_if_fits(self, indent: i32) -> DocRc<Doc> {
+        let properties = CommonProperties(
+            query_inline_position(&self),
+            0,
+            annotated_width(&self).map(|width| width + 1),
+        );
+        DocRc::new(Doc::BreakBeforeIfFits(indent, self, properties))
     }
 }
 
-pub(crate) struct DocBuffer<'a>(pub(crate) &'a VecDeque<(i32, Mode, Rc<Doc>)>);
+pub(crate) struct DocBuffer<'a>(pub(crate) &'a VecDeque<(i32, Mode, DocRc<Doc>)>);
 
 impl std::fmt::Display for DocBuffer<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -200,91 +324,198 @@ impl std::fmt::Display for DocBuffer<'_> {
 
 #[derive(Debug, Clone)]
 pub(crate) enum SimpleDoc {
-    Text(Rc<str>),
+    Text(DocRc<str>),
     Line(usize),
+    LiteralLine,
 }
 
-pub(crate) fn it_simple_doc_to_string(docs: &[SimpleDoc]) -> String {
+/// Returns the code and the lines, counted from 0, which end inside
+/// a string, so their trailing whitespace is a part of the string.
+pub(crate) fn it_simple_doc_to_string(docs: &[SimpleDoc]) -> (String, HashSet<usize>) {
     let mut answer = String::new();
+    let mut line = 0;
+    let mut literal_lines = HashSet::new();
     for doc in docs {
         match doc {
-            SimpleDoc::Text(s) => answer.push_str(s),
+            SimpleDoc::Text(s) => {
+                answer.push_str(s);
+                line += s.matches('\n').count();
+            }
             SimpleDoc::Line(indent) => {
                 answer.push('\n');
+                line += 1;
                 for _ in 0..*indent {
                     answer.push(' ');
                 }
             }
+            SimpleDoc::LiteralLine => {
+                answer.push('\n');
+                literal_lines.insert(line);
+                line += 1;
+            }
         }
     }
-    answer
+    (answer, literal_lines)
 }
 
+/// The most groups laid out with the fits calculations. The code with more
+/// groups, e.g. an enormous vector on one line, breaks all its groups
+/// instead, because the fits calculations would take too long.
+pub(crate) const MAX_GROUPS: usize = 100_000;
+
+/// The deepest nesting of the groups laid out with the fits calculations.
+pub(crate) const MAX_GROUP_DEPTH: usize = 500;
+
 /// `broken_docs` is a set of all the docs that are being formatted
 /// with line breaks. This set is continuously being filled up during
 /// execution of `format_to_sdoc`.
+///
+/// With `break_all_groups`, the groups break without the fits calculations.
 pub(crate) fn it_format_to_sdoc(
     mut consumed: i32,
     docs: &mut VecDeque<Triple>,
     config: &impl FormattingConfig,
     broken_docs: &mut HashSet<usize>,
+    break_all_groups: bool,
 ) -> Vec<SimpleDoc> {
-    let line_length = config.line_length();
+    // The code may overflow the line by the tolerance and still stay flat
+    let line_length =
+        config.line_length() + config.line_length() * config.line_length_tolerance() / 100;
     let mut simple_docs = Vec::new();
+    // An inline comment must end the line, otherwise it would comment out
+    // the code printed after it
+    let mut after_inline_comment = false;
     while let Some(doc) = docs.pop_front() {
         let (indent, mode, doc) = doc;
         match (indent, mode, &*doc) {
             (_, _, Doc::Nil) => {}
             (i, m, Doc::Cons(first, second, _)) => {
-                docs.push_front((i, m, Rc::clone(second)));
-                docs.push_front((i, m, Rc::clone(first)));
+                docs.push_front((i, m, DocRc::clone(second)));
+                docs.push_front((i, m, DocRc::clone(first)));
             }
             (i, m, Doc::Nest(step, doc, _)) => {
-                docs.push_front((i + step, m, Rc::clone(doc)));
+                docs.push_front((i + step, m, DocRc::clone(doc)));
             }
             (i, m, Doc::NestIfBreak(step, doc, _, observed_doc)) => {
                 if broken_docs.contains(observed_doc) {
-                    docs.push_front((i + step, m, Rc::clone(doc)));
+                    docs.push_front((i + step, m, DocRc::clone(doc)));
                 } else {
-                    docs.push_front((i, m, Rc::clone(doc)));
+                    docs.push_front((i, m, DocRc::clone(doc)));
                 }
             }
             (i, m, Doc::NestHanging(doc, props)) => {
                 docs.push_front((
                     i,
                     m,
-                    Rc::new(Doc::Nest(consumed - i, Rc::clone(doc), *props)),
+                    DocRc::new(Doc::Nest(consumed - i, DocRc::clone(doc), *props)),
                 ));
             }
-            (_, _, Doc::Text(s, width, _)) => {
+            (i, m, Doc::NestHangingIfBreak(doc, props, observed_doc)) => {
+                if broken_docs.contains(observed_doc) {
+                    docs.push_front((
+                        i,
+                        m,
+                        DocRc::new(Doc::Nest(consumed - i, DocRc::clone(doc), *props)),
+                    ));
+                } else {
+                    docs.push_front((i, m, DocRc::clone(doc)));
+                }
+            }
+            (_, _, Doc::Align(spaces, observed_doc)) => {
+                if broken_docs.contains(observed_doc) && !after_inline_comment {
+                    simple_docs.push(SimpleDoc::Text(intern(&" ".repeat(*spaces))));
+                    consumed += *spaces as i32;
+                }
+            }
+            (_, _, Doc::Text(s, _, _)) if after_inline_comment && s.trim().is_empty() => {}
+            (i, _, Doc::Text(s, width, props)) => {
+                if after_inline_comment {
+                    // The code continues the statement, except for a block,
+                    // which keeps its braces aligned
+                    let indent = if s.starts_with('{') {
+                        i
+                    } else {
+                        i + config.continuation_indent()
+                    };
+                    simple_docs.push(SimpleDoc::Line(indent as usize));
+                    consumed = indent;
+                }
+                after_inline_comment = props.0 == InlineCommentPosition::End && s.starts_with('#');
                 let length = *width as i32;
-                simple_docs.push(SimpleDoc::Text(Rc::clone(s)));
+                simple_docs.push(SimpleDoc::Text(DocRc::clone(s)));
                 consumed += length;
             }
+            (i, Mode::Flat, Doc::Break(_)) if after_inline_comment => {
+                simple_docs.push(SimpleDoc::Line(i as usize));
+                consumed = i;
+                after_inline_comment = false;
+            }
             (_, Mode::Flat, Doc::Break(s)) => {
                 let length = s.len() as i32;
-                simple_docs.push(SimpleDoc::Text(Rc::from(*s)));
+                simple_docs.push(SimpleDoc::Text(intern(s)));
                 consumed += length;
             }
-            (i, m, Doc::FitsUntilLBracket(inner, _)) => {
-                docs.push_front((i, m, Rc::clone(inner)));
+            (i, Mode::Break, Doc::BreakBeforeIfFits(step, inner, _))
+                if !after_inline_comment
+                    && !fits(
+                        line_length - consumed - 1,
+                        line_length,
+                        VecDeque::from([(i, Mode::Flat, DocRc::clone(inner))]),
+                        docs,
+                        broken_docs,
+                    )
+                    && fits(
+                        line_length - i - step,
+                        line_length,
+                        VecDeque::from([(i + step, Mode::Flat, DocRc::clone(inner))]),
+                        docs,
+                        broken_docs,
+                    ) =>
+            {
+                simple_docs.push(SimpleDoc::Line((i + step) as usize));
+                consumed = i + step;
+                docs.push_front((i + step, Mode::Break, DocRc::clone(inner)));
+            }
+            (i, m, Doc::BreakBeforeIfFits(_, inner, _)) => {
+                let space = Doc::Text(
+                    intern(" "),
+                    1,
+                    CommonProperties(InlineCommentPosition::No, 0, Some(1)),
+                );
+                docs.push_front((i, m, DocRc::clone(inner)));
+                docs.push_front((i, m, DocRc::new(space)));
+            }
+            (i, m, Doc::FitsUntilLBracket(inner, _, _)) | (i, m, Doc::Embrace(inner, _)) => {
+                docs.push_front((i, m, DocRc::clone(inner)));
             }
             (i, Mode::Break, Doc::Break(_)) => {
                 simple_docs.push(SimpleDoc::Line(i as usize));
                 consumed = i;
+                after_inline_comment = false;
             }
-            (i, _, Doc::Group(groupped_doc, CommonProperties(inline_comment_pos, doc_ref))) => {
-                let group_docs = VecDeque::from([(i, Mode::Flat, Rc::clone(&groupped_doc.0))]);
-                if groupped_doc.1 == ShouldBreak::Yes
+            (_, _, Doc::LiteralLine) => {
+                simple_docs.push(SimpleDoc::LiteralLine);
+                consumed = 0;
+            }
+            (i, _, Doc::Group(groupped_doc, CommonProperties(inline_comment_pos, doc_ref, _))) => {
+                let group_docs = VecDeque::from([(i, Mode::Flat, DocRc::clone(&groupped_doc.0))]);
+                if break_all_groups
+                    || groupped_doc.1 == ShouldBreak::Yes
                     || groupped_doc.1 == ShouldBreak::Propagate
                     || matches!(inline_comment_pos, InlineCommentPosition::Middle)
                     || matches!(inline_comment_pos, InlineCommentPosition::InGroup)
-                    || !fits(line_length - consumed, group_docs)
+                    || !fits(
+                        line_length - consumed,
+                        line_length,
+                        group_docs,
+                        docs,
+                        broken_docs,
+                    )
                 {
-                    docs.push_front((i, Mode::Break, Rc::clone(&groupped_doc.0)));
+                    docs.push_front((i, Mode::Break, DocRc::clone(&groupped_doc.0)));
                     broken_docs.insert(*doc_ref);
                 } else {
-                    docs.push_front((i, Mode::Flat, Rc::clone(&groupped_doc.0)));
+                    docs.push_front((i, Mode::Flat, DocRc::clone(&groupped_doc.0)));
                 }
             }
             (_, _, Doc::HardBreak) => {}
@@ -299,54 +530,127 @@ pub(crate) enum Mode {
     Break,
 }
 
-pub(crate) type Triple = (i32, Mode, Rc<Doc>);
+pub(crate) type Triple = (i32, Mode, DocRc<Doc>);
 
-fn fits(mut remaining_width: i32, mut docs: VecDeque<Triple>) -> bool {
+/// Whether the group in `docs` fits in the remaining width on one line,
+/// together with the `rest` of the docs up to the end of the line,
+/// e.g. the ` %>%` after a call.
+///
+/// The lines of a string with line breaks must fit in the `line_length`.
+fn fits(
+    mut remaining_width: i32,
+    line_length: i32,
+    mut docs: VecDeque<Triple>,
+    rest: &VecDeque<Triple>,
+    broken_docs: &HashSet<usize>,
+) -> bool {
+    let mut rest = rest.iter();
+    let mut in_rest = false;
     while remaining_width >= 0 {
-        match docs.pop_front() {
+        let next = docs.pop_front().or_else(|| {
+            in_rest = true;
+            rest.next().cloned()
+        });
+        match next {
             None => {
                 trace!("Got None docs Fits returned true at remaining width: {remaining_width}");
                 return true;
             }
+            // The docs of the group are flat, so the width annotated
+            // when building the docs is their width on the line
+            Some((_, _, doc)) if !in_rest && annotated_width(&doc).is_some() => {
+                remaining_width -= annotated_width(&doc).unwrap_or_default() as i32;
+                continue;
+            }
             Some((indent, mode, doc)) => match (indent, mode, &*doc) {
                 (_, _, Doc::Nil) => continue,
-                (i, m, Doc::FitsUntilLBracket(inner, _)) => {
-                    docs.push_front((i, m, Rc::clone(inner)));
+                (i, m, Doc::FitsUntilLBracket(inner, bracket, _)) => {
+                    docs.push_front((i, m, DocRc::clone(inner)));
                     trace!("Delegating fits to fits until l bracket");
-                    return fits_until_l_bracket(remaining_width, docs);
+                    return fits_until_l_bracket(remaining_width, bracket, docs, broken_docs);
                 }
                 (i, m, Doc::Cons(first, second, _)) => {
-                    docs.push_front((i, m, Rc::clone(second)));
-                    docs.push_front((i, m, Rc::clone(first)));
+                    docs.push_front((i, m, DocRc::clone(second)));
+                    docs.push_front((i, m, DocRc::clone(first)));
                     continue;
                 }
                 (i, m, Doc::Nest(step, doc, _)) => {
-                    docs.push_front((i + step, m, Rc::clone(doc)));
+                    docs.push_front((i + step, m, DocRc::clone(doc)));
                     continue;
                 }
                 (i, m, Doc::NestIfBreak(step, doc, _, _)) => {
-                    docs.push_front((i + step, m, Rc::clone(doc)));
+                    docs.push_front((i + step, m, DocRc::clone(doc)));
                     continue;
                 }
-                (i, m, Doc::NestHanging(doc, _)) => {
-                    docs.push_front((i, m, Rc::clone(doc)));
+                (i, m, Doc::NestHanging(doc, _))
+                | (i, m, Doc::NestHangingIfBreak(doc, _, _))
+                | (i, m, Doc::Embrace(doc, _)) => {
+                    docs.push_front((i, m, DocRc::clone(doc)));
+                    continue;
+                }
+                // The break is taken only if the doc fits on the next line,
+                // so the line ends there
+                (_, Mode::Break, Doc::BreakBeforeIfFits(..)) if in_rest => {
+                    return true;
+                }
+                (i, m, Doc::BreakBeforeIfFits(_, doc, _)) => {
+                    remaining_width -= 1;
+                    docs.push_front((i, m, DocRc::clone(doc)));
                     continue;
                 }
                 (_, _, Doc::Text(_, s_len, _)) => {
                     remaining_width -= *s_len as i32;
                     continue;
                 }
+                (_, _, Doc::Align(spaces, observed_doc)) => {
+                    if broken_docs.contains(observed_doc) {
+                        remaining_width -= *spaces as i32;
+                    }
+                    continue;
+                }
                 (_, Mode::Flat, Doc::Break(s)) => {
                     remaining_width -= s.len() as i32;
                     continue;
                 }
-                (_, Mode::Break, Doc::Break(_)) => unreachable!(),
+                (_, _, Doc::LiteralLine) => {
+                    remaining_width = line_length;
+                    continue;
+                }
+                // Only the rest of the docs can be broken, and the line ends there
+                (_, Mode::Break, Doc::Break(_)) => {
+                    return true;
+                }
+                (_, _, Doc::HardBreak) if in_rest => {
+                    return true;
+                }
+                // The groups after the group are not decided yet, so they
+                // are assumed to break like the docs around them
+                (
+                    i,
+                    m,
+                    Doc::Group(
+                        GroupDocProperties(inner_docs, should_break),
+                        CommonProperties(inline_comment_pos, ..),
+                    ),
+                ) if in_rest => {
+                    let mode = if *should_break != ShouldBreak::No
+                        || matches!(
+                            inline_comment_pos,
+                            InlineCommentPosition::Middle | InlineCommentPosition::InGroup
+                        ) {
+                        Mode::Break
+                    } else {
+                        m
+                    };
+                    docs.push_front((i, mode, DocRc::clone(inner_docs)));
+                    continue;
+                }
                 (
                     i,
                     _,
                     Doc::Group(
                         GroupDocProperties(inner_docs, should_break),
-                        CommonProperties(inline_comment_pos, _),
+                        CommonProperties(inline_comment_pos, ..),
                     ),
                 ) => {
                     if inline_comment_pos == &InlineCommentPosition::Middle {
@@ -356,7 +660,7 @@ fn fits(mut remaining_width: i32, mut docs: VecDeque<Triple>) -> bool {
                         trace!("Fits returned false due to propagating should break");
                         return false;
                     } else {
-                        docs.push_front((i, Mode::Flat, Rc::clone(inner_docs)));
+                        docs.push_front((i, Mode::Flat, DocRc::clone(inner_docs)));
                         continue;
                     }
                 }
@@ -370,7 +674,12 @@ fn fits(mut remaining_width: i32, mut docs: VecDeque<Triple>) -> bool {
     false
 }
 
-fn fits_until_l_bracket(mut remaining_width: i32, mut docs: VecDeque<Triple>) -> bool {
+fn fits_until_l_bracket(
+    mut remaining_width: i32,
+    bracket: &str,
+    mut docs: VecDeque<Triple>,
+    broken_docs: &HashSet<usize>,
+) -> bool {
     while remaining_width >= 0 {
         match docs.pop_front() {
             None => {
@@ -378,54 +687,65 @@ fn fits_until_l_bracket(mut remaining_width: i32, mut docs: VecDeque<Triple>) ->
             }
             Some((indent, mode, doc)) => match (indent, mode, &*doc) {
                 (_, _, Doc::Nil) => continue,
-                (i, m, Doc::FitsUntilLBracket(inner, _)) => {
-                    docs.push_front((i, m, Rc::clone(inner)));
-                    return fits_until_l_bracket(remaining_width, docs);
+                (i, m, Doc::FitsUntilLBracket(inner, bracket, _)) => {
+                    docs.push_front((i, m, DocRc::clone(inner)));
+                    return fits_until_l_bracket(remaining_width, bracket, docs, broken_docs);
                 }
                 (i, m, Doc::Cons(first, second, _)) => {
-                    docs.push_front((i, m, Rc::clone(second)));
-                    docs.push_front((i, m, Rc::clone(first)));
+                    docs.push_front((i, m, DocRc::clone(second)));
+                    docs.push_front((i, m, DocRc::clone(first)));
                     continue;
                 }
                 (i, m, Doc::Nest(step, doc, _)) => {
-                    docs.push_front((i + step, m, Rc::clone(doc)));
+                    docs.push_front((i + step, m, DocRc::clone(doc)));
                     continue;
                 }
                 (i, m, Doc::NestIfBreak(step, doc, _, _)) => {
-                    docs.push_front((i + step, m, Rc::clone(doc)));
+                    docs.push_front((i + step, m, DocRc::clone(doc)));
+                    continue;
+                }
+                (i, m, Doc::NestHanging(doc, _)) | (i, m, Doc::NestHangingIfBreak(doc, _, _)) => {
+                    docs.push_front((i, m, DocRc::clone(doc)));
                     continue;
                 }
-                (i, m, Doc::NestHanging(doc, _)) => {
-                    docs.push_front((i, m, Rc::clone(doc)));
+                (i, m, Doc::BreakBeforeIfFits(_, doc, _)) => {
+                    remaining_width -= 1;
+                    docs.push_front((i, m, DocRc::clone(doc)));
                     continue;
                 }
-                (_, _, Doc::Text(text, s_len, _)) if &**text == "{" => {
-                    // Special case fot the embracing op
-                    if let Some((_, _, next_doc)) = docs.front() {
-                        if let Doc::Text(text, _, _) = &**next_doc {
-                            if &**text == "{" {
-                                remaining_width -= *s_len as i32;
-                                continue;
-                            }
-                        }
+                (_, _, Doc::Embrace(inner, _)) => match flat_width(inner, broken_docs) {
+                    Some(width) => {
+                        remaining_width -= width;
+                        continue;
                     }
-                    // Normal case
+                    None => return false,
+                },
+                (_, _, Doc::Text(text, _, _)) if &**text == bracket => {
                     return remaining_width > 0;
                 }
                 (_, _, Doc::Text(_, s_len, _)) => {
                     remaining_width -= *s_len as i32;
                     continue;
                 }
+                (_, _, Doc::Align(spaces, observed_doc)) => {
+                    if broken_docs.contains(observed_doc) {
+                        remaining_width -= *spaces as i32;
+                    }
+                    continue;
+                }
                 (_, Mode::Flat, Doc::Break(s)) => {
                     remaining_width -= s.len() as i32;
                     continue;
                 }
                 (_, Mode::Break, Doc::Break(_)) => unreachable!(),
-                (i, _, Doc::Group(groupped_doc, CommonProperties(inline_comment_pos, _))) => {
+                (_, _, Doc::LiteralLine) => {
+                    return true;
+                }
+                (i, _, Doc::Group(groupped_doc, CommonProperties(inline_comment_pos, ..))) => {
                     if inline_comment_pos == &InlineCommentPosition::Middle {
                         return false;
                     } else {
-                        docs.push_front((i, Mode::Flat, Rc::clone(&groupped_doc.0)));
+                        docs.push_front((i, Mode::Flat, DocRc::clone(&groupped_doc.0)));
                         continue;
                     }
                 }
@@ -437,3 +757,67 @@ fn fits_until_l_bracket(mut remaining_width: i32, mut docs: VecDeque<Triple>) ->
     }
     false
 }
+
+/// The width of the docs printed on one line, or `None`
+/// if they cannot be printed on one line.
+fn flat_width(doc: &Doc, broken_docs: &HashSet<usize>) -> Option<i32> {
+    if let Some(width) = annotated_width(doc) {
+        return Some(width as i32);
+    }
+    match doc {
+        Doc::Nil => Some(0),
+        Doc::Cons(first, second, _) => {
+            Some(flat_width(first, broken_docs)? + flat_width(second, broken_docs)?)
+        }
+        Doc::Text(_, width, _) => Some(*width as i32),
+        Doc::Nest(_, inner, _)
+        | Doc::NestIfBreak(_, inner, _, _)
+        | Doc::NestHanging(inner, _)
+        | Doc::NestHangingIfBreak(inner, _, _)
+        | Doc::FitsUntilLBracket(inner, _, _)
+        | Doc::Embrace(inner, _) => flat_width(inner, broken_docs),
+        Doc::BreakBeforeIfFits(_, inner, _) => Some(flat_width(inner, broken_docs)? + 1),
+        Doc::Align(spaces, observed_doc) if broken_docs.contains(observed_doc) => {
+            Some(*spaces as i32)
+        }
+        Doc::Align(..) => Some(0),
+        Doc::Break(s) => Some(s.len() as i32),
+        Doc::Group(_, CommonProperties(InlineCommentPosition::Middle, ..))
+        | Doc::HardBreak
+        | Doc::LiteralLine => None,
+        Doc::Group(groupped_doc, _) => flat_width(&groupped_doc.0, broken_docs),
+    }
+}
+
+/// The deepest nesting of the groups in the doc.
+pub(crate) fn group_depth(doc: &DocRc<Doc>) -> usize {
+    // Without recursion, so deeply nested docs do not exhaust the stack
+    let mut docs = vec![(DocRc::clone(doc), 0)];
+    let mut max_depth = 0;
+    while let Some((doc, depth)) = docs.pop() {
+        match &*doc {
+            Doc::Nil
+            | Doc::Text(..)
+            | Doc::Align(..)
+            | Doc::Break(_)
+            | Doc::LiteralLine
+            | Doc::HardBreak => {}
+            Doc::Cons(first, second, _) => {
+                docs.push((DocRc::clone(first), depth));
+                docs.push((DocRc::clone(second), depth));
+            }
+            Doc::Nest(_, inner, _)
+            | Doc::NestIfBreak(_, inner, _, _)
+            | Doc::NestHanging(inner, _)
+            | Doc::NestHangingIfBreak(inner, _, _)
+            | Doc::FitsUntilLBracket(inner, _, _)
+            | Doc::Embrace(inner, _)
+            | Doc::BreakBeforeIfFits(_, inner, _) => docs.push((DocRc::clone(inner), depth)),
+            Doc::Group(groupped_doc, _) => {
+                max_depth = max_depth.max(depth + 1);
+                docs.push((DocRc::clone(&groupped_doc.0), depth + 1));
+            }
+        }
+    }
+    max_depth
+}
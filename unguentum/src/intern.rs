@@ -0,0 +1,39 @@
+//! The shared texts of the documents, e.g. of the identifiers and operators.
+
+use std::{cell::RefCell, collections::HashSet};
+
+use crate::format::DocRc;
+
+/// The longest text interned. The longer texts, e.g. strings and comments,
+/// rarely repeat.
+const MAX_INTERNED_LENGTH: usize = 32;
+
+/// The most texts interned by a thread, so formatting many files
+/// with different identifiers does not grow the memory without bound.
+const MAX_INTERNED_TEXTS: usize = 16_384;
+
+thread_local! {
+    static TEXTS: RefCell<HashSet<DocRc<str>>> = RefCell::new(HashSet::new());
+}
+
+/// Returns the text shared by all the documents of this thread
+/// with the same text, so e.g. each `<-` or `x` is allocated once
+/// instead of once for each occurrence.
+pub(crate) fn intern(text: &str) -> DocRc<str> {
+    if text.len() > MAX_INTERNED_LENGTH {
+        return DocRc::from(text);
+    }
+    TEXTS
+        .try_with(|texts| {
+            let mut texts = texts.borrow_mut();
+            if let Some(interned) = texts.get(text) {
+                return DocRc::clone(interned);
+            }
+            let interned: DocRc<str> = DocRc::from(text);
+            if texts.len() < MAX_INTERNED_TEXTS {
+                texts.insert(DocRc::clone(&interned));
+            }
+            interned
+        })
+        .unwrap_or_else(|_| DocRc::from(text))
+}
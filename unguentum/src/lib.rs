@@ -1,24 +1,77 @@
 mod code;
+pub mod comments;
 pub mod config;
 mod format;
+mod intern;
+pub mod plugin;
 pub(crate) mod post_format_hooks;
 pub(crate) mod pre_format_hooks;
+pub mod rewrite;
+pub mod timing;
 
 use crate::code::Code;
 use crate::format::DocBuffer;
 use crate::format::Mode;
 use log::debug;
 use log::trace;
+use log::warn;
 use parser::ast::Expression;
+use post_format_hooks::pad_section_comments;
 use post_format_hooks::trim_line_endings;
 use post_format_hooks::trim_trailing_line;
+use post_format_hooks::wrap_comments;
 use std::collections::VecDeque;
+use std::time::Duration;
+use timing::Stopwatch;
 
 pub fn format_code<T: config::FormattingConfig>(
-    mut expression: Expression,
+    expression: Expression,
     formatting_config: &T,
 ) -> String {
+    format_code_timed(expression, formatting_config).0
+}
+
+/// The time spent in the stages of laying out the parsed code.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayoutTimings {
+    /// Rewriting the code and building the documents of its layout.
+    pub doc_construction: Duration,
+    /// Fitting the documents in the line length and printing them.
+    pub layout: Duration,
+}
+
+/// What laying out the parsed code took, see [format_code_with_report].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayoutReport {
+    /// The time spent in the stages of the layout.
+    pub timings: LayoutTimings,
+    /// The number of the groups of the layout, e.g. of the calls
+    /// and the operations, which may break into more lines.
+    pub groups: usize,
+    /// The number of the groups broken into more lines, including
+    /// the groups enclosing them and the groups which always break,
+    /// e.g. the blocks.
+    pub broken_groups: usize,
+}
+
+/// Formats the code like [format_code] and measures the time spent
+/// in building the documents of the layout and in laying them out.
+pub fn format_code_timed<T: config::FormattingConfig>(
+    expression: Expression,
+    formatting_config: &T,
+) -> (String, LayoutTimings) {
+    let (formatted, report) = format_code_with_report(expression, formatting_config);
+    (formatted, report.timings)
+}
+
+/// Formats the code like [format_code] and reports the time spent
+/// in the layout and how many of its groups were broken.
+pub fn format_code_with_report<T: config::FormattingConfig>(
+    mut expression: Expression,
+    formatting_config: &T,
+) -> (String, LayoutReport) {
     debug!("Starting formatting");
+    let started = Stopwatch::start();
     // Pre formatting hooks
     let mut pre_format: Vec<fn(&mut Expression<'_>)> = vec![];
     if formatting_config.strip_suffix_whitespace_in_function_defs() {
@@ -28,36 +81,83 @@ pub fn format_code<T: config::FormattingConfig>(
     for hook in pre_format {
         hook(&mut expression);
     }
+    rewrite::rewrite(&mut expression, formatting_config);
+    for plugin in formatting_config.plugins() {
+        plugin.rewrite(&mut expression);
+    }
 
     // Doc stage
     debug!("Transforming to docs");
     let mut doc_ref = 0usize;
-    let mut docs: VecDeque<_> = VecDeque::from([(
-        0i32,
-        Mode::Flat,
-        expression.to_docs(formatting_config, &mut doc_ref),
-    )]);
+    let top_doc = expression.to_docs(formatting_config, &mut doc_ref);
+    // Each group gets the next doc reference
+    let group_count = doc_ref;
+    let group_depth = format::group_depth(&top_doc);
+    let mut docs: VecDeque<_> = VecDeque::from([(0i32, Mode::Flat, top_doc)]);
     trace!("Config: {}", formatting_config);
     trace!("Docs: {}", DocBuffer(&docs));
+    let doc_construction = started.elapsed();
+    let docs_built = Stopwatch::start();
 
     // Simple docs stage
     debug!("Transforming to simple docs");
+    let break_all_groups =
+        group_count > format::MAX_GROUPS || group_depth > format::MAX_GROUP_DEPTH;
+    if break_all_groups {
+        warn!(
+            "The code has {group_count} groups nested {group_depth} deep, more than {} groups or {} deep, so all of them are broken",
+            format::MAX_GROUPS,
+            format::MAX_GROUP_DEPTH
+        );
+    }
     use std::collections::HashSet;
     let mut broken_docs = HashSet::default();
-    let simple_docs = format::it_format_to_sdoc(0, &mut docs, formatting_config, &mut broken_docs);
+    let simple_docs = format::it_format_to_sdoc(
+        0,
+        &mut docs,
+        formatting_config,
+        &mut broken_docs,
+        break_all_groups,
+    );
     trace!("Simple docs: {:?}", simple_docs);
 
     // Printing to string
     debug!("Formatting to string");
-    let mut formatted = format::it_simple_doc_to_string(&simple_docs);
+    let (formatted, literal_lines) = format::it_simple_doc_to_string(&simple_docs);
 
     // Post-format hooks
     debug!("Post-format hooks");
-    let post_format_hooks = vec![trim_line_endings, trim_trailing_line];
+    let mut formatted = trim_line_endings(formatted, &literal_lines);
+    if formatting_config.wrap_comments() {
+        formatted = wrap_comments(
+            formatted,
+            &literal_lines,
+            formatting_config.comment_line_length(),
+        );
+    }
+    if formatting_config.pad_section_comments() {
+        formatted = pad_section_comments(
+            formatted,
+            &literal_lines,
+            formatting_config.comment_line_length(),
+        );
+    }
+    let post_format_hooks = vec![trim_trailing_line];
     for hook in post_format_hooks {
         formatted = hook(formatted);
     }
+    for plugin in formatting_config.plugins() {
+        plugin.adjust_output(&mut formatted);
+    }
 
     debug!("Finished formatting");
-    formatted
+    let report = LayoutReport {
+        timings: LayoutTimings {
+            doc_construction,
+            layout: docs_built.elapsed(),
+        },
+        groups: group_count,
+        broken_groups: broken_docs.len(),
+    };
+    (formatted, report)
 }
@@ -0,0 +1,31 @@
+//! Custom formatting rules injected by the users of the formatter.
+
+use std::sync::Arc;
+
+use parser::ast::Expression;
+
+/// A custom formatting rule, e.g. a company-specific way to format some calls.
+///
+/// Register the plugins on the [Config](crate::config::Config) with
+/// [ConfigBuilder::plugin](crate::config::ConfigBuilder::plugin).
+/// They run in the order they were registered, after the built-in rules.
+pub trait FormattingPlugin: Send + Sync {
+    /// Rewrites the parsed code before it is laid out.
+    fn rewrite(&self, _expression: &mut Expression<'_>) {}
+
+    /// Adjusts the formatted code.
+    fn adjust_output(&self, _formatted: &mut String) {}
+}
+
+/// The plugins registered on a configuration.
+///
+/// The plugins are code, so they are neither read from nor written to
+/// configuration files.
+#[derive(Clone, Default)]
+pub struct Plugins(pub Vec<Arc<dyn FormattingPlugin>>);
+
+impl std::fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Plugins({})", self.0.len())
+    }
+}
@@ -1,12 +1,77 @@
-pub(crate) fn trim_line_endings(s: String) -> String {
+use std::collections::HashSet;
+
+use crate::comments::{section_marker, wrap_comment};
+
+/// Trims the whitespace at the ends of the lines, except for the lines
+/// ending inside a string.
+pub(crate) fn trim_line_endings(s: String, literal_lines: &HashSet<usize>) -> String {
     s.lines()
-        .fold(String::with_capacity(s.len()), |mut acc, line| {
-            acc.push_str(line.trim_end());
+        .enumerate()
+        .fold(String::with_capacity(s.len()), |mut acc, (index, line)| {
+            if literal_lines.contains(&index) {
+                acc.push_str(line);
+            } else {
+                acc.push_str(line.trim_end());
+            }
             acc.push('\n');
             acc
         })
 }
 
+/// Pads the section comments on their own lines with their marker
+/// to the line length, e.g. `# Setup ----` to `# Setup -------...`.
+/// The longer comments are kept as they are.
+pub(crate) fn pad_section_comments(
+    s: String,
+    literal_lines: &HashSet<usize>,
+    line_length: i32,
+) -> String {
+    let line_length = usize::try_from(line_length).unwrap_or_default();
+    s.lines()
+        .enumerate()
+        .fold(String::with_capacity(s.len()), |mut acc, (index, line)| {
+            acc.push_str(line);
+            // The line starting inside a string is not a comment
+            let in_string = index > 0 && literal_lines.contains(&(index - 1));
+            if let Some(marker) = section_marker(line.trim_start()).filter(|_| !in_string) {
+                let width = line.chars().count();
+                acc.extend(std::iter::repeat_n(
+                    marker,
+                    line_length.saturating_sub(width),
+                ));
+            }
+            acc.push('\n');
+            acc
+        })
+}
+
+/// Splits the prose comments on their own lines longer than the line length
+/// into more lines with the same indentation, see [wrap_comment].
+/// The comments are never joined, so their short lines are kept.
+pub(crate) fn wrap_comments(s: String, literal_lines: &HashSet<usize>, line_length: i32) -> String {
+    let line_length = usize::try_from(line_length).unwrap_or_default();
+    s.lines()
+        .enumerate()
+        .fold(String::with_capacity(s.len()), |mut acc, (index, line)| {
+            let comment = line.trim_start();
+            let indent = &line[..line.len() - comment.len()];
+            // The line starting inside a string is not a comment
+            let in_string = index > 0 && literal_lines.contains(&(index - 1));
+            if comment.starts_with('#') && !in_string {
+                let width = line_length.saturating_sub(indent.chars().count());
+                for comment_line in wrap_comment(comment, width) {
+                    acc.push_str(indent);
+                    acc.push_str(&comment_line);
+                    acc.push('\n');
+                }
+            } else {
+                acc.push_str(line);
+                acc.push('\n');
+            }
+            acc
+        })
+}
+
 pub(crate) fn trim_trailing_line(mut s: String) -> String {
     let trimmed = s.trim_end();
     s.truncate(trimmed.len());
@@ -0,0 +1,427 @@
+//! The built-in rules rewriting the parsed code before it is laid out,
+//! e.g. normalizing the assignment operators, and the rules rewriting
+//! the tokens before they are parsed, e.g. removing the redundant backticks
+//! or normalizing the numbers.
+//!
+//! The layout itself never adds nor removes any token, e.g. parentheses,
+//! so only these rules, which are off by default, change the code.
+//!
+//! Unlike the layout, the rules may change the syntax tree, so the meaning
+//! of the formatted code is checked against the rewritten code. The rules
+//! must give the same code when applied twice.
+
+use std::collections::BTreeMap;
+
+use parser::ast::{Arg, Args, Expression, TermExpr};
+use tokenizer::{Token, tokens::CommentedToken};
+
+use crate::config::{FormattingConfig, NormalizeAssignment};
+
+static LEFT_ASSIGN: CommentedToken<'static> = CommentedToken {
+    token: Token::LAssign,
+    offset: 0,
+    leading_comments: None,
+    inline_comment: None,
+};
+
+static EQUAL_ASSIGN: CommentedToken<'static> = CommentedToken {
+    token: Token::OldAssign,
+    offset: 0,
+    leading_comments: None,
+    inline_comment: None,
+};
+
+static LEFT_PAREN: CommentedToken<'static> = CommentedToken {
+    token: Token::LParen,
+    offset: 0,
+    leading_comments: None,
+    inline_comment: None,
+};
+
+static RIGHT_PAREN: CommentedToken<'static> = CommentedToken {
+    token: Token::RParen,
+    offset: 0,
+    leading_comments: None,
+    inline_comment: None,
+};
+
+/// The number of the changes made by each rule, by the name of the option
+/// enabling it, e.g. `normalize_assignment`. The rules which changed
+/// nothing are left out.
+pub type RewriteCounts = BTreeMap<&'static str, usize>;
+
+/// Applies the rules enabled in the configuration to the expression
+/// and to all the expressions in it, and returns how many changes
+/// each rule made.
+pub fn rewrite(expression: &mut Expression, config: &impl FormattingConfig) -> RewriteCounts {
+    let normalize_assignment = config.normalize_assignment();
+    let parenthesize_mixed_logical_operators = config.parenthesize_mixed_logical_operators();
+    let mut counts = RewriteCounts::new();
+    walk_mut(expression, &mut |expression| {
+        if parenthesize_mixed_logical_operators {
+            let parenthesized = parenthesize_and_operands(expression);
+            count(
+                &mut counts,
+                "parenthesize_mixed_logical_operators",
+                parenthesized,
+            );
+        }
+        let Expression::Term(term) = expression else {
+            return;
+        };
+        // The statements of the code or of a block, not of parentheses
+        let is_block = term
+            .pre_delimiters
+            .is_none_or(|delimiter| delimiter.token == Token::LBrace);
+        if is_block && normalize_assignment != NormalizeAssignment::None {
+            let normalized = term
+                .term
+                .iter_mut()
+                .map(|statement| normalize_assignment_operator(statement, normalize_assignment))
+                .filter(|&normalized| normalized)
+                .count();
+            count(&mut counts, "normalize_assignment", normalized);
+        }
+    });
+    counts
+}
+
+fn count(counts: &mut RewriteCounts, rule: &'static str, changes: usize) {
+    if changes > 0 {
+        *counts.entry(rule).or_default() += changes;
+    }
+}
+
+/// Returns the texts of the tokens which the rules enabled in the
+/// configuration rewrite into a text not in the code, e.g. `1e3`
+/// from `1e+03`, with the indices of the tokens. The tokens borrow
+/// their texts, so the caller keeps these while it uses the tokens
+/// rewritten by [rewrite_tokens].
+pub fn rewritten_token_texts(
+    tokens: &[CommentedToken],
+    config: &impl FormattingConfig,
+) -> Vec<(usize, String)> {
+    if !config.normalize_numbers() {
+        return vec![];
+    }
+    tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(index, token)| match token.token {
+            Token::Literal(literal) => normalize_number(literal).map(|number| (index, number)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Applies the rules enabled in the configuration which rewrite single
+/// tokens, with the `texts` returned by [rewritten_token_texts]. The syntax
+/// tree borrows the tokens, so they are rewritten before they are parsed.
+/// Returns how many tokens each rule changed.
+pub fn rewrite_tokens<'a>(
+    tokens: &mut [CommentedToken<'a>],
+    texts: &'a [(usize, String)],
+    config: &impl FormattingConfig,
+) -> RewriteCounts {
+    let mut counts = RewriteCounts::new();
+    if config.remove_redundant_backticks() {
+        let unquoted = tokens
+            .iter_mut()
+            .map(remove_redundant_backticks)
+            .filter(|&unquoted| unquoted)
+            .count();
+        count(&mut counts, "remove_redundant_backticks", unquoted);
+    }
+    for (index, text) in texts {
+        tokens[*index].token = Token::Literal(text);
+    }
+    count(&mut counts, "normalize_numbers", texts.len());
+    counts
+}
+
+/// Calls `visit` on the expression and then on each expression in it.
+pub(crate) fn walk_mut<'a>(
+    expression: &mut Expression<'a>,
+    visit: &mut impl FnMut(&mut Expression<'a>),
+) {
+    visit(expression);
+    match expression {
+        Expression::Symbol(_)
+        | Expression::Literal(_)
+        | Expression::Comment(_)
+        | Expression::Newline(_)
+        | Expression::Whitespace(_)
+        | Expression::EOF(_)
+        | Expression::Continue(_)
+        | Expression::Break(_) => {}
+        Expression::Term(term) => term
+            .term
+            .iter_mut()
+            .for_each(|expression| walk_mut(expression, visit)),
+        Expression::Unary(_, expression) | Expression::Formula(_, expression) => {
+            walk_mut(expression, visit)
+        }
+        Expression::Bop(_, lhs, rhs) => {
+            walk_mut(lhs, visit);
+            walk_mut(rhs, visit);
+        }
+        Expression::MultiBop(lhs, other) => {
+            walk_mut(lhs, visit);
+            other.iter_mut().for_each(|(_, rhs)| walk_mut(rhs, visit));
+        }
+        Expression::FunctionDef(function_def) => {
+            walk_args_mut(&mut function_def.arguments, visit);
+            walk_mut(&mut function_def.body, visit);
+        }
+        Expression::LambdaFunction(lambda) => {
+            walk_args_mut(&mut lambda.args, visit);
+            walk_mut(&mut lambda.body, visit);
+        }
+        Expression::IfExpression(if_expression) => {
+            let conditionals = std::iter::once(&mut if_expression.if_conditional).chain(
+                if_expression
+                    .else_ifs
+                    .iter_mut()
+                    .map(|else_if| &mut else_if.if_conditional),
+            );
+            for conditional in conditionals {
+                walk_mut(&mut conditional.condition, visit);
+                walk_mut(&mut conditional.body, visit);
+            }
+            if let Some(trailing_else) = &mut if_expression.trailing_else {
+                walk_mut(&mut trailing_else.body, visit);
+            }
+        }
+        Expression::WhileExpression(while_expression) => {
+            walk_mut(&mut while_expression.condition, visit);
+            walk_mut(&mut while_expression.body, visit);
+        }
+        Expression::RepeatExpression(repeat_expression) => {
+            walk_mut(&mut repeat_expression.body, visit)
+        }
+        Expression::FunctionCall(call) => {
+            walk_mut(&mut call.function_ref, visit);
+            walk_args_mut(&mut call.args, visit);
+        }
+        Expression::SubsetExpression(subset) => {
+            walk_mut(&mut subset.object_ref, visit);
+            walk_args_mut(&mut subset.args, visit);
+        }
+        Expression::ForLoopExpression(for_loop) => {
+            walk_mut(&mut for_loop.identifier, visit);
+            walk_mut(&mut for_loop.collection, visit);
+            walk_mut(&mut for_loop.body, visit);
+        }
+    }
+}
+
+fn walk_args_mut<'a>(args: &mut Args<'a>, visit: &mut impl FnMut(&mut Expression<'a>)) {
+    for arg in &mut args.args {
+        match arg {
+            Arg::Proper(expression, _) => {
+                if let Some(expression) = expression {
+                    walk_mut(expression, visit);
+                }
+            }
+            Arg::EmptyEqual(name, _, _) => walk_mut(name, visit),
+        }
+    }
+}
+
+/// Replaces `<-` with `=`, or the other way around, in the assignment
+/// statement, e.g. `x <- 1`. Only the statements in blocks are rewritten,
+/// because `=` in a call names an argument. The other assignments, e.g.
+/// `<<-` and `->`, and the operators with comments are kept.
+///
+/// The chained assignments, e.g. `x <- y <- 1`, are rewritten only
+/// as a whole, because `=` and `<-` have different precedences.
+/// Returns whether the statement was rewritten.
+fn normalize_assignment_operator(statement: &mut Expression, style: NormalizeAssignment) -> bool {
+    let (from, to) = match style {
+        NormalizeAssignment::None => return false,
+        NormalizeAssignment::Arrow => (Token::OldAssign, &LEFT_ASSIGN),
+        NormalizeAssignment::Equal => (Token::LAssign, &EQUAL_ASSIGN),
+    };
+    let is_replaceable = is_replaceable_chain(statement, &from);
+    if is_replaceable {
+        replace_chain(statement, &from, to);
+    }
+    is_replaceable
+}
+
+/// Whether the statement is an assignment whose operator, and the operators
+/// of the assignments in its right-hand side, are `from` without comments.
+fn is_replaceable_chain(statement: &Expression, from: &Token) -> bool {
+    let (lhs, op, rhs) = match statement {
+        Expression::Bop(op, lhs, rhs) => (lhs, op, rhs),
+        Expression::MultiBop(lhs, other) if other.len() == 1 => (lhs, &other[0].0, &other[0].1),
+        _ => return false,
+    };
+    op.token == *from
+        && op.leading_comments.is_none()
+        && op.inline_comment.is_none()
+        && !is_assignment(lhs)
+        && (!is_assignment(rhs) || is_replaceable_chain(rhs, from))
+}
+
+fn is_assignment(expression: &Expression) -> bool {
+    let is_assignment_op =
+        |op: &CommentedToken| matches!(op.token, Token::LAssign | Token::OldAssign);
+    match expression {
+        Expression::Bop(op, _, _) => is_assignment_op(op),
+        Expression::MultiBop(_, other) => other.iter().any(|(op, _)| is_assignment_op(op)),
+        _ => false,
+    }
+}
+
+fn replace_chain<'a>(statement: &mut Expression<'a>, from: &Token, to: &'a CommentedToken<'a>) {
+    let (op, rhs) = match statement {
+        Expression::Bop(op, _, rhs) => (op, rhs),
+        Expression::MultiBop(_, other) if other.len() == 1 => {
+            let (op, rhs) = &mut other[0];
+            (op, rhs)
+        }
+        _ => return,
+    };
+    if op.token == *from {
+        *op = to;
+        replace_chain(rhs, from, to);
+    }
+}
+
+/// Puts the parentheses around the `&&` operations in the operands
+/// of `||`, e.g. `a || b && c` becomes `a || (b && c)`. `&&` takes
+/// precedence over `||`, so the parentheses do not change the meaning.
+/// Returns the number of the operands put in the parentheses.
+fn parenthesize_and_operands(expression: &mut Expression) -> usize {
+    let is_or = |op: &CommentedToken| op.token == Token::Or;
+    let operands: Vec<&mut Box<Expression>> = match expression {
+        Expression::Bop(op, lhs, rhs) if is_or(op) => vec![lhs, rhs],
+        Expression::MultiBop(lhs, other) if other.iter().all(|(op, _)| is_or(op)) => {
+            std::iter::once(lhs)
+                .chain(other.iter_mut().map(|(_, rhs)| rhs))
+                .collect()
+        }
+        _ => return 0,
+    };
+    let is_and = |op: &CommentedToken| op.token == Token::And;
+    let mut parenthesized = 0;
+    for operand in operands {
+        let is_and_operation = match operand.as_ref() {
+            Expression::Bop(op, _, _) => is_and(op),
+            Expression::MultiBop(_, other) => other.iter().any(|(op, _)| is_and(op)),
+            _ => false,
+        };
+        if is_and_operation {
+            let empty = Expression::Term(Box::new(TermExpr::new(None, vec![], None)));
+            let and_operation = std::mem::replace(operand.as_mut(), empty);
+            **operand = Expression::Term(Box::new(TermExpr::new(
+                Some(&LEFT_PAREN),
+                vec![and_operation],
+                Some(&RIGHT_PAREN),
+            )));
+            parenthesized += 1;
+        }
+    }
+    parenthesized
+}
+
+/// The names which are not syntactic, even though they look like
+/// identifiers, see `?Reserved` in R, and `T` and `F`, which the tokenizer
+/// reads as `TRUE` and `FALSE`.
+const RESERVED_WORDS: [&str; 21] = [
+    "if",
+    "else",
+    "repeat",
+    "while",
+    "function",
+    "for",
+    "in",
+    "next",
+    "break",
+    "TRUE",
+    "FALSE",
+    "NULL",
+    "Inf",
+    "NaN",
+    "NA",
+    "NA_integer_",
+    "NA_real_",
+    "NA_character_",
+    "NA_complex_",
+    "T",
+    "F",
+];
+
+/// Removes the backticks around the name which is syntactic without them,
+/// e.g. `` `mean` `` becomes `mean`. The backticked names after `::`
+/// are a part of the namespace access token, so they are kept.
+/// Returns whether the backticks were removed.
+fn remove_redundant_backticks(token: &mut CommentedToken) -> bool {
+    let Token::Symbol(name) = token.token else {
+        return false;
+    };
+    match name
+        .strip_prefix('`')
+        .and_then(|name| name.strip_suffix('`'))
+        .filter(|name| is_syntactic_name(name))
+    {
+        Some(unquoted) => {
+            token.token = Token::Symbol(unquoted);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Whether the name can be used without the backticks: it starts with
+/// a letter or a dot not followed by a digit, has only letters, digits,
+/// dots and underscores, and is not reserved, e.g. `if` or `..1`.
+/// Only the ASCII letters are accepted, because the others depend
+/// on the locale of R.
+fn is_syntactic_name(name: &str) -> bool {
+    let starts_well = match name.chars().next() {
+        Some('.') => !name[1..].starts_with(|c: char| c.is_ascii_digit()),
+        Some(first) => first.is_ascii_alphabetic(),
+        None => false,
+    };
+    let is_dots =
+        name.starts_with("..") && (name == "..." || name[2..].chars().all(|c| c.is_ascii_digit()));
+    starts_well
+        && !is_dots
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+        && !RESERVED_WORDS.contains(&name)
+}
+
+/// Returns the decimal number with its exponent normalized, e.g. `1e3`
+/// for `1e+03` or `2.5e-7L` for `2.5E-07L`, or `None` if it is already
+/// normalized or is not a decimal number. The digits of the mantissa,
+/// e.g. the trailing zeros of `1.50`, are kept.
+fn normalize_number(literal: &str) -> Option<String> {
+    let is_decimal = literal.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && !literal.starts_with("0x")
+        && !literal.starts_with("0X");
+    if !is_decimal {
+        return None;
+    }
+    let (mantissa, exponent) = literal.split_once(['e', 'E'])?;
+    let (sign, exponent) = match exponent.strip_prefix('-') {
+        Some(exponent) => ("-", exponent),
+        None => ("", exponent.strip_prefix('+').unwrap_or(exponent)),
+    };
+    let suffix_start = exponent
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(exponent.len());
+    let (digits, suffix) = exponent.split_at(suffix_start);
+    if digits.is_empty() {
+        return None;
+    }
+    let digits = match digits.trim_start_matches('0') {
+        "" => "0",
+        digits => digits,
+    };
+    let normalized = format!("{mantissa}e{sign}{digits}{suffix}");
+    (normalized != literal).then_some(normalized)
+}
@@ -0,0 +1,25 @@
+//! Measuring the stages of formatting.
+
+use std::time::{Duration, Instant};
+
+/// Measures the time since it was started.
+///
+/// `Instant::now` panics on `wasm32-unknown-unknown`, which has no clock,
+/// so nothing is measured there and the measured times are zero.
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch(Option<Instant>);
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let started = Some(Instant::now());
+        #[cfg(target_arch = "wasm32")]
+        let started = None;
+        Self(started)
+    }
+
+    /// Returns the time since the stopwatch was started.
+    pub fn elapsed(&self) -> Duration {
+        self.0.map(|started| started.elapsed()).unwrap_or_default()
+    }
+}
@@ -1,4 +1,4 @@
-use tergo_formatter::config::{Config, FunctionLineBreaks};
+use tergo_formatter::config::{Config, FunctionLineBreaks, NormalizeAssignment};
 
 fn log_init() {
     match simple_logger::init_with_env() {
@@ -34,3 +34,79 @@ fn parses_the_partial_config() {
 
     assert!(config.is_ok(), "Error was {config:?}");
 }
+
+#[test]
+fn rejects_unknown_keys() {
+    log_init();
+    let config: Result<Config, _> = toml::from_str("line_lenght = 80");
+
+    assert!(config.is_err());
+    assert!(config.unwrap_err().to_string().contains("line_lenght"));
+}
+
+#[test]
+fn parses_normalize_assignment_false_as_none() {
+    log_init();
+    let config: Config = toml::from_str("normalize_assignment = false").unwrap();
+    assert_eq!(config.normalize_assignment, NormalizeAssignment::None);
+
+    let config: Config = toml::from_str("normalize_assignment = \"equal\"").unwrap();
+    assert_eq!(config.normalize_assignment, NormalizeAssignment::Equal);
+
+    assert!(toml::from_str::<Config>("normalize_assignment = true").is_err());
+    assert!(toml::from_str::<Config>("normalize_assignment = \"arow\"").is_err());
+}
+
+#[test]
+fn builder_overrides_only_the_set_options() {
+    let config = Config::builder()
+        .indent(4)
+        .function_line_breaks(FunctionLineBreaks::Single)
+        .exclusion_list(["./renv"])
+        .verify(false)
+        .build();
+
+    assert_eq!(config.indent.0, 4);
+    assert_eq!(config.line_length.0, Config::default().line_length.0);
+    assert_eq!(config.function_line_breaks, FunctionLineBreaks::Single);
+    assert_eq!(config.exclusion_list.0, vec!["./renv".to_string()]);
+    assert!(!config.verify.0);
+    assert!(Config::default().verify.0);
+}
+
+#[test]
+fn new_keeps_the_defaults_of_the_other_options() {
+    let config = Config::new(
+        4,
+        80,
+        false,
+        true,
+        true,
+        true,
+        FunctionLineBreaks::Hanging,
+        false,
+        vec!["./renv".to_string()],
+    );
+
+    assert_eq!(config.indent.0, 4);
+    assert_eq!(config.line_length.0, 80);
+    assert_eq!(config.exclusion_list.0, vec!["./renv".to_string()]);
+    assert_eq!(
+        config.continuation_indent.0,
+        Config::default().continuation_indent.0
+    );
+    assert!(config.verify.0);
+}
+
+#[cfg(feature = "schema")]
+#[test]
+fn schema_lists_every_option() {
+    let schema = Config::json_schema();
+    let properties = schema.get("properties").unwrap().as_object().unwrap();
+    let options = toml::Table::try_from(Config::default()).unwrap();
+
+    for option in options.keys() {
+        assert!(properties.contains_key(option), "{option} is missing");
+    }
+    assert_eq!(properties.len(), options.len());
+}